@@ -33,7 +33,20 @@
 //! Histograms are a convenient way to measure behavior not only at the median, but at the edges of
 //! normal operating behavior.
 #![deny(missing_docs)]
-use std::{borrow::Cow, fmt, slice::Iter, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+    slice::Iter,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex, Once,
+    },
+    time::{Duration, SystemTime},
+};
 
 /// An allocation-optimized string.
 ///
@@ -42,9 +55,177 @@ use std::{borrow::Cow, fmt, slice::Iter, time::Duration};
 /// take ownership of owned strings and borrows of completely static strings.
 pub type ScopedString = Cow<'static, str>;
 
+/// The source of ids for interned label keys, shared by every [`LabelKeys`].
+static NEXT_LABEL_KEY_ID: AtomicU32 = AtomicU32::new(0);
+
+/// Gets the ids of every label key interned so far, shared by every [`LabelKeys`] so that each
+/// distinct key is only ever given one id.
+fn label_key_ids() -> &'static Mutex<HashMap<&'static str, u32>> {
+    static INIT: Once = Once::new();
+    static mut IDS: *const Mutex<HashMap<&'static str, u32>> = std::ptr::null();
+
+    // The map is created exactly once, by whichever caller gets here first, and never freed, so
+    // every caller sees the same, fully initialized, map.
+    unsafe {
+        INIT.call_once(|| IDS = Box::into_raw(Box::new(Mutex::new(HashMap::new()))));
+        &*IDS
+    }
+}
+
+/// Takes `len` consecutive ids from `next`, returning the first of them, or `None` if there aren't
+/// enough left.
+///
+/// Ids are only taken if there are enough left, so that running out can't wrap around and hand
+/// out ids which are already in use.
+fn take_label_key_ids(next: &AtomicU32, len: u32) -> Option<u32> {
+    let mut base = next.load(Ordering::Relaxed);
+    loop {
+        let end = base.checked_add(len)?;
+        match next.compare_exchange_weak(base, end, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return Some(base),
+            Err(current) => base = current,
+        }
+    }
+}
+
+/// A label key interned as part of a [`LabelKeys`] schema.
+///
+/// Interned keys are compared by their id, rather than by their contents.  Every schema interns
+/// the same key as the same id, so keys with different ids are always different keys.
+#[derive(Clone, Copy, Debug)]
+pub struct LabelKey {
+    id: u32,
+    name: &'static str,
+}
+
+impl LabelKey {
+    /// The id this key was interned as.
+    ///
+    /// Each distinct key is given a single id, shared by every schema it's part of, for the life of
+    /// the process.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The key, as a string.
+    pub fn as_str(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl PartialEq for LabelKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for LabelKey {}
+
+impl Hash for LabelKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+/// A fixed set of label keys, interned so that labels using them are cheap to compare.
+///
+/// Applications with a fixed label schema, such as every request metric having a `method`, a
+/// `status` and a `route` label, compare and hash the same handful of label keys over and over.
+/// A `LabelKeys` schema gives each of its keys a small integer id, and labels built with
+/// [`label`](LabelKeys::label) carry that id along with the key, so that comparing the keys of
+/// two such labels is a single integer comparison.
+///
+/// Interning is purely an optimization: a label with an interned key is equal to, and hashes the
+/// same as, a label created from the same key as a string.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_core::{Label, LabelKeys};
+/// static REQUEST_LABELS: &[&str] = &["method", "status", "route"];
+///
+/// let schema = LabelKeys::new(REQUEST_LABELS);
+/// let label = schema.label("method", "GET");
+///
+/// assert_eq!(label.key(), "method");
+/// assert_eq!(label, Label::new("method", "GET"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LabelKeys {
+    keys: &'static [&'static str],
+    ids: Vec<u32>,
+}
+
+impl LabelKeys {
+    /// Creates a new schema, interning the given keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the process runs out of ids, which takes interning billions of keys.
+    pub fn new(keys: &'static [&'static str]) -> Self {
+        let mut interned = label_key_ids().lock().unwrap_or_else(|e| e.into_inner());
+        let ids = keys
+            .iter()
+            .map(|key| {
+                *interned.entry(*key).or_insert_with(|| {
+                    take_label_key_ids(&NEXT_LABEL_KEY_ID, 1)
+                        .expect("ran out of ids for interned label keys")
+                })
+            })
+            .collect();
+        LabelKeys { keys, ids }
+    }
+
+    /// Gets the interned form of the given key, if it is part of this schema.
+    pub fn get(&self, key: &str) -> Option<LabelKey> {
+        self.keys
+            .iter()
+            .position(|name| *name == key)
+            .map(|index| LabelKey {
+                id: self.ids[index],
+                name: self.keys[index],
+            })
+    }
+
+    /// Creates a `Label` from a key and value, interning the key if it is part of this schema.
+    ///
+    /// Keys which aren't part of the schema are stored as strings, as with [`Label::new`].
+    pub fn label<V>(&self, key: &str, value: V) -> Label
+    where
+        V: Into<ScopedString>,
+    {
+        match self.get(key) {
+            Some(key) => Label::interned(key, value),
+            None => Label::new(key.to_string(), value),
+        }
+    }
+
+    /// Iterates over the interned keys of this schema, in the order they were given.
+    pub fn iter(&self) -> impl Iterator<Item = LabelKey> + '_ {
+        self.keys
+            .iter()
+            .zip(&self.ids)
+            .map(|(name, id)| LabelKey { id: *id, name })
+    }
+}
+
+#[derive(Clone)]
+enum LabelKeyRepr {
+    Str(ScopedString),
+    Interned(LabelKey),
+}
+
+impl LabelKeyRepr {
+    fn as_str(&self) -> &str {
+        match self {
+            LabelKeyRepr::Str(key) => key.as_ref(),
+            LabelKeyRepr::Interned(key) => key.name,
+        }
+    }
+}
+
 /// A key/value pair used to further describe a metric.
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-pub struct Label(ScopedString, ScopedString);
+#[derive(Clone)]
+pub struct Label(LabelKeyRepr, ScopedString);
 
 impl Label {
     /// Creates a `Label` from a key and value.
@@ -53,12 +234,30 @@ impl Label {
         K: Into<ScopedString>,
         V: Into<ScopedString>,
     {
-        Label(key.into(), value.into())
+        Label(LabelKeyRepr::Str(key.into()), value.into())
+    }
+
+    /// Creates a `Label` from an interned key and a value.
+    ///
+    /// See [`LabelKeys`] for more on interned keys.
+    pub fn interned<V>(key: LabelKey, value: V) -> Self
+    where
+        V: Into<ScopedString>,
+    {
+        Label(LabelKeyRepr::Interned(key), value.into())
     }
 
     /// The key of this label.
     pub fn key(&self) -> &str {
-        self.0.as_ref()
+        self.0.as_str()
+    }
+
+    /// The interned form of the key of this label, if it was created with one.
+    pub fn interned_key(&self) -> Option<LabelKey> {
+        match self.0 {
+            LabelKeyRepr::Interned(key) => Some(key),
+            LabelKeyRepr::Str(_) => None,
+        }
     }
 
     /// The value of this label.
@@ -66,10 +265,180 @@ impl Label {
         self.1.as_ref()
     }
 
+    /// Converts this `Label` into one which owns its key and value.
+    ///
+    /// See [`Key::into_owned`] for why this is useful.  Interned keys are copied into owned
+    /// strings, and so are no longer interned.
+    pub fn into_owned(self) -> Label {
+        let key = match self.0 {
+            LabelKeyRepr::Str(key) => key.into_owned(),
+            LabelKeyRepr::Interned(key) => key.name.to_owned(),
+        };
+        let key = LabelKeyRepr::Str(Cow::Owned(key));
+        Label(key, Cow::Owned(self.1.into_owned()))
+    }
+
     /// Consumes this `Label`, returning the key and value.
     pub fn into_parts(self) -> (ScopedString, ScopedString) {
-        (self.0, self.1)
+        let key = match self.0 {
+            LabelKeyRepr::Str(key) => key,
+            LabelKeyRepr::Interned(key) => Cow::Borrowed(key.name),
+        };
+        (key, self.1)
+    }
+
+    /// Whether or not the value of this label matches the given glob pattern.
+    ///
+    /// In the pattern, `*` matches any number of characters, including none, and `?` matches
+    /// exactly one character.  All other characters must match literally, and the whole value
+    /// must be matched.
+    ///
+    /// ```rust
+    /// # use metrics_core::Label;
+    /// let label = Label::new("service", "web-frontend");
+    /// assert!(label.value_matches_glob("web-*"));
+    /// assert!(label.value_matches_glob("*-front???"));
+    /// assert!(!label.value_matches_glob("web"));
+    /// ```
+    pub fn value_matches_glob(&self, pattern: &str) -> bool {
+        glob_matches(pattern, self.value())
+    }
+
+    /// The value of this label, escaped for use in the Prometheus exposition format.
+    ///
+    /// Backslashes, double quotes, and newlines are escaped with a backslash, and everything else
+    /// is left as-is.  The value is borrowed if nothing needs to be escaped.
+    ///
+    /// ```rust
+    /// # use metrics_core::Label;
+    /// let label = Label::new("path", "C:\\temp\n\"x\"");
+    /// assert_eq!(label.value_escaped_prometheus(), "C:\\\\temp\\n\\\"x\\\"");
+    /// ```
+    pub fn value_escaped_prometheus(&self) -> Cow<'_, str> {
+        escape_with(
+            self.value(),
+            |c| c == '\\' || c == '"' || c == '\n',
+            |c, output| match c {
+                '\n' => output.push_str("\\n"),
+                c => {
+                    output.push('\\');
+                    output.push(c);
+                }
+            },
+        )
+    }
+
+    /// The value of this label, escaped for use inside a JSON string.
+    ///
+    /// Backslashes and double quotes are escaped with a backslash, and control characters are
+    /// escaped with their short form, such as `\t`, or as a `\u` escape sequence if they don't
+    /// have one.  The value is borrowed if nothing needs to be escaped.
+    ///
+    /// ```rust
+    /// # use metrics_core::Label;
+    /// let label = Label::new("path", "a\tb\u{1}");
+    /// assert_eq!(label.value_escaped_json(), "a\\tb\\u0001");
+    /// ```
+    pub fn value_escaped_json(&self) -> Cow<'_, str> {
+        escape_with(
+            self.value(),
+            |c| c == '\\' || c == '"' || (c as u32) < 0x20,
+            |c, output| match c {
+                '\u{8}' => output.push_str("\\b"),
+                '\u{c}' => output.push_str("\\f"),
+                '\n' => output.push_str("\\n"),
+                '\r' => output.push_str("\\r"),
+                '\t' => output.push_str("\\t"),
+                '\\' | '"' => {
+                    output.push('\\');
+                    output.push(c);
+                }
+                c => output.push_str(&format!("\\u{:04x}", c as u32)),
+            },
+        )
+    }
+}
+
+impl PartialEq for Label {
+    fn eq(&self, other: &Self) -> bool {
+        let same_key = match (&self.0, &other.0) {
+            // Every schema interns a key as the same id, so only keys which aren't both interned
+            // need their strings compared.
+            (LabelKeyRepr::Interned(a), LabelKeyRepr::Interned(b)) => a.id == b.id,
+            _ => self.key() == other.key(),
+        };
+        same_key && self.1 == other.1
+    }
+}
+
+impl Eq for Label {}
+
+impl Hash for Label {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Hashed by contents, so that interned keys hash the same as their string forms.
+        self.key().hash(state);
+        self.1.hash(state);
+    }
+}
+
+impl fmt::Debug for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Label")
+            .field(&self.key())
+            .field(&self.1)
+            .finish()
+    }
+}
+
+/// Escapes every character in `value` for which `needs_escape` is true, using `escape` to write
+/// its escaped form, borrowing `value` when there is nothing to escape.
+fn escape_with<'a, F, E>(value: &'a str, needs_escape: F, escape: E) -> Cow<'a, str>
+where
+    F: Fn(char) -> bool,
+    E: Fn(char, &mut String),
+{
+    let first = match value.find(&needs_escape) {
+        Some(idx) => idx,
+        None => return Cow::Borrowed(value),
+    };
+
+    let mut output = String::with_capacity(value.len() + 8);
+    output.push_str(&value[..first]);
+    for c in value[first..].chars() {
+        if needs_escape(c) {
+            escape(c, &mut output);
+        } else {
+            output.push(c);
+        }
     }
+    Cow::Owned(output)
+}
+
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let value = value.chars().collect::<Vec<_>>();
+
+    let (mut p, mut v) = (0, 0);
+    // Position of the last `*` seen in the pattern, and of the value when it was seen, so that we
+    // can backtrack and have the star consume one more character when a later match fails.
+    let mut backtrack = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star_p, star_v)) = backtrack {
+            p = star_p + 1;
+            v = star_v + 1;
+            backtrack = Some((star_p, star_v + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
 }
 
 /// A metric key.
@@ -95,6 +464,9 @@ impl Key {
     }
 
     /// Creates a `Key` from a name and vector of `Label`s.
+    ///
+    /// Neither the name nor the labels are copied when they are already in their final form: a
+    /// `&'static str` name is kept borrowed, and a `Vec<Label>` is used as-is.
     pub fn from_name_and_labels<N, L>(name: N, labels: L) -> Self
     where
         N: Into<ScopedString>,
@@ -106,14 +478,123 @@ impl Key {
         }
     }
 
+    /// Creates a `Key` from a name and labels which have already been built.
+    ///
+    /// This is the inverse of [`into_parts`](Key::into_parts), and is the cheapest way to build a
+    /// key: the name and labels are moved into the key as-is, without any conversion or
+    /// allocation.  In particular, a borrowed name stays borrowed, which suits recorder layers that
+    /// pass along the static name of an incoming key while computing new labels for it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// # use std::borrow::Cow;
+    /// let incoming = Key::from_name("requests");
+    /// let (name, _) = incoming.into_parts();
+    ///
+    /// let key = Key::from_parts(name, vec![Label::new("region", "us-east")]);
+    /// match key.name() {
+    ///     Cow::Borrowed(name) => assert_eq!(name, "requests"),
+    ///     Cow::Owned(_) => panic!("name should still be borrowed"),
+    /// }
+    /// ```
+    pub fn from_parts(name: ScopedString, labels: Vec<Label>) -> Self {
+        Key { name, labels }
+    }
+
     /// Adds a new set of labels to this key.
     ///
-    /// New labels will be appended to any existing labels.
-    pub fn add_labels<L>(&mut self, new_labels: L)
+    /// New labels will be appended to any existing labels.  Returns the key itself so that calls
+    /// can be chained.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// let mut key = Key::from_name("requests");
+    /// key.add_labels(vec![Label::new("service", "api")])
+    ///     .add_labels(vec![Label::new("region", "us-east")]);
+    ///
+    /// let labels = key.labels().map(|l| l.key()).collect::<Vec<_>>();
+    /// assert_eq!(labels, vec!["service", "region"]);
+    /// ```
+    pub fn add_labels<L>(&mut self, new_labels: L) -> &mut Self
     where
         L: IntoLabels,
     {
         self.labels.extend(new_labels.into_labels());
+        self
+    }
+
+    /// Adds a single label to this key.
+    ///
+    /// This is a cheaper alternative to [`add_labels`](Key::add_labels) when only one label is
+    /// being added, as no intermediate collection of labels is built.  Returns the key itself so
+    /// that calls can be chained.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// let mut key = Key::from_name("requests");
+    /// key.append_label(("region", "us-east"));
+    ///
+    /// assert_eq!(key, Key::from_name_and_labels("requests", vec![Label::new("region", "us-east")]));
+    /// ```
+    pub fn append_label<L>(&mut self, label: L) -> &mut Self
+    where
+        L: Into<Label>,
+    {
+        self.labels.push(label.into());
+        self
+    }
+
+    /// Replaces the value of the label with the given key.
+    ///
+    /// Returns whether or not a label with the given key was found.  The label keeps its position
+    /// among the other labels, so the key is otherwise left as it was.  If several labels share
+    /// the given key, they are all updated.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// let mut key = Key::from_name_and_labels("requests", vec![Label::new("user", "1234")]);
+    /// assert!(key.replace_label_value("user", "other"));
+    /// assert!(!key.replace_label_value("region", "other"));
+    ///
+    /// assert_eq!(key, Key::from_name_and_labels("requests", vec![Label::new("user", "other")]));
+    /// ```
+    pub fn replace_label_value<V>(&mut self, key: &str, new_value: V) -> bool
+    where
+        V: Into<ScopedString>,
+    {
+        let new_value = new_value.into();
+        let mut found = false;
+        for label in self.labels.iter_mut().filter(|label| label.key() == key) {
+            label.1 = new_value.clone();
+            found = true;
+        }
+        found
+    }
+
+    /// Drops every label beyond the first `max`.
+    ///
+    /// Labels are kept in the order they were added, so the most significant labels should come
+    /// first.  Keys with `max` labels or fewer are left as they are.  This is meant as a last
+    /// resort against labels exploding the number of series, as the key still gets recorded
+    /// rather than rejected.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// let mut key = Key::from_name_and_labels(
+    ///     "requests",
+    ///     vec![Label::new("service", "api"), Label::new("user", "1234")],
+    /// );
+    /// key.truncate_labels(1);
+    ///
+    /// assert_eq!(key, Key::from_name_and_labels("requests", vec![Label::new("service", "api")]));
+    /// ```
+    pub fn truncate_labels(&mut self, max: usize) {
+        self.labels.truncate(max);
     }
 
     /// Name of this key.
@@ -121,6 +602,22 @@ impl Key {
         self.name.clone()
     }
 
+    /// Whether or not the name of this key is equal to the given name.
+    ///
+    /// Labels are ignored, so this matches every labeled variant of a metric.  Unlike comparing
+    /// against [`name`](Key::name), the name is not cloned.
+    pub fn eq_name(&self, name: &str) -> bool {
+        self.name.as_ref() == name
+    }
+
+    /// Whether or not the name of this key matches the given glob pattern.
+    ///
+    /// Patterns use the same syntax as [`Label::value_matches_glob`], and must match the whole
+    /// name.
+    pub fn name_matches_glob(&self, pattern: &str) -> bool {
+        glob_matches(pattern, &self.name)
+    }
+
     /// Labels of this key, if they exist.
     pub fn labels(&self) -> Iter<Label> {
         self.labels.iter()
@@ -142,6 +639,386 @@ impl Key {
     pub fn into_parts(self) -> (ScopedString, Vec<Label>) {
         (self.name, self.labels)
     }
+
+    /// Converts this `Key` into one which owns all of its strings.
+    ///
+    /// Names and labels can borrow static strings, which is usually what makes creating keys
+    /// cheap.  A static string is only as static as the code it lives in, though: strings from a
+    /// dynamically loaded library go away when the library is unloaded.  Recorders which keep
+    /// keys in a long-lived map can call this first, so that the keys they keep never point into
+    /// memory they don't control.
+    ///
+    /// Label keys interned with [`LabelKeys`] are copied into owned strings too, as a schema's
+    /// strings can live in a dynamically loaded library just the same.  The owned labels are
+    /// still equal to the interned ones.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// # use std::borrow::Cow;
+    /// let key = Key::from_name_and_labels("requests", vec![Label::new("service", "api")]);
+    /// let owned = key.clone().into_owned();
+    /// assert_eq!(owned, key);
+    ///
+    /// let (name, _) = owned.into_parts();
+    /// assert!(match name {
+    ///     Cow::Owned(_) => true,
+    ///     Cow::Borrowed(_) => false,
+    /// });
+    /// ```
+    pub fn into_owned(self) -> Key {
+        Key {
+            name: Cow::Owned(self.name.into_owned()),
+            labels: self.labels.into_iter().map(Label::into_owned).collect(),
+        }
+    }
+
+    /// Computes a stable hash of the name of this `Key`, ignoring its labels.
+    ///
+    /// Like [`labels_hash`](Key::labels_hash), this is computed with FNV-1a and no random seed,
+    /// so it is the same across runs.  Exporters can use it to cheaply group all series with the
+    /// same name, and can combine it with `labels_hash` to identify a series without hashing the
+    /// whole key again.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// let api = Key::from_name_and_labels("requests", vec![Label::new("service", "api")]);
+    /// let db = Key::from_name_and_labels("requests", vec![Label::new("service", "db")]);
+    /// assert_eq!(api.name_hash(), db.name_hash());
+    /// ```
+    pub fn name_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+        hasher.write(self.name.as_bytes());
+        hasher.finish()
+    }
+
+    /// Computes a stable hash of the labels of this `Key`, ignoring its name.
+    ///
+    /// Labels are sorted before hashing, so the order they were added in does not matter.  The
+    /// hash is computed with FNV-1a and no random seed, so it is the same across runs and
+    /// processes, and can be used to consistently route all series with the same label set to the
+    /// same place.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// let requests = Key::from_name_and_labels("requests", vec![Label::new("shard", "1")]);
+    /// let errors = Key::from_name_and_labels("errors", vec![Label::new("shard", "1")]);
+    /// assert_eq!(requests.labels_hash(), errors.labels_hash());
+    /// ```
+    pub fn labels_hash(&self) -> u64 {
+        let labels = self.sorted_labels();
+
+        let mut hasher = Fnv1a::new();
+        for label in labels {
+            hasher.write(label.key().as_bytes());
+            hasher.write(&[0xff]);
+            hasher.write(label.value().as_bytes());
+            hasher.write(&[0xff]);
+        }
+        hasher.finish()
+    }
+
+    /// The labels of this `Key`, sorted by key and then by value.
+    fn sorted_labels(&self) -> Vec<&Label> {
+        let mut labels = self.labels.iter().collect::<Vec<_>>();
+        labels.sort_by(|a, b| (a.key(), a.value()).cmp(&(b.key(), b.value())));
+        labels
+    }
+
+    /// Renders this `Key` as a string, using the given options.
+    ///
+    /// Different backends expect different renderings of a labeled metric, such as
+    /// `name{k="v"}` or `name,k=v`, and [`RenderOptions`] controls the delimiters, quoting and
+    /// escaping used.  Keys without labels render as just their name.
+    pub fn render_with(&self, opts: &RenderOptions) -> String {
+        let mut output = self.name.to_string();
+        if self.labels.is_empty() {
+            return output;
+        }
+
+        output.push_str(&opts.labels_start);
+        for (i, label) in self.labels.iter().enumerate() {
+            if i > 0 {
+                output.push_str(&opts.label_separator);
+            }
+            output.push_str(label.key());
+            output.push_str(&opts.kv_separator);
+            if let Some(quote) = opts.quote {
+                output.push(quote);
+            }
+            opts.escape_into(label.value(), &mut output);
+            if let Some(quote) = opts.quote {
+                output.push(quote);
+            }
+        }
+        output.push_str(&opts.labels_end);
+        output
+    }
+
+    /// Renders this `Key` as a canonical string, which can be parsed back with
+    /// [`from_canonical_string`](Key::from_canonical_string).
+    ///
+    /// The string has the form `name|k1=v1,k2=v2`, or just `name` for keys without labels.
+    /// Labels are sorted, so keys which only differ in the order of their labels render the same,
+    /// making the string usable as a stable cache key.  Any `\`, `|`, `=` or `,` in the name,
+    /// label keys or label values is escaped with a backslash, so distinct keys never render the
+    /// same.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// let key = Key::from_name_and_labels(
+    ///     "requests",
+    ///     vec![Label::new("service", "api"), Label::new("path", "/a,b")],
+    /// );
+    /// assert_eq!(key.to_canonical_string(), "requests|path=/a\\,b,service=api");
+    /// ```
+    pub fn to_canonical_string(&self) -> String {
+        let labels = self.sorted_labels();
+
+        let mut output = escape_canonical(&self.name).into_owned();
+        for (i, label) in labels.iter().enumerate() {
+            output.push(if i == 0 { '|' } else { ',' });
+            output.push_str(&escape_canonical(label.key()));
+            output.push('=');
+            output.push_str(&escape_canonical(label.value()));
+        }
+        output
+    }
+
+    /// Parses a `Key` from its canonical string, as rendered by
+    /// [`to_canonical_string`](Key::to_canonical_string).
+    ///
+    /// Labels come back in sorted order.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the string is not a well-formed canonical string.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// let key = Key::from_canonical_string("requests|service=api").unwrap();
+    /// assert_eq!(key, Key::from_name_and_labels("requests", vec![Label::new("service", "api")]));
+    ///
+    /// assert!(Key::from_canonical_string("requests|service").is_err());
+    /// ```
+    pub fn from_canonical_string(s: &str) -> Result<Key, ParseError> {
+        let mut name = None;
+        let mut labels = Vec::new();
+        let mut label_key = None;
+        let mut current = String::new();
+
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(c) if is_canonical_delimiter(c) => current.push(c),
+                    _ => return Err(ParseError::InvalidEscape),
+                },
+                '|' if name.is_none() => name = Some(mem::replace(&mut current, String::new())),
+                '=' if name.is_some() && label_key.is_none() => {
+                    label_key = Some(mem::replace(&mut current, String::new()))
+                }
+                ',' if name.is_some() => {
+                    let key = label_key.take().ok_or(ParseError::MissingSeparator)?;
+                    labels.push(Label::new(key, mem::replace(&mut current, String::new())));
+                }
+                '|' | '=' | ',' => return Err(ParseError::UnexpectedDelimiter(c)),
+                c => current.push(c),
+            }
+        }
+
+        match name {
+            None => Ok(Key::from_name(current)),
+            Some(name) => {
+                let key = label_key.ok_or(ParseError::MissingSeparator)?;
+                labels.push(Label::new(key, current));
+                Ok(Key::from_name_and_labels(name, labels))
+            }
+        }
+    }
+}
+
+fn is_canonical_delimiter(c: char) -> bool {
+    c == '\\' || c == '|' || c == '=' || c == ','
+}
+
+fn escape_canonical(value: &str) -> Cow<'_, str> {
+    escape_with(value, is_canonical_delimiter, |c, output| {
+        output.push('\\');
+        output.push(c);
+    })
+}
+
+/// Errors during parsing of a [`Key`] from its canonical string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// A backslash was not followed by a character which needs escaping.
+    InvalidEscape,
+
+    /// A label was missing the `=` between its key and value.
+    MissingSeparator,
+
+    /// A delimiter appeared unescaped where it was not expected.
+    UnexpectedDelimiter(char),
+}
+
+impl Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::InvalidEscape => write!(f, "invalid escape sequence"),
+            ParseError::MissingSeparator => write!(f, "label is missing a '=' separator"),
+            ParseError::UnexpectedDelimiter(c) => write!(f, "unexpected unescaped '{}'", c),
+        }
+    }
+}
+
+/// A 64-bit FNV-1a hasher.
+///
+/// Unlike the standard library's default hasher, this is unseeded, so hashes are stable across
+/// runs.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Self {
+        Fnv1a(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Options for rendering a [`Key`] as a string.
+///
+/// The defaults match the Prometheus exposition format: `name{k1="v1",k2="v2"}`.
+///
+/// When escaping is enabled, label values have any backslashes, quote characters, and additional
+/// configured characters escaped with a backslash, while newlines are rendered as `\n`.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    labels_start: ScopedString,
+    labels_end: ScopedString,
+    label_separator: ScopedString,
+    kv_separator: ScopedString,
+    quote: Option<char>,
+    escape: bool,
+    escape_chars: Vec<char>,
+}
+
+impl RenderOptions {
+    /// Creates a new [`RenderOptions`] with default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the delimiters placed between the name and the labels, and after the labels.
+    ///
+    /// Defaults to `{` and `}`.
+    pub fn labels_delimiters<S, E>(mut self, start: S, end: E) -> Self
+    where
+        S: Into<ScopedString>,
+        E: Into<ScopedString>,
+    {
+        self.labels_start = start.into();
+        self.labels_end = end.into();
+        self
+    }
+
+    /// Sets the separator placed between individual labels.
+    ///
+    /// Defaults to `,`.
+    pub fn label_separator<S>(mut self, separator: S) -> Self
+    where
+        S: Into<ScopedString>,
+    {
+        self.label_separator = separator.into();
+        self
+    }
+
+    /// Sets the separator placed between the key and value of a label.
+    ///
+    /// Defaults to `=`.
+    pub fn kv_separator<S>(mut self, separator: S) -> Self
+    where
+        S: Into<ScopedString>,
+    {
+        self.kv_separator = separator.into();
+        self
+    }
+
+    /// Sets the character used to quote label values, if any.
+    ///
+    /// Defaults to `"`.
+    pub fn quote(mut self, quote: Option<char>) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Sets whether or not label values are escaped.
+    ///
+    /// Defaults to `true`.
+    pub fn escape(mut self, escape: bool) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Sets additional characters that must be escaped in label values.
+    ///
+    /// This is useful for unquoted formats, where a delimiter showing up in a label value would
+    /// otherwise be ambiguous.  Defaults to no additional characters.
+    pub fn escape_chars(mut self, chars: &[char]) -> Self {
+        self.escape_chars = chars.to_vec();
+        self
+    }
+
+    fn escape_into(&self, value: &str, output: &mut String) {
+        if !self.escape {
+            output.push_str(value);
+            return;
+        }
+
+        for c in value.chars() {
+            match c {
+                '\\' => output.push_str("\\\\"),
+                '\n' => output.push_str("\\n"),
+                c if Some(c) == self.quote || self.escape_chars.contains(&c) => {
+                    output.push('\\');
+                    output.push(c);
+                }
+                c => output.push(c),
+            }
+        }
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            labels_start: "{".into(),
+            labels_end: "}".into(),
+            label_separator: ",".into(),
+            kv_separator: "=".into(),
+            quote: Some('"'),
+            escape: true,
+            escape_chars: Vec::new(),
+        }
+    }
 }
 
 impl fmt::Display for Key {
@@ -152,13 +1029,89 @@ impl fmt::Display for Key {
             let kv_pairs = self
                 .labels
                 .iter()
-                .map(|label| format!("{} = {}", label.0, label.1))
+                .map(|label| format!("{} = {}", label.key(), label.1))
                 .collect::<Vec<_>>();
             write!(f, "Key({}, [{}])", self.name, kv_pairs.join(", "))
         }
     }
 }
 
+/// A [`Key`] which compares and hashes the same whatever order its labels were added in.
+///
+/// `Key` keeps its labels in the order they were added, and two keys are only equal if their
+/// labels are in the same order.  `UnorderedKey` wraps a `Key` and compares and hashes it over
+/// its labels in sorted order instead, so keys built up in different orders are treated as the
+/// same series, while the wrapped key still lists and displays its labels in the order they were
+/// added.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_core::{Key, Label, UnorderedKey};
+/// let a = Key::from_name_and_labels(
+///     "requests",
+///     vec![Label::new("service", "api"), Label::new("region", "eu")],
+/// );
+/// let b = Key::from_name_and_labels(
+///     "requests",
+///     vec![Label::new("region", "eu"), Label::new("service", "api")],
+/// );
+/// assert_ne!(a, b);
+///
+/// let (a, b) = (UnorderedKey::new(a), UnorderedKey::new(b));
+/// assert_eq!(a, b);
+/// assert_eq!(a.to_string(), "Key(requests, [service = api, region = eu])");
+/// assert_eq!(b.to_string(), "Key(requests, [region = eu, service = api])");
+/// ```
+#[derive(Clone, Debug)]
+pub struct UnorderedKey(Key);
+
+impl UnorderedKey {
+    /// Creates an `UnorderedKey` from a `Key`.
+    pub fn new(key: Key) -> Self {
+        UnorderedKey(key)
+    }
+
+    /// Gets a reference to the wrapped key, with its labels in the order they were added.
+    pub fn key(&self) -> &Key {
+        &self.0
+    }
+
+    /// Consumes this `UnorderedKey`, returning the wrapped key.
+    pub fn into_key(self) -> Key {
+        self.0
+    }
+}
+
+impl PartialEq for UnorderedKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.name == other.0.name
+            && self.0.labels.len() == other.0.labels.len()
+            && self.0.sorted_labels() == other.0.sorted_labels()
+    }
+}
+
+impl Eq for UnorderedKey {}
+
+impl Hash for UnorderedKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Hashed over the same sorted view that equality uses, so the two always agree.
+        self.0.name.hash(state);
+        self.0.sorted_labels().hash(state);
+    }
+}
+
+impl fmt::Display for UnorderedKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Key> for UnorderedKey {
+    fn from(key: Key) -> Self {
+        UnorderedKey::new(key)
+    }
+}
+
 impl From<String> for Key {
     fn from(name: String) -> Key {
         Key::from_name(name)
@@ -207,6 +1160,69 @@ where
     }
 }
 
+/// The kind of a metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MetricKind {
+    /// A counter.
+    Counter,
+
+    /// A gauge.
+    Gauge,
+
+    /// A histogram.
+    Histogram,
+}
+
+/// The unit of a metric.
+///
+/// Units are attached to a metric as metadata, with `Recorder::describe_with_meta` in the
+/// `metrics` crate, so that exporters can render its values in a fitting way, such as by scaling
+/// bucket bounds or adding a unit suffix to its name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    /// A plain count of things.
+    Count,
+
+    /// A percentage, from 0 to 100.
+    Percent,
+
+    /// Seconds.
+    Seconds,
+
+    /// Milliseconds.
+    Milliseconds,
+
+    /// Microseconds.
+    Microseconds,
+
+    /// Nanoseconds.
+    Nanoseconds,
+
+    /// Bytes.
+    Bytes,
+}
+
+impl Unit {
+    /// Name of the unit, such as `seconds` or `bytes`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unit::Count => "count",
+            Unit::Percent => "percent",
+            Unit::Seconds => "seconds",
+            Unit::Milliseconds => "milliseconds",
+            Unit::Microseconds => "microseconds",
+            Unit::Nanoseconds => "nanoseconds",
+            Unit::Bytes => "bytes",
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// A value that can be converted to `Label`s.
 pub trait IntoLabels {
     /// Consumes this value, turning it into a vector of `Label`s.
@@ -219,13 +1235,75 @@ impl IntoLabels for Vec<Label> {
     }
 }
 
+/// A collection of optional labels, with absent labels left out.
+///
+/// This allows a label to be added only when its value is known, without having to filter the
+/// labels first.  Iterators can be collected into a `Vec<Option<Label>>` first.
+///
+/// ```rust
+/// # use metrics_core::{IntoLabels, Label};
+/// let region: Option<&'static str> = None;
+/// let labels = vec![
+///     Some(Label::new("service", "api")),
+///     region.map(|region| Label::new("region", region)),
+/// ];
+/// assert_eq!(labels.into_labels(), vec![Label::new("service", "api")]);
+/// ```
+impl IntoLabels for Vec<Option<Label>> {
+    fn into_labels(self) -> Vec<Label> {
+        self.into_iter().flatten().collect()
+    }
+}
+
+/// Labels which may be absent altogether, with `None` treated as no labels.
+impl<L> IntoLabels for Option<L>
+where
+    L: IntoLabels,
+{
+    fn into_labels(self) -> Vec<Label> {
+        self.map(IntoLabels::into_labels).unwrap_or_default()
+    }
+}
+
 impl<T, L> IntoLabels for &T
 where
     Self: IntoIterator<Item = L>,
-    L: Into<Label>,
+    L: MaybeLabel,
 {
     fn into_labels(self) -> Vec<Label> {
-        self.into_iter().map(|l| l.into()).collect()
+        self.into_iter()
+            .filter_map(MaybeLabel::into_label)
+            .collect()
+    }
+}
+
+/// A value that may be converted to a `Label`, if present.
+///
+/// This is implemented for anything that can be converted into a `Label`, as well as for
+/// `Option<Label>`, so that a collection of either can be used as [`IntoLabels`].
+pub trait MaybeLabel {
+    /// Consumes this value, turning it into a `Label` if there is one.
+    fn into_label(self) -> Option<Label>;
+}
+
+impl<L> MaybeLabel for L
+where
+    L: Into<Label>,
+{
+    fn into_label(self) -> Option<Label> {
+        Some(self.into())
+    }
+}
+
+impl MaybeLabel for Option<Label> {
+    fn into_label(self) -> Option<Label> {
+        self
+    }
+}
+
+impl MaybeLabel for &Option<Label> {
+    fn into_label(self) -> Option<Label> {
+        self.clone()
     }
 }
 
@@ -251,6 +1329,64 @@ impl AsNanoseconds for Duration {
     }
 }
 
+/// Used to do an integer conversion for gauge values.
+///
+/// Gauges hold an `i64`, but it's common to have a gauge value at hand as some other integer type,
+/// such as a `usize` length or a `u64` byte count.  This trait lets gauges accept any integer
+/// type, so that the conversion happens in one place rather than with an `as` cast at every call
+/// site.
+///
+/// Every signed integer type, as well as `u8`, `u16`, and `u32`, converts losslessly.  `u64` and
+/// `usize` values above `i64::MAX` can't be represented, and saturate to `i64::MAX` instead of
+/// wrapping around to a negative value, as an `as` cast would.  In debug builds, such a value
+/// triggers a debug assertion, so the overflow is noticed during development.
+pub trait AsGaugeValue {
+    /// Performs the conversion.
+    fn as_gauge_value(&self) -> i64;
+}
+
+macro_rules! impl_as_gauge_value_lossless {
+    ($($ty:ty),*) => {
+        $(
+            impl AsGaugeValue for $ty {
+                fn as_gauge_value(&self) -> i64 {
+                    i64::from(*self)
+                }
+            }
+        )*
+    };
+}
+
+impl_as_gauge_value_lossless!(i8, i16, i32, i64, u8, u16, u32);
+
+impl AsGaugeValue for isize {
+    fn as_gauge_value(&self) -> i64 {
+        // `isize` is at most 64 bits wide on every supported platform.
+        *self as i64
+    }
+}
+
+impl AsGaugeValue for u64 {
+    fn as_gauge_value(&self) -> i64 {
+        debug_assert!(
+            *self <= std::i64::MAX as u64,
+            "gauge value {} is too large for an i64",
+            self
+        );
+        if *self > std::i64::MAX as u64 {
+            std::i64::MAX
+        } else {
+            *self as i64
+        }
+    }
+}
+
+impl AsGaugeValue for usize {
+    fn as_gauge_value(&self) -> i64 {
+        (*self as u64).as_gauge_value()
+    }
+}
+
 /// A value that observes metrics.
 pub trait Observer {
     /// The method called when a counter is observed.
@@ -278,8 +1414,54 @@ pub trait Observer {
     ///
     /// There is no guarantee that this method will not be called multiple times for the same key.
     fn observe_histogram(&mut self, key: Key, values: &[u64]);
+
+    /// The method called when a histogram holding weighted values is observed.
+    ///
+    /// Each value is given as a pair of the value and the number of times it was recorded, which
+    /// lets histograms fed with pre-counted data be observed without expanding every value.
+    /// Observers which can add a value to their histogram view many times at once should
+    /// implement this.
+    ///
+    /// The default implementation expands the values, and passes them along to
+    /// [`observe_histogram`](Observer::observe_histogram) a bounded chunk at a time.
+    fn observe_histogram_weighted(&mut self, key: Key, values: &[(u64, u64)]) {
+        let mut chunk = Vec::with_capacity(WEIGHTED_CHUNK_SIZE);
+        for (value, count) in values {
+            for _ in 0..*count {
+                chunk.push(*value);
+                if chunk.len() == WEIGHTED_CHUNK_SIZE {
+                    self.observe_histogram(key.clone(), &chunk);
+                    chunk.clear();
+                }
+            }
+        }
+        if !chunk.is_empty() {
+            self.observe_histogram(key, &chunk);
+        }
+    }
+
+    /// The method called when the time a counter was created at is observed.
+    ///
+    /// Sources which track when their counters were created call this alongside
+    /// [`observe_counter`](Observer::observe_counter), so that observers can emit it, such as
+    /// the OpenMetrics `_created` sample, and rates are calculated correctly across restarts.
+    ///
+    /// Observers which don't emit creation times can ignore this, which is the default behavior.
+    fn observe_counter_created(&mut self, _key: Key, _created: SystemTime) {}
+
+    /// The method called when the unit of a metric is observed.
+    ///
+    /// Sources which know the [`Unit`] a metric was described with call this before observing
+    /// its value, so that observers can render the value to suit, such as with bucket bounds
+    /// scaled to the unit.
+    ///
+    /// Observers which don't use units can ignore this, which is the default behavior.
+    fn observe_unit(&mut self, _key: Key, _unit: Unit) {}
 }
 
+/// Number of values expanded at a time by the default [`Observer::observe_histogram_weighted`].
+const WEIGHTED_CHUNK_SIZE: usize = 1024;
+
 /// A value that can build an observer.
 ///
 /// Observers are containers used for rendering a snapshot in a particular format.
@@ -327,6 +1509,21 @@ pub trait Observe {
 ///
 /// takes_labels("requests_processed", labels!("request_type" => "admin"));
 /// ```
+///
+/// A trailing expression implementing [`IntoLabels`] can also be given, which is useful when
+/// some labels are known at the call site and the rest are built at runtime.  Its labels are
+/// appended after any inline labels.  As with [`Key::add_labels`], labels are kept in the order
+/// given, and labels with duplicate keys are not removed.
+///
+/// ```rust
+/// # #[macro_use] extern crate metrics_core;
+/// # use metrics_core::Label;
+/// let dynamic = vec![Label::new("user", "jane")];
+/// let labels = labels!("service" => "admin", "region" => "us-east", dynamic);
+///
+/// let keys = labels.iter().map(|l| l.key()).collect::<Vec<_>>();
+/// assert_eq!(keys, vec!["service", "region", "user"]);
+/// ```
 #[macro_export]
 macro_rules! labels {
     (@ { $($out:expr),* $(,)* } $(,)*) => {
@@ -341,7 +1538,718 @@ macro_rules! labels {
         $crate::labels!(@ { $($out),+, $crate::Label::new($k, $v) } $($rest)*)
     };
 
+    (@ { $($out:expr),* } $splat:expr, $(,)*) => {{
+        let mut labels: std::vec::Vec<$crate::Label> = std::vec![ $($out),* ];
+        labels.extend($crate::IntoLabels::into_labels($splat));
+        labels
+    }};
+
     ($($args:tt)*) => {
         $crate::labels!(@ { } $($args)*, )
     };
 }
+
+/// Checks a [`Key`] against a pattern.
+///
+/// Routing and filtering rules usually come down to "metrics named like this, with labels like
+/// that", which is verbose to spell out against a `Key` by hand.  This macro takes the key, an
+/// optional `name:` glob pattern, and any number of `"label" => "value"` pairs, and evaluates to
+/// whether the key matches all of them.  Label values are glob patterns too, and a label pair
+/// matches if any label of the key with the given label key has a matching value.
+///
+/// Patterns use the syntax of [`Label::value_matches_glob`]: `*` matches any number of characters
+/// and `?` matches exactly one.
+///
+/// # Examples
+/// ```rust
+/// # #[macro_use] extern crate metrics_core;
+/// # use metrics_core::{Key, Label};
+/// let key = Key::from_name_and_labels(
+///     "http.requests",
+///     vec![Label::new("env", "prod"), Label::new("code", "503")],
+/// );
+///
+/// assert!(key_matches!(key, name: "http.*"));
+/// assert!(key_matches!(key, name: "http.*", "env" => "prod"));
+/// assert!(key_matches!(key, "code" => "5??"));
+/// assert!(!key_matches!(key, name: "http.*", "env" => "staging"));
+/// assert!(!key_matches!(key, name: "db.*", "env" => "prod"));
+/// ```
+#[macro_export]
+macro_rules! key_matches {
+    ($key:expr, name: $name:expr $(, $k:expr => $v:expr)* $(,)?) => {{
+        let key: &$crate::Key = &$key;
+        key.name_matches_glob($name) $(
+            && key.labels().any(|label| label.key() == $k && label.value_matches_glob($v))
+        )*
+    }};
+
+    ($key:expr $(, $k:expr => $v:expr)* $(,)?) => {{
+        #[allow(unused_variables)]
+        let key: &$crate::Key = &$key;
+        true $(
+            && key.labels().any(|label| label.key() == $k && label.value_matches_glob($v))
+        )*
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        take_label_key_ids, AsGaugeValue, Key, Label, LabelKeys, Observer, ParseError,
+        RenderOptions, Unit, UnorderedKey,
+    };
+    use std::borrow::Cow;
+    use std::collections::{hash_map::DefaultHasher, HashMap};
+    use std::hash::{Hash, Hasher};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_labels_macro_splat() {
+        let dynamic = vec![Label::new("user", "jane"), Label::new("service", "other")];
+        let labels = labels!("service" => "admin", dynamic.clone());
+        assert_eq!(
+            labels,
+            vec![
+                Label::new("service", "admin"),
+                Label::new("user", "jane"),
+                Label::new("service", "other"),
+            ]
+        );
+
+        let only_splat = labels!(dynamic.clone());
+        assert_eq!(only_splat, dynamic);
+
+        let pairs = labels!("service" => "admin", &[("user", "jane")],);
+        assert_eq!(
+            pairs,
+            vec![Label::new("service", "admin"), Label::new("user", "jane")]
+        );
+
+        let inline = labels!("service" => "admin", "user" => "jane");
+        assert_eq!(inline.len(), 2);
+    }
+
+    #[test]
+    fn test_key_name_hash() {
+        let api = Key::from_name_and_labels("requests", vec![Label::new("service", "api")]);
+        let db = Key::from_name_and_labels("requests", vec![Label::new("service", "db")]);
+        assert_eq!(api.name_hash(), db.name_hash());
+        assert_eq!(api.name_hash(), Key::from_name("requests").name_hash());
+        assert_ne!(api.name_hash(), Key::from_name("errors").name_hash());
+
+        // Known inputs are stable across runs.
+        assert_eq!(Key::from_name("a").name_hash(), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_key_labels_hash() {
+        let labels = vec![
+            Label::new("service", "api"),
+            Label::new("region", "us-east"),
+        ];
+        let requests = Key::from_name_and_labels("requests", labels.clone());
+        let errors = Key::from_name_and_labels("errors", labels);
+        assert_eq!(requests.labels_hash(), errors.labels_hash());
+
+        // Label order does not matter.
+        let reordered = Key::from_name_and_labels(
+            "requests",
+            vec![
+                Label::new("region", "us-east"),
+                Label::new("service", "api"),
+            ],
+        );
+        assert_eq!(requests.labels_hash(), reordered.labels_hash());
+
+        // Different labels hash differently, including when only the split between key and value
+        // changes.
+        let other = Key::from_name_and_labels("requests", vec![Label::new("service", "db")]);
+        assert_ne!(requests.labels_hash(), other.labels_hash());
+        let a = Key::from_name_and_labels("requests", vec![Label::new("ab", "c")]);
+        let b = Key::from_name_and_labels("requests", vec![Label::new("a", "bc")]);
+        assert_ne!(a.labels_hash(), b.labels_hash());
+
+        // No labels hashes to the FNV-1a offset basis, and known inputs are stable across runs.
+        assert_eq!(
+            Key::from_name("requests").labels_hash(),
+            0xcbf2_9ce4_8422_2325
+        );
+        assert_eq!(
+            Key::from_name_and_labels("x", vec![Label::new("a", "b")]).labels_hash(),
+            0xd2b3_7181_9297_f98a
+        );
+    }
+
+    #[test]
+    fn test_key_render_with() {
+        let key = Key::from_name_and_labels(
+            "requests",
+            vec![
+                Label::new("service", "api"),
+                Label::new("path", "/a\"b\\c\n"),
+            ],
+        );
+
+        let prometheus = RenderOptions::new();
+        assert_eq!(
+            key.render_with(&prometheus),
+            "requests{service=\"api\",path=\"/a\\\"b\\\\c\\n\"}"
+        );
+
+        let influx = RenderOptions::new()
+            .labels_delimiters(",", "")
+            .quote(None)
+            .escape_chars(&[',', '=', ' ']);
+        let key = Key::from_name_and_labels(
+            "requests",
+            vec![Label::new("service", "api"), Label::new("tag", "a=b, c")],
+        );
+        assert_eq!(
+            key.render_with(&influx),
+            "requests,service=api,tag=a\\=b\\,\\ c"
+        );
+
+        let dotted = RenderOptions::new()
+            .labels_delimiters(".", "")
+            .label_separator(".")
+            .kv_separator(".")
+            .quote(None)
+            .escape(false);
+        assert_eq!(key.render_with(&dotted), "requests.service.api.tag.a=b, c");
+
+        let unlabeled = Key::from_name("requests");
+        assert_eq!(unlabeled.render_with(&prometheus), "requests");
+    }
+
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_take_label_key_ids() {
+        let next = AtomicU32::new(0);
+        assert_eq!(take_label_key_ids(&next, 3), Some(0));
+        assert_eq!(take_label_key_ids(&next, 2), Some(3));
+        assert_eq!(next.load(Ordering::Relaxed), 5);
+
+        // Running out leaves the ids as they were, rather than wrapping around to ones in use.
+        next.store(u32::max_value() - 2, Ordering::Relaxed);
+        assert_eq!(take_label_key_ids(&next, 3), None);
+        assert_eq!(next.load(Ordering::Relaxed), u32::max_value() - 2);
+        assert_eq!(take_label_key_ids(&next, 2), Some(u32::max_value() - 2));
+        assert_eq!(take_label_key_ids(&next, 1), None);
+        assert_eq!(take_label_key_ids(&next, 0), Some(u32::max_value()));
+    }
+
+    #[test]
+    fn test_interned_label_keys() {
+        static SCHEMA: &[&str] = &["method", "status", "route"];
+        let schema = LabelKeys::new(SCHEMA);
+        let other = LabelKeys::new(&["status"]);
+
+        let interned = schema.label("status", "200");
+        assert_eq!(interned.interned_key(), schema.get("status"));
+        assert_eq!(interned.key(), "status");
+
+        // Interned and string keys are interchangeable, whichever schema interned them.
+        let string = Label::new("status", "200");
+        assert!(string.interned_key().is_none());
+        assert_eq!(interned, string);
+        assert_eq!(string, interned);
+        assert_eq!(hash_of(&interned), hash_of(&string));
+        assert_eq!(interned, other.label("status", "200"));
+        assert_eq!(schema.get("status"), other.get("status"));
+        assert_ne!(interned, schema.label("status", "500"));
+        assert_ne!(interned, schema.label("method", "200"));
+        assert_eq!(format!("{:?}", interned), format!("{:?}", string));
+
+        let key = Key::from_name_and_labels("requests", vec![interned.clone()]);
+        let string_key = Key::from_name_and_labels("requests", vec![string]);
+        assert_eq!(key, string_key);
+        assert_eq!(hash_of(&key), hash_of(&string_key));
+        assert_eq!(
+            interned.into_parts(),
+            (Cow::from("status"), Cow::from("200"))
+        );
+
+        // Keys outside of the schema are stored as strings.
+        let unknown = schema.label("region", "us-east");
+        assert!(unknown.interned_key().is_none());
+        assert_eq!(unknown, Label::new("region", "us-east"));
+
+        let mut ids = schema.iter().map(|key| key.id()).collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(
+            schema.iter().map(|key| key.as_str()).collect::<Vec<_>>(),
+            SCHEMA.to_vec()
+        );
+    }
+
+    #[test]
+    fn test_unordered_key() {
+        let labels = vec![
+            Label::new("service", "api"),
+            Label::new("region", "eu"),
+            Label::new("shard", "1"),
+        ];
+        let mut reversed = labels.clone();
+        reversed.reverse();
+
+        let a = UnorderedKey::new(Key::from_name_and_labels("requests", labels.clone()));
+        let b = UnorderedKey::from(Key::from_name_and_labels("requests", reversed.clone()));
+        assert_ne!(a.key(), b.key());
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+
+        // Each key still lists its labels in the order they were added.
+        assert_eq!(a.key().labels().cloned().collect::<Vec<_>>(), labels);
+        assert_eq!(
+            b.clone().into_key().labels().cloned().collect::<Vec<_>>(),
+            reversed
+        );
+        assert_eq!(
+            b.to_string(),
+            "Key(requests, [shard = 1, region = eu, service = api])"
+        );
+
+        let mut totals = HashMap::new();
+        *totals.entry(a.clone()).or_insert(0) += 1;
+        *totals.entry(b).or_insert(0) += 1;
+        assert_eq!(totals[&a], 2);
+
+        let other_name = UnorderedKey::new(Key::from_name_and_labels("errors", labels.clone()));
+        let fewer = UnorderedKey::new(Key::from_name_and_labels("requests", labels[..2].to_vec()));
+        let other_value = UnorderedKey::new(Key::from_name_and_labels(
+            "requests",
+            vec![
+                Label::new("service", "api"),
+                Label::new("region", "us"),
+                Label::new("shard", "1"),
+            ],
+        ));
+        assert_ne!(a, other_name);
+        assert_ne!(a, fewer);
+        assert_ne!(a, other_value);
+    }
+
+    fn is_owned(value: Cow<'static, str>) -> bool {
+        match value {
+            Cow::Owned(_) => true,
+            Cow::Borrowed(_) => false,
+        }
+    }
+
+    #[test]
+    fn test_key_into_owned() {
+        let schema = LabelKeys::new(&["method"]);
+        let key = Key::from_name_and_labels(
+            "requests",
+            vec![
+                Label::new("service", "api"),
+                Label::new(String::from("region"), "eu"),
+                schema.label("method", "GET"),
+            ],
+        );
+
+        let owned = key.clone().into_owned();
+        assert_eq!(owned, key);
+
+        // Owned keys can be kept around without borrowing anything from the original key.
+        let mut store: Vec<Key> = Vec::new();
+        store.push(owned);
+        drop(key);
+
+        let (name, labels) = store.pop().unwrap().into_parts();
+        assert!(is_owned(name));
+        // Interned keys are copied too, rather than pointing into the schema.
+        assert!(labels[2].interned_key().is_none());
+        assert_eq!(labels[2], schema.label("method", "GET"));
+        for label in labels {
+            let (key, value) = label.into_parts();
+            assert!(is_owned(key));
+            assert!(is_owned(value));
+        }
+    }
+
+    #[test]
+    fn test_label_value_matches_glob() {
+        let label = Label::new("service", "web-frontend");
+
+        assert!(label.value_matches_glob("web-frontend"));
+        assert!(!label.value_matches_glob("web-backend"));
+        assert!(!label.value_matches_glob("web-frontend2"));
+
+        assert!(label.value_matches_glob("web-*"));
+        assert!(label.value_matches_glob("*frontend"));
+        assert!(label.value_matches_glob("web*end"));
+        assert!(label.value_matches_glob("w*-*n*d"));
+        assert!(label.value_matches_glob("*"));
+        assert!(label.value_matches_glob("**"));
+        assert!(!label.value_matches_glob("api-*"));
+        assert!(!label.value_matches_glob("*backend"));
+        assert!(!label.value_matches_glob("web*x*"));
+
+        assert!(label.value_matches_glob("web-f?ontend"));
+        assert!(label.value_matches_glob("???-*"));
+        assert!(!label.value_matches_glob("web-frontend?"));
+
+        let empty = Label::new("service", "");
+        assert!(empty.value_matches_glob(""));
+        assert!(empty.value_matches_glob("*"));
+        assert!(!empty.value_matches_glob("?"));
+
+        let unicode = Label::new("city", "zürich");
+        assert!(unicode.value_matches_glob("z?rich"));
+    }
+
+    #[test]
+    fn test_key_append_label() {
+        let mut unlabeled = Key::from_name("requests");
+        unlabeled.append_label(Label::new("region", "us-east"));
+        assert_eq!(
+            unlabeled,
+            Key::from_name_and_labels("requests", vec![Label::new("region", "us-east")])
+        );
+
+        let mut labeled = Key::from_name_and_labels("requests", vec![Label::new("service", "api")]);
+        labeled
+            .append_label(("region", "us-east"))
+            .append_label(("zone", "b"));
+        let labels = labeled
+            .labels()
+            .map(|l| (l.key(), l.value()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![("service", "api"), ("region", "us-east"), ("zone", "b")]
+        );
+    }
+
+    #[test]
+    fn test_key_replace_label_value() {
+        let mut key = Key::from_name_and_labels(
+            "requests",
+            vec![Label::new("user", "1234"), Label::new("code", "200")],
+        );
+        assert!(key.replace_label_value("user", String::from("other")));
+        assert_eq!(
+            key,
+            Key::from_name_and_labels(
+                "requests",
+                vec![Label::new("user", "other"), Label::new("code", "200")],
+            )
+        );
+
+        // Replacing a missing label leaves the key as it was.
+        assert!(!key.replace_label_value("region", "us-east"));
+        assert_eq!(key.labels().count(), 2);
+
+        let mut duplicated = Key::from_name_and_labels(
+            "requests",
+            vec![Label::new("user", "1"), Label::new("user", "2")],
+        );
+        assert!(duplicated.replace_label_value("user", "other"));
+        assert!(duplicated.labels().all(|l| l.value() == "other"));
+
+        let mut unlabeled = Key::from_name("requests");
+        assert!(!unlabeled.replace_label_value("user", "other"));
+        assert_eq!(unlabeled, Key::from_name("requests"));
+    }
+
+    #[test]
+    fn test_key_canonical_string() {
+        let keys = vec![
+            Key::from_name("requests"),
+            Key::from_name(""),
+            Key::from_name_and_labels("requests", vec![Label::new("service", "api")]),
+            Key::from_name_and_labels(
+                "a|b=c,d\\e",
+                vec![
+                    Label::new("k|1", "v=1"),
+                    Label::new("k,2", "v\\2,"),
+                    Label::new("k3", ""),
+                    Label::new("", "|"),
+                ],
+            ),
+        ];
+        for key in keys {
+            let canonical = key.to_canonical_string();
+            let parsed = Key::from_canonical_string(&canonical).unwrap();
+            assert_eq!(parsed.to_canonical_string(), canonical);
+            assert!(parsed.eq_name(&key.name()));
+
+            let mut expected = key.labels().cloned().collect::<Vec<_>>();
+            expected.sort_by(|a, b| (a.key(), a.value()).cmp(&(b.key(), b.value())));
+            assert_eq!(parsed.labels().cloned().collect::<Vec<_>>(), expected);
+        }
+
+        // Label order doesn't matter, but delimiters inside names and values do.
+        let forward =
+            Key::from_name_and_labels("requests", vec![Label::new("a", "1"), Label::new("b", "2")]);
+        let reversed =
+            Key::from_name_and_labels("requests", vec![Label::new("b", "2"), Label::new("a", "1")]);
+        assert_eq!(
+            forward.to_canonical_string(),
+            reversed.to_canonical_string()
+        );
+        let merged = Key::from_name_and_labels("requests", vec![Label::new("a", "1,b=2")]);
+        assert_ne!(forward.to_canonical_string(), merged.to_canonical_string());
+    }
+
+    #[test]
+    fn test_key_from_canonical_string_errors() {
+        assert_eq!(
+            Key::from_canonical_string("requests\\x"),
+            Err(ParseError::InvalidEscape)
+        );
+        assert_eq!(
+            Key::from_canonical_string("requests\\"),
+            Err(ParseError::InvalidEscape)
+        );
+        assert_eq!(
+            Key::from_canonical_string("requests|"),
+            Err(ParseError::MissingSeparator)
+        );
+        assert_eq!(
+            Key::from_canonical_string("requests|a=1,b"),
+            Err(ParseError::MissingSeparator)
+        );
+        assert_eq!(
+            Key::from_canonical_string("requests|a=1=2"),
+            Err(ParseError::UnexpectedDelimiter('='))
+        );
+        assert_eq!(
+            Key::from_canonical_string("requests|a=1|b=2"),
+            Err(ParseError::UnexpectedDelimiter('|'))
+        );
+        assert_eq!(
+            Key::from_canonical_string("a,b"),
+            Err(ParseError::UnexpectedDelimiter(','))
+        );
+    }
+
+    #[test]
+    fn test_key_truncate_labels() {
+        let labels = (0..10)
+            .map(|i| Label::new(format!("key{}", i), format!("value{}", i)))
+            .collect::<Vec<_>>();
+        let mut key = Key::from_name_and_labels("requests", labels.clone());
+
+        key.truncate_labels(3);
+        assert_eq!(
+            key,
+            Key::from_name_and_labels("requests", labels[..3].to_vec())
+        );
+
+        // Keys that are already short enough are left alone.
+        key.truncate_labels(5);
+        assert_eq!(key.labels().count(), 3);
+
+        key.truncate_labels(0);
+        assert_eq!(key, Key::from_name("requests"));
+    }
+
+    #[test]
+    fn test_key_matches_macro() {
+        let key = Key::from_name_and_labels(
+            "http.requests",
+            vec![
+                Label::new("env", "prod"),
+                Label::new("code", "503"),
+                Label::new("code", "200"),
+            ],
+        );
+        let by_ref = &key;
+
+        assert!(key_matches!(key, name: "http.requests"));
+        assert!(key_matches!(by_ref, name: "http.*",));
+        assert!(!key_matches!(key, name: "http"));
+        assert!(!key_matches!(key, name: "*.responses"));
+
+        assert!(key_matches!(key, name: "http.*", "env" => "prod"));
+        assert!(key_matches!(key, name: "*", "env" => "p*", "code" => "5??"));
+        assert!(!key_matches!(key, name: "http.*", "env" => "staging"));
+        assert!(!key_matches!(key, name: "db.*", "env" => "prod"));
+
+        // Any label with the given key can match, and missing labels never do.
+        assert!(key_matches!(key, "code" => "200"));
+        assert!(key_matches!(key, "code" => "503", "code" => "200",));
+        assert!(!key_matches!(key, "region" => "*"));
+
+        // With no patterns at all, every key matches.
+        assert!(key_matches!(key));
+        assert!(key_matches!(Key::from_name("anything")));
+    }
+
+    #[test]
+    fn test_key_eq_name() {
+        let unlabeled = Key::from_name("requests");
+        let labeled = Key::from_name_and_labels("requests", vec![Label::new("code", "200")]);
+        let owned = Key::from_name(String::from("requests"));
+
+        assert!(unlabeled.eq_name("requests"));
+        assert!(labeled.eq_name("requests"));
+        assert!(owned.eq_name("requests"));
+        assert!(!labeled.eq_name("request"));
+        assert!(!labeled.eq_name("requests_total"));
+    }
+
+    #[test]
+    fn test_key_borrowed_name_owned_labels() {
+        let labels = vec![Label::new("region", "us-east"), Label::new("code", "200")];
+        let labels_ptr = labels.as_ptr();
+
+        let key = Key::from_parts(Cow::Borrowed("requests"), labels);
+        assert!(matches_borrowed(key.name()));
+        let (name, labels) = key.into_parts();
+        assert!(matches_borrowed(name.clone()));
+        assert_eq!(labels.as_ptr(), labels_ptr);
+
+        let key = Key::from_name_and_labels("requests", labels);
+        assert!(matches_borrowed(key.name()));
+        assert_eq!(key.labels().next().unwrap() as *const Label, labels_ptr);
+        assert_eq!(Key::from_parts(name, key.clone().into_parts().1), key);
+    }
+
+    #[test]
+    fn test_as_gauge_value() {
+        assert_eq!((-5i8).as_gauge_value(), -5);
+        assert_eq!(std::u32::MAX.as_gauge_value(), i64::from(std::u32::MAX));
+        assert_eq!(std::i64::MIN.as_gauge_value(), std::i64::MIN);
+        assert_eq!(42usize.as_gauge_value(), 42);
+
+        // Values above 2^53 can't be held exactly by an f64, but are exact as gauge values.
+        let large = (1u64 << 53) + 1;
+        assert_eq!(large.as_gauge_value(), large as i64);
+        assert_eq!((std::i64::MAX as u64).as_gauge_value(), std::i64::MAX);
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "too large for an i64"))]
+    fn test_as_gauge_value_saturates() {
+        assert_eq!(std::u64::MAX.as_gauge_value(), std::i64::MAX);
+    }
+
+    #[test]
+    fn test_optional_labels() {
+        let present = Some("eu-west");
+        let absent: Option<&'static str> = None;
+        let expected = Key::from_name_and_labels(
+            "requests",
+            vec![
+                Label::new("service", "api"),
+                Label::new("region", "eu-west"),
+            ],
+        );
+
+        let labels = vec![
+            Some(Label::new("service", "api")),
+            absent.map(|host| Label::new("host", host)),
+            present.map(|region| Label::new("region", region)),
+        ];
+        assert_eq!(Key::from_name_and_labels("requests", &labels), expected);
+        assert_eq!(Key::from_name_and_labels("requests", labels), expected);
+
+        let labels = &[
+            Some(Label::new("service", "api")),
+            None,
+            Some(Label::new("region", "eu-west")),
+        ];
+        assert_eq!(Key::from_name_and_labels("requests", labels), expected);
+
+        let labels = Some(vec![
+            Label::new("service", "api"),
+            Label::new("region", "eu-west"),
+        ]);
+        assert_eq!(Key::from_name_and_labels("requests", labels), expected);
+
+        let none: Option<Vec<Label>> = None;
+        assert_eq!(
+            Key::from_name_and_labels("requests", none),
+            Key::from_name("requests")
+        );
+
+        // Plain labels and pairs still work as before.
+        assert_eq!(
+            Key::from_name_and_labels("requests", &[("service", "api"), ("region", "eu-west")]),
+            expected
+        );
+    }
+
+    #[derive(Default)]
+    struct ChunkObserver {
+        chunks: Vec<(Key, Vec<u64>)>,
+    }
+
+    impl Observer for ChunkObserver {
+        fn observe_counter(&mut self, _key: Key, _value: u64) {}
+
+        fn observe_gauge(&mut self, _key: Key, _value: i64) {}
+
+        fn observe_histogram(&mut self, key: Key, values: &[u64]) {
+            self.chunks.push((key, values.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_observe_histogram_weighted_default() {
+        let mut observer = ChunkObserver::default();
+        let key = Key::from_name("latency");
+        observer.observe_histogram_weighted(key.clone(), &[(5, 2), (7, 0), (9, 2000)]);
+
+        // Values are expanded in order, a bounded chunk at a time.
+        let sizes = observer
+            .chunks
+            .iter()
+            .map(|(_, values)| values.len())
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![1024, 978]);
+        assert!(observer.chunks.iter().all(|(k, _)| *k == key));
+        assert_eq!(observer.chunks[0].1[..3], [5, 5, 9]);
+
+        observer.chunks.clear();
+        observer.observe_histogram_weighted(key, &[]);
+        assert!(observer.chunks.is_empty());
+    }
+
+    #[test]
+    fn test_label_value_escaped() {
+        let plain = Label::new("path", "/api/users");
+        assert!(matches_borrowed(plain.value_escaped_prometheus()));
+        assert!(matches_borrowed(plain.value_escaped_json()));
+
+        let quoted = Label::new("query", r#"say "hi" \ bye"#);
+        assert_eq!(quoted.value_escaped_prometheus(), r#"say \"hi\" \\ bye"#);
+        assert_eq!(quoted.value_escaped_json(), r#"say \"hi\" \\ bye"#);
+
+        let control = Label::new("message", "line\nnext\ttab\r\u{8}\u{c}\u{1b}");
+        assert_eq!(
+            control.value_escaped_prometheus(),
+            "line\\nnext\ttab\r\u{8}\u{c}\u{1b}"
+        );
+        assert_eq!(control.value_escaped_json(), r"line\nnext\ttab\r\b\f\u001b");
+
+        let unicode = Label::new("city", "Zürich \"Süd\"");
+        assert_eq!(unicode.value_escaped_prometheus(), "Zürich \\\"Süd\\\"");
+        assert_eq!(unicode.value_escaped_json(), "Zürich \\\"Süd\\\"");
+    }
+
+    fn matches_borrowed(value: Cow<str>) -> bool {
+        match value {
+            Cow::Borrowed(_) => true,
+            Cow::Owned(_) => false,
+        }
+    }
+
+    #[test]
+    fn test_unit_display() {
+        assert_eq!(Unit::Bytes.as_str(), "bytes");
+        assert_eq!(Unit::Nanoseconds.to_string(), "nanoseconds");
+        assert_eq!(format!("{}", Unit::Percent), "percent");
+    }
+}
@@ -127,6 +127,19 @@ impl Observer for JsonObserver {
                 .expect("failed to observe histogram value");
         }
     }
+
+    fn observe_histogram_weighted(&mut self, key: Key, values: &[(u64, u64)]) {
+        let entry = self
+            .histos
+            .entry(key)
+            .or_insert_with(|| Histogram::<u64>::new(3).expect("failed to create histogram"));
+
+        for (value, count) in values {
+            entry
+                .record_n(*value, *count)
+                .expect("failed to observe histogram value");
+        }
+    }
 }
 
 impl Drain<String> for JsonObserver {
@@ -1,8 +1,8 @@
 //! Records metrics in the Prometheus exposition format.
 #![deny(missing_docs)]
 use hdrhistogram::Histogram;
-use metrics_core::{Builder, Drain, Key, Label, Observer};
-use metrics_util::{parse_quantiles, Quantile};
+use metrics_core::{Builder, Drain, Key, Observer, Unit};
+use metrics_util::{parse_quantiles, size_buckets, Quantile};
 use std::iter::FromIterator;
 use std::{collections::HashMap, time::SystemTime};
 
@@ -52,6 +52,9 @@ impl PrometheusBuilder {
     ///
     /// This option changes the observer's output of histogram-type metric into summaries.
     /// It only affects matching metrics if set_buckets was not used.
+    ///
+    /// Histograms observed with [`Unit::Bytes`] and no buckets set for them are rendered with the
+    /// buckets of [`size_buckets`], from 1 KiB up to 1 GiB.
     pub fn set_buckets_for_metric(mut self, name: &str, values: &[u64]) -> Self {
         let buckets = self.buckets_by_name.get_or_insert_with(|| HashMap::new());
         buckets.insert(name.to_owned(), values.to_vec());
@@ -69,6 +72,8 @@ impl Builder for PrometheusBuilder {
             histos: HashMap::new(),
             output: get_prom_expo_header(),
             counters: HashMap::new(),
+            created: HashMap::new(),
+            units: HashMap::new(),
             gauges: HashMap::new(),
             buckets_by_name: self.buckets_by_name.clone(),
         }
@@ -88,6 +93,8 @@ pub struct PrometheusObserver {
     pub(crate) histos: HashMap<String, HashMap<Vec<String>, (u64, Histogram<u64>)>>,
     pub(crate) output: String,
     pub(crate) counters: HashMap<String, HashMap<Vec<String>, u64>>,
+    pub(crate) created: HashMap<String, HashMap<Vec<String>, SystemTime>>,
+    pub(crate) units: HashMap<String, Unit>,
     pub(crate) gauges: HashMap<String, HashMap<Vec<String>, i64>>,
     pub(crate) buckets_by_name: Option<HashMap<String, Vec<u64>>>,
 }
@@ -106,6 +113,19 @@ impl Observer for PrometheusObserver {
         *entry += value;
     }
 
+    fn observe_counter_created(&mut self, key: Key, created: SystemTime) {
+        let (name, labels) = key_to_parts(key);
+        self.created
+            .entry(name)
+            .or_default()
+            .insert(labels, created);
+    }
+
+    fn observe_unit(&mut self, key: Key, unit: Unit) {
+        let (name, _) = key_to_parts(key);
+        self.units.insert(name, unit);
+    }
+
     fn observe_gauge(&mut self, key: Key, value: i64) {
         let (name, labels) = key_to_parts(key);
 
@@ -138,6 +158,27 @@ impl Observer for PrometheusObserver {
             *sum += *value;
         }
     }
+
+    fn observe_histogram_weighted(&mut self, key: Key, values: &[(u64, u64)]) {
+        let (name, labels) = key_to_parts(key);
+
+        let entry = self
+            .histos
+            .entry(name)
+            .or_default()
+            .entry(labels)
+            .or_insert_with(|| {
+                let h = Histogram::<u64>::new(3).expect("failed to create histogram");
+                (0, h)
+            });
+
+        let (sum, h) = entry;
+        for (value, count) in values {
+            h.record_n(*value, *count)
+                .expect("failed to observe histogram value");
+            *sum += value.saturating_mul(*count);
+        }
+    }
 }
 
 impl Drain<String> for PrometheusObserver {
@@ -145,6 +186,7 @@ impl Drain<String> for PrometheusObserver {
         let mut output: String = self.output.drain(..).collect();
 
         for (name, mut by_labels) in self.counters.drain() {
+            let mut created = self.created.remove(&name).unwrap_or_default();
             output.push_str("\n# TYPE ");
             output.push_str(name.as_str());
             output.push_str(" counter\n");
@@ -154,8 +196,22 @@ impl Drain<String> for PrometheusObserver {
                 output.push_str(" ");
                 output.push_str(value.to_string().as_str());
                 output.push_str("\n");
+
+                if let Some(created) = created.remove(&labels) {
+                    let seconds = created
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .map(|d| d.as_secs_f64())
+                        .unwrap_or(0.0);
+                    let created_name = format!("{}_created", name);
+                    let full_name = render_labeled_name(&created_name, &labels);
+                    output.push_str(full_name.as_str());
+                    output.push(' ');
+                    output.push_str(seconds.to_string().as_str());
+                    output.push('\n');
+                }
             }
         }
+        self.created.clear();
 
         for (name, mut by_labels) in self.gauges.drain() {
             output.push_str("\n# TYPE ");
@@ -175,6 +231,10 @@ impl Drain<String> for PrometheusObserver {
             .map(|h| Vec::from_iter(h.iter()))
             .unwrap_or_else(|| vec![]);
         sorted_overrides.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+        let byte_buckets = size_buckets()
+            .into_iter()
+            .map(|bound| bound as u64)
+            .collect::<Vec<_>>();
 
         for (name, mut by_labels) in self.histos.drain() {
             let buckets = sorted_overrides
@@ -187,6 +247,11 @@ impl Drain<String> for PrometheusObserver {
                     }
                 })
                 .unwrap_or(&self.buckets);
+            let buckets = if buckets.is_empty() && self.units.get(&name) == Some(&Unit::Bytes) {
+                &byte_buckets
+            } else {
+                buckets
+            };
             let use_quantiles = buckets.is_empty();
 
             output.push_str("\n# TYPE ");
@@ -248,6 +313,7 @@ impl Drain<String> for PrometheusObserver {
                 output.push_str("\n");
             }
         }
+        self.units.clear();
 
         output
     }
@@ -258,17 +324,8 @@ fn key_to_parts(key: Key) -> (String, Vec<String>) {
     let sanitize = |c| c == '.' || c == '=' || c == '{' || c == '}' || c == '+' || c == '-';
     let name = name.replace(sanitize, "_");
     let labels = labels
-        .into_iter()
-        .map(Label::into_parts)
-        .map(|(k, v)| {
-            format!(
-                "{}=\"{}\"",
-                k,
-                v.replace("\\", "\\\\")
-                    .replace("\"", "\\\"")
-                    .replace("\n", "\\n")
-            )
-        })
+        .iter()
+        .map(|label| format!("{}=\"{}\"", label.key(), label.value_escaped_prometheus()))
         .collect();
 
     (name, labels)
@@ -296,3 +353,57 @@ fn get_prom_expo_header() -> String {
         ts
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PrometheusBuilder;
+    use metrics_core::{Builder, Drain, Key, Label, Observer, Unit};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_counter_created() {
+        let mut observer = PrometheusBuilder::new().build();
+        let key = Key::from_name_and_labels("http.requests", vec![Label::new("code", "200")]);
+        observer.observe_counter_created(
+            key.clone(),
+            UNIX_EPOCH + Duration::from_millis(1_600_000_000_500),
+        );
+        observer.observe_counter(key, 3);
+        observer.observe_counter(Key::from_name("errors"), 1);
+
+        let output = observer.drain();
+        assert!(output.contains("\nhttp_requests{code=\"200\"} 3\n"));
+        assert!(output.contains("\nhttp_requests_created{code=\"200\"} 1600000000.5\n"));
+        // Counters observed without a creation time have no `_created` sample.
+        assert!(output.contains("\nerrors 1\n"));
+        assert!(!output.contains("errors_created"));
+
+        // Creation times are drained along with the counters.
+        observer.observe_counter(Key::from_name("errors"), 1);
+        assert!(!observer.drain().contains("_created"));
+    }
+
+    #[test]
+    fn test_byte_unit_buckets() {
+        let mut observer = PrometheusBuilder::new().build();
+        observer.observe_unit(Key::from_name("payload.size"), Unit::Bytes);
+        observer.observe_histogram(Key::from_name("payload.size"), &[512, 3_000, 2_000_000]);
+        observer.observe_histogram(Key::from_name("latency"), &[10]);
+
+        // Histograms in bytes are rendered with size buckets, and others are left as summaries.
+        let output = observer.drain();
+        assert!(output.contains("\n# TYPE payload_size histogram\n"));
+        assert!(output.contains("\npayload_size_bucket{le=\"1024\"} 1\n"));
+        assert!(output.contains("\npayload_size_bucket{le=\"4096\"} 2\n"));
+        assert!(output.contains("\npayload_size_bucket{le=\"1073741824\"} 3\n"));
+        assert!(output.contains("\n# TYPE latency summary\n"));
+
+        // Explicit buckets take precedence over the unit.
+        let mut observer = PrometheusBuilder::new().set_buckets(&[100]).build();
+        observer.observe_unit(Key::from_name("payload.size"), Unit::Bytes);
+        observer.observe_histogram(Key::from_name("payload.size"), &[512]);
+        let output = observer.drain();
+        assert!(output.contains("\npayload_size_bucket{le=\"100\"} 0\n"));
+        assert!(!output.contains("le=\"1024\""));
+    }
+}
@@ -0,0 +1,46 @@
+#[macro_use]
+extern crate criterion;
+extern crate ckb_metrics_runtime as metrics_runtime;
+
+use criterion::{Benchmark, Criterion};
+use metrics_core::{Key, Label};
+use metrics_runtime::Receiver;
+
+fn key() -> Key {
+    Key::from_name_and_labels(
+        "http_requests_total",
+        vec![
+            Label::new("service", "api"),
+            Label::new("method", "GET"),
+            Label::new("code", "200"),
+        ],
+    )
+}
+
+fn registry_benchmark(c: &mut Criterion) {
+    c.bench(
+        "registry",
+        Benchmark::new("counter lookup", |b| {
+            let receiver = Receiver::builder()
+                .build()
+                .expect("failed to create receiver");
+            let mut sink = receiver.sink();
+            let key = key();
+
+            b.iter(|| sink.counter(key.clone()))
+        })
+        .with_function("counter lookup prehashed", |b| {
+            let receiver = Receiver::builder()
+                .build()
+                .expect("failed to create receiver");
+            let sink = receiver.sink();
+            let key = key();
+            let hash = key.name_hash() ^ key.labels_hash().rotate_left(32);
+
+            b.iter(|| sink.counter_prehashed(hash, key.clone()))
+        }),
+    );
+}
+
+criterion_group!(benches, registry_benchmark);
+criterion_main!(benches);
@@ -1,4 +1,8 @@
-use crate::{config::Configuration, Receiver};
+use crate::{
+    config::{Configuration, Hook},
+    Receiver,
+};
+use metrics_core::Key;
 use std::{error::Error, fmt, time::Duration};
 
 /// Errors during receiver creation.
@@ -40,6 +44,9 @@ pub struct Builder {
     pub(crate) histogram_window: Duration,
     pub(crate) histogram_granularity: Duration,
     pub(crate) upkeep_interval: Duration,
+    pub(crate) case_insensitive_names: bool,
+    pub(crate) on_register: Option<Hook>,
+    pub(crate) on_deregister: Option<Hook>,
 }
 
 impl Default for Builder {
@@ -48,6 +55,9 @@ impl Default for Builder {
             histogram_window: Duration::from_secs(10),
             histogram_granularity: Duration::from_secs(1),
             upkeep_interval: Duration::from_millis(50),
+            case_insensitive_names: false,
+            on_register: None,
+            on_deregister: None,
         }
     }
 }
@@ -88,6 +98,48 @@ impl Builder {
         self
     }
 
+    /// Sets whether or not metric names are case-insensitive.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// When enabled, metric names are folded to ASCII lowercase when they are registered, so
+    /// `Foo` and `foo` refer to the same metric, and are reported under the name `foo`.  Names
+    /// are folded the same way wherever a [`Controller`](crate::Controller) takes a key or name,
+    /// such as when getting handles, metadata or state, and when removing metrics by key or name.
+    /// Label keys and values are not affected, and are still compared exactly.
+    pub fn case_insensitive_names(mut self, enabled: bool) -> Self {
+        self.case_insensitive_names = enabled;
+        self
+    }
+
+    /// Sets a callback to invoke whenever a new metric is registered.
+    ///
+    /// The callback is given the key of the metric, as it appears in snapshots, and is invoked
+    /// exactly once per metric, from whichever thread registered it.  This lets push-based
+    /// backends announce new metrics as soon as they exist.
+    ///
+    /// The callback is invoked inline with the registration, so it should be quick.
+    pub fn on_register<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Key) + Send + Sync + 'static,
+    {
+        self.on_register = Some(Hook::new(f));
+        self
+    }
+
+    /// Sets a callback to invoke whenever a metric is removed.
+    ///
+    /// The callback is given the key of the metric, as it appears in snapshots, and is invoked
+    /// once for each metric removed through [`Controller::remove`](crate::Controller::remove).
+    /// This lets push-based backends send a deletion, or tombstone, for the metric.
+    pub fn on_deregister<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Key) + Send + Sync + 'static,
+    {
+        self.on_deregister = Some(Hook::new(f));
+        self
+    }
+
     /// Create a [`Receiver`] based on this configuration.
     pub fn build(self) -> Result<Receiver, BuilderError> {
         let config = Configuration::from_builder(&self);
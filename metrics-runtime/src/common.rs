@@ -2,10 +2,11 @@ use crate::data::AtomicWindowedHistogram;
 use arc_swap::ArcSwapOption;
 use atomic_shim::{AtomicI64, AtomicU64};
 use metrics_core::Key;
-use metrics_util::StreamingIntegers;
+use metrics_util::{MetricValue, StreamingIntegers};
 use quanta::Clock;
 use std::{
     fmt,
+    hash::{Hash, Hasher},
     ops::Deref,
     sync::{atomic::Ordering, Arc},
     time::{Duration, Instant},
@@ -61,23 +62,73 @@ pub(crate) enum Kind {
     Proxy,
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
-pub(crate) struct Identifier(Key, ScopeHandle, Kind);
+// Keys are shared so that identifiers registered with an interned key can be compared by pointer
+// before falling back to comparing their keys by value.
+#[derive(Eq, Clone, Debug)]
+pub(crate) struct Identifier(Arc<Key>, ScopeHandle, Kind);
 
 impl Identifier {
     pub fn new<K>(key: K, handle: ScopeHandle, kind: Kind) -> Self
     where
         K: Into<Key>,
     {
-        Identifier(key.into(), handle, kind)
+        Identifier(Arc::new(key.into()), handle, kind)
     }
 
     pub fn kind(&self) -> Kind {
         self.2.clone()
     }
 
+    pub fn key(&self) -> &Arc<Key> {
+        &self.0
+    }
+
+    /// Replaces the key of this identifier with an equal, shared copy of it.
+    pub fn with_shared_key(self, key: Arc<Key>) -> Self {
+        debug_assert_eq!(*key, *self.0);
+        Identifier(key, self.1, self.2)
+    }
+
     pub fn into_parts(self) -> (Key, ScopeHandle, Kind) {
-        (self.0, self.1, self.2)
+        let key = Arc::try_unwrap(self.0).unwrap_or_else(|key| Key::clone(&key));
+        (key, self.1, self.2)
+    }
+
+    /// Folds the name of this identifier to ASCII lowercase.
+    pub fn fold_name_case(self) -> Self {
+        if self.0.name().bytes().any(|b| b.is_ascii_uppercase()) {
+            let (key, handle, kind) = self.into_parts();
+            Identifier::new(fold_name_case(key), handle, kind)
+        } else {
+            self
+        }
+    }
+}
+
+/// Folds the name of the given key to ASCII lowercase, leaving its labels as they are.
+///
+/// Names without any uppercase letters are returned as they are, without being copied.
+pub(crate) fn fold_name_case(key: Key) -> Key {
+    if key.name().bytes().any(|b| b.is_ascii_uppercase()) {
+        key.map_name(|name| name.to_ascii_lowercase())
+    } else {
+        key
+    }
+}
+
+impl PartialEq for Identifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.2 == other.2
+            && self.1 == other.1
+            && (Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0)
+    }
+}
+
+impl Hash for Identifier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+        self.1.hash(state);
+        self.2.hash(state);
     }
 }
 
@@ -89,14 +140,14 @@ enum ValueState {
     Proxy(ArcSwapOption<Box<ProxyFn>>),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) enum ValueSnapshot {
     Single(Measurement),
     Multiple(Vec<(Key, Measurement)>),
 }
 
 /// A point-in-time metric measurement.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Measurement {
     /// Counters represent a single value that can only ever be incremented over time, or reset to
     /// zero.
@@ -111,6 +162,23 @@ pub enum Measurement {
     /// callers to pass around the compressed dataset and decompress/access the actual integers on
     /// demand.
     Histogram(StreamingIntegers),
+    /// Histograms holding values that were recorded many times at once, such as pre-aggregated
+    /// buckets.
+    ///
+    /// Each value is stored once, as a pair of the value and the number of times it was recorded,
+    /// sorted by value.  Any values recorded one at a time are folded in with a count of one.
+    WeightedHistogram(Vec<(u64, u64)>),
+}
+
+impl From<Measurement> for MetricValue {
+    fn from(measurement: Measurement) -> Self {
+        match measurement {
+            Measurement::Counter(value) => MetricValue::Counter(value),
+            Measurement::Gauge(value) => MetricValue::Gauge(value),
+            Measurement::Histogram(stream) => MetricValue::Histogram(stream.decompress()),
+            Measurement::WeightedHistogram(values) => MetricValue::WeightedHistogram(values),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -183,6 +251,13 @@ impl ValueHandle {
         }
     }
 
+    pub fn update_histogram_many(&self, value: u64, count: u64) {
+        match self.state.deref() {
+            ValueState::Histogram(inner) => inner.record_many(value, count),
+            _ => unreachable!("tried to access as histogram, not a histogram"),
+        }
+    }
+
     pub fn update_proxy<F>(&self, value: F)
     where
         F: Fn() -> Vec<(Key, Measurement)> + Send + Sync + 'static,
@@ -195,6 +270,28 @@ impl ValueHandle {
         }
     }
 
+    /// Gets an identifier for the underlying storage, which is the same for every handle sharing
+    /// it.
+    pub fn storage_id(&self) -> usize {
+        &*self.state as *const ValueState as usize
+    }
+
+    /// Takes a snapshot of the value, resetting it if it can be reset.
+    ///
+    /// Counters are reset to zero and histograms are emptied, with each value recorded
+    /// concurrently ending up in exactly one snapshot.  Gauges hold a current value rather than
+    /// an accumulation, and proxies are computed on demand, so neither is reset.
+    pub fn snapshot_and_reset(&self) -> ValueSnapshot {
+        match self.state.deref() {
+            ValueState::Counter(inner) => {
+                let value = inner.swap(0, Ordering::AcqRel);
+                ValueSnapshot::Single(Measurement::Counter(value))
+            }
+            ValueState::Histogram(inner) => ValueSnapshot::Single(inner.drain()),
+            _ => self.snapshot(),
+        }
+    }
+
     pub fn snapshot(&self) -> ValueSnapshot {
         match self.state.deref() {
             ValueState::Counter(inner) => {
@@ -205,10 +302,7 @@ impl ValueHandle {
                 let value = inner.load(Ordering::Acquire);
                 ValueSnapshot::Single(Measurement::Gauge(value))
             }
-            ValueState::Histogram(inner) => {
-                let stream = inner.snapshot();
-                ValueSnapshot::Single(Measurement::Histogram(stream))
-            }
+            ValueState::Histogram(inner) => ValueSnapshot::Single(inner.snapshot()),
             ValueState::Proxy(maybe) => {
                 let measurements = match *maybe.load() {
                     None => Vec::new(),
@@ -1,5 +1,29 @@
 use crate::Builder;
-use std::time::Duration;
+use metrics_core::Key;
+use std::{fmt, sync::Arc, time::Duration};
+
+/// A callback invoked with the key of a metric during its lifecycle.
+#[derive(Clone)]
+pub(crate) struct Hook(Arc<dyn Fn(&Key) + Send + Sync>);
+
+impl Hook {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&Key) + Send + Sync + 'static,
+    {
+        Hook(Arc::new(f))
+    }
+
+    pub fn call(&self, key: &Key) {
+        (self.0)(key)
+    }
+}
+
+impl fmt::Debug for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
 
 /// Holds the configuration for complex metric types.
 #[derive(Clone, Debug)]
@@ -7,6 +31,9 @@ pub(crate) struct Configuration {
     pub histogram_window: Duration,
     pub histogram_granularity: Duration,
     pub upkeep_interval: Duration,
+    pub case_insensitive_names: bool,
+    pub on_register: Option<Hook>,
+    pub on_deregister: Option<Hook>,
 }
 
 impl Configuration {
@@ -15,6 +42,9 @@ impl Configuration {
             histogram_window: builder.histogram_window,
             histogram_granularity: builder.histogram_granularity,
             upkeep_interval: builder.upkeep_interval,
+            case_insensitive_names: builder.case_insensitive_names,
+            on_register: builder.on_register.clone(),
+            on_deregister: builder.on_deregister.clone(),
         }
     }
 
@@ -24,6 +54,9 @@ impl Configuration {
             histogram_window: Duration::from_secs(5),
             histogram_granularity: Duration::from_secs(1),
             upkeep_interval: Duration::from_millis(10),
+            case_insensitive_names: false,
+            on_register: None,
+            on_deregister: None,
         }
     }
 }
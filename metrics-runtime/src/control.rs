@@ -1,11 +1,45 @@
 use crate::{
-    data::Snapshot,
-    registry::{MetricRegistry, ScopeRegistry},
+    common::Measurement,
+    data::{MetricHandle, Snapshot},
+    registry::{MetricRegistry, RegistryView, ScopeRegistry},
 };
 
-use metrics_core::{Observe, Observer};
+use metrics_core::{Key, Observe, Observer};
+use metrics_util::{MetricValue, Selector};
 
-use std::sync::Arc;
+use std::{
+    any::Any,
+    error::Error,
+    fmt,
+    sync::{mpsc, Arc},
+};
+
+/// What [`Controller::merge_from`] does with metrics registered in both controllers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeConflict {
+    /// Keeps the metric already registered, and skips the other one.
+    KeepExisting,
+
+    /// Fails the merge, without registering anything.
+    Fail,
+}
+
+/// Errors during merging of controllers.
+#[derive(Debug, Clone)]
+pub enum MergeError {
+    /// A metric with the given key was registered in both controllers.
+    Conflict(Key),
+}
+
+impl Error for MergeError {}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeError::Conflict(key) => write!(f, "metric '{}' is already registered", key),
+        }
+    }
+}
 
 /// Handle for acquiring snapshots.
 ///
@@ -31,9 +65,342 @@ impl Controller {
     }
 
     /// Provide a snapshot of its collected metrics.
+    ///
+    /// Metrics are listed in the order they were first registered.
     pub fn snapshot(&self) -> Snapshot {
         self.metric_registry.snapshot()
     }
+
+    /// Provides a shared, read-only view of the currently registered metrics.
+    ///
+    /// Unlike [`snapshot`](Controller::snapshot), this doesn't copy anything: the returned
+    /// [`RegistryView`] holds a reference to the registry's current set of metrics, and cloning it
+    /// is as cheap as cloning an `Arc`.  Metrics registered after the view was taken don't show up
+    /// in it, and registering them never waits on readers of the view.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_runtime::Receiver;
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// let mut sink = receiver.sink();
+    /// sink.increment_counter("requests", 1);
+    ///
+    /// let view = receiver.controller().snapshot_arc();
+    /// sink.update_gauge("connections", 3);
+    ///
+    /// assert_eq!(view.len(), 1);
+    /// assert_eq!(receiver.controller().len(), 2);
+    /// ```
+    pub fn snapshot_arc(&self) -> RegistryView {
+        self.metric_registry.snapshot_arc()
+    }
+
+    /// Folds over the current value of every registered metric.
+    ///
+    /// This walks the registry directly, calling `f` with the running accumulator and the key and
+    /// measurement of each metric, in the order they were registered, so that aggregates can be
+    /// computed without allocating a [`Snapshot`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_runtime::{Measurement, Receiver};
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// let mut sink = receiver.sink();
+    /// sink.increment_counter("requests", 5);
+    /// sink.increment_counter("errors", 2);
+    /// sink.update_gauge("connections", 100);
+    ///
+    /// let total = receiver.controller().fold(0, |total, _key, measurement| match measurement {
+    ///     Measurement::Counter(value) => total + value,
+    ///     _ => total,
+    /// });
+    /// assert_eq!(total, 7);
+    /// ```
+    pub fn fold<A, F>(&self, init: A, f: F) -> A
+    where
+        F: FnMut(A, &Key, &Measurement) -> A,
+    {
+        self.metric_registry.fold(init, f)
+    }
+
+    /// Takes a snapshot of every registered metric, resetting each one that can be reset.
+    ///
+    /// This is meant for exporters which push the change in each metric since their last push,
+    /// rather than its running total.  Counters are read and reset to zero in a single atomic
+    /// step, and histograms are emptied as they are read, so an update made while the snapshot
+    /// is being taken lands either in this snapshot or in the next one, and is never lost or
+    /// counted twice.  Gauges and proxies have no accumulated value to reset, so they are only
+    /// read.
+    ///
+    /// Metrics which share their storage, such as those created with
+    /// [`Sink::share_counter`](crate::Sink::share_counter) or registered with
+    /// [`merge_from`](Controller::merge_from), are reset once, and report the same value under
+    /// each of their keys.  As merged metrics share their storage with the controller they were
+    /// merged from, resetting them here resets them there as well.
+    ///
+    /// Metrics are listed in the order they were first registered.  Every metric registered
+    /// before this is called is included; metrics registered while it runs are picked up by the
+    /// next call instead.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_core::Key;
+    /// # use metrics_runtime::Receiver;
+    /// # use metrics_util::MetricValue;
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// let mut sink = receiver.sink();
+    /// sink.increment_counter("requests", 3);
+    ///
+    /// let controller = receiver.controller();
+    /// let delta = controller.snapshot_and_reset();
+    /// assert_eq!(delta, vec![(Key::from_name("requests"), MetricValue::Counter(3))]);
+    ///
+    /// sink.increment_counter("requests", 1);
+    /// let delta = controller.snapshot_and_reset();
+    /// assert_eq!(delta, vec![(Key::from_name("requests"), MetricValue::Counter(1))]);
+    /// ```
+    pub fn snapshot_and_reset(&self) -> Vec<(Key, MetricValue)> {
+        self.metric_registry.snapshot_and_reset()
+    }
+
+    /// Gets a typed handle to the registered metric with the given key.
+    ///
+    /// The registry stores every metric as one of a fixed set of concrete backends, so rather than
+    /// downcasting from a trait object, the handle is returned as a [`MetricHandle`]: an enum with
+    /// a variant for each backend, which can be matched on to recover the concrete [`Counter`],
+    /// [`Gauge`], or [`Histogram`] and update it, or read its current value with
+    /// [`MetricHandle::measurement`].
+    ///
+    /// The key is matched against metrics as they appear in snapshots, with their scope applied.
+    /// If metrics of different kinds were registered with the same key, the one registered first
+    /// is returned.  Proxies have no handle, and are never returned.
+    ///
+    /// [`Counter`]: crate::data::Counter
+    /// [`Gauge`]: crate::data::Gauge
+    /// [`Histogram`]: crate::data::Histogram
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_core::Key;
+    /// # use metrics_runtime::{data::MetricHandle, Measurement, Receiver};
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// receiver.sink().increment_counter("requests", 3);
+    ///
+    /// let controller = receiver.controller();
+    /// match controller.handle(&Key::from_name("requests")) {
+    ///     Some(MetricHandle::Counter(counter)) => counter.increment(),
+    ///     _ => panic!("expected a counter"),
+    /// }
+    ///
+    /// let handle = controller.handle(&Key::from_name("requests")).unwrap();
+    /// match handle.measurement() {
+    ///     Measurement::Counter(value) => assert_eq!(value, 4),
+    ///     other => panic!("unexpected measurement: {:?}", other),
+    /// }
+    /// ```
+    pub fn handle(&self, key: &Key) -> Option<MetricHandle> {
+        self.metric_registry.handle(key)
+    }
+
+    /// Gets the number of registered metrics.
+    pub fn len(&self) -> usize {
+        self.metric_registry.len()
+    }
+
+    /// Whether or not any metrics have been registered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes the given metric.
+    ///
+    /// The key is matched against metrics as they appear in snapshots, with their scope applied.
+    /// Returns whether or not a metric was removed.  Any callback set with
+    /// [`Builder::on_deregister`](crate::Builder::on_deregister) is invoked for the removed
+    /// metric.
+    ///
+    /// Handles to the metric that are still held elsewhere keep working, but their updates are no
+    /// longer reported.  A [`Sink`](crate::Sink) that has already used the metric holds on to its
+    /// handle, and so will not register the metric again; only new sinks, or sinks that have not
+    /// used it yet, will.
+    pub fn remove(&self, key: &Key) -> bool {
+        self.metric_registry.remove(key)
+    }
+
+    /// Removes every metric with the given name, whatever its labels.
+    ///
+    /// The name is matched against metrics as they appear in snapshots, with their scope applied.
+    /// Returns the number of metrics removed.  See [`remove`](Controller::remove) for what happens
+    /// to the removed metrics.
+    pub fn remove_by_name(&self, name: &str) -> usize {
+        self.metric_registry.remove_by_name(name)
+    }
+
+    /// Removes every metric matched by the given selector.
+    ///
+    /// The selector is evaluated against metrics as they appear in snapshots, with their scope
+    /// applied.  Returns the number of metrics removed.  See [`remove`](Controller::remove) for
+    /// what happens to the removed metrics.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_runtime::Receiver;
+    /// use metrics_util::Selector;
+    ///
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// let mut sink = receiver.sink();
+    /// sink.increment_counter_with_labels("requests", 1, &[("env", "test")]);
+    /// sink.increment_counter_with_labels("requests", 1, &[("env", "prod")]);
+    ///
+    /// let selector = Selector::parse(r#"{env="test"}"#).expect("invalid selector");
+    /// let controller = receiver.controller();
+    /// assert_eq!(controller.remove_matching(&selector), 1);
+    /// assert_eq!(controller.len(), 1);
+    /// ```
+    pub fn remove_matching(&self, selector: &Selector) -> usize {
+        self.metric_registry.remove_where(|k| selector.matches(k))
+    }
+
+    /// Registers every metric of another controller with this one.
+    ///
+    /// This lets subsystems which are instrumented with a [`Receiver`](crate::Receiver) of their
+    /// own have their metrics exported alongside everything else.  Metrics are registered under
+    /// their keys as they appear in snapshots of `other`, with their scope applied, and share
+    /// their storage with `other`, so they keep reflecting updates made through its sinks.
+    /// Removing a merged metric from either controller leaves the other controller untouched, but
+    /// as the storage is shared, [`snapshot_and_reset`](Controller::snapshot_and_reset) on either
+    /// one resets the metric for both.
+    ///
+    /// Metrics registered in both controllers with the same key and kind are handled according
+    /// to `on_conflict`.  Returns the number of metrics registered.
+    ///
+    /// # Errors
+    ///
+    /// With [`MergeConflict::Fail`], an error is returned for the first conflicting metric, and
+    /// nothing is registered.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_runtime::{MergeConflict, Receiver};
+    /// let app = Receiver::builder().build().expect("failed to create receiver");
+    /// app.sink().increment_counter("requests", 1);
+    ///
+    /// let subsystem = Receiver::builder().build().expect("failed to create receiver");
+    /// let mut sink = subsystem.sink().scoped("cache");
+    /// sink.increment_counter("hits", 1);
+    ///
+    /// let controller = app.controller();
+    /// let merged = controller.merge_from(&subsystem.controller(), MergeConflict::Fail);
+    /// assert_eq!(merged.expect("failed to merge"), 1);
+    /// assert_eq!(controller.len(), 2);
+    ///
+    /// // Updates made through the subsystem's sinks show up in the merged metric.
+    /// sink.increment_counter("hits", 2);
+    /// ```
+    pub fn merge_from(
+        &self,
+        other: &Controller,
+        on_conflict: MergeConflict,
+    ) -> Result<usize, MergeError> {
+        self.metric_registry
+            .merge_from(&other.metric_registry, on_conflict == MergeConflict::Fail)
+            .map_err(MergeError::Conflict)
+    }
+
+    /// Subscribes to changes in the set of registered metrics.
+    ///
+    /// The returned receiver is signalled whenever a metric is registered or removed, which lets
+    /// exporters that cache something per metric, such as descriptors, rebuild their cache only
+    /// when it is out of date, rather than polling the registry.  Updates to the values of
+    /// metrics that are already registered do not signal the receiver.
+    ///
+    /// Signals are coalesced: at most one is pending at a time, and it stands for every change
+    /// made since the last signal was received, so the receiver should look at the registry as a
+    /// whole once woken.  Dropping the receiver unsubscribes it.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_runtime::Receiver;
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// let changes = receiver.controller().subscribe();
+    /// assert!(changes.try_recv().is_err());
+    ///
+    /// let mut sink = receiver.sink();
+    /// sink.increment_counter("requests", 1);
+    /// sink.increment_counter("errors", 1);
+    /// assert!(changes.try_recv().is_ok());
+    /// assert!(changes.try_recv().is_err());
+    ///
+    /// // Updating an existing metric is not a change.
+    /// sink.increment_counter("requests", 1);
+    /// assert!(changes.try_recv().is_err());
+    /// ```
+    pub fn subscribe(&self) -> mpsc::Receiver<()> {
+        self.metric_registry.subscribe()
+    }
+
+    /// Gets the number of times that registering a metric had to take the slow path.
+    ///
+    /// Looking up a registered metric is lock-free and cheap, but registering a new one copies
+    /// the registry and swaps it in, retrying from scratch if another registration got there
+    /// first.  Every new metric takes the slow path at least once, so a count that grows much
+    /// faster than [`len`](Controller::len) means registrations are racing with each other, which
+    /// is a sign that the registry would benefit from sharding.
+    pub fn contention_count(&self) -> u64 {
+        self.metric_registry.contention_count()
+    }
+
+    /// Gets the metadata attached to the given metric, if any.
+    ///
+    /// Metadata is attached through [`Recorder::describe_with_meta`], and can be downcast back to
+    /// its original type with [`Arc::downcast`].
+    ///
+    /// [`Recorder::describe_with_meta`]: metrics::Recorder::describe_with_meta
+    pub fn metadata(&self, key: &Key) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.metric_registry.metadata(key)
+    }
+
+    /// Reads and modifies the state kept alongside the given metric.
+    ///
+    /// Exporters can use this to track per-metric scrape state, such as the last value they saw
+    /// or when they last exported it.  `f` is handed the current state while holding the lock
+    /// that guards it, so the whole read-modify-write happens atomically with respect to other
+    /// callers.
+    ///
+    /// State is created with [`Default::default`] the first time it is asked for, and is
+    /// replaced with a fresh default if it was previously stored as a different type.
+    ///
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_runtime::Receiver;
+    /// # use metrics_core::Key;
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// let controller = receiver.controller();
+    /// let key = Key::from_name("requests");
+    ///
+    /// let delta = controller.with_state_mut(&key, |last: &mut u64| {
+    ///     let current = 42;
+    ///     let delta = current - *last;
+    ///     *last = current;
+    ///     delta
+    /// });
+    /// assert_eq!(delta, 42);
+    /// assert_eq!(controller.with_state_mut(&key, |last: &mut u64| *last), 42);
+    /// ```
+    pub fn with_state_mut<T, F, R>(&self, key: &Key, f: F) -> R
+    where
+        T: Any + Send + Default,
+        F: FnOnce(&mut T) -> R,
+    {
+        self.metric_registry.with_state_mut(key, f)
+    }
 }
 
 impl Observe for Controller {
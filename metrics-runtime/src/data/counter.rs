@@ -1,8 +1,12 @@
-use crate::common::ValueHandle;
+use crate::common::{Measurement, ValueHandle, ValueSnapshot};
+use metrics_core::MetricKind;
 
 /// A reference to a [`Counter`].
 ///
 /// A [`Counter`] is used for directly updating a counter, without any lookup overhead.
+///
+/// Cloning a counter only bumps a reference count, and every clone adds to the same total, so
+/// handles can be kept in your own data structures, such as a map keyed by request type.
 #[derive(Clone)]
 pub struct Counter {
     handle: ValueHandle,
@@ -18,6 +22,23 @@ impl Counter {
     pub fn increment(&self) {
         self.handle.update_counter(1);
     }
+
+    /// Gets the current value of the counter.
+    pub fn value(&self) -> u64 {
+        match self.handle.snapshot() {
+            ValueSnapshot::Single(Measurement::Counter(value)) => value,
+            _ => 0,
+        }
+    }
+
+    /// Gets the kind of this metric, which is always [`MetricKind::Counter`].
+    pub fn kind(&self) -> MetricKind {
+        MetricKind::Counter
+    }
+
+    pub(crate) fn value_handle(&self) -> &ValueHandle {
+        &self.handle
+    }
 }
 
 impl From<ValueHandle> for Counter {
@@ -25,3 +46,29 @@ impl From<ValueHandle> for Counter {
         Self { handle }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Counter;
+    use crate::common::{Measurement, ValueHandle, ValueSnapshot};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_counter_clones_share_storage() {
+        let handle = ValueHandle::counter();
+        let counter: Counter = handle.clone().into();
+
+        let mut by_request = HashMap::new();
+        by_request.insert("read", counter.clone());
+        by_request.insert("write", counter);
+
+        by_request["read"].record(2);
+        by_request["write"].increment();
+
+        match handle.snapshot() {
+            ValueSnapshot::Single(Measurement::Counter(value)) => assert_eq!(value, 3),
+            _ => panic!("incorrect value snapshot type for counter"),
+        }
+        assert_eq!(by_request["read"].value(), 3);
+    }
+}
@@ -1,8 +1,13 @@
-use crate::common::ValueHandle;
+use crate::common::{Measurement, ValueHandle, ValueSnapshot};
+use metrics_core::MetricKind;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// A reference to a [`Gauge`].
 ///
 /// A [`Gauge`] is used for directly updating a gauge, without any lookup overhead.
+///
+/// Every clone of a gauge points at the same value, so a resource can be counted up by the
+/// clone held where it's acquired and back down by the clone held where it's released.
 #[derive(Clone)]
 pub struct Gauge {
     handle: ValueHandle,
@@ -23,6 +28,35 @@ impl Gauge {
     pub fn decrement(&self, value: i64) {
         self.handle.decrement_gauge(value);
     }
+
+    /// Gets the current value of the gauge.
+    pub fn value(&self) -> i64 {
+        match self.handle.snapshot() {
+            ValueSnapshot::Single(Measurement::Gauge(value)) => value,
+            _ => 0,
+        }
+    }
+
+    /// Gets the kind of this metric, which is always [`MetricKind::Gauge`].
+    pub fn kind(&self) -> MetricKind {
+        MetricKind::Gauge
+    }
+
+    /// Sets the gauge's value to the current UNIX time, in seconds.
+    ///
+    /// This is useful for tracking when something last happened, such as the last successful run
+    /// of a periodic job.
+    pub fn set_to_current_time(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        self.handle.update_gauge(now);
+    }
+
+    pub(crate) fn value_handle(&self) -> &ValueHandle {
+        &self.handle
+    }
 }
 
 impl From<ValueHandle> for Gauge {
@@ -30,3 +64,50 @@ impl From<ValueHandle> for Gauge {
         Self { handle }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Gauge;
+    use crate::common::{Measurement, ValueHandle, ValueSnapshot};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_gauge_clones_share_storage() {
+        let handle = ValueHandle::gauge();
+        let acquire: Gauge = handle.clone().into();
+        let release = acquire.clone();
+
+        acquire.increment(3);
+        release.decrement(1);
+
+        match handle.snapshot() {
+            ValueSnapshot::Single(Measurement::Gauge(value)) => assert_eq!(value, 2),
+            _ => panic!("expected gauge measurement"),
+        }
+        assert_eq!(release.value(), 2);
+    }
+
+    #[test]
+    fn test_gauge_set_to_current_time() {
+        let handle = ValueHandle::gauge();
+        let gauge: Gauge = handle.clone().into();
+
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        gauge.set_to_current_time();
+        let after = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        match handle.snapshot() {
+            ValueSnapshot::Single(Measurement::Gauge(value)) => {
+                assert!(value >= before && value <= after)
+            }
+            _ => panic!("expected gauge measurement"),
+        }
+        assert!(gauge.value() >= before && gauge.value() <= after);
+    }
+}
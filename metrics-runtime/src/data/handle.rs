@@ -0,0 +1,185 @@
+use crate::common::{Measurement, ValueSnapshot};
+use crate::data::{Counter, Gauge, Histogram};
+use metrics_core::MetricKind;
+
+/// A handle to a metric of any kind.
+///
+/// [`Counter`], [`Gauge`], and [`Histogram`] each have their own methods for updating them, which
+/// makes it hard to write code that works with whichever kind of metric it's given, such as when
+/// the kind of a metric is chosen by configuration at runtime.  `MetricHandle` wraps any of them
+/// and provides [`observe`](MetricHandle::observe) to update them uniformly.
+#[derive(Clone)]
+pub enum MetricHandle {
+    /// A counter.
+    Counter(Counter),
+
+    /// A gauge.
+    Gauge(Gauge),
+
+    /// A histogram.
+    Histogram(Histogram),
+}
+
+impl MetricHandle {
+    /// Observes a value.
+    ///
+    /// Counters are incremented by the value, gauges are set to the value, and histograms record
+    /// the value.  The value is truncated to an integer first, and values outside the range of
+    /// the underlying metric are saturated to the nearest value in range.  NaN has no meaningful
+    /// value to saturate to, so it is dropped.
+    pub fn observe(&self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+
+        match self {
+            MetricHandle::Counter(counter) => counter.record(to_u64(value)),
+            MetricHandle::Gauge(gauge) => gauge.record(to_i64(value)),
+            MetricHandle::Histogram(histogram) => histogram.record_value(to_u64(value)),
+        }
+    }
+
+    /// Reads the current value of the wrapped metric.
+    ///
+    /// Histograms hold every value recorded within their window, which are all returned, so that
+    /// quantiles or any other statistic can be computed from them.
+    pub fn measurement(&self) -> Measurement {
+        let handle = match self {
+            MetricHandle::Counter(counter) => counter.value_handle(),
+            MetricHandle::Gauge(gauge) => gauge.value_handle(),
+            MetricHandle::Histogram(histogram) => histogram.value_handle(),
+        };
+
+        match handle.snapshot() {
+            ValueSnapshot::Single(measurement) => measurement,
+            ValueSnapshot::Multiple(_) => unreachable!("only proxies hold multiple measurements"),
+        }
+    }
+
+    /// Gets the kind of the wrapped metric.
+    pub fn kind(&self) -> MetricKind {
+        match self {
+            MetricHandle::Counter(counter) => counter.kind(),
+            MetricHandle::Gauge(gauge) => gauge.kind(),
+            MetricHandle::Histogram(histogram) => histogram.kind(),
+        }
+    }
+}
+
+impl From<Counter> for MetricHandle {
+    fn from(counter: Counter) -> Self {
+        MetricHandle::Counter(counter)
+    }
+}
+
+impl From<Gauge> for MetricHandle {
+    fn from(gauge: Gauge) -> Self {
+        MetricHandle::Gauge(gauge)
+    }
+}
+
+impl From<Histogram> for MetricHandle {
+    fn from(histogram: Histogram) -> Self {
+        MetricHandle::Histogram(histogram)
+    }
+}
+
+fn to_u64(value: f64) -> u64 {
+    let value = value.max(0.0);
+    if value >= std::u64::MAX as f64 {
+        std::u64::MAX
+    } else {
+        value as u64
+    }
+}
+
+fn to_i64(value: f64) -> i64 {
+    if value <= std::i64::MIN as f64 {
+        std::i64::MIN
+    } else if value >= std::i64::MAX as f64 {
+        std::i64::MAX
+    } else {
+        value as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetricHandle;
+    use crate::common::{Measurement, ValueHandle, ValueSnapshot};
+    use crate::data::{Counter, Gauge, Histogram};
+    use metrics_core::MetricKind;
+    use quanta::Clock;
+    use std::time::Duration;
+
+    fn measurement(handle: &ValueHandle) -> Measurement {
+        match handle.snapshot() {
+            ValueSnapshot::Single(measurement) => measurement,
+            _ => panic!("expected single measurement"),
+        }
+    }
+
+    #[test]
+    fn test_metric_handle_observe() {
+        let counter_handle = ValueHandle::counter();
+        let counter: MetricHandle = Counter::from(counter_handle.clone()).into();
+        counter.observe(2.0);
+        counter.observe(3.7);
+        counter.observe(-1.0);
+        match measurement(&counter_handle) {
+            Measurement::Counter(value) => assert_eq!(value, 5),
+            _ => panic!("expected counter"),
+        }
+
+        let gauge_handle = ValueHandle::gauge();
+        let gauge: MetricHandle = Gauge::from(gauge_handle.clone()).into();
+        gauge.observe(42.0);
+        gauge.observe(-7.5);
+        gauge.observe(std::f64::NAN);
+        match measurement(&gauge_handle) {
+            Measurement::Gauge(value) => assert_eq!(value, -7),
+            _ => panic!("expected gauge"),
+        }
+
+        let (clock, _) = Clock::mock();
+        let histogram_handle =
+            ValueHandle::histogram(Duration::from_secs(5), Duration::from_secs(1), clock);
+        let histogram: MetricHandle = Histogram::from(histogram_handle.clone()).into();
+        histogram.observe(10.0);
+        histogram.observe(1e30);
+        histogram.observe(std::f64::NAN);
+        match measurement(&histogram_handle) {
+            Measurement::Histogram(stream) => {
+                assert_eq!(stream.decompress(), vec![10, std::u64::MAX])
+            }
+            _ => panic!("expected histogram"),
+        }
+    }
+
+    #[test]
+    fn test_metric_handle_kind() {
+        let counter = Counter::from(ValueHandle::counter());
+        let gauge = Gauge::from(ValueHandle::gauge());
+        let (clock, _) = Clock::mock();
+        let histogram = Histogram::from(ValueHandle::histogram(
+            Duration::from_secs(5),
+            Duration::from_secs(1),
+            clock,
+        ));
+
+        assert_eq!(counter.kind(), MetricKind::Counter);
+        assert_eq!(gauge.kind(), MetricKind::Gauge);
+        assert_eq!(histogram.kind(), MetricKind::Histogram);
+
+        let handles: Vec<MetricHandle> = vec![histogram.into(), counter.into(), gauge.into()];
+        let kinds = handles.iter().map(|h| h.kind()).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                MetricKind::Histogram,
+                MetricKind::Counter,
+                MetricKind::Gauge
+            ]
+        );
+    }
+}
@@ -1,7 +1,8 @@
-use crate::common::{Delta, ValueHandle};
+use crate::common::{Delta, Measurement, ValueHandle};
 use crate::helper::duration_as_nanos;
 use atomic_shim::AtomicU64;
 use crossbeam_utils::Backoff;
+use metrics_core::MetricKind;
 use metrics_util::{AtomicBucket, StreamingIntegers};
 use quanta::Clock;
 use std::cmp;
@@ -11,6 +12,9 @@ use std::time::Duration;
 /// A reference to a [`Histogram`].
 ///
 /// A [`Histogram`] is used for directly updating a gauge, without any lookup overhead.
+///
+/// Clones record into the same window of values, so a histogram can be handed to several workers
+/// and their timings are reported together.
 #[derive(Clone)]
 pub struct Histogram {
     handle: ValueHandle,
@@ -27,6 +31,52 @@ impl Histogram {
     pub fn record_value(&self, value: u64) {
         self.handle.update_histogram(value);
     }
+
+    /// Records pre-aggregated buckets for the histogram.
+    ///
+    /// Each bucket is a pair of its upper bound and the number of values that fell into it.
+    /// Counts are per-bucket, not cumulative.  Every bucket is recorded as its upper bound,
+    /// weighted by its count, so quantiles are accurate to the resolution of the given buckets.
+    ///
+    /// `sum` and `count` cover every value, including those above the highest bound.  Any values
+    /// which `count` has beyond the buckets are recorded as their mean, taken from whatever part of
+    /// `sum` the buckets don't account for, or as the highest bound if that is larger.
+    pub fn record_buckets(&self, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        let mut bucketed = 0u64;
+        let mut bucketed_sum = 0u64;
+        let mut highest_bound = 0;
+        for (upper_bound, bucket_count) in buckets {
+            self.handle
+                .update_histogram_many(*upper_bound, *bucket_count);
+            bucketed = bucketed.saturating_add(*bucket_count);
+            bucketed_sum = bucketed_sum.saturating_add(upper_bound.saturating_mul(*bucket_count));
+            highest_bound = cmp::max(highest_bound, *upper_bound);
+        }
+
+        let overflow = count.saturating_sub(bucketed);
+        if let Some(mean) = sum.saturating_sub(bucketed_sum).checked_div(overflow) {
+            self.handle
+                .update_histogram_many(cmp::max(mean, highest_bound), overflow);
+        }
+    }
+
+    /// Records a value for the histogram as if it had been recorded `count` times.
+    ///
+    /// This is useful for recording weighted samples, such as when importing data that was
+    /// already counted or downsampled.  The value is stored once along with its count, so this
+    /// takes the same time and memory no matter how large `count` is.
+    pub fn record_value_many(&self, value: u64, count: u64) {
+        self.handle.update_histogram_many(value, count);
+    }
+
+    /// Gets the kind of this metric, which is always [`MetricKind::Histogram`].
+    pub fn kind(&self) -> MetricKind {
+        MetricKind::Histogram
+    }
+
+    pub(crate) fn value_handle(&self) -> &ValueHandle {
+        &self.handle
+    }
 }
 
 impl From<ValueHandle> for Histogram {
@@ -35,14 +85,91 @@ impl From<ValueHandle> for Histogram {
     }
 }
 
+/// A group of linked [`Histogram`]s.
+///
+/// Some observations belong in more than one histogram, such as a request latency that is
+/// tracked both overall and per endpoint.  A `HistogramGroup` holds the handles for each of them,
+/// acquired once up front, and records every value into all of them with a single call, so
+/// callers don't have to look up, or remember to update, each histogram separately.
+///
+/// ```rust
+/// # extern crate ckb_metrics_runtime as metrics_runtime;
+/// # use metrics_runtime::{Receiver, data::HistogramGroup};
+/// let receiver = Receiver::builder().build().expect("failed to create receiver");
+/// let mut sink = receiver.sink();
+///
+/// let latency = HistogramGroup::new(vec![
+///     sink.histogram("request_latency"),
+///     sink.histogram("users_request_latency"),
+/// ]);
+/// latency.record_value(42);
+/// ```
+#[derive(Clone, Default)]
+pub struct HistogramGroup {
+    histograms: Vec<Histogram>,
+}
+
+impl HistogramGroup {
+    /// Creates a new `HistogramGroup` from the given histograms.
+    pub fn new(histograms: Vec<Histogram>) -> Self {
+        HistogramGroup { histograms }
+    }
+
+    /// Adds a histogram to the group.
+    pub fn push(&mut self, histogram: Histogram) {
+        self.histograms.push(histogram);
+    }
+
+    /// Records a timing for every histogram in the group.
+    pub fn record_timing<D: Delta>(&self, start: D, end: D) {
+        self.record_value(end.delta(start));
+    }
+
+    /// Records a value for every histogram in the group.
+    pub fn record_value(&self, value: u64) {
+        for histogram in &self.histograms {
+            histogram.record_value(value);
+        }
+    }
+
+    /// Records a value for every histogram in the group as if it had been recorded `count` times.
+    ///
+    /// See [`Histogram::record_value_many`] for details.
+    pub fn record_value_many(&self, value: u64, count: u64) {
+        for histogram in &self.histograms {
+            histogram.record_value_many(value, count);
+        }
+    }
+
+    /// Gets the number of histograms in the group.
+    pub fn len(&self) -> usize {
+        self.histograms.len()
+    }
+
+    /// Whether or not the group is empty.
+    pub fn is_empty(&self) -> bool {
+        self.histograms.is_empty()
+    }
+}
+
+impl std::iter::FromIterator<Histogram> for HistogramGroup {
+    fn from_iter<I: IntoIterator<Item = Histogram>>(iter: I) -> Self {
+        HistogramGroup::new(iter.into_iter().collect())
+    }
+}
+
 /// An atomic windowed histogram.
 ///
 /// This histogram provides a windowed view of values that rolls forward over time, dropping old
 /// values as they exceed the window of the histogram.  Writes into the histogram are lock-free, as
 /// well as snapshots of the histogram.
+///
+/// Values recorded with [`record_many`](AtomicWindowedHistogram::record_many) are held as a single
+/// weighted value, so the memory they take doesn't depend on how many times they were recorded.
 #[derive(Debug)]
 pub struct AtomicWindowedHistogram {
     buckets: Vec<AtomicBucket<u64>>,
+    weighted: Vec<AtomicBucket<(u64, u64)>>,
     bucket_count: usize,
     granularity: u64,
     upkeep_index: AtomicUsize,
@@ -72,14 +199,17 @@ impl AtomicWindowedHistogram {
 
         let bucket_count = ((window_ns / granularity_ns) as usize) + 1;
         let mut buckets = Vec::new();
+        let mut weighted = Vec::new();
         for _ in 0..bucket_count {
             buckets.push(AtomicBucket::new());
+            weighted.push(AtomicBucket::new());
         }
 
         let next_upkeep = now + granularity_ns;
 
         AtomicWindowedHistogram {
             buckets,
+            weighted,
             bucket_count,
             granularity: granularity_ns,
             upkeep_index: AtomicUsize::new(0),
@@ -91,14 +221,17 @@ impl AtomicWindowedHistogram {
 
     /// Takes a snapshot of the current histogram.
     ///
-    /// Returns a [`StreamingIntegers`] value, representing all observed values in the
-    /// histogram.  As writes happen concurrently, along with buckets being cleared, a snapshot is
-    /// not guaranteed to have all values present at the time the method was called.
-    pub fn snapshot(&self) -> StreamingIntegers {
+    /// Returns a [`Measurement::Histogram`], holding all observed values in the histogram, or a
+    /// [`Measurement::WeightedHistogram`] if any values were recorded with
+    /// [`record_many`](AtomicWindowedHistogram::record_many).  As writes happen concurrently,
+    /// along with buckets being cleared, a snapshot is not guaranteed to have all values present
+    /// at the time the method was called.
+    pub fn snapshot(&self) -> Measurement {
         // Run upkeep to make sure our window reflects any time passage since the last write.
         let index = self.upkeep();
 
         let mut streaming = StreamingIntegers::new();
+        let mut weighted = Vec::new();
 
         // Start from the bucket ahead of the currently-being-written-to-bucket so that we outrace
         // any upkeep and get access to more of the data.
@@ -106,8 +239,27 @@ impl AtomicWindowedHistogram {
             let bucket_index = (index + i + 1) % self.bucket_count;
             let bucket = &self.buckets[bucket_index];
             bucket.data_with(|block| streaming.compress(block));
+            self.weighted[bucket_index].data_with(|block| weighted.extend_from_slice(block));
         }
-        streaming
+        into_measurement(streaming, weighted)
+    }
+
+    /// Takes a snapshot of the current histogram, and empties it.
+    ///
+    /// Each bucket is drained as it is read, so every value recorded concurrently ends up either
+    /// in the returned snapshot or in the histogram, but never in both and never in neither.
+    pub fn drain(&self) -> Measurement {
+        let index = self.upkeep();
+
+        let mut streaming = StreamingIntegers::new();
+        let mut weighted = Vec::new();
+        for i in 0..self.bucket_count {
+            let bucket_index = (index + i + 1) % self.bucket_count;
+            let values = self.buckets[bucket_index].drain();
+            streaming.compress(&values);
+            weighted.extend(self.weighted[bucket_index].drain());
+        }
+        into_measurement(streaming, weighted)
     }
 
     /// Records a value to the histogram.
@@ -116,6 +268,21 @@ impl AtomicWindowedHistogram {
         self.buckets[index].push(value);
     }
 
+    /// Records a value to the histogram as if it had been recorded `count` times.
+    ///
+    /// The value is stored once, along with its count, so this takes the same time and memory no
+    /// matter how large `count` is.
+    pub fn record_many(&self, value: u64, count: u64) {
+        match count {
+            0 => {}
+            1 => self.record(value),
+            _ => {
+                let index = self.upkeep();
+                self.weighted[index].push((value, count));
+            }
+        }
+    }
+
     fn upkeep(&self) -> usize {
         let backoff = Backoff::new();
 
@@ -171,6 +338,7 @@ impl AtomicWindowedHistogram {
                 // soon as possible.
                 let clear_index = new_index % self.bucket_count;
                 self.buckets[clear_index].clear();
+                self.weighted[clear_index].clear();
 
                 let now = self.clock.now();
                 let next_upkeep = now + self.granularity;
@@ -185,6 +353,7 @@ impl AtomicWindowedHistogram {
                     index += 1;
                     let clear_index = index % self.bucket_count;
                     self.buckets[clear_index].clear();
+                    self.weighted[clear_index].clear();
                 }
 
                 // We've cleared the old buckets, so upkeep is done.  Push our upkeep index forward
@@ -195,12 +364,48 @@ impl AtomicWindowedHistogram {
     }
 }
 
+/// Builds the measurement for a histogram's values, folding the raw values in with the weighted
+/// ones if there are any of the latter.
+fn into_measurement(streaming: StreamingIntegers, mut weighted: Vec<(u64, u64)>) -> Measurement {
+    if weighted.is_empty() {
+        return Measurement::Histogram(streaming);
+    }
+
+    streaming.decompress_with(|values| weighted.extend(values.iter().map(|value| (*value, 1))));
+    weighted.sort_unstable_by_key(|(value, _)| *value);
+
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(weighted.len());
+    for (value, count) in weighted {
+        match merged.last_mut() {
+            Some((last, total)) if *last == value => *total = total.saturating_add(count),
+            _ => merged.push((value, count)),
+        }
+    }
+    Measurement::WeightedHistogram(merged)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{AtomicWindowedHistogram, Clock};
+    use super::{AtomicWindowedHistogram, Clock, Histogram, HistogramGroup};
+    use crate::common::{Measurement, ValueHandle, ValueSnapshot};
     use crossbeam_utils::thread;
+    use metrics_util::StreamingIntegers;
     use std::time::Duration;
 
+    fn stream(measurement: Measurement) -> StreamingIntegers {
+        match measurement {
+            Measurement::Histogram(stream) => stream,
+            _ => panic!("expected unweighted histogram"),
+        }
+    }
+
+    fn weighted(handle: &ValueHandle) -> Vec<(u64, u64)> {
+        match handle.snapshot() {
+            ValueSnapshot::Single(Measurement::WeightedHistogram(values)) => values,
+            _ => panic!("expected weighted histogram"),
+        }
+    }
+
     #[test]
     fn test_histogram_simple_update() {
         let (clock, _ctl) = Clock::mock();
@@ -208,7 +413,7 @@ mod tests {
 
         h.record(1245);
 
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 1);
 
         let values = snapshot.decompress();
@@ -226,7 +431,7 @@ mod tests {
         h.record(1022);
         h.record(1248);
 
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 4);
 
         let values = snapshot.decompress();
@@ -237,6 +442,121 @@ mod tests {
         assert_eq!(values.get(3).unwrap(), &1248);
     }
 
+    #[test]
+    fn test_histogram_record_many() {
+        let (clock, _ctl) = Clock::mock();
+        let h = AtomicWindowedHistogram::new(Duration::from_secs(5), Duration::from_secs(1), clock);
+
+        // A single value is stored as-is, and nothing at all is stored for a count of zero.
+        h.record_many(3, 1);
+        h.record_many(5, 0);
+        assert_eq!(stream(h.drain()).decompress(), vec![3]);
+
+        // Any larger count is stored as one weighted value, however large it is.
+        h.record_many(42, 1 << 40);
+        match h.drain() {
+            Measurement::WeightedHistogram(values) => assert_eq!(values, vec![(42, 1 << 40)]),
+            _ => panic!("expected weighted histogram"),
+        }
+        assert_eq!(stream(h.drain()).len(), 0);
+    }
+
+    #[test]
+    fn test_histogram_record_buckets() {
+        let (clock, _ctl) = Clock::mock();
+        let handle = ValueHandle::histogram(Duration::from_secs(5), Duration::from_secs(1), clock);
+        let histogram: Histogram = handle.clone().into();
+
+        histogram.record_buckets(&[(10, 2), (100, 5), (1000, 3)], 3520, 10);
+        assert_eq!(weighted(&handle), vec![(10, 2), (100, 5), (1000, 3)]);
+
+        // Buckets are stored as they are, no matter how large their counts.
+        let handle = ValueHandle::histogram(
+            Duration::from_secs(5),
+            Duration::from_secs(1),
+            Clock::mock().0,
+        );
+        let histogram: Histogram = handle.clone().into();
+        histogram.record_buckets(&[(10, 1 << 40), (100, 0)], 10 << 40, 1 << 40);
+        assert_eq!(weighted(&handle), vec![(10, 1 << 40)]);
+    }
+
+    #[test]
+    fn test_histogram_record_buckets_overflow() {
+        let (clock, _ctl) = Clock::mock();
+        let handle = ValueHandle::histogram(Duration::from_secs(5), Duration::from_secs(1), clock);
+        let histogram: Histogram = handle.clone().into();
+
+        // Two values fell above the highest bound, with 10,000 of the sum left to them.
+        histogram.record_buckets(&[(10, 2), (100, 3)], 10_320, 7);
+        assert_eq!(weighted(&handle), vec![(10, 2), (100, 3), (5000, 2)]);
+
+        // Values above the highest bound are never recorded as less than it.
+        histogram.record_buckets(&[(100, 1)], 100, 2);
+        assert_eq!(weighted(&handle), vec![(10, 2), (100, 5), (5000, 2)]);
+    }
+
+    #[test]
+    fn test_histogram_clones_share_storage() {
+        let (clock, _ctl) = Clock::mock();
+        let handle = ValueHandle::histogram(Duration::from_secs(5), Duration::from_secs(1), clock);
+        let histogram: Histogram = handle.clone().into();
+
+        let workers = [histogram.clone(), histogram];
+        workers[0].record_value(10);
+        workers[1].record_value(20);
+
+        match handle.snapshot() {
+            ValueSnapshot::Single(measurement) => {
+                assert_eq!(stream(measurement).decompress(), vec![10, 20])
+            }
+            _ => panic!("expected single histogram snapshot"),
+        }
+    }
+
+    #[test]
+    fn test_histogram_record_value_many() {
+        let (clock, _ctl) = Clock::mock();
+        let handle = ValueHandle::histogram(Duration::from_secs(5), Duration::from_secs(1), clock);
+        let histogram: Histogram = handle.clone().into();
+
+        histogram.record_value(7);
+        histogram.record_value_many(42, 5);
+        histogram.record_value_many(99, 0);
+        histogram.record_value_many(42, 1);
+        assert_eq!(weighted(&handle), vec![(7, 1), (42, 6)]);
+    }
+
+    #[test]
+    fn test_histogram_group() {
+        let (clock, _ctl) = Clock::mock();
+        let handles = (0..3)
+            .map(|_| {
+                ValueHandle::histogram(
+                    Duration::from_secs(5),
+                    Duration::from_secs(1),
+                    clock.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let group = handles
+            .iter()
+            .cloned()
+            .map(Histogram::from)
+            .collect::<HistogramGroup>();
+        assert_eq!(group.len(), 3);
+
+        group.record_value(42);
+        group.record_value_many(7, 2);
+
+        for handle in handles {
+            assert_eq!(weighted(&handle), vec![(7, 2), (42, 1)]);
+        }
+
+        assert!(HistogramGroup::default().is_empty());
+    }
+
     #[test]
     fn test_windowed_histogram_rollover() {
         let (clock, ctl) = Clock::mock();
@@ -248,34 +568,34 @@ mod tests {
             AtomicWindowedHistogram::new(Duration::from_secs(5), Duration::from_millis(999), clock);
 
         // Histogram is empty, snapshot is empty.
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 0);
 
         // Immediately add two values, and observe the histogram and snapshot having two values.
         h.record(1);
         h.record(2);
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 2);
         let total: u64 = snapshot.decompress().iter().sum();
         assert_eq!(total, 3);
 
         // Roll forward 3 seconds, should still have everything.
         ctl.increment(Duration::from_secs(3));
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 2);
         let total: u64 = snapshot.decompress().iter().sum();
         assert_eq!(total, 3);
 
         // Roll forward 1 second, should still have everything.
         ctl.increment(Duration::from_secs(1));
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 2);
         let total: u64 = snapshot.decompress().iter().sum();
         assert_eq!(total, 3);
 
         // Roll forward 1 second, should still have everything.
         ctl.increment(Duration::from_secs(1));
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 2);
         let total: u64 = snapshot.decompress().iter().sum();
         assert_eq!(total, 3);
@@ -285,7 +605,7 @@ mod tests {
         h.record(4);
         h.record(5);
 
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 5);
         let total: u64 = snapshot.decompress().iter().sum();
         assert_eq!(total, 15);
@@ -294,19 +614,19 @@ mod tests {
         // cleans bucket #0, the first one we wrote to.  The second and third ones get us right up
         // to the last three values, and then clear them out.
         ctl.increment(Duration::from_secs(1));
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 3);
         let total: u64 = snapshot.decompress().iter().sum();
         assert_eq!(total, 12);
 
         ctl.increment(Duration::from_secs(4));
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 3);
         let total: u64 = snapshot.decompress().iter().sum();
         assert_eq!(total, 12);
 
         ctl.increment(Duration::from_secs(1));
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 0);
 
         // We should also be able to advance by vast periods of time and observe not only old
@@ -315,13 +635,13 @@ mod tests {
         // idle for a while and just got a write" scenarios.
         h.record(42);
 
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 1);
         let total: u64 = snapshot.decompress().iter().sum();
         assert_eq!(total, 42);
 
         ctl.increment(Duration::from_secs(1000));
-        let snapshot = h.snapshot();
+        let snapshot = stream(h.snapshot());
         assert_eq!(snapshot.len(), 0);
     }
 
@@ -367,7 +687,7 @@ mod tests {
             let t3_total = t3.join().expect("thread 3 panicked during test");
 
             let total = t1_total + t2_total + t3_total;
-            let snap = h.snapshot();
+            let snap = stream(h.snapshot());
             assert_eq!(total, snap.len());
         })
         .unwrap();
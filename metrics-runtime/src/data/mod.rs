@@ -5,8 +5,11 @@ pub use counter::Counter;
 mod gauge;
 pub use gauge::Gauge;
 
+mod handle;
+pub use handle::MetricHandle;
+
 mod histogram;
-pub use histogram::{AtomicWindowedHistogram, Histogram};
+pub use histogram::{AtomicWindowedHistogram, Histogram, HistogramGroup};
 
 mod snapshot;
 pub use snapshot::Snapshot;
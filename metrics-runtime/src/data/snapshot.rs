@@ -1,15 +1,36 @@
 use crate::common::Measurement;
-use metrics_core::Key;
+use metrics_core::{Key, ScopedString};
+use metrics_util::MetricValue;
+use std::collections::HashMap;
+use std::time::SystemTime;
 
 /// A collection of point-in-time metric measurements.
 #[derive(Default, Debug)]
 pub struct Snapshot {
     measurements: Vec<(Key, Measurement)>,
+    created: HashMap<Key, SystemTime>,
 }
 
 impl Snapshot {
     pub(crate) fn new(measurements: Vec<(Key, Measurement)>) -> Self {
-        Self { measurements }
+        Self {
+            measurements,
+            created: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn with_created(mut self, created: HashMap<Key, SystemTime>) -> Self {
+        self.created = created;
+        self
+    }
+
+    /// Gets the time at which the given counter was registered.
+    ///
+    /// Only counters carry a creation time.  Exporters can use it to emit the `_created` sample
+    /// defined by OpenMetrics, which lets rates be calculated correctly across restarts; see
+    /// [`MetricValue::render_prometheus_created`].
+    pub fn created(&self, key: &Key) -> Option<SystemTime> {
+        self.created.get(key).cloned()
     }
 
     /// Number of measurements in this snapshot.
@@ -26,4 +47,96 @@ impl Snapshot {
     pub fn into_measurements(self) -> Vec<(Key, Measurement)> {
         self.measurements
     }
+
+    /// Converts a [`Snapshot`] into renderable metric values.
+    ///
+    /// Histograms are decompressed in the process.  See [`MetricValue`] for rendering the values
+    /// in common wire formats.
+    pub fn into_values(self) -> Vec<(Key, MetricValue)> {
+        self.measurements
+            .into_iter()
+            .map(|(key, measurement)| (key, measurement.into()))
+            .collect()
+    }
+
+    /// Converts a [`Snapshot`] into the internal measurements, grouped by metric name.
+    ///
+    /// Every labeled variant of a metric ends up in the same group, keyed by the shared name.
+    /// This mirrors formats like Prometheus, where all series with the same name must be
+    /// rendered together under a single `# TYPE` block.
+    pub fn into_grouped(self) -> HashMap<ScopedString, Vec<(Key, Measurement)>> {
+        let mut groups: HashMap<ScopedString, Vec<(Key, Measurement)>> = HashMap::new();
+        for (key, measurement) in self.measurements {
+            groups
+                .entry(key.name())
+                .or_default()
+                .push((key, measurement));
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Measurement, Snapshot};
+    use metrics_core::{Key, Label};
+    use metrics_util::{MetricValue, StreamingIntegers};
+
+    #[test]
+    fn test_into_values() {
+        let mut stream = StreamingIntegers::new();
+        stream.compress(&[5, 1, 3]);
+
+        let snapshot = Snapshot::new(vec![
+            (Key::from_name("requests"), Measurement::Counter(3)),
+            (Key::from_name("connections"), Measurement::Gauge(-2)),
+            (Key::from_name("latency"), Measurement::Histogram(stream)),
+        ]);
+
+        let values = snapshot.into_values();
+        assert_eq!(
+            values,
+            vec![
+                (Key::from_name("requests"), MetricValue::Counter(3)),
+                (Key::from_name("connections"), MetricValue::Gauge(-2)),
+                (
+                    Key::from_name("latency"),
+                    MetricValue::Histogram(vec![5, 1, 3])
+                ),
+            ]
+        );
+        assert_eq!(values[0].1.render_statsd(&values[0].0), "requests:3|c\n");
+    }
+
+    #[test]
+    fn test_into_grouped() {
+        let snapshot = Snapshot::new(vec![
+            (
+                Key::from_name_and_labels("requests", vec![Label::new("code", "200")]),
+                Measurement::Counter(3),
+            ),
+            (
+                Key::from_name_and_labels("requests", vec![Label::new("code", "500")]),
+                Measurement::Counter(1),
+            ),
+            (Key::from_name("connections"), Measurement::Gauge(7)),
+        ]);
+
+        let groups = snapshot.into_grouped();
+        assert_eq!(groups.len(), 2);
+
+        let requests = groups.get("requests").expect("missing requests group");
+        assert_eq!(requests.len(), 2);
+        let mut codes = requests
+            .iter()
+            .flat_map(|(key, _)| key.labels().map(|l| l.value().to_string()))
+            .collect::<Vec<_>>();
+        codes.sort();
+        assert_eq!(codes, vec!["200", "500"]);
+
+        let connections = groups
+            .get("connections")
+            .expect("missing connections group");
+        assert_eq!(connections.len(), 1);
+    }
 }
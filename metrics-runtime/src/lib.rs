@@ -336,7 +336,8 @@ pub mod observers;
 pub use self::{
     builder::{Builder, BuilderError},
     common::{Delta, Measurement, Scope},
-    control::Controller,
+    control::{Controller, MergeConflict, MergeError},
     receiver::Receiver,
+    registry::RegistryView,
     sink::{AsScoped, Sink, SinkError},
 };
@@ -3,13 +3,14 @@ use crate::{
     common::Scope,
     config::Configuration,
     control::Controller,
+    data::MetricHandle,
     registry::{MetricRegistry, ScopeRegistry},
     sink::Sink,
 };
-use metrics::Recorder;
+use metrics::{MetricOp, Recorder};
 use metrics_core::Key;
 use quanta::{Builder as UpkeepBuilder, Clock, Handle as UpkeepHandle};
-use std::{cell::RefCell, sync::Arc};
+use std::{any::Any, cell::RefCell, sync::Arc};
 
 thread_local! {
     static SINK: RefCell<Option<Sink>> = RefCell::new(None);
@@ -115,4 +116,543 @@ impl Recorder for Receiver {
             sink.as_mut().unwrap().record_value(key, value);
         });
     }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        // Histograms accept any value, so there's nothing to reject.
+        self.record_histogram(key, value);
+        true
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        SINK.with(move |sink| {
+            let mut sink = sink.borrow_mut();
+            if sink.is_none() {
+                let new_sink = self.sink();
+                *sink = Some(new_sink);
+            }
+
+            sink.as_mut()
+                .unwrap()
+                .histogram(key)
+                .record_value_many(value, count);
+        });
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        SINK.with(move |sink| {
+            let mut sink = sink.borrow_mut();
+            if sink.is_none() {
+                let new_sink = self.sink();
+                *sink = Some(new_sink);
+            }
+
+            sink.as_mut()
+                .unwrap()
+                .histogram(key)
+                .record_buckets(buckets, sum, count);
+        });
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.metric_registry.set_metadata(key, Arc::from(meta));
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        match self.metric_registry.handle(key) {
+            Some(MetricHandle::Counter(counter)) => Some(counter.value()),
+            _ => None,
+        }
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        match self.metric_registry.handle(key) {
+            Some(MetricHandle::Gauge(gauge)) => Some(gauge.value()),
+            _ => None,
+        }
+    }
+
+    fn record_batch(&self, ops: &[MetricOp]) {
+        SINK.with(move |sink| {
+            let mut sink = sink.borrow_mut();
+            if sink.is_none() {
+                let new_sink = self.sink();
+                *sink = Some(new_sink);
+            }
+
+            let sink = sink.as_mut().unwrap();
+            for op in ops {
+                match op {
+                    MetricOp::IncrementCounter(key, value) => {
+                        sink.increment_counter(key.clone(), *value)
+                    }
+                    MetricOp::UpdateGauge(key, value) => sink.update_gauge(key.clone(), *value),
+                    MetricOp::RecordHistogram(key, value) => sink.record_value(key.clone(), *value),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Receiver;
+    use crate::{common::Measurement, data::MetricHandle};
+    use crate::{MergeConflict, MergeError};
+    use metrics::{MetricOp, Recorder};
+    use metrics_core::{Key, Label, Observe, Observer};
+    use metrics_util::{MetricValue, Selector};
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Retention {
+        days: u32,
+    }
+
+    #[test]
+    fn test_describe_with_meta() {
+        let receiver = Receiver::builder().build().unwrap();
+        receiver.describe_with_meta(Key::from_name("requests"), Box::new(Retention { days: 30 }));
+
+        let controller = receiver.controller();
+        assert!(controller.metadata(&Key::from_name("errors")).is_none());
+
+        let meta = controller.metadata(&Key::from_name("requests")).unwrap();
+        assert!(meta.clone().downcast::<String>().is_err());
+        let retention = meta.downcast::<Retention>().unwrap();
+        assert_eq!(*retention, Retention { days: 30 });
+    }
+
+    fn counter_total(values: &[(Key, MetricValue)]) -> u64 {
+        values
+            .iter()
+            .map(|(_, value)| match value {
+                MetricValue::Counter(value) => *value,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_snapshot_and_reset() {
+        let receiver = Receiver::builder().build().unwrap();
+        let controller = receiver.controller();
+        let mut sink = receiver.sink();
+        sink.increment_counter("requests", 5);
+        sink.update_gauge("connections", 7);
+        sink.record_value("latency", 10);
+        sink.record_value("latency", 20);
+
+        let mut values = controller.snapshot_and_reset();
+        values.sort_by(|a, b| a.0.name().cmp(&b.0.name()));
+        assert_eq!(
+            values,
+            vec![
+                (Key::from_name("connections"), MetricValue::Gauge(7)),
+                (
+                    Key::from_name("latency"),
+                    MetricValue::Histogram(vec![10, 20])
+                ),
+                (Key::from_name("requests"), MetricValue::Counter(5)),
+            ]
+        );
+
+        // Counters and histograms start over from nothing, while gauges keep their value.
+        let mut values = controller.snapshot_and_reset();
+        values.sort_by(|a, b| a.0.name().cmp(&b.0.name()));
+        assert_eq!(
+            values,
+            vec![
+                (Key::from_name("connections"), MetricValue::Gauge(7)),
+                (Key::from_name("latency"), MetricValue::Histogram(vec![])),
+                (Key::from_name("requests"), MetricValue::Counter(0)),
+            ]
+        );
+
+        sink.increment_counter("requests", 2);
+        sink.record_value("latency", 30);
+        assert_eq!(receiver.counter_value(&Key::from_name("requests")), Some(2));
+        match controller
+            .handle(&Key::from_name("latency"))
+            .unwrap()
+            .measurement()
+        {
+            Measurement::Histogram(stream) => assert_eq!(stream.decompress(), vec![30]),
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_and_reset_concurrent() {
+        let receiver = Receiver::builder().build().unwrap();
+        let controller = receiver.controller();
+        receiver.sink().increment_counter("requests", 0);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = thread::spawn({
+            let mut sink = receiver.sink();
+            let done = done.clone();
+            move || {
+                for _ in 0..100_000 {
+                    sink.increment_counter("requests", 1);
+                }
+                done.store(true, Ordering::Release);
+            }
+        });
+
+        // Every increment shows up in exactly one of the snapshots.
+        let mut seen = 0;
+        while !done.load(Ordering::Acquire) {
+            seen += counter_total(&controller.snapshot_and_reset());
+        }
+        writer.join().unwrap();
+        seen += counter_total(&controller.snapshot_and_reset());
+        assert_eq!(seen, 100_000);
+    }
+
+    #[test]
+    fn test_snapshot_and_reset_shared() {
+        let receiver = Receiver::builder().build().unwrap();
+        let controller = receiver.controller();
+        let mut sink = receiver.sink();
+        let counter = sink.counter("messages");
+        let alias = sink.share_counter("messages_total", &counter);
+        counter.record(2);
+        alias.record(1);
+
+        // Both keys report the whole value, even though it's only reset once.
+        assert_eq!(
+            controller.snapshot_and_reset(),
+            vec![
+                (Key::from_name("messages"), MetricValue::Counter(3)),
+                (Key::from_name("messages_total"), MetricValue::Counter(3)),
+            ]
+        );
+        assert_eq!(counter_total(&controller.snapshot_and_reset()), 0);
+
+        // Merged metrics share their storage with the controller they came from.
+        let other = Receiver::builder().build().unwrap();
+        other.sink().increment_counter("hits", 4);
+        controller
+            .merge_from(&other.controller(), MergeConflict::Fail)
+            .unwrap();
+        assert_eq!(counter_total(&controller.snapshot_and_reset()), 4);
+        assert_eq!(other.counter_value(&Key::from_name("hits")), Some(0));
+    }
+
+    #[test]
+    fn test_read_back_values() {
+        let receiver = Receiver::builder().build().unwrap();
+        let requests = Key::from_name("requests");
+        let connections = Key::from_name("connections");
+        assert_eq!(receiver.counter_value(&requests), None);
+
+        receiver.increment_counter(requests.clone(), 2);
+        receiver.increment_counter(requests.clone(), 3);
+        receiver.update_gauge(connections.clone(), -4);
+
+        assert_eq!(receiver.counter_value(&requests), Some(5));
+        assert_eq!(receiver.gauge_value(&connections), Some(-4));
+        assert_eq!(receiver.gauge_value(&requests), None);
+    }
+
+    #[test]
+    fn test_record_batch() {
+        let receiver = Receiver::builder().build().unwrap();
+        receiver.record_batch(&[
+            MetricOp::IncrementCounter(Key::from_name("requests"), 2),
+            MetricOp::UpdateGauge(Key::from_name("connections"), 7),
+            MetricOp::RecordHistogram(Key::from_name("latency"), 40),
+            MetricOp::IncrementCounter(Key::from_name("requests"), 3),
+            MetricOp::UpdateGauge(Key::from_name("connections"), -1),
+            MetricOp::RecordHistogram(Key::from_name("latency"), 2),
+        ]);
+
+        let mut snapshot = receiver.controller().snapshot().into_measurements();
+        snapshot.sort_by_key(|(k, _)| k.name());
+        assert_eq!(snapshot.len(), 3);
+
+        match &snapshot[0] {
+            (key, Measurement::Gauge(value)) if key.name() == "connections" => {
+                assert_eq!(*value, -1)
+            }
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+        match &snapshot[1] {
+            (key, Measurement::Histogram(stream)) if key.name() == "latency" => {
+                assert_eq!(stream.decompress(), vec![40, 2])
+            }
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+        match &snapshot[2] {
+            (key, Measurement::Counter(value)) if key.name() == "requests" => {
+                assert_eq!(*value, 5)
+            }
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+    }
+
+    #[derive(Default)]
+    struct WeightedObserver {
+        histograms: Vec<(Key, Vec<(u64, u64)>)>,
+    }
+
+    impl Observer for WeightedObserver {
+        fn observe_counter(&mut self, _key: Key, _value: u64) {}
+
+        fn observe_gauge(&mut self, _key: Key, _value: i64) {}
+
+        fn observe_histogram(&mut self, _key: Key, _values: &[u64]) {
+            panic!("weighted histograms should be observed as such");
+        }
+
+        fn observe_histogram_weighted(&mut self, key: Key, values: &[(u64, u64)]) {
+            self.histograms.push((key, values.to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_record_histogram_buckets() {
+        let receiver = Receiver::builder().build().unwrap();
+        let key = Key::from_name("federated.latency");
+
+        // A million values in the lowest bucket, and one above the highest bound.
+        receiver.record_histogram_buckets(
+            key.clone(),
+            &[(10, 1_000_000), (50, 4)],
+            10_001_000,
+            1_000_005,
+        );
+        receiver.record_histogram_many(key.clone(), 50, 2);
+
+        let mut observer = WeightedObserver::default();
+        receiver.controller().observe(&mut observer);
+        assert_eq!(
+            observer.histograms,
+            vec![(key.clone(), vec![(10, 1_000_000), (50, 6), (800, 1)])]
+        );
+
+        // Weighted values are handed over as they are, rather than expanded.
+        assert_eq!(
+            receiver.controller().snapshot_and_reset(),
+            vec![(
+                key,
+                MetricValue::WeightedHistogram(vec![(10, 1_000_000), (50, 6), (800, 1)])
+            )]
+        );
+    }
+
+    #[test]
+    fn test_remove_by_name() {
+        let receiver = Receiver::builder().build().unwrap();
+        let mut sink = receiver.sink();
+        sink.increment_counter_with_labels("requests", 1, &[("code", "200")]);
+        sink.increment_counter_with_labels("requests", 1, &[("code", "500")]);
+        sink.increment_counter("requests", 1);
+        sink.increment_counter("requests_total", 1);
+        sink.update_gauge("connections", 4);
+
+        let controller = receiver.controller();
+        assert_eq!(controller.remove_by_name("requests"), 3);
+        assert_eq!(controller.remove_by_name("requests"), 0);
+
+        let mut remaining = controller
+            .snapshot()
+            .into_measurements()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        remaining.sort_by_key(|k| k.name());
+        assert_eq!(
+            remaining,
+            vec![
+                Key::from_name("connections"),
+                Key::from_name("requests_total")
+            ]
+        );
+
+        let mut scoped = receiver.sink().scoped("db");
+        scoped.increment_counter_with_labels("queries", 1, vec![Label::new("table", "users")]);
+        assert_eq!(controller.remove_by_name("queries"), 0);
+        assert_eq!(controller.remove_by_name("db.queries"), 1);
+    }
+
+    #[test]
+    fn test_remove_matching() {
+        let receiver = Receiver::builder().build().unwrap();
+        let mut sink = receiver.sink();
+        sink.increment_counter_with_labels("requests", 1, &[("env", "test"), ("code", "200")]);
+        sink.increment_counter_with_labels("requests", 1, &[("env", "test"), ("code", "500")]);
+        sink.update_gauge_with_labels("connections", 2, &[("env", "test")]);
+        sink.increment_counter_with_labels("requests", 1, &[("env", "prod"), ("code", "200")]);
+        sink.increment_counter("requests", 1);
+
+        let controller = receiver.controller();
+        let selector = Selector::parse(r#"{env="test"}"#).unwrap();
+        assert_eq!(controller.remove_matching(&selector), 3);
+        assert_eq!(controller.remove_matching(&selector), 0);
+
+        let remaining = controller
+            .snapshot()
+            .into_measurements()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            remaining,
+            vec![
+                Key::from_name_and_labels(
+                    "requests",
+                    vec![Label::new("env", "prod"), Label::new("code", "200")]
+                ),
+                Key::from_name("requests"),
+            ]
+        );
+
+        let named = Selector::parse(r#"requests{code!="200"}"#).unwrap();
+        assert_eq!(controller.remove_matching(&named), 1);
+        assert_eq!(controller.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_from() {
+        let app = Receiver::builder().build().unwrap();
+        let mut app_sink = app.sink();
+        app_sink.increment_counter("requests", 1);
+        app_sink.update_gauge("connections", 2);
+
+        let subsystem = Receiver::builder().build().unwrap();
+        let mut subsystem_sink = subsystem.sink().scoped("cache");
+        subsystem_sink.increment_counter("hits", 3);
+        subsystem_sink.record_value("latency", 4);
+        subsystem.sink().increment_counter("requests", 5);
+
+        let controller = app.controller();
+        let other = subsystem.controller();
+        match controller.merge_from(&other, MergeConflict::Fail) {
+            Err(MergeError::Conflict(key)) => assert_eq!(key, Key::from_name("requests")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(controller.len(), 2);
+
+        assert_eq!(
+            controller
+                .merge_from(&other, MergeConflict::KeepExisting)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            controller
+                .merge_from(&other, MergeConflict::KeepExisting)
+                .unwrap(),
+            0
+        );
+
+        // Merged metrics share their storage with the other controller.
+        subsystem_sink.increment_counter("hits", 10);
+
+        let mut values = controller
+            .snapshot()
+            .into_values()
+            .into_iter()
+            .map(|(key, value)| (key.name().into_owned(), value))
+            .collect::<Vec<_>>();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            values,
+            vec![
+                ("cache.hits".to_string(), MetricValue::Counter(13)),
+                ("cache.latency".to_string(), MetricValue::Histogram(vec![4])),
+                ("connections".to_string(), MetricValue::Gauge(2)),
+                ("requests".to_string(), MetricValue::Counter(1)),
+            ]
+        );
+
+        // Removing a merged metric leaves the other controller alone.
+        assert!(controller.remove(&Key::from_name("cache.hits")));
+        assert_eq!(other.len(), 3);
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let receiver = Receiver::builder().build().unwrap();
+        let controller = receiver.controller();
+        let changes = controller.subscribe();
+        let dropped = controller.subscribe();
+        drop(dropped);
+
+        // A subscriber blocked waiting for a change is woken by a new registration.
+        let waiter = thread::spawn(move || {
+            let woken = changes.recv_timeout(Duration::from_secs(10)).is_ok();
+            (changes, woken)
+        });
+        receiver.sink().increment_counter("requests", 1);
+        let (changes, woken) = waiter.join().unwrap();
+        assert!(woken);
+        assert!(changes.try_recv().is_err());
+
+        // Removals are changes too.
+        assert!(controller.remove(&Key::from_name("requests")));
+        assert!(changes.try_recv().is_ok());
+        assert!(!controller.remove(&Key::from_name("requests")));
+        assert!(changes.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_handle() {
+        let receiver = Receiver::builder().build().unwrap();
+        let mut sink = receiver.sink();
+        sink.increment_counter("requests", 2);
+        sink.update_gauge("connections", 5);
+        sink.record_value("latency", 40);
+        sink.proxy("proxied", Vec::new);
+        sink.scoped("db").increment_counter("queries", 1);
+
+        let controller = receiver.controller();
+        assert!(controller.handle(&Key::from_name("errors")).is_none());
+        assert!(controller.handle(&Key::from_name("proxied")).is_none());
+        assert!(controller.handle(&Key::from_name("queries")).is_none());
+
+        match controller.handle(&Key::from_name("requests")) {
+            Some(MetricHandle::Counter(counter)) => counter.record(3),
+            other => panic!("unexpected handle: {:?}", other.map(|h| h.kind())),
+        }
+        match controller.handle(&Key::from_name("connections")) {
+            Some(MetricHandle::Gauge(gauge)) => gauge.increment(1),
+            other => panic!("unexpected handle: {:?}", other.map(|h| h.kind())),
+        }
+        match controller.handle(&Key::from_name("latency")) {
+            Some(MetricHandle::Histogram(histogram)) => histogram.record_value(2),
+            other => panic!("unexpected handle: {:?}", other.map(|h| h.kind())),
+        }
+
+        let read = |name: &'static str| {
+            controller
+                .handle(&Key::from_name(name))
+                .unwrap()
+                .measurement()
+        };
+        match read("requests") {
+            Measurement::Counter(value) => assert_eq!(value, 5),
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+        match read("connections") {
+            Measurement::Gauge(value) => assert_eq!(value, 6),
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+        match read("latency") {
+            Measurement::Histogram(stream) => assert_eq!(stream.decompress(), vec![40, 2]),
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+        match read("db.queries") {
+            Measurement::Counter(value) => assert_eq!(value, 1),
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+    }
 }
@@ -1,17 +1,172 @@
-use crate::common::{Identifier, Kind, Measurement, ValueHandle, ValueSnapshot};
+use crate::common::{fold_name_case, Identifier, Kind, Measurement, ValueHandle, ValueSnapshot};
 use crate::config::Configuration;
-use crate::data::Snapshot;
+use crate::data::{MetricHandle, Snapshot};
 use crate::registry::ScopeRegistry;
 use arc_swap::ArcSwap;
-use metrics_core::Observer;
+use atomic_shim::AtomicU64;
+use metrics_core::{Key, Observer, Unit};
+use metrics_util::{KeyInterner, MetricValue};
+use parking_lot::{Mutex, RwLock};
 use quanta::Clock;
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc};
+use std::time::SystemTime;
+
+/// Maximum number of registered keys held for sharing.  Keys past this are still registered, but
+/// are stored separately for each kind and scope they're registered under.
+const KEY_INTERNER_CAPACITY: usize = 4096;
+
+/// Hasher for keys which are already hashes, and so are used as-is.
+#[derive(Debug, Default)]
+struct PrehashedHasher(u64);
+
+impl Hasher for PrehashedHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(*byte);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n;
+    }
+}
+
+type PrehashedMap<V> = HashMap<u64, V, BuildHasherDefault<PrehashedHasher>>;
+
+/// Registered metrics, in the order they were registered.
+#[derive(Debug, Default, Clone)]
+struct Metrics {
+    // Maps each identifier to its position in `handles`.
+    index: HashMap<Arc<Identifier>, usize>,
+    handles: Vec<(Arc<Identifier>, ValueHandle)>,
+    // Maps each key, as it appears in snapshots and with its name folded, to the position of the
+    // first metric registered with it that isn't a proxy.
+    by_key: HashMap<Key, usize>,
+    // When each counter was registered.
+    created: HashMap<Arc<Identifier>, SystemTime>,
+    // Maps hashes given by callers to the metrics they've been used to look up.  Different
+    // identifiers can share a hash, so every candidate is checked for equality.
+    prehashed: PrehashedMap<Vec<(Arc<Identifier>, ValueHandle)>>,
+}
+
+impl Metrics {
+    fn get(&self, id: &Identifier) -> Option<&ValueHandle> {
+        self.index.get(id).map(|idx| &self.handles[*idx].1)
+    }
+
+    fn get_prehashed(&self, hash: u64, id: &Identifier) -> Option<&ValueHandle> {
+        self.prehashed
+            .get(&hash)?
+            .iter()
+            .find(|(candidate, _)| **candidate == *id)
+            .map(|(_, handle)| handle)
+    }
+
+    /// Gets the first metric registered with the given key, as it appears in snapshots, that
+    /// isn't a proxy.
+    fn get_by_key(&self, key: &Key) -> Option<(&Identifier, &ValueHandle)> {
+        self.by_key.get(key).map(|idx| {
+            let (id, handle) = &self.handles[*idx];
+            (id.as_ref(), handle)
+        })
+    }
+
+    fn insert(
+        &mut self,
+        id: Arc<Identifier>,
+        scoped_key: Key,
+        handle: ValueHandle,
+    ) -> Option<ValueHandle> {
+        if let Some(existing) = self.get(&id) {
+            return Some(existing.clone());
+        }
+
+        if id.kind() == Kind::Counter {
+            self.created.insert(id.clone(), SystemTime::now());
+        }
+        if id.kind() != Kind::Proxy {
+            self.by_key.entry(scoped_key).or_insert(self.handles.len());
+        }
+        self.index.insert(id.clone(), self.handles.len());
+        self.handles.push((id, handle));
+        None
+    }
+
+    /// Removes all metrics matching the given predicate, returning the removed identifiers.
+    ///
+    /// `scoped_key` gives the key of each remaining metric, as it appears in snapshots and with
+    /// its name folded, so that they can be indexed by it again.
+    fn remove_where<F, K>(&mut self, mut f: F, scoped_key: K) -> Vec<Arc<Identifier>>
+    where
+        F: FnMut(&Identifier) -> bool,
+        K: Fn(&Identifier) -> Key,
+    {
+        let (removed, kept) = self
+            .handles
+            .drain(..)
+            .partition::<Vec<_>, _>(|(id, _)| f(id));
+        self.handles = kept;
+        self.index = self
+            .handles
+            .iter()
+            .enumerate()
+            .map(|(idx, (id, _))| (id.clone(), idx))
+            .collect();
+        self.by_key.clear();
+        for (idx, (id, _)) in self.handles.iter().enumerate() {
+            if id.kind() != Kind::Proxy {
+                self.by_key.entry(scoped_key(id)).or_insert(idx);
+            }
+        }
+
+        for (id, _) in &removed {
+            self.created.remove(id);
+        }
+        if !self.prehashed.is_empty() {
+            let removed = removed
+                .iter()
+                .map(|(id, _)| &**id as *const Identifier)
+                .collect::<HashSet<_>>();
+            for candidates in self.prehashed.values_mut() {
+                candidates.retain(|(id, _)| !removed.contains(&(&**id as *const Identifier)));
+            }
+            self.prehashed
+                .retain(|_, candidates| !candidates.is_empty());
+        }
+        removed.into_iter().map(|(id, _)| id).collect()
+    }
+
+    /// Iterates over all registered metrics, in the order they were registered.
+    fn iter_by_insertion(&self) -> impl Iterator<Item = (&Identifier, &ValueHandle)> {
+        self.handles
+            .iter()
+            .map(|(id, handle)| (id.as_ref(), handle))
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct MetricRegistry {
     scope_registry: Arc<ScopeRegistry>,
-    metrics: ArcSwap<HashMap<Identifier, ValueHandle>>,
+    // Identifiers are reference counted so that the metrics, which are copied every time a new
+    // metric is registered, don't need to deep clone every key they hold.
+    metrics: ArcSwap<Metrics>,
+    // Registered keys, so that a key registered under several kinds or scopes is stored once, and
+    // identifiers sharing it compare by pointer.
+    keys: KeyInterner,
+    metadata: RwLock<HashMap<Key, Arc<dyn Any + Send + Sync>>>,
+    state: Mutex<HashMap<Key, Box<dyn Any + Send>>>,
+    // Number of passes through the slow path of registration.
+    contention: AtomicU64,
+    // Channels to signal whenever a metric is registered or removed.
+    subscribers: Mutex<Vec<mpsc::SyncSender<()>>>,
     config: Configuration,
     clock: Clock,
 }
@@ -20,31 +175,72 @@ impl MetricRegistry {
     pub fn new(scope_registry: Arc<ScopeRegistry>, config: Configuration, clock: Clock) -> Self {
         MetricRegistry {
             scope_registry,
-            metrics: ArcSwap::new(Arc::new(HashMap::new())),
+            metrics: ArcSwap::new(Arc::new(Metrics::default())),
+            keys: KeyInterner::with_capacity(KEY_INTERNER_CAPACITY),
+            metadata: RwLock::new(HashMap::new()),
+            state: Mutex::new(HashMap::new()),
+            contention: AtomicU64::new(0),
+            subscribers: Mutex::new(Vec::new()),
             config,
             clock,
         }
     }
 
     pub fn get_or_register(&self, id: Identifier) -> ValueHandle {
+        let kind = id.kind();
+        self.get_or_register_with(id, || match kind {
+            Kind::Counter => ValueHandle::counter(),
+            Kind::Gauge => ValueHandle::gauge(),
+            Kind::Histogram => ValueHandle::histogram(
+                self.config.histogram_window,
+                self.config.histogram_granularity,
+                self.clock.clone(),
+            ),
+            Kind::Proxy => ValueHandle::proxy(),
+        })
+    }
+
+    /// Gets the handle registered for the given identifier, or registers the handle created by
+    /// `f` if there isn't one.
+    ///
+    /// As handles are reference counted, `f` can return a clone of a handle that is already
+    /// registered elsewhere, which lets several identifiers share the same underlying storage.
+    ///
+    /// `f` is called before the new set of metrics is swapped in, and no lock is held while it
+    /// runs, so if it panics, nothing is registered and the registry is left as it was.
+    pub fn get_or_register_with<F>(&self, id: Identifier, f: F) -> ValueHandle
+    where
+        F: FnOnce() -> ValueHandle,
+    {
+        let id = if self.config.case_insensitive_names {
+            id.fold_name_case()
+        } else {
+            id
+        };
+        let id = Arc::new(id);
+        let scoped_key = self.fold_key(self.scoped_key(&id));
+
+        let mut f = Some(f);
+        let mut new_handle = None;
+        let mut shared_id = None;
         loop {
             let old_metrics = self.metrics.load();
             match old_metrics.get(&id) {
                 Some(handle) => return handle.clone(),
                 None => {
-                    let value_handle = match id.kind() {
-                        Kind::Counter => ValueHandle::counter(),
-                        Kind::Gauge => ValueHandle::gauge(),
-                        Kind::Histogram => ValueHandle::histogram(
-                            self.config.histogram_window,
-                            self.config.histogram_granularity,
-                            self.clock.clone(),
-                        ),
-                        Kind::Proxy => ValueHandle::proxy(),
-                    };
+                    self.contention.fetch_add(1, Ordering::Relaxed);
+                    let value_handle = new_handle
+                        .get_or_insert_with(|| (f.take().unwrap())())
+                        .clone();
+                    let id = shared_id
+                        .get_or_insert_with(|| {
+                            let key = self.keys.intern_shared(id.key().clone());
+                            Arc::new(Identifier::clone(&id).with_shared_key(key))
+                        })
+                        .clone();
 
                     let mut new_metrics = (**self.metrics.load()).clone();
-                    match new_metrics.insert(id.clone(), value_handle.clone()) {
+                    match new_metrics.insert(id.clone(), scoped_key.clone(), value_handle.clone()) {
                         Some(other_value_handle) => {
                             // Somebody else beat us to it.
                             return other_value_handle;
@@ -54,6 +250,12 @@ impl MetricRegistry {
                                 .metrics
                                 .compare_and_swap(&old_metrics, Arc::new(new_metrics));
                             if Arc::ptr_eq(&old_metrics, &prev_metrics) {
+                                // Subscribers are told first, so that a panicking hook can't
+                                // keep them from hearing about a metric that is registered.
+                                self.notify_subscribers();
+                                if let Some(hook) = &self.config.on_register {
+                                    hook.call(&self.scoped_key(&id));
+                                }
                                 return value_handle;
                             }
                             // If we weren't able to cleanly update the map, then try again.
@@ -64,85 +266,458 @@ impl MetricRegistry {
         }
     }
 
-    pub fn snapshot(&self) -> Snapshot {
-        let mut values = Vec::new();
+    /// Gets the handle registered for the given identifier, looking it up by a hash supplied by the
+    /// caller rather than by hashing the identifier, and registering a new handle if there isn't
+    /// one.
+    ///
+    /// The same identifier must always be given the same hash.  The first lookup of each
+    /// identifier with a given hash takes the regular path, and remembers the hash for later
+    /// lookups.
+    pub fn get_or_register_prehashed(&self, hash: u64, id: Identifier) -> ValueHandle {
+        let id = if self.config.case_insensitive_names {
+            id.fold_name_case()
+        } else {
+            id
+        };
+
+        if let Some(handle) = self.metrics.load().get_prehashed(hash, &id) {
+            return handle.clone();
+        }
+
+        let handle = self.get_or_register(id.clone());
+        loop {
+            let old_metrics = self.metrics.load();
+            if old_metrics.get_prehashed(hash, &id).is_some() {
+                return handle;
+            }
+
+            let mut new_metrics = (**old_metrics).clone();
+            let registered = match new_metrics.index.get(&id) {
+                Some(idx) => new_metrics.handles[*idx].clone(),
+                // The metric was removed in the meantime, so there's nothing to remember.
+                None => return handle,
+            };
+            new_metrics
+                .prehashed
+                .entry(hash)
+                .or_default()
+                .push(registered);
 
-        let metrics = (**self.metrics.load()).clone();
-        for (id, value) in metrics.into_iter() {
-            let (key, scope_handle, _) = id.into_parts();
-            let scope = self.scope_registry.get(scope_handle);
+            let prev_metrics = self
+                .metrics
+                .compare_and_swap(&old_metrics, Arc::new(new_metrics));
+            if Arc::ptr_eq(&old_metrics, &prev_metrics) {
+                return handle;
+            }
+            // If we weren't able to cleanly update the map, then try again.
+        }
+    }
+
+    /// Removes every metric whose key, as it appears in snapshots, matches the given predicate,
+    /// along with any metadata and state attached to its key.
+    ///
+    /// Returns the number of metrics removed.
+    pub fn remove_where<F>(&self, mut f: F) -> usize
+    where
+        F: FnMut(&Key) -> bool,
+    {
+        loop {
+            let old_metrics = self.metrics.load();
+            let mut new_metrics = (**old_metrics).clone();
+            let removed = new_metrics.remove_where(
+                |id| f(&self.scoped_key(id)),
+                |id| self.fold_key(self.scoped_key(id)),
+            );
+            if removed.is_empty() {
+                return 0;
+            }
 
-            match value.snapshot() {
-                ValueSnapshot::Single(measurement) => {
-                    let key = key.map_name(|name| scope.into_string(name));
-                    values.push((key, measurement));
+            let prev_metrics = self
+                .metrics
+                .compare_and_swap(&old_metrics, Arc::new(new_metrics));
+            if Arc::ptr_eq(&old_metrics, &prev_metrics) {
+                // Every metric with a matching key is removed at once, so nothing registered is
+                // left referring to the metadata or state of a removed key.
+                let keys = removed
+                    .iter()
+                    .map(|id| self.fold_key(self.scoped_key(id)))
+                    .collect::<HashSet<_>>();
+                {
+                    let mut metadata = self.metadata.write();
+                    let mut state = self.state.lock();
+                    for key in &keys {
+                        metadata.remove(key);
+                        state.remove(key);
+                    }
                 }
-                ValueSnapshot::Multiple(mut measurements) => {
-                    // Tack on the key name that this proxy was registered with to the scope so
-                    // that we can clone _that_, and then scope our individual measurements.
-                    let (base_key, labels) = key.into_parts();
-                    let scope = scope.clone().add_part(base_key);
-
-                    for (subkey, measurement) in measurements.drain(..) {
-                        let scope = scope.clone();
-                        let mut subkey = subkey.map_name(|name| scope.into_string(name));
-                        subkey.add_labels(labels.clone());
-                        values.push((subkey, measurement));
+
+                self.notify_subscribers();
+                if let Some(hook) = &self.config.on_deregister {
+                    for id in &removed {
+                        hook.call(&self.scoped_key(id));
                     }
                 }
+                return removed.len();
+            }
+            // If we weren't able to cleanly update the map, then try again.
+        }
+    }
+
+    /// Registers every metric of `other` in this registry, sharing its storage.
+    ///
+    /// Metrics are registered under their keys as they appear in snapshots of `other`, with
+    /// their scope applied.  Metrics registered in both, with the same key and kind, are
+    /// conflicts: with `fail_on_conflict`, the key of the first one is returned as an error
+    /// before anything is registered, and otherwise they are skipped.  Returns the number of
+    /// metrics registered.
+    pub fn merge_from(&self, other: &MetricRegistry, fail_on_conflict: bool) -> Result<usize, Key> {
+        let existing = self
+            .metrics
+            .load()
+            .iter_by_insertion()
+            .map(|(id, _)| (self.scoped_key(id), id.kind()))
+            .collect::<HashSet<_>>();
+
+        let other_metrics = other.metrics.load();
+        let mut merged = Vec::new();
+        for (id, handle) in other_metrics.iter_by_insertion() {
+            let entry = (other.scoped_key(id), id.kind());
+            if existing.contains(&entry) {
+                if fail_on_conflict {
+                    return Err(entry.0);
+                }
+                continue;
             }
+            merged.push((entry, handle.clone()));
         }
 
-        Snapshot::new(values)
+        let count = merged.len();
+        for ((key, kind), handle) in merged {
+            // Scopes are specific to each registry, but the key already has its scope applied.
+            self.get_or_register_with(Identifier::new(key, 0, kind), || handle);
+        }
+        Ok(count)
     }
 
-    pub fn observe<O: Observer>(&self, observer: &mut O) {
-        let metrics = (**self.metrics.load()).clone();
-        for (id, value) in metrics.into_iter() {
-            let (key, scope_handle, _) = id.into_parts();
-            let scope = self.scope_registry.get(scope_handle);
-
-            let observe = |observer: &mut O, key, measurement| match measurement {
-                Measurement::Counter(value) => observer.observe_counter(key, value),
-                Measurement::Gauge(value) => observer.observe_gauge(key, value),
-                Measurement::Histogram(stream) => stream.decompress_with(|values| {
-                    observer.observe_histogram(key.clone(), values);
-                }),
-            };
+    /// Creates a channel which is signalled whenever a metric is registered or removed.
+    ///
+    /// At most one signal is pending at a time, so a signal stands for any number of changes
+    /// since the receiver last took one.
+    pub fn subscribe(&self) -> mpsc::Receiver<()> {
+        let (tx, rx) = mpsc::sync_channel(1);
+        self.subscribers.lock().push(tx);
+        rx
+    }
 
-            match value.snapshot() {
-                ValueSnapshot::Single(measurement) => {
-                    let key = key.map_name(|name| scope.into_string(name));
-                    observe(observer, key, measurement);
+    fn notify_subscribers(&self) {
+        let mut subscribers = self.subscribers.lock();
+        // A full channel already has a signal pending, but a disconnected one has no receiver
+        // left to signal.
+        subscribers.retain(|tx| match tx.try_send(()) {
+            Ok(()) | Err(mpsc::TrySendError::Full(())) => true,
+            Err(mpsc::TrySendError::Disconnected(())) => false,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.metrics.load().handles.len()
+    }
+
+    pub fn contention_count(&self) -> u64 {
+        self.contention.load(Ordering::Relaxed)
+    }
+
+    pub fn set_metadata(&self, key: Key, meta: Arc<dyn Any + Send + Sync>) {
+        self.metadata.write().insert(self.fold_key(key), meta);
+    }
+
+    pub fn metadata(&self, key: &Key) -> Option<Arc<dyn Any + Send + Sync>> {
+        self.metadata
+            .read()
+            .get(&self.fold_key(key.clone()))
+            .cloned()
+    }
+
+    /// Gets the unit the given metric was described with, if any.
+    fn unit(&self, key: &Key) -> Option<Unit> {
+        self.metadata
+            .read()
+            .get(&self.fold_key(key.clone()))
+            .and_then(|meta| meta.downcast_ref::<Unit>())
+            .cloned()
+    }
+
+    pub fn with_state_mut<T, F, R>(&self, key: &Key, f: F) -> R
+    where
+        T: Any + Send + Default,
+        F: FnOnce(&mut T) -> R,
+    {
+        let key = self.fold_key(key.clone());
+        let mut state = self.state.lock();
+        if !state.get(&key).map_or(false, |s| s.is::<T>()) {
+            state.insert(key.clone(), Box::new(T::default()));
+        }
+
+        let value = state
+            .get_mut(&key)
+            .and_then(|s| s.downcast_mut::<T>())
+            .expect("state was just set to the requested type");
+        f(value)
+    }
+
+    /// Removes the metrics with the given key, as it appears in snapshots.
+    ///
+    /// Returns whether or not any metric was removed.
+    pub fn remove(&self, key: &Key) -> bool {
+        self.remove_where(|k| self.keys_match(k, key)) > 0
+    }
+
+    /// Removes every metric with the given name, as it appears in snapshots, whatever its labels.
+    ///
+    /// Returns the number of metrics removed.
+    pub fn remove_by_name(&self, name: &str) -> usize {
+        self.remove_where(|k| self.names_match(&k.name(), name))
+    }
+
+    /// Gets a typed handle to the first registered metric with the given key, as it appears in
+    /// snapshots.  Proxies have no handle, and are skipped.
+    pub fn handle(&self, key: &Key) -> Option<MetricHandle> {
+        let metrics = self.metrics.load();
+        let (id, handle) = metrics.get_by_key(&self.fold_key(key.clone()))?;
+
+        let handle = handle.clone();
+        Some(match id.kind() {
+            Kind::Counter => MetricHandle::Counter(handle.into()),
+            Kind::Gauge => MetricHandle::Gauge(handle.into()),
+            Kind::Histogram => MetricHandle::Histogram(handle.into()),
+            Kind::Proxy => unreachable!("proxies are filtered out"),
+        })
+    }
+
+    /// Folds the name of the given key to ASCII lowercase, if names are case-insensitive, so that
+    /// it can be used to look up anything stored by key.
+    fn fold_key(&self, key: Key) -> Key {
+        if self.config.case_insensitive_names {
+            fold_name_case(key)
+        } else {
+            key
+        }
+    }
+
+    fn names_match(&self, a: &str, b: &str) -> bool {
+        if self.config.case_insensitive_names {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    }
+
+    fn keys_match(&self, a: &Key, b: &Key) -> bool {
+        self.names_match(&a.name(), &b.name()) && a.labels().eq(b.labels())
+    }
+
+    /// Gets the key of the given metric, with its scope applied, as it appears in snapshots.
+    fn scoped_key(&self, id: &Identifier) -> Key {
+        let (key, scope_handle, _) = id.clone().into_parts();
+        let scope = self.scope_registry.get(scope_handle);
+        key.map_name(|name| scope.into_string(name))
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        snapshot_metrics(&self.metrics.load(), &self.scope_registry)
+    }
+
+    pub fn snapshot_arc(&self) -> RegistryView {
+        RegistryView {
+            metrics: self.metrics.load_full(),
+            scope_registry: self.scope_registry.clone(),
+        }
+    }
+
+    pub fn fold<A, F>(&self, init: A, mut f: F) -> A
+    where
+        F: FnMut(A, &Key, &Measurement) -> A,
+    {
+        fold_values(
+            &self.metrics.load(),
+            &self.scope_registry,
+            init,
+            ValueHandle::snapshot,
+            |acc, key, measurement, _| f(acc, &key, &measurement),
+        )
+    }
+
+    pub fn snapshot_and_reset(&self) -> Vec<(Key, MetricValue)> {
+        // Metrics sharing their storage are only reset once, and every one of them reports what
+        // was read then, rather than the first taking the whole value and the rest nothing.
+        let mut read = HashMap::new();
+        fold_values(
+            &self.metrics.load(),
+            &self.scope_registry,
+            Vec::new(),
+            |value: &ValueHandle| {
+                read.entry(value.storage_id())
+                    .or_insert_with(|| value.snapshot_and_reset())
+                    .clone()
+            },
+            |mut values, key, measurement, _| {
+                values.push((key, measurement.into()));
+                values
+            },
+        )
+    }
+
+    pub fn observe<O: Observer>(&self, observer: &mut O) {
+        fold_values(
+            &self.metrics.load(),
+            &self.scope_registry,
+            observer,
+            ValueHandle::snapshot,
+            |observer, key, measurement, created| {
+                if let Some(unit) = self.unit(&key) {
+                    observer.observe_unit(key.clone(), unit);
                 }
-                ValueSnapshot::Multiple(mut measurements) => {
-                    // Tack on the key name that this proxy was registered with to the scope so
-                    // that we can clone _that_, and then scope our individual measurements.
-                    let (base_key, labels) = key.into_parts();
-                    let scope = scope.clone().add_part(base_key);
-
-                    for (subkey, measurement) in measurements.drain(..) {
-                        let scope = scope.clone();
-                        let mut subkey = subkey.map_name(|name| scope.into_string(name));
-                        subkey.add_labels(labels.clone());
-                        observe(observer, subkey, measurement);
+                if let Some(created) = created {
+                    observer.observe_counter_created(key.clone(), created);
+                }
+                match measurement {
+                    Measurement::Counter(value) => observer.observe_counter(key, value),
+                    Measurement::Gauge(value) => observer.observe_gauge(key, value),
+                    Measurement::Histogram(stream) => stream.decompress_with(|values| {
+                        observer.observe_histogram(key.clone(), values);
+                    }),
+                    Measurement::WeightedHistogram(values) => {
+                        observer.observe_histogram_weighted(key, &values)
                     }
                 }
+                observer
+            },
+        );
+    }
+}
+
+/// Folds over every metric in `metrics`, reading each value with `read`.
+///
+/// Each metric is given to `f` with its scope applied to its key, along with the time it was
+/// created at, if it's a counter.  Metrics are visited in the order they were registered.
+fn fold_values<A, R, F>(
+    metrics: &Metrics,
+    scope_registry: &ScopeRegistry,
+    init: A,
+    mut read: R,
+    mut f: F,
+) -> A
+where
+    R: FnMut(&ValueHandle) -> ValueSnapshot,
+    F: FnMut(A, Key, Measurement, Option<SystemTime>) -> A,
+{
+    let mut acc = init;
+    for (id, value) in metrics.iter_by_insertion() {
+        let (key, scope_handle, _) = Identifier::clone(id).into_parts();
+        let scope = scope_registry.get(scope_handle);
+
+        match read(value) {
+            ValueSnapshot::Single(measurement) => {
+                let key = key.map_name(|name| scope.into_string(name));
+                let created = metrics.created.get(id).cloned();
+                acc = f(acc, key, measurement, created);
+            }
+            ValueSnapshot::Multiple(measurements) => {
+                // Tack on the key name that this proxy was registered with to the scope so
+                // that we can clone _that_, and then scope our individual measurements.
+                let (base_key, labels) = key.into_parts();
+                let scope = scope.clone().add_part(base_key);
+
+                for (subkey, measurement) in measurements {
+                    let scope = scope.clone();
+                    let mut subkey = subkey.map_name(|name| scope.into_string(name));
+                    subkey.add_labels(labels.clone());
+                    acc = f(acc, subkey, measurement, None);
+                }
             }
         }
     }
+    acc
+}
+
+fn snapshot_metrics(metrics: &Metrics, scope_registry: &ScopeRegistry) -> Snapshot {
+    let (values, created) = fold_values(
+        metrics,
+        scope_registry,
+        (Vec::new(), HashMap::new()),
+        ValueHandle::snapshot,
+        |(mut values, mut created), key, measurement, at| {
+            if let Some(at) = at {
+                created.insert(key.clone(), at);
+            }
+            values.push((key, measurement));
+            (values, created)
+        },
+    );
+
+    Snapshot::new(values).with_created(created)
+}
+
+/// A read-only view of the metrics registered at a point in time.
+///
+/// Taking a view is cheap, as the registry already keeps its set of registered metrics in an
+/// immutable, reference-counted structure, and a new version is swapped in whenever a metric is
+/// registered or removed.  A view holds on to the version that was current when it was taken,
+/// so it can be cloned and shared between concurrent readers, such as the scrapes of an exporter,
+/// without copying anything, and it is unaffected by metrics registered afterwards.
+///
+/// Only the set of metrics is fixed: [`snapshot`](RegistryView::snapshot) reads their current
+/// values.
+#[derive(Debug, Clone)]
+pub struct RegistryView {
+    metrics: Arc<Metrics>,
+    scope_registry: Arc<ScopeRegistry>,
+}
+
+impl RegistryView {
+    /// Gets the number of metrics in this view.
+    pub fn len(&self) -> usize {
+        self.metrics.handles.len()
+    }
+
+    /// Whether or not this view holds any metrics.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the keys of the metrics in this view, in the order they were registered.
+    pub fn keys(&self) -> Vec<Key> {
+        self.metrics
+            .iter_by_insertion()
+            .map(|(id, _)| {
+                let (key, scope_handle, _) = id.clone().into_parts();
+                let scope = self.scope_registry.get(scope_handle);
+                key.map_name(|name| scope.into_string(name))
+            })
+            .collect()
+    }
+
+    /// Provides a snapshot of the current values of the metrics in this view.
+    pub fn snapshot(&self) -> Snapshot {
+        snapshot_metrics(&self.metrics, &self.scope_registry)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        Clock, Configuration, Identifier, Kind, Measurement, MetricRegistry, ScopeRegistry,
+        Clock, Configuration, Identifier, Kind, Measurement, MetricHandle, MetricRegistry,
+        ScopeRegistry,
     };
+    use crate::common::Scope;
+    use crate::config::Hook;
     use crate::data::{Counter, Gauge, Histogram};
-    use metrics_core::{Key, Label};
+    use metrics_core::{Key, Label, Observer, Unit};
     use metrics_util::StreamingIntegers;
     use std::mem;
-    use std::sync::Arc;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{Arc, Mutex};
+    use std::time::{SystemTime, UNIX_EPOCH};
 
     #[test]
     fn test_snapshot() {
@@ -248,4 +823,569 @@ mod tests {
             assert_eq!(mem::discriminant(&lhs.1), mem::discriminant(&rhs.1));
         }
     }
+
+    #[test]
+    fn test_case_insensitive_names() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        let upper: Counter = mr
+            .get_or_register(Identifier::new("Foo", 0, Kind::Counter))
+            .into();
+        let lower: Counter = mr
+            .get_or_register(Identifier::new("foo", 0, Kind::Counter))
+            .into();
+        upper.record(1);
+        lower.record(2);
+        assert_eq!(mr.snapshot().into_measurements().len(), 2);
+
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mut config = Configuration::mock();
+        config.case_insensitive_names = true;
+        let mr = MetricRegistry::new(sr, config, clock);
+
+        let upper: Counter = mr
+            .get_or_register(Identifier::new("Foo", 0, Kind::Counter))
+            .into();
+        let lower: Counter = mr
+            .get_or_register(Identifier::new("foo", 0, Kind::Counter))
+            .into();
+        upper.record(1);
+        lower.record(2);
+
+        // Labels are still compared exactly.
+        let labeled: Counter = mr
+            .get_or_register(Identifier::new(
+                ("FOO", vec![Label::new("Type", "A")]),
+                0,
+                Kind::Counter,
+            ))
+            .into();
+        labeled.record(4);
+
+        let mut snapshot = mr.snapshot().into_measurements();
+        snapshot.sort_by_key(|(k, _)| k.labels().count());
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].0, Key::from_name("foo"));
+        match snapshot[0].1 {
+            Measurement::Counter(value) => assert_eq!(value, 3),
+            _ => panic!("expected counter"),
+        }
+        assert_eq!(
+            snapshot[1].0,
+            Key::from_name_and_labels("foo", vec![Label::new("Type", "A")])
+        );
+
+        // Every lookup by key or name folds the name as well.
+        assert!(mr.handle(&Key::from_name("FOO")).is_some());
+        mr.set_metadata(Key::from_name("Foo"), Arc::new("requests"));
+        let meta = mr.metadata(&Key::from_name("fOO")).unwrap();
+        assert_eq!(meta.downcast_ref::<&str>(), Some(&"requests"));
+        mr.with_state_mut(&Key::from_name("FOO"), |count: &mut u64| *count += 1);
+        assert_eq!(
+            mr.with_state_mut(&Key::from_name("foo"), |count: &mut u64| *count),
+            1
+        );
+
+        assert!(!mr.remove(&Key::from_name("FOO.bar")));
+        assert!(mr.remove(&Key::from_name("Foo")));
+        assert_eq!(mr.len(), 1);
+        assert_eq!(mr.remove_by_name("FOO"), 1);
+        assert_eq!(mr.len(), 0);
+    }
+
+    #[test]
+    fn test_get_or_register_prehashed() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        // A counter registered the regular way is found by its hash, too.
+        let regular: Counter = mr
+            .get_or_register(Identifier::new("requests", 0, Kind::Counter))
+            .into();
+        let prehashed: Counter = mr
+            .get_or_register_prehashed(42, Identifier::new("requests", 0, Kind::Counter))
+            .into();
+        regular.record(1);
+        prehashed.record(2);
+
+        // Different identifiers sharing a hash are still told apart.
+        let colliding: Counter = mr
+            .get_or_register_prehashed(42, Identifier::new("errors", 0, Kind::Counter))
+            .into();
+        colliding.record(8);
+        let gauge: Gauge = mr
+            .get_or_register_prehashed(42, Identifier::new("requests", 0, Kind::Gauge))
+            .into();
+        gauge.record(-1);
+        assert_eq!(mr.len(), 3);
+
+        // Later lookups are served from the hash, and keep pointing at the same storage.
+        let again: Counter = mr
+            .get_or_register_prehashed(42, Identifier::new("requests", 0, Kind::Counter))
+            .into();
+        again.record(4);
+        let contention = mr.contention_count();
+        let _ = mr.get_or_register_prehashed(42, Identifier::new("errors", 0, Kind::Counter));
+        assert_eq!(mr.contention_count(), contention);
+
+        let mut snapshot = mr.snapshot().into_measurements();
+        snapshot.sort_by_key(|(k, m)| match m {
+            Measurement::Counter(_) => (k.name(), 0),
+            _ => (k.name(), 1),
+        });
+        match &snapshot[..] {
+            [(errors, Measurement::Counter(8)), (requests, Measurement::Counter(7)), (gauge, Measurement::Gauge(-1))] =>
+            {
+                assert_eq!(*errors, Key::from_name("errors"));
+                assert_eq!(*requests, Key::from_name("requests"));
+                assert_eq!(*gauge, Key::from_name("requests"));
+            }
+            other => panic!("unexpected snapshot: {:?}", other),
+        }
+
+        // Removed metrics are forgotten by their hash as well.
+        assert_eq!(mr.remove_where(|key| key.name() == "errors"), 1);
+        let recreated: Counter = mr
+            .get_or_register_prehashed(42, Identifier::new("errors", 0, Kind::Counter))
+            .into();
+        recreated.record(1);
+        let errors = mr
+            .snapshot()
+            .into_measurements()
+            .into_iter()
+            .find(|(key, _)| key.name() == "errors");
+        match errors {
+            Some((_, Measurement::Counter(value))) => assert_eq!(value, 1),
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_identifiers_shared_across_registrations() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        let labels = (0..32)
+            .map(|i| Label::new(format!("label{}", i), "x".repeat(64)))
+            .collect::<Vec<_>>();
+        let id = Identifier::new(("big", labels), 0, Kind::Counter);
+        let _ = mr.get_or_register(id.clone());
+        let before = mr.metrics.load();
+        let stored = before.handles[0].0.clone();
+
+        // Registering more metrics copies the map, but the existing identifier is shared rather
+        // than cloned.
+        for i in 0..8 {
+            let _ = mr.get_or_register(Identifier::new(format!("other{}", i), 0, Kind::Gauge));
+        }
+        let after = mr.metrics.load();
+        assert_eq!(after.handles.len(), 9);
+        let current = &after.handles[0].0;
+        assert_eq!(**current, id);
+        assert!(Arc::ptr_eq(&stored, current));
+    }
+
+    #[test]
+    fn test_keys_shared_across_kinds() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        let key = || Key::from_name_and_labels("requests", vec![Label::new("code", "200")]);
+        let _ = mr.get_or_register(Identifier::new(key(), 0, Kind::Counter));
+        let _ = mr.get_or_register(Identifier::new(key(), 0, Kind::Histogram));
+
+        // The key is stored once, so the identifiers compare by pointer, and only differ by kind.
+        let metrics = mr.metrics.load();
+        let (counter, histogram) = (&metrics.handles[0].0, &metrics.handles[1].0);
+        assert!(Arc::ptr_eq(counter.key(), histogram.key()));
+        assert_ne!(**counter, **histogram);
+        let shared =
+            Identifier::new(key(), 0, Kind::Counter).with_shared_key(counter.key().clone());
+        assert_eq!(shared, **counter);
+    }
+
+    #[test]
+    fn test_snapshot_in_insertion_order() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        let names = ["zebra", "apple", "mango", "banana", "kiwi"];
+        for name in names.iter() {
+            let _ = mr.get_or_register(Identifier::new(*name, 0, Kind::Counter));
+        }
+        // Registering an existing metric again does not move it.
+        let _ = mr.get_or_register(Identifier::new("zebra", 0, Kind::Counter));
+
+        let metrics = mr.metrics.load();
+        assert_eq!(metrics.handles.len(), names.len());
+        let registered = metrics
+            .iter_by_insertion()
+            .map(|(id, _)| id.clone().into_parts().0.name())
+            .collect::<Vec<_>>();
+        assert_eq!(registered, names);
+
+        let snapshot = mr
+            .snapshot()
+            .into_measurements()
+            .into_iter()
+            .map(|(key, _)| key.name())
+            .collect::<Vec<_>>();
+        assert_eq!(snapshot, names);
+    }
+
+    #[test]
+    fn test_handle_by_scoped_key() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let scope = sr.register(Scope::Root.add_part("db"));
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        let _ = mr.get_or_register(Identifier::new("queries", 0, Kind::Proxy));
+        let _ = mr.get_or_register(Identifier::new("queries", scope, Kind::Gauge));
+        let _ = mr.get_or_register(Identifier::new("queries", scope, Kind::Counter));
+        let errors: Counter = mr
+            .get_or_register(Identifier::new("errors", 0, Kind::Counter))
+            .into();
+        errors.record(2);
+
+        // Keys are looked up as they appear in snapshots, and proxies are skipped.
+        assert!(mr.handle(&Key::from_name("queries")).is_none());
+        match mr.handle(&Key::from_name("db.queries")) {
+            Some(MetricHandle::Gauge(_)) => {}
+            _ => panic!("expected the first metric registered with the key"),
+        }
+
+        // Removing a metric reindexes the ones that remain.
+        assert!(mr.remove(&Key::from_name("db.queries")));
+        assert!(mr.handle(&Key::from_name("db.queries")).is_none());
+        match mr.handle(&Key::from_name("errors")) {
+            Some(MetricHandle::Counter(counter)) => assert_eq!(counter.value(), 2),
+            _ => panic!("expected counter"),
+        }
+    }
+
+    #[test]
+    fn test_with_state_mut() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        let requests = Key::from_name("requests");
+        let errors = Key::from_name("errors");
+
+        // State starts out as the default value, and is kept between calls.
+        let previous = mr.with_state_mut(&requests, |last: &mut u64| {
+            let previous = *last;
+            *last = 15;
+            previous
+        });
+        assert_eq!(previous, 0);
+        assert_eq!(mr.with_state_mut(&requests, |last: &mut u64| *last), 15);
+        assert_eq!(mr.with_state_mut(&errors, |last: &mut u64| *last), 0);
+
+        // Asking for a different type replaces the state.
+        mr.with_state_mut(&requests, |s: &mut String| s.push_str("scraped"));
+        assert_eq!(
+            mr.with_state_mut(&requests, |s: &mut String| s.clone()),
+            "scraped"
+        );
+        assert_eq!(mr.with_state_mut(&requests, |last: &mut u64| *last), 0);
+    }
+
+    #[test]
+    fn test_lifecycle_hooks() {
+        let registered = Arc::new(Mutex::new(Vec::new()));
+        let deregistered = Arc::new(Mutex::new(Vec::new()));
+
+        let mut config = Configuration::mock();
+        let events = registered.clone();
+        config.on_register = Some(Hook::new(move |key| {
+            events.lock().unwrap().push(key.clone())
+        }));
+        let events = deregistered.clone();
+        config.on_deregister = Some(Hook::new(move |key| {
+            events.lock().unwrap().push(key.clone())
+        }));
+
+        let sr = Arc::new(ScopeRegistry::new());
+        let scope = sr.register(Scope::Root.add_part("db"));
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, config, clock);
+
+        let _ = mr.get_or_register(Identifier::new("queries", scope, Kind::Counter));
+        let _ = mr.get_or_register(Identifier::new("requests", 0, Kind::Counter));
+        // Registering an existing metric again does not fire the hook.
+        let _ = mr.get_or_register(Identifier::new("requests", 0, Kind::Counter));
+        assert_eq!(
+            *registered.lock().unwrap(),
+            vec![Key::from_name("db.queries"), Key::from_name("requests")]
+        );
+        assert!(deregistered.lock().unwrap().is_empty());
+
+        assert_eq!(mr.remove_where(|key| key.name() == "missing"), 0);
+        assert!(deregistered.lock().unwrap().is_empty());
+
+        mr.set_metadata(Key::from_name("db.queries"), Arc::new("queries"));
+        mr.set_metadata(Key::from_name("requests"), Arc::new("requests"));
+        mr.with_state_mut(&Key::from_name("db.queries"), |count: &mut u64| *count += 1);
+        assert_eq!(mr.remove_where(|key| key.name() == "db.queries"), 1);
+        assert_eq!(
+            *deregistered.lock().unwrap(),
+            vec![Key::from_name("db.queries")]
+        );
+        assert_eq!(mr.len(), 1);
+
+        // Metadata and state go with the metric, but other metrics keep theirs.
+        assert!(mr.metadata(&Key::from_name("db.queries")).is_none());
+        assert!(mr.metadata(&Key::from_name("requests")).is_some());
+        assert_eq!(
+            mr.with_state_mut(&Key::from_name("db.queries"), |count: &mut u64| *count),
+            0
+        );
+
+        // A removed metric is registered anew if it's used again.
+        let _ = mr.get_or_register(Identifier::new("queries", scope, Kind::Counter));
+        assert_eq!(registered.lock().unwrap().len(), 3);
+        assert_eq!(mr.len(), 2);
+    }
+
+    #[test]
+    fn test_panic_safety() {
+        let mut config = Configuration::mock();
+        config.on_register = Some(Hook::new(|key| {
+            if key.name() == "hooked" {
+                panic!("hook failed");
+            }
+        }));
+
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, config, clock);
+        let changes = mr.subscribe();
+
+        // A panic while creating a handle leaves nothing registered.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            mr.get_or_register_with(Identifier::new("broken", 0, Kind::Counter), || {
+                panic!("failed to create handle")
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(mr.len(), 0);
+        assert!(changes.try_recv().is_err());
+
+        // Later registrations, including of the same key, still go through.
+        let requests = mr.get_or_register(Identifier::new("requests", 0, Kind::Counter));
+        requests.update_counter(1);
+        let broken = mr.get_or_register(Identifier::new("broken", 0, Kind::Counter));
+        broken.update_counter(2);
+        assert_eq!(mr.len(), 2);
+        assert!(changes.try_recv().is_ok());
+
+        // A panicking hook runs after the metric is registered and subscribers are told.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            mr.get_or_register(Identifier::new("hooked", 0, Kind::Gauge))
+        }));
+        assert!(result.is_err());
+        assert_eq!(mr.len(), 3);
+        assert!(changes.try_recv().is_ok());
+
+        // State is left usable by a panic in the middle of updating it.
+        let key = Key::from_name("requests");
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            mr.with_state_mut(&key, |last: &mut u64| {
+                *last = 1;
+                panic!("failed to update state");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(mr.with_state_mut(&key, |last: &mut u64| *last), 1);
+    }
+
+    #[test]
+    fn test_contention_count() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+        assert_eq!(mr.contention_count(), 0);
+
+        // Only the first registration of a metric misses the fast path.
+        for _ in 0..100 {
+            let _ = mr.get_or_register(Identifier::new("requests", 0, Kind::Counter));
+        }
+        assert_eq!(mr.contention_count(), 1);
+
+        crossbeam_utils::thread::scope(|s| {
+            for t in 0..4 {
+                let mr = &mr;
+                s.spawn(move |_| {
+                    for i in 0..50 {
+                        let name = format!("metric{}_{}", t, i);
+                        let _ = mr.get_or_register(Identifier::new(name, 0, Kind::Counter));
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert_eq!(mr.len(), 201);
+        assert!(mr.contention_count() >= 201);
+    }
+
+    #[test]
+    fn test_snapshot_arc_stable() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        let requests = mr.get_or_register(Identifier::new("requests", 0, Kind::Counter));
+        requests.update_counter(2);
+
+        let view = mr.snapshot_arc();
+        let shared = view.clone();
+        assert!(Arc::ptr_eq(&view.metrics, &shared.metrics));
+        assert!(Arc::ptr_eq(&view.metrics, &mr.snapshot_arc().metrics));
+
+        let _ = mr.get_or_register(Identifier::new("connections", 0, Kind::Gauge));
+        assert_eq!(mr.len(), 2);
+
+        assert_eq!(view.len(), 1);
+        assert_eq!(shared.keys(), vec![Key::from_name("requests")]);
+        assert_eq!(mr.snapshot_arc().len(), 2);
+
+        // The set of metrics is fixed, but their values are read when snapshotting.
+        requests.update_counter(3);
+        let values = view.snapshot().into_measurements();
+        assert_eq!(values.len(), 1);
+        match &values[0] {
+            (key, Measurement::Counter(value)) => {
+                assert_eq!(key, &Key::from_name("requests"));
+                assert_eq!(*value, 5);
+            }
+            other => panic!("unexpected measurement: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        for (name, value) in &[("requests", 5), ("errors", 2), ("retries", 7)] {
+            let handle = mr.get_or_register(Identifier::new(*name, 0, Kind::Counter));
+            handle.update_counter(*value);
+        }
+        let gauge = mr.get_or_register(Identifier::new("connections", 0, Kind::Gauge));
+        gauge.update_gauge(100);
+
+        let total = mr.fold(0, |total, _, measurement| match measurement {
+            Measurement::Counter(value) => total + value,
+            _ => total,
+        });
+
+        let expected = mr
+            .snapshot()
+            .into_measurements()
+            .into_iter()
+            .filter_map(|(_, measurement)| match measurement {
+                Measurement::Counter(value) => Some(value),
+                _ => None,
+            })
+            .sum::<u64>();
+        assert_eq!(total, 14);
+        assert_eq!(total, expected);
+
+        let names = mr.fold(Vec::new(), |mut names, key, _| {
+            names.push(key.name().into_owned());
+            names
+        });
+        assert_eq!(names, vec!["requests", "errors", "retries", "connections"]);
+    }
+
+    #[test]
+    fn test_counter_created() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        let before = SystemTime::now();
+        let counter: Counter = mr
+            .get_or_register(Identifier::new("requests", 0, Kind::Counter))
+            .into();
+        let _ = mr.get_or_register(Identifier::new("connections", 0, Kind::Gauge));
+        let after = SystemTime::now();
+
+        let snapshot = mr.snapshot();
+        let created = snapshot.created(&Key::from_name("requests")).unwrap();
+        assert!(before <= created && created <= after);
+        assert!(snapshot.created(&Key::from_name("connections")).is_none());
+
+        // The creation time doesn't change as the counter is updated, or registered again.
+        counter.record(5);
+        let _ = mr.get_or_register(Identifier::new("requests", 0, Kind::Counter));
+        let snapshot = mr.snapshot();
+        assert_eq!(snapshot.created(&Key::from_name("requests")), Some(created));
+
+        let since_epoch = created.duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+        let rendered = snapshot
+            .into_values()
+            .into_iter()
+            .filter(|(key, _)| key.name() == "requests")
+            .map(|(key, value)| value.render_prometheus_created(&key, created))
+            .collect::<String>();
+        assert_eq!(rendered, format!("requests_created {}\n", since_epoch));
+
+        // Observers are given the creation time of each counter along with its value.
+        #[derive(Default)]
+        struct CreatedObserver(Vec<(String, SystemTime)>);
+
+        impl Observer for CreatedObserver {
+            fn observe_counter(&mut self, _key: Key, _value: u64) {}
+            fn observe_gauge(&mut self, _key: Key, _value: i64) {}
+            fn observe_histogram(&mut self, _key: Key, _values: &[u64]) {}
+            fn observe_counter_created(&mut self, key: Key, created: SystemTime) {
+                self.0.push((key.name().into_owned(), created));
+            }
+        }
+
+        let mut observer = CreatedObserver::default();
+        mr.observe(&mut observer);
+        assert_eq!(observer.0, vec![("requests".to_string(), created)]);
+    }
+
+    #[test]
+    fn test_observe_unit() {
+        let sr = Arc::new(ScopeRegistry::new());
+        let (clock, _) = Clock::mock();
+        let mr = MetricRegistry::new(sr, Configuration::mock(), clock);
+
+        let _ = mr.get_or_register(Identifier::new("payload.size", 0, Kind::Histogram));
+        let _ = mr.get_or_register(Identifier::new("requests", 0, Kind::Counter));
+        mr.set_metadata(Key::from_name("payload.size"), Arc::new(Unit::Bytes));
+        // Metadata of other types isn't taken as a unit.
+        mr.set_metadata(Key::from_name("requests"), Arc::new("requests"));
+
+        #[derive(Default)]
+        struct UnitObserver(Vec<(String, Unit)>);
+
+        impl Observer for UnitObserver {
+            fn observe_counter(&mut self, _key: Key, _value: u64) {}
+            fn observe_gauge(&mut self, _key: Key, _value: i64) {}
+            fn observe_histogram(&mut self, _key: Key, _values: &[u64]) {}
+            fn observe_unit(&mut self, key: Key, unit: Unit) {
+                self.0.push((key.name().into_owned(), unit));
+            }
+        }
+
+        let mut observer = UnitObserver::default();
+        mr.observe(&mut observer);
+        assert_eq!(observer.0, vec![("payload.size".to_string(), Unit::Bytes)]);
+    }
 }
@@ -3,3 +3,4 @@ pub(crate) use self::scope::ScopeRegistry;
 
 mod metric;
 pub(crate) use self::metric::MetricRegistry;
+pub use self::metric::RegistryView;
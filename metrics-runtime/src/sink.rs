@@ -3,7 +3,7 @@ use crate::{
     data::{Counter, Gauge, Histogram},
     registry::{MetricRegistry, ScopeRegistry},
 };
-use metrics_core::{IntoLabels, Key, Label, ScopedString};
+use metrics_core::{IntoLabels, Key, Label, MetricKind, ScopedString};
 use quanta::Clock;
 use std::{collections::HashMap, error::Error, fmt, sync::Arc};
 
@@ -523,6 +523,160 @@ impl Sink {
         self.histogram((name, labels))
     }
 
+    /// Creates a handle to a counter that shares its storage with an existing counter.
+    ///
+    /// The counter is registered under `name`, but any updates made through either handle are
+    /// visible through both, and are reported under both names.  This is useful for exposing the
+    /// same value under an additional name, such as when migrating to a new naming scheme.  If a
+    /// counter is already registered under `name`, a handle to it is returned as-is.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_runtime::Receiver;
+    /// # fn main() {
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// let mut sink = receiver.sink();
+    /// let counter = sink.counter("messages_processed");
+    /// let alias = sink.share_counter("messages_processed_total", &counter);
+    /// alias.record(1);
+    /// # }
+    /// ```
+    pub fn share_counter<N>(&mut self, name: N, counter: &Counter) -> Counter
+    where
+        N: Into<Key>,
+    {
+        let key = self.construct_key(name);
+        self.get_shared_value_handle(key, Kind::Counter, counter.value_handle())
+            .into()
+    }
+
+    /// Creates a handle to a gauge that shares its storage with an existing gauge.
+    ///
+    /// See [`share_counter`](Sink::share_counter) for more details.
+    pub fn share_gauge<N>(&mut self, name: N, gauge: &Gauge) -> Gauge
+    where
+        N: Into<Key>,
+    {
+        let key = self.construct_key(name);
+        self.get_shared_value_handle(key, Kind::Gauge, gauge.value_handle())
+            .into()
+    }
+
+    /// Creates a handle to a histogram that shares its storage with an existing histogram.
+    ///
+    /// See [`share_counter`](Sink::share_counter) for more details.
+    pub fn share_histogram<N>(&mut self, name: N, histogram: &Histogram) -> Histogram
+    where
+        N: Into<Key>,
+    {
+        let key = self.construct_key(name);
+        self.get_shared_value_handle(key, Kind::Histogram, histogram.value_handle())
+            .into()
+    }
+
+    /// Creates a handle to the given counter, looking it up by a precomputed hash of its key.
+    ///
+    /// Looking up a handle with [`counter`](Sink::counter) hashes the whole key, name and labels,
+    /// every time.  On a hot path where the key has already been hashed, such as by an interner,
+    /// this looks the counter up by the given hash instead, and only compares the key against the
+    /// counters registered with the same hash.
+    ///
+    /// Any hash function can be used, as long as the same key is always given the same hash.
+    /// Different keys may share a hash, at the cost of a slower lookup for each of them.  As
+    /// hashes are used as-is, they should be well distributed across all 64 bits, such as those
+    /// from [`Key::name_hash`] and [`Key::labels_hash`].  The first lookup of each key takes the
+    /// same path as [`counter`](Sink::counter), and remembers the hash for later lookups.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # use metrics_core::{Key, Label};
+    /// # use metrics_runtime::Receiver;
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// let mut sink = receiver.sink();
+    ///
+    /// let key = Key::from_name_and_labels("requests", vec![Label::new("code", "200")]);
+    /// let hash = key.name_hash() ^ key.labels_hash().rotate_left(32);
+    ///
+    /// for _ in 0..10 {
+    ///     sink.counter_prehashed(hash, key.clone()).increment();
+    /// }
+    /// ```
+    pub fn counter_prehashed<N>(&self, hash: u64, name: N) -> Counter
+    where
+        N: Into<Key>,
+    {
+        let key = self.construct_key(name);
+        self.get_prehashed_value_handle(hash, key, Kind::Counter)
+            .into()
+    }
+
+    /// Creates a handle to the given gauge, looking it up by a precomputed hash of its key.
+    ///
+    /// See [`counter_prehashed`](Sink::counter_prehashed) for more details.
+    pub fn gauge_prehashed<N>(&self, hash: u64, name: N) -> Gauge
+    where
+        N: Into<Key>,
+    {
+        let key = self.construct_key(name);
+        self.get_prehashed_value_handle(hash, key, Kind::Gauge)
+            .into()
+    }
+
+    /// Creates a handle to the given histogram, looking it up by a precomputed hash of its key.
+    ///
+    /// See [`counter_prehashed`](Sink::counter_prehashed) for more details.
+    pub fn histogram_prehashed<N>(&self, hash: u64, name: N) -> Histogram
+    where
+        N: Into<Key>,
+    {
+        let key = self.construct_key(name);
+        self.get_prehashed_value_handle(hash, key, Kind::Histogram)
+            .into()
+    }
+
+    /// Registers metrics of the given kind up front, without updating them.
+    ///
+    /// Metrics normally only show up in snapshots once they've been updated for the first time.
+    /// Preregistering them at startup means they're present, with a zero or empty value, from the
+    /// very first snapshot, so that dashboards don't show gaps for metrics which are updated
+    /// rarely, such as error counters.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate ckb_metrics_runtime as metrics_runtime;
+    /// # extern crate metrics_core;
+    /// # use metrics_runtime::Receiver;
+    /// # use metrics_core::MetricKind;
+    /// # fn main() {
+    /// let receiver = Receiver::builder().build().expect("failed to create receiver");
+    /// let mut sink = receiver.sink();
+    /// sink.preregister(MetricKind::Counter, vec!["login_failures", "payment_failures"]);
+    ///
+    /// assert_eq!(receiver.controller().len(), 2);
+    /// # }
+    /// ```
+    pub fn preregister<N, I>(&mut self, kind: MetricKind, names: I)
+    where
+        N: Into<Key>,
+        I: IntoIterator<Item = N>,
+    {
+        let kind = match kind {
+            MetricKind::Counter => Kind::Counter,
+            MetricKind::Gauge => Kind::Gauge,
+            MetricKind::Histogram => Kind::Histogram,
+        };
+
+        for name in names {
+            let key = self.construct_key(name);
+            let _ = self.get_owned_value_handle(key, kind.clone());
+        }
+    }
+
     /// Creates a proxy metric.
     ///
     /// Proxy metrics allow you to register a closure that, when a snapshot of the metric state is
@@ -641,6 +795,29 @@ impl Sink {
         self.get_cached_value_handle(id).clone()
     }
 
+    fn get_prehashed_value_handle(&self, hash: u64, key: Key, kind: Kind) -> ValueHandle {
+        let id = Identifier::new(key, self.scope_handle, kind);
+        self.metric_registry.get_or_register_prehashed(hash, id)
+    }
+
+    fn get_shared_value_handle(
+        &mut self,
+        key: Key,
+        kind: Kind,
+        shared: &ValueHandle,
+    ) -> ValueHandle {
+        let id = Identifier::new(key, self.scope_handle, kind);
+        if let Some(handle) = self.metric_cache.get(&id) {
+            return handle.clone();
+        }
+
+        let handle = self
+            .metric_registry
+            .get_or_register_with(id.clone(), || shared.clone());
+        self.metric_cache.insert(id, handle.clone());
+        handle
+    }
+
     fn get_cached_value_handle(&mut self, identifier: Identifier) -> &ValueHandle {
         // This gross hack gets around lifetime rules until full NLL is stable.  Without it, the
         // borrow checker doesn't understand the flow control and thinks the reference lives all
@@ -691,9 +868,120 @@ where
 #[cfg(test)]
 mod tests {
     use super::{Clock, MetricRegistry, Scope, ScopeRegistry, Sink};
+    use crate::common::Measurement;
     use crate::config::Configuration;
+    use metrics_core::{Key, Label, MetricKind};
     use std::sync::Arc;
 
+    #[test]
+    fn test_share_handles() {
+        let sregistry = Arc::new(ScopeRegistry::new());
+        let config = Configuration::mock();
+        let (clock, _) = Clock::mock();
+        let mregistry = Arc::new(MetricRegistry::new(
+            sregistry.clone(),
+            config,
+            clock.clone(),
+        ));
+        let mut sink = Sink::new(mregistry.clone(), sregistry, Scope::Root, clock);
+
+        let counter = sink.counter("requests");
+        let alias = sink.share_counter("requests_total", &counter);
+        counter.record(2);
+        alias.record(3);
+
+        let gauge = sink.gauge("connections");
+        let gauge_alias = sink.share_gauge("open_connections", &gauge);
+        gauge_alias.record(7);
+
+        // Sharing onto a name that's already registered keeps the existing metric.
+        let other = sink.counter("errors");
+        other.record(1);
+        let not_shared = sink.share_counter("errors", &counter);
+        not_shared.record(1);
+
+        let mut snapshot = mregistry.snapshot().into_measurements();
+        snapshot.sort_by_key(|(k, _)| k.name());
+        let values = snapshot
+            .into_iter()
+            .map(|(k, m)| {
+                let value = match m {
+                    Measurement::Counter(value) => value as i64,
+                    Measurement::Gauge(value) => value,
+                    Measurement::Histogram(_) | Measurement::WeightedHistogram(_) => {
+                        panic!("unexpected histogram")
+                    }
+                };
+                (k.name().to_string(), value)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                ("connections".to_string(), 7),
+                ("errors".to_string(), 2),
+                ("open_connections".to_string(), 7),
+                ("requests".to_string(), 5),
+                ("requests_total".to_string(), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preregister() {
+        let sregistry = Arc::new(ScopeRegistry::new());
+        let config = Configuration::mock();
+        let (clock, _) = Clock::mock();
+        let mregistry = Arc::new(MetricRegistry::new(
+            sregistry.clone(),
+            config,
+            clock.clone(),
+        ));
+        let mut sink = Sink::new(mregistry.clone(), sregistry, Scope::Root, clock);
+
+        sink.preregister(MetricKind::Counter, vec!["errors", "retries"]);
+        sink.preregister(
+            MetricKind::Gauge,
+            vec![("connections", vec![Label::new("pool", "primary")])],
+        );
+        sink.preregister(MetricKind::Histogram, Some("latency"));
+        assert_eq!(mregistry.len(), 4);
+
+        let values = |registry: &MetricRegistry| {
+            registry
+                .snapshot()
+                .into_measurements()
+                .into_iter()
+                .map(|(k, m)| {
+                    let value = match m {
+                        Measurement::Counter(value) => value as usize,
+                        Measurement::Gauge(value) => value as usize,
+                        Measurement::Histogram(stream) => stream.len(),
+                        Measurement::WeightedHistogram(values) => values.len(),
+                    };
+                    (k, value)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            values(&mregistry),
+            vec![
+                (Key::from_name("errors"), 0),
+                (Key::from_name("retries"), 0),
+                (
+                    Key::from_name_and_labels("connections", vec![Label::new("pool", "primary")]),
+                    0
+                ),
+                (Key::from_name("latency"), 0),
+            ]
+        );
+
+        // Updating a preregistered metric updates the existing handle.
+        sink.increment_counter("errors", 2);
+        assert_eq!(mregistry.len(), 4);
+        assert_eq!(values(&mregistry)[0], (Key::from_name("errors"), 2));
+    }
+
     #[test]
     fn test_construct_key() {
         // TODO(tobz): this is a lot of boilerplate to get a `Sink` for testing, wonder if there's
@@ -0,0 +1,89 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Benchmark, Criterion, Throughput};
+use metrics_util::Interner;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const KEYS: usize = 10_000;
+const VALUES: &[&str] = &[
+    "GET",
+    "POST",
+    "PUT",
+    "DELETE",
+    "200",
+    "404",
+    "500",
+    "/api/v1/users",
+];
+
+// Tracks the number of bytes currently allocated, so that the memory held by each approach can
+// be reported alongside the timings.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn owned_values() -> Vec<String> {
+    (0..KEYS)
+        .map(|i| VALUES[i % VALUES.len()].to_string())
+        .collect()
+}
+
+fn interned_values(interner: &Interner) -> Vec<std::sync::Arc<str>> {
+    (0..KEYS)
+        .map(|i| interner.intern(VALUES[i % VALUES.len()]))
+        .collect()
+}
+
+fn retained_bytes<T, F: FnOnce() -> T>(f: F) -> usize {
+    let before = ALLOCATED.load(Ordering::SeqCst);
+    let held = f();
+    let after = ALLOCATED.load(Ordering::SeqCst);
+    drop(held);
+    after.saturating_sub(before)
+}
+
+fn interner_benchmark(c: &mut Criterion) {
+    let owned = retained_bytes(owned_values);
+    let interned = retained_bytes(|| {
+        let interner = Interner::with_capacity(64);
+        let values = interned_values(&interner);
+        (interner, values)
+    });
+    println!(
+        "interner: {} label values over {} distinct strings: owned {} bytes, interned {} bytes",
+        KEYS,
+        VALUES.len(),
+        owned,
+        interned
+    );
+
+    c.bench(
+        "interner",
+        Benchmark::new("owned", |b| b.iter(owned_values))
+            .with_function("interned", |b| {
+                let interner = Interner::with_capacity(64);
+                b.iter(|| interned_values(&interner))
+            })
+            .throughput(Throughput::Elements(KEYS as u32)),
+    );
+}
+
+criterion_group!(benches, interner_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Benchmark, Criterion, Throughput};
+use metrics_core::{Key, Label};
+use metrics_util::KeyInterner;
+use std::sync::Arc;
+
+const ITERATIONS: usize = 1024;
+
+fn build_key() -> Key {
+    Key::from_name_and_labels(
+        "requests_total",
+        vec![
+            Label::new("code", "200"),
+            Label::new("method", "GET"),
+            Label::new("path", String::from("/api/v1/users")),
+        ],
+    )
+}
+
+fn key_interner_benchmark(c: &mut Criterion) {
+    c.bench(
+        "key_interner",
+        Benchmark::new("owned", |b| {
+            b.iter(|| {
+                (0..ITERATIONS)
+                    .map(|_| Arc::new(build_key()))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .with_function("interned", |b| {
+            let interner = KeyInterner::with_capacity(64);
+            b.iter(|| {
+                (0..ITERATIONS)
+                    .map(|_| interner.intern(build_key()))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .throughput(Throughput::Elements(ITERATIONS as u32)),
+    );
+}
+
+criterion_group!(benches, key_interner_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::{Benchmark, Criterion, Throughput};
+use metrics_core::{Key, Label, LabelKeys};
+
+const ITERATIONS: usize = 1024;
+
+static REQUEST_LABELS: &[&str] = &["method", "status", "route"];
+
+fn build_key() -> Key {
+    Key::from_name_and_labels(
+        "requests_total",
+        vec![
+            Label::new("method", "GET"),
+            Label::new("status", "200"),
+            Label::new("route", "/api/v1/users"),
+        ],
+    )
+}
+
+fn build_interned_key(schema: &LabelKeys) -> Key {
+    Key::from_name_and_labels(
+        "requests_total",
+        vec![
+            schema.label("method", "GET"),
+            schema.label("status", "200"),
+            schema.label("route", "/api/v1/users"),
+        ],
+    )
+}
+
+fn label_keys_benchmark(c: &mut Criterion) {
+    c.bench(
+        "label_keys_build",
+        Benchmark::new("string", |b| {
+            b.iter(|| (0..ITERATIONS).map(|_| build_key()).collect::<Vec<_>>())
+        })
+        .with_function("interned", |b| {
+            let schema = LabelKeys::new(REQUEST_LABELS);
+            b.iter(|| {
+                (0..ITERATIONS)
+                    .map(|_| build_interned_key(&schema))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .throughput(Throughput::Elements(ITERATIONS as u32)),
+    );
+
+    c.bench(
+        "label_keys_compare",
+        Benchmark::new("string", |b| {
+            let (left, right) = (build_key(), build_key());
+            b.iter(|| (0..ITERATIONS).filter(|_| left == right).count())
+        })
+        .with_function("interned", |b| {
+            let schema = LabelKeys::new(REQUEST_LABELS);
+            let (left, right) = (build_interned_key(&schema), build_interned_key(&schema));
+            b.iter(|| (0..ITERATIONS).filter(|_| left == right).count())
+        })
+        .throughput(Throughput::Elements(ITERATIONS as u32)),
+    );
+}
+
+criterion_group!(benches, label_keys_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,289 @@
+use crate::{clock::Interval, Clock, SystemClock};
+use atomic_shim::AtomicU64;
+use metrics::Recorder;
+use metrics_core::{Key, Label};
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{atomic::Ordering, RwLock},
+    time::Duration,
+};
+
+/// A recorder wrapper that aggregates counter increments before passing them along.
+///
+/// Every increment of a hot counter that reaches a backend costs whatever bookkeeping the backend
+/// does for it, such as taking a lock, and with many threads incrementing the same counter that
+/// becomes a bottleneck.  `PreAggregate` instead adds each increment to an atomic of its own for
+/// the counter, and passes the accumulated total along to the wrapped recorder as a single
+/// increment once per `interval`.  This trades how fresh counters are in the wrapped recorder for
+/// throughput.
+///
+/// Accumulated increments are passed along by the first increment made after each interval has
+/// elapsed, and whenever the wrapper is [flushed](Recorder::flush).  Counters without any
+/// increments since they were last passed along are skipped.  Gauges and histograms are passed
+/// through to the wrapped recorder unchanged.
+///
+/// Times are taken from the system clock by default, or from any other [`Clock`] given to
+/// [`with_clock`](PreAggregate::with_clock).
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::Key;
+/// # use metrics_util::PreAggregate;
+/// # use std::time::Duration;
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let recorder = PreAggregate::new(NoopRecorder, Duration::from_secs(1));
+/// for _ in 0..1000 {
+///     recorder.increment_counter(Key::from_name("requests"), 1);
+/// }
+///
+/// // A single increment of 1000 is sent along to the wrapped recorder.
+/// recorder.flush();
+/// ```
+pub struct PreAggregate<R, C = SystemClock> {
+    inner: R,
+    counters: RwLock<HashMap<Key, AtomicU64>>,
+    clock: C,
+    interval: Interval,
+}
+
+impl<R> PreAggregate<R> {
+    /// Creates a new `PreAggregate` wrapper around the given recorder, passing counters along
+    /// once per `interval`.
+    pub fn new(inner: R, interval: Duration) -> Self {
+        PreAggregate::with_clock(inner, interval, SystemClock)
+    }
+}
+
+impl<R, C: Clock> PreAggregate<R, C> {
+    /// Creates a new `PreAggregate` wrapper around the given recorder, passing counters along
+    /// once per `interval`, as measured by the given clock.
+    pub fn with_clock(inner: R, interval: Duration, clock: C) -> Self {
+        let interval = Interval::new(interval, clock.now());
+        PreAggregate {
+            inner,
+            counters: RwLock::new(HashMap::new()),
+            clock,
+            interval,
+        }
+    }
+
+    /// Gets a reference to the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the wrapped recorder.
+    ///
+    /// Counter increments that have not been passed along are discarded.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Recorder, C: Clock> PreAggregate<R, C> {
+    fn forward_counters(&self) {
+        let counters = self.counters.read().unwrap_or_else(|e| e.into_inner());
+        for (key, pending) in counters.iter() {
+            let delta = pending.swap(0, Ordering::AcqRel);
+            if delta > 0 {
+                self.inner.increment_counter(key.clone(), delta);
+            }
+        }
+    }
+
+    /// Passes counters along if the interval has elapsed since they last were.
+    fn maybe_forward_counters(&self) {
+        // Only one thread gets to pass counters along for each interval.
+        if self.interval.fire(self.clock.now()) {
+            self.forward_counters();
+        }
+    }
+}
+
+impl<R: Recorder, C: Clock> Recorder for PreAggregate<R, C> {
+    fn increment_counter(&self, key: Key, value: u64) {
+        let found = {
+            let counters = self.counters.read().unwrap_or_else(|e| e.into_inner());
+            match counters.get(&key) {
+                Some(pending) => {
+                    pending.fetch_add(value, Ordering::AcqRel);
+                    true
+                }
+                None => false,
+            }
+        };
+
+        if !found {
+            let mut counters = self.counters.write().unwrap_or_else(|e| e.into_inner());
+            counters
+                .entry(key)
+                .or_insert_with(|| AtomicU64::new(0))
+                .fetch_add(value, Ordering::AcqRel);
+        }
+
+        self.maybe_forward_counters();
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.inner.update_gauge(key, value);
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.inner.record_histogram(key, value);
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        self.inner.record_histogram_many(key, value, count);
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        self.inner
+            .record_histogram_buckets(key, buckets, sum, count);
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.inner.try_record_histogram(key, value)
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        self.inner
+            .record_histogram_with_exemplar(key, value, exemplar);
+    }
+
+    fn flush(&self) {
+        self.forward_counters();
+        self.inner.flush();
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        // Increments which haven't been passed along yet still count towards the total, even
+        // before the wrapped recorder has seen the counter at all.
+        let pending = {
+            let counters = self.counters.read().unwrap_or_else(|e| e.into_inner());
+            counters
+                .get(key)
+                .map_or(0, |pending| pending.load(Ordering::Acquire))
+        };
+        match self.inner.counter_value(key) {
+            Some(value) => Some(value.wrapping_add(pending)),
+            None if pending > 0 => Some(pending),
+            None => None,
+        }
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        self.inner.gauge_value(key)
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.inner.describe_counter(key, description);
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.inner.describe_gauge(key, description);
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.inner.describe_histogram(key, description);
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.inner.describe_with_meta(key, meta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreAggregate;
+    use crate::{test_util::CapturingRecorder, MockClock, PrometheusRecorder};
+    use crossbeam_utils::thread;
+    use metrics::Recorder;
+    use metrics_core::Key;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pre_aggregate() {
+        let clock = MockClock::new();
+        let recorder = PreAggregate::with_clock(
+            CapturingRecorder::default(),
+            Duration::from_secs(10),
+            clock.clone(),
+        );
+
+        // A burst of increments from several threads, all within one interval.
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|_| {
+                    for _ in 0..1000 {
+                        recorder.increment_counter(Key::from_name("requests"), 1);
+                    }
+                });
+            }
+        })
+        .unwrap();
+        recorder.increment_counter(Key::from_name("errors"), 2);
+        recorder.record_histogram(Key::from_name("latency"), 40);
+        // Only the histogram goes straight through.
+        assert_eq!(
+            recorder.inner().take_sorted_values(),
+            vec![("latency".to_string(), 40)]
+        );
+
+        // The first increment after the interval passes everything along in one go.
+        clock.advance(Duration::from_secs(10));
+        recorder.increment_counter(Key::from_name("requests"), 1);
+        assert_eq!(
+            recorder.inner().take_sorted_values(),
+            vec![("errors".to_string(), 2), ("requests".to_string(), 4001)]
+        );
+
+        // Nothing is passed along again until the next interval.
+        clock.advance(Duration::from_secs(5));
+        recorder.increment_counter(Key::from_name("requests"), 3);
+        assert!(recorder.inner().take_sorted_values().is_empty());
+        clock.advance(Duration::from_secs(5));
+        recorder.increment_counter(Key::from_name("requests"), 1);
+        assert_eq!(
+            recorder.inner().take_sorted_values(),
+            vec![("requests".to_string(), 4)]
+        );
+
+        // Flushing passes along whatever is pending right away.
+        recorder.increment_counter(Key::from_name("requests"), 5);
+        recorder.flush();
+        assert_eq!(
+            recorder.inner().take_sorted_values(),
+            vec![("requests".to_string(), 5)]
+        );
+        recorder.flush();
+        assert!(recorder.inner().take_sorted_values().is_empty());
+    }
+
+    #[test]
+    fn test_pre_aggregate_counter_value() {
+        let recorder = PreAggregate::with_clock(
+            PrometheusRecorder::new(),
+            Duration::from_secs(10),
+            MockClock::new(),
+        );
+        let requests = Key::from_name("requests");
+        assert_eq!(recorder.counter_value(&requests), None);
+
+        // Pending increments are read back before the wrapped recorder has seen the counter.
+        recorder.increment_counter(requests.clone(), 3);
+        assert_eq!(recorder.inner().counter_value(&requests), None);
+        assert_eq!(recorder.counter_value(&requests), Some(3));
+
+        recorder.flush();
+        recorder.increment_counter(requests.clone(), 2);
+        assert_eq!(recorder.inner().counter_value(&requests), Some(3));
+        assert_eq!(recorder.counter_value(&requests), Some(5));
+    }
+}
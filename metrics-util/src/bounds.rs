@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+/// Converts a slice of [`Duration`]s into histogram bucket boundaries, in seconds.
+///
+/// Latency buckets are far easier to read, and harder to get wrong, when written as durations
+/// rather than as raw fractions of a second.  The resulting boundaries are in the same order as
+/// the given durations.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::duration_buckets;
+/// # use std::time::Duration;
+/// let buckets = duration_buckets(&[Duration::from_millis(5), Duration::from_secs(1)]);
+/// assert_eq!(buckets, vec![0.005, 1.0]);
+/// ```
+pub fn duration_buckets(durations: &[Duration]) -> Vec<f64> {
+    durations.iter().map(Duration::as_secs_f64).collect()
+}
+
+/// Generates `count` histogram bucket boundaries, starting at `start` and each `factor` times the
+/// previous one.
+///
+/// This follows the Prometheus client libraries, and suits latencies, which tend to span several
+/// orders of magnitude.
+///
+/// # Panics
+/// Panics if `count` is zero, if `start` is not positive, or if `factor` is not greater than one.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::exponential_buckets;
+/// let buckets = exponential_buckets(0.001, 10.0, 4);
+/// assert_eq!(buckets.len(), 4);
+/// assert!((buckets[3] - 1.0).abs() < 1e-9);
+/// ```
+pub fn exponential_buckets(start: f64, factor: f64, count: usize) -> Vec<f64> {
+    assert!(count > 0, "bucket count must be greater than zero");
+    assert!(start > 0.0, "exponential buckets must start above zero");
+    assert!(
+        factor > 1.0,
+        "exponential bucket factor must be greater than one"
+    );
+
+    let mut buckets = Vec::with_capacity(count);
+    let mut bound = start;
+    for _ in 0..count {
+        buckets.push(bound);
+        bound *= factor;
+    }
+    buckets
+}
+
+/// Generates `count` histogram bucket boundaries, starting at `start` and each `width` above the
+/// previous one.
+///
+/// This follows the Prometheus client libraries.
+///
+/// # Panics
+/// Panics if `count` is zero, or if `width` is not positive.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::linear_buckets;
+/// assert_eq!(linear_buckets(0.5, 0.25, 3), vec![0.5, 0.75, 1.0]);
+/// ```
+pub fn linear_buckets(start: f64, width: f64, count: usize) -> Vec<f64> {
+    assert!(count > 0, "bucket count must be greater than zero");
+    assert!(width > 0.0, "linear bucket width must be greater than zero");
+
+    (0..count).map(|i| start + width * i as f64).collect()
+}
+
+/// Histogram bucket boundaries for sizes, in bytes, from 1 KiB up to 1 GiB.
+///
+/// Each boundary is four times the previous one, giving 11 buckets: 1 KiB, 4 KiB, 16 KiB,
+/// 64 KiB, 256 KiB, 1 MiB, 4 MiB, 16 MiB, 64 MiB, 256 MiB and 1 GiB.  This covers most payload and
+/// message sizes without hand-listing boundaries.  For other ranges, use
+/// [`exponential_buckets`] directly.
+///
+/// These are the buckets the Prometheus observer renders histograms described with
+/// [`Unit::Bytes`](metrics_core::Unit::Bytes) with, unless other buckets are set for them.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::size_buckets;
+/// let buckets = size_buckets();
+/// assert_eq!(buckets.first(), Some(&1024.0));
+/// assert_eq!(buckets.last(), Some(&1_073_741_824.0));
+/// ```
+pub fn size_buckets() -> Vec<f64> {
+    exponential_buckets(1024.0, 4.0, 11)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{duration_buckets, exponential_buckets, linear_buckets, size_buckets};
+    use std::time::Duration;
+
+    #[test]
+    fn test_duration_buckets() {
+        let buckets = duration_buckets(&[
+            Duration::from_millis(5),
+            Duration::from_millis(10),
+            Duration::from_millis(25),
+            Duration::from_millis(250),
+            Duration::from_millis(2500),
+        ]);
+        assert_eq!(buckets, vec![0.005, 0.01, 0.025, 0.25, 2.5]);
+
+        assert_eq!(
+            duration_buckets(&[Duration::from_micros(500), Duration::from_nanos(1)]),
+            vec![0.0005, 0.000_000_001]
+        );
+        assert!(duration_buckets(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_exponential_buckets() {
+        assert_eq!(
+            exponential_buckets(1.0, 2.0, 6),
+            vec![1.0, 2.0, 4.0, 8.0, 16.0, 32.0]
+        );
+        assert_eq!(
+            exponential_buckets(100.0, 1.5, 3),
+            vec![100.0, 150.0, 225.0]
+        );
+        assert_eq!(exponential_buckets(0.005, 2.0, 1), vec![0.005]);
+    }
+
+    #[test]
+    fn test_size_buckets() {
+        const KIB: f64 = 1024.0;
+        const MIB: f64 = 1024.0 * KIB;
+        const GIB: f64 = 1024.0 * MIB;
+        assert_eq!(
+            size_buckets(),
+            vec![
+                KIB,
+                4.0 * KIB,
+                16.0 * KIB,
+                64.0 * KIB,
+                256.0 * KIB,
+                MIB,
+                4.0 * MIB,
+                16.0 * MIB,
+                64.0 * MIB,
+                256.0 * MIB,
+                GIB,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_linear_buckets() {
+        assert_eq!(linear_buckets(1.0, 1.0, 5), vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(linear_buckets(-10.0, 5.0, 4), vec![-10.0, -5.0, 0.0, 5.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_exponential_buckets_factor_too_small() {
+        let _ = exponential_buckets(1.0, 1.0, 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_linear_buckets_zero_count() {
+        let _ = linear_buckets(0.0, 1.0, 0);
+    }
+}
@@ -1,8 +1,9 @@
 use crossbeam_epoch::{pin as epoch_pin, Atomic, Guard, Owned, Shared};
 use std::{
     cell::UnsafeCell,
-    mem, slice,
+    cmp, mem, slice,
     sync::atomic::{AtomicUsize, Ordering},
+    thread,
 };
 
 const BLOCK_SIZE: usize = 128;
@@ -146,17 +147,19 @@ impl<T> AtomicBucket<T> {
                 Ok(_) => return,
                 // The block was full, so we've been given the value back and we need to install a new block.
                 Err(value) => {
-                    match self.tail.compare_and_set(
-                        tail,
-                        Owned::new(Block::new()),
-                        Ordering::AcqRel,
-                        guard,
-                    ) {
-                        // We managed to install the block, so we need to link this new block to
-                        // the previous block.
+                    // Link the new block to the previous block before installing it, so that
+                    // a concurrent drain which detaches the new block also gets every block
+                    // before it.
+                    let new_block = Owned::new(Block::new());
+                    new_block.set_prev(tail, guard);
+
+                    match self
+                        .tail
+                        .compare_and_set(tail, new_block, Ordering::AcqRel, guard)
+                    {
+                        // We managed to install the block.
                         Ok(ptr) => {
                             let new_tail = unsafe { ptr.deref() };
-                            new_tail.set_prev(tail, guard);
 
                             // Now push into our new block.
                             match new_tail.push(value) {
@@ -170,7 +173,10 @@ impl<T> AtomicBucket<T> {
                             }
                         }
                         // Somebody else installed the block before us, so let's just start over.
-                        Err(_) => {
+                        // The block we made must not take the previous block down with it when
+                        // it's dropped.
+                        Err(e) => {
+                            e.new.prev.store(Shared::null(), Ordering::Release);
                             original = value;
                             continue;
                         }
@@ -222,6 +228,52 @@ impl<T> AtomicBucket<T> {
         }
     }
 
+    /// Takes all of the elements written to the bucket, leaving it empty.
+    ///
+    /// Unlike calling [`data`](AtomicBucket::data) followed by [`clear`](AtomicBucket::clear),
+    /// this is atomic with respect to concurrent writers: every element is either returned here or
+    /// left in the bucket for the next caller, and never lost or returned twice.  Writes which
+    /// are in progress when the bucket is drained are waited on, so they are included in the
+    /// returned elements.
+    ///
+    /// Elements are in partial reverse order: blocks are iterated in reverse order, but the
+    /// elements within them will appear in their original order.
+    pub fn drain(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let guard = &epoch_pin();
+
+        // Detach the blocks from the bucket, so that new writers start a new chain of blocks.
+        let tail = self.tail.swap(Shared::null(), Ordering::AcqRel, guard);
+
+        let mut values = Vec::new();
+        let mut block_ptr = tail;
+        while !block_ptr.is_null() {
+            let block = unsafe { block_ptr.deref() };
+
+            // Writers which loaded this block before we detached it may still try to push into
+            // it.  Marking it as full sends any writer that hasn't claimed a slot yet back to the
+            // bucket, and we wait for the writers that already claimed one to finish up.
+            let claimed = cmp::min(block.write.swap(BLOCK_SIZE, Ordering::AcqRel), BLOCK_SIZE);
+            while block.len() < claimed {
+                thread::yield_now();
+            }
+            values.extend_from_slice(block.data());
+
+            block_ptr = block.prev.load(Ordering::Acquire, guard);
+        }
+
+        if !tail.is_null() {
+            unsafe {
+                guard.defer_destroy(tail);
+            }
+            guard.flush();
+        }
+
+        values
+    }
+
     /// Clears the bucket.
     ///
     /// Deallocation of the internal blocks happens only when all readers have finished, and so
@@ -403,6 +455,24 @@ mod tests {
         assert_eq!(sum, total);
     }
 
+    #[test]
+    fn test_bucket_drain() {
+        let bucket = AtomicBucket::new();
+        assert!(bucket.drain().is_empty());
+
+        for i in 0..BLOCK_SIZE as u64 * 3 {
+            bucket.push(i);
+        }
+
+        let mut drained = bucket.drain();
+        drained.sort();
+        assert_eq!(drained, (0..BLOCK_SIZE as u64 * 3).collect::<Vec<_>>());
+        assert!(bucket.data().is_empty());
+
+        bucket.push(42);
+        assert_eq!(bucket.drain(), vec![42]);
+    }
+
     #[test]
     fn test_bucket_write_then_read_mt() {
         let bucket = AtomicBucket::new();
@@ -0,0 +1,218 @@
+use metrics::{MetricOp, Recorder};
+use metrics_core::{Key, Label};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+/// Name of the gauge emitted by [`CardinalityTracker`].
+const CARDINALITY_METRIC: &str = "metrics_series_cardinality";
+
+/// A recorder wrapper that reports the label cardinality of each metric.
+///
+/// Every distinct set of labels recorded for a metric name creates a new series, and a label
+/// with unbounded values, such as a user ID, can create enough series to exhaust the memory of
+/// the process.  `CardinalityTracker` keeps count of the distinct label sets seen for each metric
+/// name, and every time a new one is seen, updates a `metrics_series_cardinality{name="..."}`
+/// gauge on the wrapped recorder with the new count.  This surfaces cardinality explosions long
+/// before they become a problem.
+///
+/// Label sets are tracked by [`Key::labels_hash`], so only a single integer is held per series.
+/// The cardinality gauge itself is never tracked.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::{Key, Label};
+/// # use metrics_util::CardinalityTracker;
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let recorder = CardinalityTracker::new(NoopRecorder);
+/// for user in &["alice", "bob", "alice"] {
+///     let key = Key::from_name_and_labels("logins", vec![Label::new("user", *user)]);
+///     recorder.increment_counter(key, 1);
+/// }
+///
+/// assert_eq!(recorder.cardinality("logins"), 2);
+/// ```
+pub struct CardinalityTracker<R> {
+    inner: R,
+    series: Mutex<HashMap<String, HashSet<u64>>>,
+}
+
+impl<R> CardinalityTracker<R> {
+    /// Creates a new `CardinalityTracker` around the given recorder.
+    pub fn new(inner: R) -> Self {
+        CardinalityTracker {
+            inner,
+            series: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Gets the number of distinct label sets seen for the given metric name.
+    pub fn cardinality(&self, name: &str) -> usize {
+        let series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+        series.get(name).map(|s| s.len()).unwrap_or(0)
+    }
+
+    /// Gets a reference to the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the wrapped recorder.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Recorder> CardinalityTracker<R> {
+    fn track(&self, key: &Key) {
+        let name = key.name();
+        if name == CARDINALITY_METRIC {
+            return;
+        }
+
+        let cardinality = {
+            let mut series = self.series.lock().unwrap_or_else(|e| e.into_inner());
+            let labelings = match series.get_mut(name.as_ref()) {
+                Some(labelings) => labelings,
+                None => series.entry(name.to_string()).or_default(),
+            };
+            if !labelings.insert(key.labels_hash()) {
+                return;
+            }
+            labelings.len()
+        };
+
+        let key = Key::from_name_and_labels(
+            CARDINALITY_METRIC,
+            vec![Label::new("name", name.into_owned())],
+        );
+        self.inner.update_gauge(key, cardinality as i64);
+    }
+}
+
+impl<R: Recorder> Recorder for CardinalityTracker<R> {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.track(&key);
+        self.inner.increment_counter(key, value);
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.track(&key);
+        self.inner.update_gauge(key, value);
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.track(&key);
+        self.inner.record_histogram(key, value);
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        self.track(&key);
+        self.inner.record_histogram_many(key, value, count);
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        self.track(&key);
+        self.inner
+            .record_histogram_buckets(key, buckets, sum, count);
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.track(&key);
+        self.inner.try_record_histogram(key, value)
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        self.track(&key);
+        self.inner
+            .record_histogram_with_exemplar(key, value, exemplar);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        self.inner.counter_value(key)
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        self.inner.gauge_value(key)
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.inner.describe_counter(key, description);
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.inner.describe_gauge(key, description);
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.inner.describe_histogram(key, description);
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.inner.describe_with_meta(key, meta);
+    }
+
+    fn record_batch(&self, ops: &[MetricOp]) {
+        for op in ops {
+            match op {
+                MetricOp::IncrementCounter(key, _) => self.track(key),
+                MetricOp::UpdateGauge(key, _) => self.track(key),
+                MetricOp::RecordHistogram(key, _) => self.track(key),
+            }
+        }
+        self.inner.record_batch(ops);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CardinalityTracker;
+    use crate::test_util::CapturingRecorder;
+    use metrics::Recorder;
+    use metrics_core::{Key, Label};
+
+    fn cardinality_key(name: &'static str) -> Key {
+        Key::from_name_and_labels("metrics_series_cardinality", vec![Label::new("name", name)])
+    }
+
+    #[test]
+    fn test_cardinality_tracker() {
+        let recorder = CardinalityTracker::new(CapturingRecorder::default());
+
+        for i in 0..5 {
+            let key = Key::from_name_and_labels("requests", vec![Label::new("id", i.to_string())]);
+            recorder.increment_counter(key.clone(), 1);
+            recorder.increment_counter(key, 1);
+        }
+        recorder.record_histogram(Key::from_name("latency"), 10);
+        recorder.record_histogram(Key::from_name("latency"), 20);
+
+        assert_eq!(recorder.cardinality("requests"), 5);
+        assert_eq!(recorder.cardinality("latency"), 1);
+        assert_eq!(recorder.cardinality("unknown"), 0);
+        assert_eq!(recorder.cardinality("metrics_series_cardinality"), 0);
+
+        // Only new label sets update the gauge, and the gauge itself is never tracked.
+        let gauges = recorder.inner().take_gauges();
+        let requests = gauges
+            .iter()
+            .filter(|(k, _)| *k == cardinality_key("requests"))
+            .map(|(_, v)| *v)
+            .collect::<Vec<_>>();
+        assert_eq!(requests, vec![1, 2, 3, 4, 5]);
+        assert_eq!(gauges.len(), 6);
+        assert_eq!(gauges[5], (cardinality_key("latency"), 1));
+    }
+}
@@ -0,0 +1,463 @@
+use atomic_shim::AtomicU64;
+use metrics::{MetricOp, Recorder};
+use metrics_core::{Key, MetricKind};
+use std::{
+    any::Any,
+    collections::VecDeque,
+    mem,
+    sync::{atomic::Ordering, Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+/// What a [`ChannelRecorder`] does with an operation when its channel is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drops the operation being recorded, keeping everything already in the channel.
+    DropNewest,
+
+    /// Drops the oldest operation in the channel to make room for the one being recorded.
+    DropOldest,
+
+    /// Blocks the recording thread until the channel has room.
+    ///
+    /// If the [`ChannelDrain`] has been dropped, nothing will ever make room, so operations are
+    /// dropped as with [`DropNewest`](OverflowPolicy::DropNewest) instead.
+    Block,
+}
+
+/// An operation waiting in the channel.
+#[derive(Debug)]
+enum Queued {
+    Op(MetricOp),
+    Many(Key, u64, u64),
+    Buckets(Key, Vec<(u64, u64)>, u64, u64),
+    Describe(Key, MetricKind, &'static str),
+    Meta(Key, Box<dyn Any + Send + Sync>),
+}
+
+#[derive(Debug)]
+struct State {
+    ops: VecDeque<Queued>,
+    closed: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+    dropped: AtomicU64,
+}
+
+impl Shared {
+    /// Queues the operation, returning whether or not it was queued rather than dropped.
+    fn push(&self, op: Queued) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.ops.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::Block if !state.closed => {
+                    state = self.not_full.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+                OverflowPolicy::DropOldest => {
+                    state.ops.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                _ => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+            }
+        }
+
+        state.ops.push_back(op);
+        self.not_empty.notify_one();
+        true
+    }
+}
+
+/// A recorder that sends every operation over a bounded channel.
+///
+/// Exporters that write to a slow backend, such as over the network, shouldn't make the code
+/// recording metrics wait on those writes.  `ChannelRecorder` queues each operation as a
+/// [`MetricOp`] instead, and the other end of the channel, a [`ChannelDrain`], is drained by a
+/// separate flush task which applies the operations to the real recorder.
+///
+/// The channel holds at most `capacity` operations.  When the flush task falls behind and the
+/// channel fills up, operations are dropped or the recording thread is blocked, according to the
+/// [`OverflowPolicy`].  Every dropped operation is counted, and the count can be read with
+/// [`dropped`](ChannelRecorder::dropped) from either end of the channel.
+///
+/// Weighted and pre-aggregated histogram values are queued as a single operation each, however
+/// many values they stand for.  Histogram values recorded with
+/// [`try_record_histogram`](Recorder::try_record_histogram) report whether they were queued, or
+/// dropped because the channel was full.
+///
+/// Descriptions and metadata are queued alongside the operations, so the real recorder receives
+/// them in the same order, and they're dropped the same way when the channel is full.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::Key;
+/// # use metrics_util::{ChannelRecorder, OverflowPolicy};
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let (recorder, drain) = ChannelRecorder::bounded(1024, OverflowPolicy::DropOldest);
+/// recorder.increment_counter(Key::from_name("requests"), 1);
+/// recorder.update_gauge(Key::from_name("connections"), 5);
+///
+/// // Usually done periodically by a flush task.
+/// let backend = NoopRecorder;
+/// assert_eq!(drain.drain_into(&backend), 2);
+/// assert_eq!(recorder.dropped(), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChannelRecorder {
+    shared: Arc<Shared>,
+}
+
+impl ChannelRecorder {
+    /// Creates a new channel holding at most `capacity` operations, returning the recorder for
+    /// sending operations and the drain for receiving them.
+    ///
+    /// A capacity of zero is treated as one, as an operation must be held somewhere to be handed
+    /// over to the drain.
+    pub fn bounded(capacity: usize, policy: OverflowPolicy) -> (ChannelRecorder, ChannelDrain) {
+        let capacity = if capacity == 0 { 1 } else { capacity };
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                ops: VecDeque::with_capacity(capacity),
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity,
+            policy,
+            dropped: AtomicU64::new(0),
+        });
+
+        (
+            ChannelRecorder {
+                shared: shared.clone(),
+            },
+            ChannelDrain { shared },
+        )
+    }
+
+    /// Number of operations dropped because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of operations currently waiting in the channel.
+    pub fn len(&self) -> usize {
+        let state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.ops.len()
+    }
+
+    /// Whether or not the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Recorder for ChannelRecorder {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.shared
+            .push(Queued::Op(MetricOp::IncrementCounter(key, value)));
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.shared
+            .push(Queued::Op(MetricOp::UpdateGauge(key, value)));
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.shared
+            .push(Queued::Op(MetricOp::RecordHistogram(key, value)));
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.shared
+            .push(Queued::Op(MetricOp::RecordHistogram(key, value)))
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        self.shared.push(Queued::Many(key, value, count));
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        self.shared
+            .push(Queued::Buckets(key, buckets.to_vec(), sum, count));
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.shared
+            .push(Queued::Describe(key, MetricKind::Counter, description));
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.shared
+            .push(Queued::Describe(key, MetricKind::Gauge, description));
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.shared
+            .push(Queued::Describe(key, MetricKind::Histogram, description));
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.shared.push(Queued::Meta(key, meta));
+    }
+}
+
+/// The receiving end of a [`ChannelRecorder`].
+///
+/// Dropping the drain closes the channel: recorders using [`OverflowPolicy::Block`] stop blocking,
+/// and drop operations once the channel is full.
+#[derive(Debug)]
+pub struct ChannelDrain {
+    shared: Arc<Shared>,
+}
+
+impl ChannelDrain {
+    /// Applies every operation waiting in the channel to the given recorder, in the order they
+    /// were recorded, and returns how many there were.
+    ///
+    /// The operations are taken out of the channel before being applied, so recording isn't held
+    /// up while the given recorder works through them.
+    pub fn drain_into<R: Recorder + ?Sized>(&self, recorder: &R) -> usize {
+        let ops = {
+            let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.ops.drain(..).collect::<Vec<_>>()
+        };
+        self.shared.not_full.notify_all();
+
+        let len = ops.len();
+        let mut batch = Vec::new();
+        for op in ops {
+            match op {
+                Queued::Op(op) => batch.push(op),
+                op => {
+                    // Runs of plain operations are applied as one batch, in order with the rest.
+                    if !batch.is_empty() {
+                        recorder.record_batch(&mem::replace(&mut batch, Vec::new()));
+                    }
+                    match op {
+                        Queued::Many(key, value, count) => {
+                            recorder.record_histogram_many(key, value, count)
+                        }
+                        Queued::Buckets(key, buckets, sum, count) => {
+                            recorder.record_histogram_buckets(key, &buckets, sum, count)
+                        }
+                        Queued::Describe(key, MetricKind::Counter, description) => {
+                            recorder.describe_counter(key, description)
+                        }
+                        Queued::Describe(key, MetricKind::Gauge, description) => {
+                            recorder.describe_gauge(key, description)
+                        }
+                        Queued::Describe(key, MetricKind::Histogram, description) => {
+                            recorder.describe_histogram(key, description)
+                        }
+                        Queued::Meta(key, meta) => recorder.describe_with_meta(key, meta),
+                        Queued::Op(_) => unreachable!("plain operations are batched"),
+                    }
+                }
+            }
+        }
+        if !batch.is_empty() {
+            recorder.record_batch(&batch);
+        }
+        len
+    }
+
+    /// Waits until at least one operation is in the channel, or the timeout elapses, and then
+    /// applies every waiting operation to the given recorder as with
+    /// [`drain_into`](ChannelDrain::drain_into).
+    pub fn wait_and_drain_into<R: Recorder + ?Sized>(
+        &self,
+        recorder: &R,
+        timeout: Duration,
+    ) -> usize {
+        {
+            let state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.ops.is_empty() {
+                let _ = self
+                    .shared
+                    .not_empty
+                    .wait_timeout(state, timeout)
+                    .unwrap_or_else(|e| e.into_inner());
+            }
+        }
+        self.drain_into(recorder)
+    }
+
+    /// Number of operations dropped because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for ChannelDrain {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.closed = true;
+        self.shared.not_full.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelRecorder, OverflowPolicy};
+    use crate::test_util::CapturingRecorder;
+    use metrics::{MetricOp, Recorder};
+    use metrics_core::Key;
+    use std::{thread, time::Duration};
+
+    fn values(backend: &CapturingRecorder) -> Vec<i64> {
+        backend
+            .take_values()
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    fn fill(recorder: &ChannelRecorder, values: &[u64]) {
+        for value in values {
+            recorder.increment_counter(Key::from_name("requests"), *value);
+        }
+    }
+
+    #[test]
+    fn test_drop_newest() {
+        let (recorder, drain) = ChannelRecorder::bounded(2, OverflowPolicy::DropNewest);
+        fill(&recorder, &[1, 2, 3, 4]);
+        assert_eq!(recorder.len(), 2);
+        assert_eq!(recorder.dropped(), 2);
+
+        let backend = CapturingRecorder::default();
+        assert_eq!(drain.drain_into(&backend), 2);
+        assert_eq!(values(&backend), vec![1, 2]);
+        assert!(recorder.is_empty());
+
+        fill(&recorder, &[5]);
+        assert_eq!(drain.drain_into(&backend), 1);
+        assert_eq!(values(&backend), vec![5]);
+        assert_eq!(drain.dropped(), 2);
+    }
+
+    #[test]
+    fn test_drop_oldest() {
+        let (recorder, drain) = ChannelRecorder::bounded(2, OverflowPolicy::DropOldest);
+        fill(&recorder, &[1, 2, 3, 4]);
+        assert_eq!(recorder.len(), 2);
+        assert_eq!(recorder.dropped(), 2);
+
+        let backend = CapturingRecorder::default();
+        assert_eq!(drain.drain_into(&backend), 2);
+        assert_eq!(values(&backend), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_try_record_histogram() {
+        let (recorder, drain) = ChannelRecorder::bounded(1, OverflowPolicy::DropNewest);
+        assert!(recorder.try_record_histogram(Key::from_name("latency"), 1));
+        assert!(!recorder.try_record_histogram(Key::from_name("latency"), 2));
+        assert_eq!(recorder.dropped(), 1);
+
+        // Dropping the oldest value makes room for the new one, so it's still accepted.
+        let (recorder, drain_oldest) = ChannelRecorder::bounded(1, OverflowPolicy::DropOldest);
+        assert!(recorder.try_record_histogram(Key::from_name("latency"), 1));
+        assert!(recorder.try_record_histogram(Key::from_name("latency"), 2));
+        assert_eq!(recorder.dropped(), 1);
+
+        let backend = CapturingRecorder::default();
+        assert_eq!(drain.drain_into(&backend), 1);
+        assert_eq!(drain_oldest.drain_into(&backend), 1);
+        assert_eq!(
+            backend.take_ops(),
+            vec![
+                MetricOp::RecordHistogram(Key::from_name("latency"), 1),
+                MetricOp::RecordHistogram(Key::from_name("latency"), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_weighted_histogram() {
+        let (recorder, drain) = ChannelRecorder::bounded(2, OverflowPolicy::Block);
+
+        // However many values it stands for, a weighted value takes up one slot, and doesn't block.
+        recorder.record_histogram_many(Key::from_name("latency"), 7, 1_000);
+        recorder.record_histogram(Key::from_name("latency"), 8);
+        assert_eq!(recorder.len(), 2);
+
+        let backend = CapturingRecorder::default();
+        assert_eq!(drain.drain_into(&backend), 2);
+        let ops = backend.take_ops();
+        assert_eq!(ops.len(), 1_001);
+        assert_eq!(
+            ops[0],
+            MetricOp::RecordHistogram(Key::from_name("latency"), 7)
+        );
+        assert_eq!(
+            ops[1_000],
+            MetricOp::RecordHistogram(Key::from_name("latency"), 8)
+        );
+    }
+
+    #[test]
+    fn test_descriptions() {
+        let (recorder, drain) = ChannelRecorder::bounded(4, OverflowPolicy::DropNewest);
+        recorder.describe_counter(Key::from_name("requests"), "Requests served.");
+        recorder.increment_counter(Key::from_name("requests"), 1);
+        recorder.describe_histogram(Key::from_name("latency"), "Request latency.");
+        assert_eq!(recorder.len(), 3);
+
+        let backend = CapturingRecorder::default();
+        assert_eq!(drain.drain_into(&backend), 3);
+        assert_eq!(values(&backend), vec![1]);
+        assert_eq!(
+            backend.take_descriptions(),
+            vec![
+                (Key::from_name("requests"), "Requests served."),
+                (Key::from_name("latency"), "Request latency."),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block() {
+        let (recorder, drain) = ChannelRecorder::bounded(2, OverflowPolicy::Block);
+        fill(&recorder, &[1, 2]);
+
+        let sender = recorder.clone();
+        let blocked = thread::spawn(move || fill(&sender, &[3]));
+
+        // The sender can't make progress until the channel is drained.
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(recorder.len(), 2);
+
+        let backend = CapturingRecorder::default();
+        assert_eq!(drain.drain_into(&backend), 2);
+        blocked.join().unwrap();
+        assert_eq!(
+            drain.wait_and_drain_into(&backend, Duration::from_secs(5)),
+            1
+        );
+        assert_eq!(values(&backend), vec![1, 2, 3]);
+        assert_eq!(recorder.dropped(), 0);
+
+        // Once the drain is gone, a full channel drops rather than blocking forever.
+        drop(drain);
+        fill(&recorder, &[4, 5, 6]);
+        assert_eq!(recorder.len(), 2);
+        assert_eq!(recorder.dropped(), 1);
+    }
+}
@@ -0,0 +1,139 @@
+use atomic_shim::AtomicU64;
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+/// A source of the current time.
+///
+/// Types which depend on time take a `Clock` rather than calling [`Instant::now`] directly, so
+/// that tests can swap in a [`MockClock`] and control the passage of time, rather than sleeping.
+pub trait Clock: Send + Sync {
+    /// Gets the current time.
+    fn now(&self) -> Instant;
+}
+
+/// A [`Clock`] backed by the system's monotonic clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A [`Clock`] which only moves forward when told to.
+///
+/// Clones of a `MockClock` share the same time, so a test can hand a clone to the type under test
+/// and keep the original around to advance it.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::{Clock, MockClock};
+/// # use std::time::Duration;
+/// let clock = MockClock::new();
+/// let start = clock.now();
+///
+/// clock.advance(Duration::from_secs(5));
+/// assert_eq!(clock.now() - start, Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    start: Instant,
+    offset_ns: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// Creates a new `MockClock`, starting at the current time.
+    pub fn new() -> Self {
+        MockClock {
+            start: Instant::now(),
+            offset_ns: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Moves the clock forward by the given amount.
+    pub fn advance(&self, amount: Duration) {
+        let amount = duration_as_nanos(amount);
+        self.offset_ns.fetch_add(amount, Ordering::AcqRel);
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + Duration::from_nanos(self.offset_ns.load(Ordering::Acquire))
+    }
+}
+
+/// Fires at most once per interval, as measured by a [`Clock`].
+///
+/// Shared by the wrappers which pass along what they've held back on a timer.
+#[derive(Debug)]
+pub(crate) struct Interval {
+    interval_ns: u64,
+    start: Instant,
+    // When the interval last fired, in nanoseconds since `start`.
+    last_ns: AtomicU64,
+}
+
+impl Interval {
+    pub fn new(interval: Duration, now: Instant) -> Self {
+        Interval {
+            interval_ns: duration_as_nanos(interval),
+            start: now,
+            last_ns: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the interval has elapsed since it last fired.  If so, it fires, and only one
+    /// caller out of any racing callers gets `true`.
+    pub fn fire(&self, now: Instant) -> bool {
+        let now = duration_as_nanos(now.duration_since(self.start));
+        let last = self.last_ns.load(Ordering::Acquire);
+        if now.saturating_sub(last) < self.interval_ns {
+            return false;
+        }
+
+        self.last_ns
+            .compare_exchange(last, now, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+fn duration_as_nanos(d: Duration) -> u64 {
+    d.as_secs() * 1_000_000_000 + u64::from(d.subsec_nanos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, MockClock, SystemClock};
+    use std::time::Duration;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        assert_eq!(clock.now(), start);
+
+        let shared = clock.clone();
+        shared.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now() - start, Duration::from_millis(1500));
+
+        clock.advance(Duration::from_nanos(1));
+        assert_eq!(shared.now() - start, Duration::from_nanos(1_500_000_001));
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let clock = SystemClock;
+        let first = clock.now();
+        assert!(clock.now() >= first);
+    }
+}
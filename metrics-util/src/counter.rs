@@ -0,0 +1,107 @@
+use atomic_shim::AtomicU64;
+use std::sync::atomic::Ordering;
+
+/// A monotonic counter with atomic read/write access.
+///
+/// On top of being incremented directly, the counter can be set from an absolute value, which is
+/// useful when reconciling against a total that is tracked by some other system, such as a
+/// counter scraped from the operating system or an upstream service.  Counters never go
+/// backwards, so an absolute value lower than the current total is ignored.
+///
+/// An absolute value going backwards usually means that the upstream counter was reset, perhaps
+/// because the process tracking it restarted.  As ignoring these updates would otherwise hide the
+/// reset entirely, the counter tracks how many times it has happened, and exposes it via
+/// [`reset_detections`](AtomicCounter::reset_detections) so that operators can alert on it.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::AtomicCounter;
+/// let counter = AtomicCounter::new();
+/// counter.absolute(42);
+/// counter.absolute(10);
+///
+/// assert_eq!(counter.get(), 42);
+/// assert_eq!(counter.reset_detections(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct AtomicCounter {
+    value: AtomicU64,
+    resets: AtomicU64,
+}
+
+impl AtomicCounter {
+    /// Creates a new counter, starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Increments the counter by the given amount.
+    pub fn increment(&self, value: u64) {
+        self.value.fetch_add(value, Ordering::Release);
+    }
+
+    /// Sets the counter to the given absolute value.
+    ///
+    /// If the value is lower than the current total, the counter is left unchanged and the
+    /// update is counted as a reset detection.
+    pub fn absolute(&self, value: u64) {
+        let mut current = self.value.load(Ordering::Acquire);
+        loop {
+            if value < current {
+                self.resets.fetch_add(1, Ordering::Release);
+                return;
+            }
+
+            match self
+                .value
+                .compare_exchange(current, value, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Gets the current value of the counter.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Acquire)
+    }
+
+    /// Gets the number of times an absolute value lower than the current total was seen.
+    pub fn reset_detections(&self) -> u64 {
+        self.resets.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicCounter;
+
+    #[test]
+    fn test_counter_increment_and_absolute() {
+        let counter = AtomicCounter::new();
+        counter.increment(5);
+        counter.absolute(12);
+        counter.increment(3);
+        counter.absolute(15);
+
+        assert_eq!(counter.get(), 15);
+        assert_eq!(counter.reset_detections(), 0);
+    }
+
+    #[test]
+    fn test_counter_reset_detections() {
+        let counter = AtomicCounter::new();
+        for value in (1..=10).rev() {
+            counter.absolute(value * 100);
+        }
+
+        // The first value is accepted, and every lower value after it is a detected reset.
+        assert_eq!(counter.get(), 1000);
+        assert_eq!(counter.reset_detections(), 9);
+
+        counter.absolute(1500);
+        assert_eq!(counter.get(), 1500);
+        assert_eq!(counter.reset_detections(), 9);
+    }
+}
@@ -0,0 +1,166 @@
+use atomic_shim::AtomicU64;
+use metrics::Recorder;
+use metrics_core::{Key, Label};
+use std::{any::Any, sync::atomic::Ordering};
+
+/// A recorder wrapper that counts the operations routed through it.
+///
+/// `Counting` keeps a running total of the counter, gauge, and histogram operations it has
+/// processed before passing them along to the wrapped recorder.  This is useful for checking that
+/// instrumentation is actually firing, without needing to inspect the values that the wrapped
+/// recorder ends up with.
+///
+/// Calls to `record_histogram_many` and `record_histogram_buckets` are each counted as a single
+/// histogram operation.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::Key;
+/// # use metrics_util::Counting;
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let recorder = Counting::new(NoopRecorder);
+/// recorder.increment_counter(Key::from_name("requests"), 1);
+/// recorder.record_histogram(Key::from_name("latency"), 42);
+///
+/// assert_eq!(recorder.stats(), (1, 0, 1));
+/// ```
+pub struct Counting<R> {
+    inner: R,
+    counters: AtomicU64,
+    gauges: AtomicU64,
+    histograms: AtomicU64,
+}
+
+impl<R> Counting<R> {
+    /// Creates a new `Counting` wrapper around the given recorder.
+    pub fn new(inner: R) -> Self {
+        Counting {
+            inner,
+            counters: AtomicU64::new(0),
+            gauges: AtomicU64::new(0),
+            histograms: AtomicU64::new(0),
+        }
+    }
+
+    /// Gets the total number of operations processed so far.
+    ///
+    /// Totals are returned as a tuple of counter, gauge, and histogram operations, in that order.
+    pub fn stats(&self) -> (u64, u64, u64) {
+        (
+            self.counters.load(Ordering::Relaxed),
+            self.gauges.load(Ordering::Relaxed),
+            self.histograms.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Gets a reference to the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the wrapped recorder.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Recorder> Recorder for Counting<R> {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.counters.fetch_add(1, Ordering::Relaxed);
+        self.inner.increment_counter(key, value);
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.gauges.fetch_add(1, Ordering::Relaxed);
+        self.inner.update_gauge(key, value);
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.histograms.fetch_add(1, Ordering::Relaxed);
+        self.inner.record_histogram(key, value);
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        self.histograms.fetch_add(1, Ordering::Relaxed);
+        self.inner.record_histogram_many(key, value, count);
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        self.histograms.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .record_histogram_buckets(key, buckets, sum, count);
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.histograms.fetch_add(1, Ordering::Relaxed);
+        self.inner.try_record_histogram(key, value)
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        self.histograms.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .record_histogram_with_exemplar(key, value, exemplar);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        self.inner.counter_value(key)
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        self.inner.gauge_value(key)
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.inner.describe_counter(key, description);
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.inner.describe_gauge(key, description);
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.inner.describe_histogram(key, description);
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.inner.describe_with_meta(key, meta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Counting;
+    use crate::test_util::CapturingRecorder;
+    use metrics::Recorder;
+    use metrics_core::Key;
+
+    #[test]
+    fn test_counting_stats() {
+        let recorder = Counting::new(CapturingRecorder::default());
+        assert_eq!(recorder.stats(), (0, 0, 0));
+
+        recorder.increment_counter(Key::from_name("requests"), 1);
+        recorder.increment_counter(Key::from_name("requests"), 5);
+        recorder.update_gauge(Key::from_name("connections"), -3);
+        recorder.record_histogram(Key::from_name("latency"), 100);
+        recorder.record_histogram(Key::from_name("latency"), 200);
+        recorder.record_histogram(Key::from_name("latency"), 300);
+        recorder.record_histogram_buckets(Key::from_name("latency"), &[(10, 2), (100, 4)], 420, 6);
+
+        assert_eq!(recorder.stats(), (2, 1, 4));
+
+        // Everything except the bucketed histogram reaches the wrapped recorder.
+        let inner = recorder.into_inner();
+        assert_eq!(inner.take_ops().len(), 6);
+    }
+}
@@ -0,0 +1,43 @@
+use std::{error::Error, fmt, io};
+
+/// Errors during the encoding or decoding of exported metrics.
+#[derive(Debug)]
+pub enum ExportError {
+    /// A metric could not be encoded in the requested format.
+    Encode(String),
+
+    /// The input was not valid for the format being decoded.
+    Decode(String),
+
+    /// The input referred to a metric kind that is not known.
+    UnknownMetricKind(String),
+
+    /// An I/O error occurred while reading or writing.
+    Io(io::Error),
+}
+
+impl Error for ExportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExportError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportError::Encode(reason) => write!(f, "failed to encode metric: {}", reason),
+            ExportError::Decode(reason) => write!(f, "failed to decode metric: {}", reason),
+            ExportError::UnknownMetricKind(kind) => write!(f, "unknown metric kind '{}'", kind),
+            ExportError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
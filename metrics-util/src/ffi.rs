@@ -0,0 +1,175 @@
+use metrics::Recorder;
+use metrics_core::Key;
+use std::{
+    ffi::CString,
+    os::raw::{c_char, c_void},
+};
+
+/// A label, as passed to the functions of a [`CRecorder`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CLabel {
+    /// The key of the label, as a NUL-terminated string.
+    pub key: *const c_char,
+
+    /// The value of the label, as a NUL-terminated string.
+    pub value: *const c_char,
+}
+
+/// A metric key, as passed to the functions of a [`CRecorder`].
+///
+/// The key, and every string and label it points to, is only valid for the duration of the call
+/// it was passed to, so anything the C side wants to keep must be copied.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CKey {
+    /// The name of the metric, as a NUL-terminated string.
+    pub name: *const c_char,
+
+    /// The labels of the metric, as an array of `labels_len` labels.
+    pub labels: *const CLabel,
+
+    /// The number of labels in `labels`.
+    pub labels_len: usize,
+}
+
+/// A recorder implemented by C functions.
+///
+/// Each function is passed `context` as its first argument, so that it can find whatever state
+/// the C side keeps for the recorder.  `flush` and `free` are optional, and may be null.
+#[repr(C)]
+#[derive(Debug)]
+pub struct CRecorder {
+    /// An opaque pointer passed along to every function.
+    pub context: *mut c_void,
+
+    /// Increments a counter.
+    pub increment_counter: unsafe extern "C" fn(context: *mut c_void, key: *const CKey, value: u64),
+
+    /// Updates a gauge.
+    pub update_gauge: unsafe extern "C" fn(context: *mut c_void, key: *const CKey, value: i64),
+
+    /// Records a value for a histogram.
+    pub record_histogram: unsafe extern "C" fn(context: *mut c_void, key: *const CKey, value: u64),
+
+    /// Sends along anything the recorder is buffering.
+    pub flush: Option<unsafe extern "C" fn(context: *mut c_void)>,
+
+    /// Frees `context`, once the recorder is no longer used.
+    pub free: Option<unsafe extern "C" fn(context: *mut c_void)>,
+}
+
+/// A recorder which forwards everything to the functions of a [`CRecorder`].
+///
+/// This lets a backend written in another language, such as an existing C metrics library, be
+/// used as a recorder.  Keys are marshaled into a [`CKey`] for every call, with the name and
+/// labels converted to NUL-terminated strings.  C strings can't hold NUL bytes, so any NUL bytes
+/// in names and labels are removed.
+///
+/// Histograms are passed along one value at a time, and descriptions aren't passed along at all.
+/// Reading values back isn't supported.
+///
+/// The recorder can be installed as the global recorder from C with
+/// [`metrics_install_c_recorder`].
+///
+/// This is only available when the `ffi` feature is enabled.
+pub struct FfiRecorder {
+    inner: CRecorder,
+}
+
+// The caller of `FfiRecorder::new` promises that the functions can be called from any thread.
+unsafe impl Send for FfiRecorder {}
+unsafe impl Sync for FfiRecorder {}
+
+impl FfiRecorder {
+    /// Creates a new `FfiRecorder` which forwards to the given functions.
+    ///
+    /// # Safety
+    ///
+    /// The functions of `recorder` must be safe to call with its `context`, from any number of
+    /// threads at once, until `free` is called.  If `free` is given, it is called exactly once,
+    /// when the recorder is dropped.
+    pub unsafe fn new(recorder: CRecorder) -> Self {
+        FfiRecorder { inner: recorder }
+    }
+}
+
+impl Drop for FfiRecorder {
+    fn drop(&mut self) {
+        if let Some(free) = self.inner.free {
+            unsafe { free(self.inner.context) }
+        }
+    }
+}
+
+impl Recorder for FfiRecorder {
+    fn increment_counter(&self, key: Key, value: u64) {
+        with_c_key(&key, |key| unsafe {
+            (self.inner.increment_counter)(self.inner.context, key, value)
+        });
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        with_c_key(&key, |key| unsafe {
+            (self.inner.update_gauge)(self.inner.context, key, value)
+        });
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        with_c_key(&key, |key| unsafe {
+            (self.inner.record_histogram)(self.inner.context, key, value)
+        });
+    }
+
+    fn flush(&self) {
+        if let Some(flush) = self.inner.flush {
+            unsafe { flush(self.inner.context) }
+        }
+    }
+}
+
+/// Installs a recorder implemented by C functions as the global recorder.
+///
+/// Returns whether or not the recorder was installed.  If a global recorder has already been
+/// set, the recorder is dropped straight away, calling its `free` function.
+///
+/// This is only available when the `ffi` feature is enabled.
+///
+/// # Safety
+///
+/// `recorder` must meet the requirements of [`FfiRecorder::new`].
+#[no_mangle]
+pub unsafe extern "C" fn metrics_install_c_recorder(recorder: CRecorder) -> bool {
+    metrics::set_boxed_recorder(Box::new(FfiRecorder::new(recorder))).is_ok()
+}
+
+fn to_c_string(value: &str) -> CString {
+    CString::new(value)
+        .unwrap_or_else(|_| CString::new(value.replace('\0', "")).expect("NUL bytes were removed"))
+}
+
+/// Marshals `key` into a [`CKey`], which is only valid while `f` runs.
+fn with_c_key<F>(key: &Key, f: F)
+where
+    F: FnOnce(*const CKey),
+{
+    let name = to_c_string(&key.name());
+    let strings = key
+        .labels()
+        .map(|label| (to_c_string(label.key()), to_c_string(label.value())))
+        .collect::<Vec<_>>();
+    let labels = strings
+        .iter()
+        .map(|(key, value)| CLabel {
+            key: key.as_ptr(),
+            value: value.as_ptr(),
+        })
+        .collect::<Vec<_>>();
+
+    let key = CKey {
+        name: name.as_ptr(),
+        labels: labels.as_ptr(),
+        labels_len: labels.len(),
+    };
+    f(&key);
+}
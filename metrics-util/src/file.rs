@@ -0,0 +1,155 @@
+use metrics::Recorder;
+use metrics_core::{Key, RenderOptions};
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A recorder that appends every operation to a file.
+///
+/// Each counter, gauge, and histogram operation is written as a line of the form
+/// `timestamp kind key value`, where the timestamp is in seconds since the UNIX epoch, the kind is
+/// one of `counter`, `gauge`, or `histogram`, and the key is rendered as `name{label="value"}`.
+/// For example:
+///
+/// ```text
+/// 1700000000.123456 counter requests{code="200"} 1
+/// ```
+///
+/// This is meant for local debugging, where running a full exporter is overkill: point it at a
+/// file and `tail -f` it.  Lines are buffered, and written out when the buffer fills up or when
+/// the recorder is [flushed](Recorder::flush).  As recording can't fail, any error while writing
+/// is ignored.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::Key;
+/// # use metrics_util::FileRecorder;
+/// # let path = std::env::temp_dir().join(format!("metrics-doc-{}.log", std::process::id()));
+/// let recorder = FileRecorder::open_append(&path).expect("failed to open file");
+/// recorder.increment_counter(Key::from_name("requests"), 1);
+/// recorder.flush();
+///
+/// let contents = std::fs::read_to_string(&path).unwrap();
+/// assert!(contents.ends_with(" counter requests 1\n"));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct FileRecorder {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl FileRecorder {
+    /// Creates a new `FileRecorder` that writes to the given file.
+    pub fn new(file: File) -> Self {
+        FileRecorder {
+            writer: Mutex::new(BufWriter::new(file)),
+        }
+    }
+
+    /// Creates a new `FileRecorder` that appends to the file at the given path, creating the file
+    /// if it doesn't exist.
+    ///
+    /// Any existing contents are kept, and new lines are written after them.
+    pub fn open_append<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileRecorder::new(file))
+    }
+
+    fn write_line<V: Display>(&self, kind: &str, key: &Key, value: V) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+        let key = key.render_with(&RenderOptions::new());
+
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(writer, "{} {} {} {}", timestamp, kind, key, value);
+    }
+}
+
+impl Recorder for FileRecorder {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.write_line("counter", &key, value);
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.write_line("gauge", &key, value);
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.write_line("histogram", &key, value);
+    }
+
+    fn flush(&self) {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writer.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileRecorder;
+    use metrics::Recorder;
+    use metrics_core::{Key, Label};
+    use std::{
+        fs,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    #[test]
+    fn test_file_recorder() {
+        let path = std::env::temp_dir().join(format!(
+            "metrics-util-file-recorder-{}.log",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64();
+
+        let recorder = FileRecorder::open_append(&path).unwrap();
+        let key = Key::from_name_and_labels("requests", vec![Label::new("code", "200")]);
+        recorder.increment_counter(key, 2);
+        recorder.update_gauge(Key::from_name("connections"), -3);
+        recorder.record_histogram(Key::from_name("latency"), 40);
+
+        // Nothing is written until the buffer is flushed.
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        recorder.flush();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines = contents
+            .lines()
+            .map(|line| {
+                let mut parts = line.splitn(2, ' ');
+                let timestamp = parts.next().unwrap().parse::<f64>().unwrap();
+                assert!(timestamp >= before.floor());
+                parts.next().unwrap().to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                "counter requests{code=\"200\"} 2",
+                "gauge connections -3",
+                "histogram latency 40",
+            ]
+        );
+
+        // Reopening appends rather than truncating.
+        drop(recorder);
+        let recorder = FileRecorder::open_append(&path).unwrap();
+        recorder.increment_counter(Key::from_name("requests"), 1);
+        recorder.flush();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 4);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use crate::AtomicBucket;
+
+/// A lock-free histogram which holds on to every recorded sample.
+///
+/// Most histograms only answer quantile queries, but federation and remote-write setups need the
+/// raw samples so that a downstream aggregator can merge them correctly.  `AtomicHistogram`
+/// stores samples in an [`AtomicBucket`], so recording never blocks, and hands them over with
+/// [`drain_samples`](AtomicHistogram::drain_samples).
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::AtomicHistogram;
+/// let histogram = AtomicHistogram::new();
+/// histogram.record(1.5);
+/// histogram.record(4.0);
+///
+/// let mut samples = histogram.drain_samples();
+/// samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+/// assert_eq!(samples, vec![1.5, 4.0]);
+/// assert!(histogram.samples().is_empty());
+/// ```
+#[derive(Debug, Default)]
+pub struct AtomicHistogram {
+    samples: AtomicBucket<f64>,
+}
+
+impl AtomicHistogram {
+    /// Creates a new, empty histogram.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a sample.
+    pub fn record(&self, value: f64) {
+        self.samples.push(value);
+    }
+
+    /// Gets a copy of the samples recorded so far, in no particular order.
+    pub fn samples(&self) -> Vec<f64> {
+        self.samples.data()
+    }
+
+    /// Takes the samples recorded so far, in no particular order, leaving the histogram empty.
+    ///
+    /// This is atomic with respect to concurrent calls to [`record`](AtomicHistogram::record):
+    /// every sample is handed over by exactly one call to `drain_samples`.
+    pub fn drain_samples(&self) -> Vec<f64> {
+        self.samples.drain()
+    }
+
+    /// Clears the histogram.
+    pub fn clear(&self) {
+        self.samples.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomicHistogram;
+    use crossbeam_utils::thread::scope;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_drain_samples_mt() {
+        const THREADS: u64 = 4;
+        const SAMPLES: u64 = 50_000;
+
+        let histogram = AtomicHistogram::new();
+        let done = AtomicBool::new(false);
+
+        let mut drained = scope(|s| {
+            let drainer = s.spawn(|_| {
+                let mut drained = Vec::new();
+                while !done.load(Ordering::Acquire) {
+                    drained.extend(histogram.drain_samples());
+                }
+                drained
+            });
+
+            let writers = (0..THREADS)
+                .map(|t| {
+                    let histogram = &histogram;
+                    s.spawn(move |_| {
+                        for i in 0..SAMPLES {
+                            histogram.record((t * SAMPLES + i) as f64);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            done.store(true, Ordering::Release);
+
+            let mut drained = drainer.join().unwrap();
+            drained.extend(histogram.drain_samples());
+            drained
+        })
+        .unwrap();
+
+        // Every sample shows up exactly once.
+        drained.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let expected = (0..THREADS * SAMPLES).map(|v| v as f64).collect::<Vec<_>>();
+        assert_eq!(drained.len(), expected.len());
+        assert!(drained == expected);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::lru::Lru;
+use metrics_core::Key;
+use std::sync::{Arc, Mutex};
+
+/// A bounded pool of shared strings.
+///
+/// In high-cardinality deployments, the same handful of label values, such as `GET` or `200`, end
+/// up stored over and over across thousands of keys.  `Interner` hands out a shared, reference
+/// counted copy of each distinct string, so that repeated values are stored once.
+///
+/// To stop values that are truly unique, such as request IDs, from growing the pool forever, it
+/// holds at most `capacity` strings and evicts the least recently used string when full.  Evicted
+/// strings stay alive for as long as something else still holds them, and are simply interned
+/// again if they're seen again.
+///
+/// Interning only changes how strings are stored, not their contents, so interned strings compare
+/// equal to any other string with the same value.
+///
+/// As [`ScopedString`](metrics_core::ScopedString) is either borrowed for the `'static` lifetime
+/// or owned outright, interned strings can't be stored in a [`Label`](metrics_core::Label)
+/// without copying them, so this is meant for places which keep their own copies of label
+/// values, such as exporters and observers.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::Interner;
+/// # use std::sync::Arc;
+/// let interner = Interner::with_capacity(1024);
+/// let a = interner.intern("GET");
+/// let b = interner.intern(&String::from("GET"));
+///
+/// assert!(Arc::ptr_eq(&a, &b));
+/// assert_eq!(&*a, "GET");
+/// ```
+#[derive(Debug)]
+pub struct Interner {
+    inner: Mutex<Lru<Arc<str>, ()>>,
+    capacity: usize,
+}
+
+impl Interner {
+    /// Creates a new, empty interner that holds at most `capacity` strings.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Interner {
+            inner: Mutex::new(Lru::new()),
+            capacity,
+        }
+    }
+
+    /// Gets the shared copy of the given string, interning it if needed.
+    pub fn intern(&self, value: &str) -> Arc<str> {
+        if self.capacity == 0 {
+            return Arc::from(value);
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((shared, _)) = inner.get(value) {
+            return shared.clone();
+        }
+        let shared = Arc::<str>::from(value);
+        inner.insert(shared.clone(), (), self.capacity);
+        shared
+    }
+
+    /// Maximum number of strings held by this interner.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of strings currently held by this interner.
+    pub fn len(&self) -> usize {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.len()
+    }
+
+    /// Whether or not this interner is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A bounded pool of shared keys.
+///
+/// Call sites which emit the same metric over and over build an identical [`Key`] every time.
+/// `KeyInterner` hands out a shared, reference counted copy of each distinct key, so that
+/// identical keys are stored once and can be compared with [`Arc::ptr_eq`] before falling back
+/// to comparing them by value.
+///
+/// Like [`Interner`], it holds at most `capacity` keys and evicts the least recently used key
+/// when full, so keys with unbounded label values can't grow it forever.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_core::{Key, Label};
+/// # use metrics_util::KeyInterner;
+/// # use std::sync::Arc;
+/// let interner = KeyInterner::with_capacity(1024);
+/// let key = || Key::from_name_and_labels("requests_total", vec![Label::new("code", "200")]);
+/// let a = interner.intern(key());
+/// let b = interner.intern(key());
+///
+/// assert!(Arc::ptr_eq(&a, &b));
+/// ```
+#[derive(Debug)]
+pub struct KeyInterner {
+    inner: Mutex<Lru<Arc<Key>, ()>>,
+    capacity: usize,
+}
+
+impl KeyInterner {
+    /// Creates a new, empty interner that holds at most `capacity` keys.
+    pub fn with_capacity(capacity: usize) -> Self {
+        KeyInterner {
+            inner: Mutex::new(Lru::new()),
+            capacity,
+        }
+    }
+
+    /// Gets the shared copy of the given key, interning it if needed.
+    pub fn intern(&self, key: Key) -> Arc<Key> {
+        if self.capacity == 0 {
+            return Arc::new(key);
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((shared, _)) = inner.get(&key) {
+            return shared.clone();
+        }
+        let shared = Arc::new(key);
+        inner.insert(shared.clone(), (), self.capacity);
+        shared
+    }
+
+    /// Gets the shared copy of the given key, interning it as-is if needed.
+    ///
+    /// This is the same as [`intern`](KeyInterner::intern), for keys which are already reference
+    /// counted.
+    pub fn intern_shared(&self, key: Arc<Key>) -> Arc<Key> {
+        if self.capacity == 0 {
+            return key;
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((shared, _)) = inner.get(&*key) {
+            return shared.clone();
+        }
+        inner.insert(key.clone(), (), self.capacity);
+        key
+    }
+
+    /// Maximum number of keys held by this interner.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of keys currently held by this interner.
+    pub fn len(&self) -> usize {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.len()
+    }
+
+    /// Whether or not this interner is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interner, KeyInterner};
+    use metrics_core::{Key, Label};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_interner_shares_values() {
+        let interner = Interner::with_capacity(16);
+
+        let values = (0..10_000)
+            .map(|i| interner.intern(["GET", "POST", "PUT", "DELETE"][i % 4]))
+            .collect::<Vec<_>>();
+
+        // Only four distinct strings are ever allocated, however many times they are used.
+        assert_eq!(interner.len(), 4);
+        assert!(Arc::ptr_eq(&values[0], &values[4]));
+        assert!(Arc::ptr_eq(&values[1], &values[9_997]));
+        // Every use shares the same allocation, which the interner holds two references to.
+        assert_eq!(Arc::strong_count(&values[0]), 2_500 + 2);
+
+        // Interned strings still compare by value.
+        assert_eq!(&*values[2], "PUT");
+        assert_eq!(values[3], Arc::from("DELETE"));
+    }
+
+    #[test]
+    fn test_interner_evicts_least_recently_used() {
+        let interner = Interner::with_capacity(2);
+
+        let a = interner.intern("a");
+        let _ = interner.intern("b");
+        // Touch "a" so that "b" becomes the least recently used.
+        let _ = interner.intern("a");
+        let _ = interner.intern("c");
+        assert_eq!(interner.len(), 2);
+
+        assert!(Arc::ptr_eq(&a, &interner.intern("a")));
+        assert_eq!(interner.len(), 2);
+
+        let unbounded = (0..1000)
+            .map(|i| interner.intern(&format!("request-{}", i)))
+            .count();
+        assert_eq!(unbounded, 1000);
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_interner_zero_capacity() {
+        let interner = Interner::with_capacity(0);
+        let a = interner.intern("a");
+        let b = interner.intern("a");
+        assert_eq!(a, b);
+        assert!(!Arc::ptr_eq(&a, &b));
+        assert!(interner.is_empty());
+    }
+
+    #[test]
+    fn test_key_interner() {
+        let interner = KeyInterner::with_capacity(2);
+        let key = |code: &'static str| {
+            Key::from_name_and_labels("requests_total", vec![Label::new("code", code)])
+        };
+
+        let ok = interner.intern(key("200"));
+        assert!(Arc::ptr_eq(&ok, &interner.intern(key("200"))));
+        assert_eq!(*ok, key("200"));
+
+        let missing = interner.intern(key("404"));
+        assert!(!Arc::ptr_eq(&ok, &missing));
+        assert_eq!(interner.len(), 2);
+
+        // "200" is the least recently used, so it's evicted first.
+        let _ = interner.intern(key("500"));
+        assert_eq!(interner.len(), 2);
+        assert!(Arc::ptr_eq(&missing, &interner.intern(key("404"))));
+        assert!(!Arc::ptr_eq(&ok, &interner.intern(key("200"))));
+    }
+
+    #[test]
+    fn test_key_interner_shared() {
+        let interner = KeyInterner::with_capacity(2);
+        let key = Arc::new(Key::from_name("requests_total"));
+
+        // A key that isn't interned yet is kept as the shared copy, rather than copied.
+        let shared = interner.intern_shared(key.clone());
+        assert!(Arc::ptr_eq(&key, &shared));
+        assert!(Arc::ptr_eq(
+            &key,
+            &interner.intern_shared(Arc::new(Key::from_name("requests_total")))
+        ));
+        assert!(Arc::ptr_eq(
+            &key,
+            &interner.intern(Key::from_name("requests_total"))
+        ));
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{clock::Interval, Clock, SystemClock};
+use atomic_shim::AtomicI64;
+use metrics::Recorder;
+use metrics_core::{Key, Label};
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+
+#[derive(Default)]
+struct LatestValue {
+    value: AtomicI64,
+    dirty: AtomicBool,
+}
+
+/// A recorder wrapper that downsamples gauges to their latest value.
+///
+/// A gauge that is updated thousands of times a second can flood a push-based backend, even
+/// though only its most recent value matters by the time it is pushed.  `LatestGauge` holds on to
+/// just the latest value of each gauge, and only passes gauges along to the wrapped recorder when
+/// [`flush`](Recorder::flush) is called, at which point every gauge updated since the last flush
+/// is forwarded once, with its latest value.  Callers are expected to flush on whatever interval
+/// suits their backend, or to create the wrapper with
+/// [`with_interval`](LatestGauge::with_interval), in which case gauges are also passed along by the
+/// first update made after each interval has elapsed.
+///
+/// Times are taken from the system clock by default, or from any other [`Clock`] given to
+/// [`with_clock`](LatestGauge::with_clock).
+///
+/// Counters and histograms are passed through to the wrapped recorder unchanged.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::Key;
+/// # use metrics_util::LatestGauge;
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let recorder = LatestGauge::new(NoopRecorder);
+/// for depth in 0..1000 {
+///     recorder.update_gauge(Key::from_name("queue_depth"), depth);
+/// }
+///
+/// // Only `queue_depth = 999` is sent along to the wrapped recorder.
+/// recorder.flush();
+/// ```
+pub struct LatestGauge<R, C = SystemClock> {
+    inner: R,
+    gauges: RwLock<HashMap<Key, LatestValue>>,
+    clock: C,
+    interval: Option<Interval>,
+}
+
+impl<R> LatestGauge<R> {
+    /// Creates a new `LatestGauge` wrapper around the given recorder, passing gauges along only
+    /// when flushed.
+    pub fn new(inner: R) -> Self {
+        LatestGauge {
+            inner,
+            gauges: RwLock::new(HashMap::new()),
+            clock: SystemClock,
+            interval: None,
+        }
+    }
+
+    /// Creates a new `LatestGauge` wrapper around the given recorder, passing gauges along once
+    /// per `interval` as well as when flushed.
+    pub fn with_interval(inner: R, interval: Duration) -> Self {
+        LatestGauge::with_clock(inner, interval, SystemClock)
+    }
+}
+
+impl<R, C: Clock> LatestGauge<R, C> {
+    /// Creates a new `LatestGauge` wrapper around the given recorder, passing gauges along once
+    /// per `interval`, as measured by the given clock, as well as when flushed.
+    pub fn with_clock(inner: R, interval: Duration, clock: C) -> Self {
+        let interval = Interval::new(interval, clock.now());
+        LatestGauge {
+            inner,
+            gauges: RwLock::new(HashMap::new()),
+            clock,
+            interval: Some(interval),
+        }
+    }
+
+    /// Gets a reference to the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the wrapped recorder.
+    ///
+    /// Gauge updates that have not been flushed are discarded.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Recorder, C: Clock> LatestGauge<R, C> {
+    fn forward_gauges(&self) {
+        let gauges = self.gauges.read().unwrap_or_else(|e| e.into_inner());
+        for (key, latest) in gauges.iter() {
+            if latest.dirty.swap(false, Ordering::AcqRel) {
+                let value = latest.value.load(Ordering::Acquire);
+                self.inner.update_gauge(key.clone(), value);
+            }
+        }
+    }
+
+    /// Passes gauges along if there is an interval, and it has elapsed since they last were.
+    fn maybe_forward_gauges(&self) {
+        if let Some(interval) = &self.interval {
+            if interval.fire(self.clock.now()) {
+                self.forward_gauges();
+            }
+        }
+    }
+}
+
+impl<R: Recorder, C: Clock> Recorder for LatestGauge<R, C> {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.inner.increment_counter(key, value);
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        {
+            let gauges = self.gauges.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(latest) = gauges.get(&key) {
+                latest.value.store(value, Ordering::Release);
+                latest.dirty.store(true, Ordering::Release);
+                drop(gauges);
+                self.maybe_forward_gauges();
+                return;
+            }
+        }
+
+        {
+            let mut gauges = self.gauges.write().unwrap_or_else(|e| e.into_inner());
+            let latest = gauges.entry(key).or_default();
+            latest.value.store(value, Ordering::Release);
+            latest.dirty.store(true, Ordering::Release);
+        }
+        self.maybe_forward_gauges();
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.inner.record_histogram(key, value);
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        self.inner.record_histogram_many(key, value, count);
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        self.inner
+            .record_histogram_buckets(key, buckets, sum, count);
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.inner.try_record_histogram(key, value)
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        self.inner
+            .record_histogram_with_exemplar(key, value, exemplar);
+    }
+
+    fn flush(&self) {
+        self.forward_gauges();
+        self.inner.flush();
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        self.inner.counter_value(key)
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        // The latest value is held here, even if it hasn't been flushed to the wrapped recorder.
+        {
+            let gauges = self.gauges.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(latest) = gauges.get(key) {
+                return Some(latest.value.load(Ordering::Acquire));
+            }
+        }
+        self.inner.gauge_value(key)
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.inner.describe_counter(key, description);
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.inner.describe_gauge(key, description);
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.inner.describe_histogram(key, description);
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.inner.describe_with_meta(key, meta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LatestGauge;
+    use crate::{test_util::CapturingRecorder, MockClock};
+    use metrics::Recorder;
+    use metrics_core::Key;
+    use std::time::Duration;
+
+    #[test]
+    fn test_latest_gauge() {
+        let recorder = LatestGauge::new(CapturingRecorder::default());
+
+        for i in 0..1000 {
+            recorder.update_gauge(Key::from_name("queue_depth"), i);
+        }
+        recorder.update_gauge(Key::from_name("connections"), 4);
+        recorder.update_gauge(Key::from_name("connections"), -2);
+        assert!(recorder.inner().take_sorted_values().is_empty());
+
+        // Counters and histograms are not held back.
+        recorder.increment_counter(Key::from_name("requests"), 3);
+        recorder.record_histogram(Key::from_name("latency"), 42);
+        assert_eq!(
+            recorder.inner().take_sorted_values(),
+            vec![("latency".to_string(), 42), ("requests".to_string(), 3)]
+        );
+
+        recorder.flush();
+        assert_eq!(
+            recorder.inner().take_sorted_values(),
+            vec![
+                ("connections".to_string(), -2),
+                ("queue_depth".to_string(), 999)
+            ]
+        );
+
+        // Gauges are only forwarded again once they've been updated.
+        recorder.flush();
+        assert!(recorder.inner().take_sorted_values().is_empty());
+
+        recorder.update_gauge(Key::from_name("queue_depth"), 7);
+        recorder.update_gauge(Key::from_name("queue_depth"), 3);
+        recorder.flush();
+        assert_eq!(
+            recorder.inner().take_sorted_values(),
+            vec![("queue_depth".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_latest_gauge_interval() {
+        let clock = MockClock::new();
+        let recorder = LatestGauge::with_clock(
+            CapturingRecorder::default(),
+            Duration::from_secs(1),
+            clock.clone(),
+        );
+
+        recorder.update_gauge(Key::from_name("queue_depth"), 1);
+        clock.advance(Duration::from_millis(999));
+        recorder.update_gauge(Key::from_name("queue_depth"), 2);
+        assert!(recorder.inner().take_sorted_values().is_empty());
+
+        // The first update after the interval passes along the latest value of every gauge.
+        clock.advance(Duration::from_millis(1));
+        recorder.update_gauge(Key::from_name("connections"), 4);
+        assert_eq!(
+            recorder.inner().take_sorted_values(),
+            vec![
+                ("connections".to_string(), 4),
+                ("queue_depth".to_string(), 2)
+            ]
+        );
+
+        // The interval starts over from then, and flushing still passes gauges along at any time.
+        clock.advance(Duration::from_millis(500));
+        recorder.update_gauge(Key::from_name("queue_depth"), 3);
+        assert!(recorder.inner().take_sorted_values().is_empty());
+        recorder.flush();
+        assert_eq!(
+            recorder.inner().take_sorted_values(),
+            vec![("queue_depth".to_string(), 3)]
+        );
+    }
+}
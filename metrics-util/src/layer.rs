@@ -0,0 +1,166 @@
+use metrics::{Recorder, SetRecorderError};
+
+/// Wraps a recorder in another recorder.
+///
+/// A layer holds whatever configuration a recorder wrapper needs, and builds the wrapper once it
+/// is given the recorder to wrap.  This lets wrappers be composed with a [`Stack`] without having
+/// to spell out the nested types.
+///
+/// Any function or closure taking the inner recorder and returning the wrapper is a layer, so the
+/// `new` constructors of wrappers such as [`Counting`](crate::Counting) and
+/// [`LatestGauge`](crate::LatestGauge) can be used directly.
+pub trait Layer<R> {
+    /// The recorder this layer wraps the inner recorder in.
+    type Recorder;
+
+    /// Wraps the given recorder.
+    fn layer(self, inner: R) -> Self::Recorder;
+}
+
+impl<R, W, F> Layer<R> for F
+where
+    F: FnOnce(R) -> W,
+{
+    type Recorder = W;
+
+    fn layer(self, inner: R) -> W {
+        self(inner)
+    }
+}
+
+/// Builds a recorder out of layers.
+///
+/// A stack starts out with the recorder which ultimately receives every operation, and each call
+/// to [`layer`](Stack::layer) wraps everything built so far.  Operations therefore pass through
+/// the layers in the reverse of the order they were added in: the last layer added sees each
+/// operation first, and hands it on to the layer added before it.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::Key;
+/// # use metrics_util::{Counting, LatestGauge, RingBufferRecorder, Stack};
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let recorder = Stack::new(NoopRecorder)
+///     .layer(LatestGauge::new)
+///     .layer(|inner| RingBufferRecorder::new(inner, 128))
+///     .layer(Counting::new)
+///     .into_inner();
+///
+/// recorder.update_gauge(Key::from_name("connections"), 5);
+/// assert_eq!(recorder.stats(), (0, 1, 0));
+/// assert_eq!(recorder.inner().recent().len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct Stack<R> {
+    inner: R,
+}
+
+impl<R> Stack<R> {
+    /// Creates a new stack around the given recorder.
+    pub fn new(inner: R) -> Self {
+        Stack { inner }
+    }
+
+    /// Wraps the recorder built so far with the given layer.
+    pub fn layer<L>(self, layer: L) -> Stack<L::Recorder>
+    where
+        L: Layer<R>,
+    {
+        Stack {
+            inner: layer.layer(self.inner),
+        }
+    }
+
+    /// Gets a reference to the recorder built so far.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this stack, returning the recorder built so far.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Recorder + 'static> Stack<R> {
+    /// Installs the recorder built so far as the global recorder.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if a recorder has already been set.
+    pub fn install(self) -> Result<(), SetRecorderError> {
+        metrics::set_boxed_recorder(Box::new(self.inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Layer, Stack};
+    use crate::test_util::CapturingRecorder;
+    use metrics::Recorder;
+    use metrics_core::{Key, Label};
+
+    /// Applies a transformation to the key of every operation.
+    struct MapKey<R> {
+        inner: R,
+        f: Box<dyn Fn(Key) -> Key>,
+    }
+
+    impl<R: Recorder> Recorder for MapKey<R> {
+        fn increment_counter(&self, key: Key, value: u64) {
+            self.inner.increment_counter((self.f)(key), value);
+        }
+        fn update_gauge(&self, key: Key, value: i64) {
+            self.inner.update_gauge((self.f)(key), value);
+        }
+        fn record_histogram(&self, key: Key, value: u64) {
+            self.inner.record_histogram((self.f)(key), value);
+        }
+    }
+
+    struct Prefix(&'static str);
+
+    impl<R> Layer<R> for Prefix {
+        type Recorder = MapKey<R>;
+
+        fn layer(self, inner: R) -> MapKey<R> {
+            let prefix = self.0;
+            MapKey {
+                inner,
+                f: Box::new(move |key| key.map_name(|name| format!("{}.{}", prefix, name))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_stack_applies_layers_in_order() {
+        let recorder = Stack::new(CapturingRecorder::default())
+            .layer(Prefix("app"))
+            .layer(|inner| MapKey {
+                inner,
+                f: Box::new(|key: Key| {
+                    let mut key = key.map_name(|name| format!("{}_total", name));
+                    key.append_label(Label::new("env", "test"));
+                    key
+                }),
+            })
+            .into_inner();
+
+        recorder.increment_counter(Key::from_name("requests"), 1);
+
+        // The suffix layer was added last, so it sees the key before the prefix layer does.
+        assert_eq!(
+            recorder.inner.inner.take_keys(),
+            vec![Key::from_name_and_labels(
+                "app.requests_total",
+                vec![Label::new("env", "test")]
+            )]
+        );
+    }
+}
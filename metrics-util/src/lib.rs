@@ -1,13 +1,115 @@
 //! Helper types and functions used within the metrics ecosystem.
 #![deny(missing_docs)]
+mod aggregate;
+pub use aggregate::PreAggregate;
+
 mod bucket;
 pub use bucket::AtomicBucket;
 
+mod bounds;
+pub use bounds::{duration_buckets, exponential_buckets, linear_buckets, size_buckets};
+
+mod cardinality;
+pub use cardinality::CardinalityTracker;
+
+mod channel;
+pub use channel::{ChannelDrain, ChannelRecorder, OverflowPolicy};
+
+mod clock;
+pub use clock::{Clock, MockClock, SystemClock};
+
+mod counter;
+pub use counter::AtomicCounter;
+
+mod counting;
+pub use counting::Counting;
+
+mod error;
+pub use error::ExportError;
+
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "ffi")]
+pub use ffi::{metrics_install_c_recorder, CKey, CLabel, CRecorder, FfiRecorder};
+
+mod file;
+pub use file::FileRecorder;
+
+mod histogram;
+pub use histogram::AtomicHistogram;
+
+mod interner;
+pub use interner::{Interner, KeyInterner};
+
+mod latest;
+pub use latest::LatestGauge;
+
+mod layer;
+pub use layer::{Layer, Stack};
+
+mod lint;
+pub use lint::{LintingRecorder, Violation, ViolationPolicy};
+
+mod log_histogram;
+pub use log_histogram::LogHistogram;
+
+mod lru;
+pub use lru::KeyLru;
+
+mod normalize;
+pub use normalize::NormalizeLabels;
+
 mod streaming;
 pub use streaming::StreamingIntegers;
 
+mod process;
+pub use process::ProcessCollector;
+
+mod prometheus;
+pub use prometheus::PrometheusRecorder;
+
 mod quantile;
 pub use quantile::{parse_quantiles, Quantile};
 
+mod render;
+pub use render::{prometheus_lines, render_prometheus_to, PrometheusLines};
+
+mod reservoir;
+pub use reservoir::Reservoir;
+
+mod ring_buffer;
+pub use ring_buffer::RingBufferRecorder;
+
+mod selector;
+pub use selector::{Selector, SelectorError};
+
+#[cfg(feature = "async-timing")]
+mod timed;
+#[cfg(feature = "async-timing")]
+pub use timed::{timed_histogram, Timed};
+
+#[cfg(feature = "tracing-context")]
+mod tracing_context;
+#[cfg(feature = "tracing-context")]
+pub use tracing_context::{TracingContext, TracingContextLayer};
+
+mod snapshot;
+pub use snapshot::{Exemplar, HistogramSnapshot, MergeError};
+
+mod summary;
+pub use summary::SlidingSummary;
+
+mod toggle;
+pub use toggle::{ToggleRecorder, ToggleRegistry};
+
 mod tree;
-pub use tree::{Integer, MetricsTree};
+pub use tree::{load_json, Integer, MetricsTree};
+
+mod validating;
+pub use validating::{NegativePolicy, ValidatingHistogram};
+
+mod value;
+pub use value::MetricValue;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,490 @@
+use atomic_shim::AtomicU64;
+use metrics::{MetricOp, Recorder};
+use metrics_core::{Key, Label, MetricKind};
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{atomic::Ordering, Mutex, RwLock},
+};
+
+type LintFn = dyn Fn(&str) -> bool + Send + Sync;
+
+enum Rule {
+    SnakeCase,
+    Suffix(MetricKind, String),
+    Function(String, Option<MetricKind>, Box<LintFn>),
+}
+
+impl Rule {
+    fn check(&self, name: &str, kind: MetricKind) -> bool {
+        match self {
+            Rule::SnakeCase => is_snake_case(name),
+            Rule::Suffix(for_kind, suffix) => *for_kind != kind || name.ends_with(suffix.as_str()),
+            Rule::Function(_, for_kind, f) => for_kind.map_or(false, |k| k != kind) || f(name),
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            Rule::SnakeCase => "names must be snake_case".to_string(),
+            Rule::Suffix(kind, suffix) => {
+                format!("{} names must end in `{}`", kind_name(*kind), suffix)
+            }
+            Rule::Function(description, _, _) => description.clone(),
+        }
+    }
+}
+
+fn kind_name(kind: MetricKind) -> &'static str {
+    match kind {
+        MetricKind::Counter => "counter",
+        MetricKind::Gauge => "gauge",
+        MetricKind::Histogram => "histogram",
+    }
+}
+
+fn is_snake_case(name: &str) -> bool {
+    // Dots separate the parts of a name, as with `http.requests_total`, and each part must be
+    // snake_case in its own right.
+    name.split('.').all(|part| {
+        part.starts_with(|c: char| c.is_ascii_lowercase())
+            && !part.ends_with('_')
+            && !part.contains("__")
+            && part
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    })
+}
+
+/// How a [`LintingRecorder`] handles operations on metrics whose names break its rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationPolicy {
+    /// Operations are passed along as usual.
+    Allow,
+
+    /// Operations are dropped, and counted as such.
+    Reject,
+}
+
+/// A metric name which broke one of the rules of a [`LintingRecorder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    name: String,
+    kind: MetricKind,
+    rule: String,
+}
+
+impl Violation {
+    /// The name of the metric.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The kind of the metric.
+    pub fn kind(&self) -> MetricKind {
+        self.kind
+    }
+
+    /// A description of the rule that was broken.
+    pub fn rule(&self) -> &str {
+        &self.rule
+    }
+}
+
+/// A recorder wrapper that checks metric names against a naming convention.
+///
+/// Across a large codebase, metric names drift: one team writes `requests_total`, another
+/// `RequestCount`, and a third `requests.count`, and dashboards end up having to know about all
+/// of them.  `LintingRecorder` checks the name of each metric the first time it is seen, for each
+/// kind of metric, against a set of rules.  Every rule a name breaks is logged as a warning and
+/// kept as a [`Violation`], so that it can be reported, or failed on in tests.
+///
+/// With [`ViolationPolicy::Reject`], operations on metrics whose names break any rule are
+/// dropped rather than passed along to the wrapped recorder, and counted as rejected.  Only the
+/// name of a key is checked, and not its labels.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::{Key, MetricKind};
+/// # use metrics_util::{LintingRecorder, ViolationPolicy};
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let recorder = LintingRecorder::new(NoopRecorder)
+///     .with_snake_case()
+///     .with_suffix(MetricKind::Counter, "_total")
+///     .with_policy(ViolationPolicy::Reject);
+///
+/// recorder.increment_counter(Key::from_name("requests_total"), 1);
+/// recorder.increment_counter(Key::from_name("Requests"), 1);
+///
+/// let violations = recorder.violations();
+/// assert_eq!(violations.len(), 2);
+/// assert_eq!(violations[0].rule(), "names must be snake_case");
+/// assert_eq!(violations[1].rule(), "counter names must end in `_total`");
+/// assert_eq!(recorder.rejected(), 1);
+/// ```
+pub struct LintingRecorder<R> {
+    inner: R,
+    rules: Vec<Rule>,
+    policy: ViolationPolicy,
+    checked: RwLock<HashMap<MetricKind, HashMap<String, bool>>>,
+    violations: Mutex<Vec<Violation>>,
+    rejected: AtomicU64,
+}
+
+impl<R> LintingRecorder<R> {
+    /// Creates a new `LintingRecorder` around the given recorder, without any rules.
+    ///
+    /// Violations are allowed until a different policy is set with
+    /// [`with_policy`](LintingRecorder::with_policy).
+    pub fn new(inner: R) -> Self {
+        LintingRecorder {
+            inner,
+            rules: Vec::new(),
+            policy: ViolationPolicy::Allow,
+            checked: RwLock::new(HashMap::new()),
+            violations: Mutex::new(Vec::new()),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds a rule that names must be snake_case.
+    ///
+    /// A snake_case name starts with a lowercase letter, and is made up of lowercase letters,
+    /// digits and single underscores, without a trailing underscore.  Names can be split into
+    /// parts by dots, as with `http.requests_total`, in which case each part must be snake_case.
+    pub fn with_snake_case(mut self) -> Self {
+        self.rules.push(Rule::SnakeCase);
+        self
+    }
+
+    /// Adds a rule that the names of metrics of the given kind must end with the given suffix.
+    pub fn with_suffix<S>(mut self, kind: MetricKind, suffix: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.rules.push(Rule::Suffix(kind, suffix.into()));
+        self
+    }
+
+    /// Adds a rule that names must be accepted by the given function.
+    ///
+    /// The rule applies to every kind of metric if `kind` is `None`, and the description is used
+    /// when reporting violations.
+    pub fn with_fn<D, F>(mut self, description: D, kind: Option<MetricKind>, f: F) -> Self
+    where
+        D: Into<String>,
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.rules
+            .push(Rule::Function(description.into(), kind, Box::new(f)));
+        self
+    }
+
+    /// Sets how operations on metrics whose names break a rule are handled.
+    pub fn with_policy(mut self, policy: ViolationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Gets every violation found so far, in the order they were found.
+    pub fn violations(&self) -> Vec<Violation> {
+        self.violations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Gets the number of operations dropped for breaking a rule.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Gets a reference to the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the wrapped recorder.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Checks the name of the given key, returning whether or not the operation should be passed
+    /// along.
+    fn lint(&self, key: &Key, kind: MetricKind) -> bool {
+        let name = key.name();
+        let cached = {
+            let checked = self.checked.read().unwrap_or_else(|e| e.into_inner());
+            checked
+                .get(&kind)
+                .and_then(|names| names.get(name.as_ref()))
+                .cloned()
+        };
+        let conforms = match cached {
+            Some(conforms) => conforms,
+            None => {
+                let mut checked = self.checked.write().unwrap_or_else(|e| e.into_inner());
+                // Another thread may have checked the name while we were waiting for the lock.
+                let names = checked.entry(kind).or_default();
+                match names.get(name.as_ref()) {
+                    Some(conforms) => *conforms,
+                    None => {
+                        let conforms = self.check(&name, kind);
+                        names.insert(name.to_string(), conforms);
+                        conforms
+                    }
+                }
+            }
+        };
+
+        if conforms || self.policy == ViolationPolicy::Allow {
+            true
+        } else {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    fn check(&self, name: &str, kind: MetricKind) -> bool {
+        let mut conforms = true;
+        for rule in self.rules.iter().filter(|rule| !rule.check(name, kind)) {
+            let violation = Violation {
+                name: name.to_string(),
+                kind,
+                rule: rule.description(),
+            };
+            log::warn!(
+                "{} `{}` breaks a naming rule: {}",
+                kind_name(kind),
+                violation.name,
+                violation.rule
+            );
+            self.violations
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(violation);
+            conforms = false;
+        }
+        conforms
+    }
+}
+
+impl<R: Recorder> Recorder for LintingRecorder<R> {
+    fn increment_counter(&self, key: Key, value: u64) {
+        if self.lint(&key, MetricKind::Counter) {
+            self.inner.increment_counter(key, value);
+        }
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        if self.lint(&key, MetricKind::Gauge) {
+            self.inner.update_gauge(key, value);
+        }
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        if self.lint(&key, MetricKind::Histogram) {
+            self.inner.record_histogram(key, value);
+        }
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        if self.lint(&key, MetricKind::Histogram) {
+            self.inner.record_histogram_many(key, value, count);
+        }
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        if self.lint(&key, MetricKind::Histogram) {
+            self.inner
+                .record_histogram_buckets(key, buckets, sum, count);
+        }
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.lint(&key, MetricKind::Histogram) && self.inner.try_record_histogram(key, value)
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        if self.lint(&key, MetricKind::Histogram) {
+            self.inner
+                .record_histogram_with_exemplar(key, value, exemplar);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        self.inner.counter_value(key)
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        self.inner.gauge_value(key)
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        if self.lint(&key, MetricKind::Counter) {
+            self.inner.describe_counter(key, description);
+        }
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        if self.lint(&key, MetricKind::Gauge) {
+            self.inner.describe_gauge(key, description);
+        }
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        if self.lint(&key, MetricKind::Histogram) {
+            self.inner.describe_histogram(key, description);
+        }
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.inner.describe_with_meta(key, meta);
+    }
+
+    fn record_batch(&self, ops: &[MetricOp]) {
+        let allowed = ops
+            .iter()
+            .map(|op| match op {
+                MetricOp::IncrementCounter(key, _) => self.lint(key, MetricKind::Counter),
+                MetricOp::UpdateGauge(key, _) => self.lint(key, MetricKind::Gauge),
+                MetricOp::RecordHistogram(key, _) => self.lint(key, MetricKind::Histogram),
+            })
+            .collect::<Vec<_>>();
+
+        if allowed.iter().all(|allowed| *allowed) {
+            self.inner.record_batch(ops);
+        } else {
+            let ops = ops
+                .iter()
+                .zip(allowed)
+                .filter(|(_, allowed)| *allowed)
+                .map(|(op, _)| op.clone())
+                .collect::<Vec<_>>();
+            if !ops.is_empty() {
+                self.inner.record_batch(&ops);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_snake_case, LintingRecorder, ViolationPolicy};
+    use crate::test_util::CapturingRecorder;
+    use metrics::{MetricOp, Recorder};
+    use metrics_core::{Key, MetricKind};
+
+    fn recorder(policy: ViolationPolicy) -> LintingRecorder<CapturingRecorder> {
+        LintingRecorder::new(CapturingRecorder::default())
+            .with_snake_case()
+            .with_suffix(MetricKind::Counter, "_total")
+            .with_fn("names must not start with `tmp`", None, |name| {
+                !name.starts_with("tmp")
+            })
+            .with_policy(policy)
+    }
+
+    #[test]
+    fn test_linting_recorder_allow() {
+        let recorder = recorder(ViolationPolicy::Allow);
+        recorder.increment_counter(Key::from_name("requests_total"), 1);
+        recorder.increment_counter(Key::from_name("requestCount"), 1);
+        recorder.increment_counter(Key::from_name("requestCount"), 2);
+        recorder.update_gauge(Key::from_name("connections"), 3);
+        recorder.record_histogram(Key::from_name("tmp_latency"), 4);
+
+        // Each name is only checked once per kind, and the suffix only applies to counters.
+        let violations = recorder
+            .violations()
+            .iter()
+            .map(|v| (v.name().to_string(), v.kind(), v.rule().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            violations,
+            vec![
+                (
+                    "requestCount".to_string(),
+                    MetricKind::Counter,
+                    "names must be snake_case".to_string()
+                ),
+                (
+                    "requestCount".to_string(),
+                    MetricKind::Counter,
+                    "counter names must end in `_total`".to_string()
+                ),
+                (
+                    "tmp_latency".to_string(),
+                    MetricKind::Histogram,
+                    "names must not start with `tmp`".to_string()
+                ),
+            ]
+        );
+
+        // Everything is still passed along.
+        assert_eq!(recorder.rejected(), 0);
+        assert_eq!(recorder.inner().take_ops().len(), 5);
+    }
+
+    #[test]
+    fn test_linting_recorder_reject() {
+        let recorder = recorder(ViolationPolicy::Reject);
+        recorder.increment_counter(Key::from_name("requests"), 1);
+        recorder.increment_counter(Key::from_name("requests_total"), 1);
+        recorder.update_gauge(Key::from_name("open__files"), 2);
+        recorder.record_batch(&[
+            MetricOp::IncrementCounter(Key::from_name("requests"), 1),
+            MetricOp::UpdateGauge(Key::from_name("connections"), 3),
+        ]);
+
+        assert_eq!(recorder.violations().len(), 2);
+        assert_eq!(recorder.rejected(), 3);
+        assert_eq!(
+            recorder.inner().take_ops(),
+            vec![
+                MetricOp::IncrementCounter(Key::from_name("requests_total"), 1),
+                MetricOp::UpdateGauge(Key::from_name("connections"), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snake_case() {
+        for name in &[
+            "requests",
+            "http_requests_total",
+            "http.requests_total",
+            "p99",
+            "a.b.c",
+        ] {
+            assert!(is_snake_case(name), "{} should be snake_case", name);
+        }
+        for name in &[
+            "",
+            "Requests",
+            "requestCount",
+            "_requests",
+            "requests_",
+            "open__files",
+            "9lives",
+            ".requests",
+            "requests.",
+            "http..requests",
+            "http._requests",
+            "http.Requests",
+            "http-requests",
+        ] {
+            assert!(!is_snake_case(name), "{} shouldn't be snake_case", name);
+        }
+    }
+}
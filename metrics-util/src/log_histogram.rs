@@ -0,0 +1,335 @@
+use std::cmp;
+
+/// A histogram which stores values with a fixed relative precision, in bounded memory.
+///
+/// Values which span many orders of magnitude, such as latencies anywhere from microseconds to
+/// seconds, are poorly served by fixed buckets: buckets narrow enough for the fast end waste
+/// memory at the slow end, and buckets wide enough for the slow end hide everything at the fast
+/// end.  `LogHistogram` uses the log-linear layout of [HdrHistogram]: values are split into
+/// buckets by powers of two, and each of those is split into equally sized sub-buckets, so every
+/// value is stored to within the configured number of significant decimal digits of precision,
+/// whatever its magnitude.
+///
+/// Memory use depends only on the precision and on the highest value to track, and not on the
+/// number of values recorded.  Tracking values up to an hour in nanoseconds with three
+/// significant digits takes a few hundred kilobytes.  Values above the highest trackable value
+/// are recorded as the highest trackable value.
+///
+/// The minimum and maximum values are tracked exactly, and quantiles are calculated to within
+/// the configured precision.
+///
+/// [HdrHistogram]: http://hdrhistogram.org/
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::LogHistogram;
+/// // Latencies of up to a minute, in microseconds, with three significant digits.
+/// let mut histogram = LogHistogram::new(60_000_000, 3);
+/// for latency in &[150, 2_300, 45_000, 1_200_000] {
+///     histogram.record(*latency);
+/// }
+///
+/// assert_eq!(histogram.count(), 4);
+/// assert_eq!(histogram.quantile(0.0), Some(150));
+/// // Within three significant digits of the actual median, 2,300.
+/// assert_eq!(histogram.quantile(0.5), Some(2_301));
+/// assert_eq!(histogram.quantile(1.0), Some(1_200_000));
+/// ```
+#[derive(Debug, Clone)]
+pub struct LogHistogram {
+    counts: Vec<u64>,
+    highest_trackable: u64,
+    significant_digits: u8,
+    sub_bucket_half_count_magnitude: u32,
+    sub_bucket_half_count: usize,
+    sub_bucket_mask: u64,
+    count: u64,
+    min: u64,
+    max: u64,
+}
+
+impl LogHistogram {
+    /// Creates a new, empty histogram which tracks values from zero up to `highest_trackable`,
+    /// keeping `significant_digits` significant decimal digits of precision.
+    ///
+    /// # Panics
+    /// Panics if `significant_digits` is not between 1 and 5, or if `highest_trackable` is less
+    /// than 2.
+    pub fn new(highest_trackable: u64, significant_digits: u8) -> Self {
+        assert!(
+            (1..=5).contains(&significant_digits),
+            "significant digits must be between 1 and 5"
+        );
+        assert!(
+            highest_trackable >= 2,
+            "highest trackable value must be at least 2"
+        );
+
+        // Enough sub-buckets that adjacent values at the top of each bucket differ by no more
+        // than one part in 10^digits.
+        let largest_single_unit = 2 * 10u64.pow(u32::from(significant_digits));
+        let sub_bucket_count = largest_single_unit.next_power_of_two();
+        let sub_bucket_half_count_magnitude = sub_bucket_count.trailing_zeros() - 1;
+        let sub_bucket_half_count = (sub_bucket_count / 2) as usize;
+
+        // Every bucket after the first covers twice the range of the one before it.
+        let mut bucket_count = 1;
+        let mut smallest_untrackable = sub_bucket_count;
+        while smallest_untrackable <= highest_trackable {
+            bucket_count += 1;
+            if smallest_untrackable > u64::max_value() / 2 {
+                break;
+            }
+            smallest_untrackable <<= 1;
+        }
+
+        LogHistogram {
+            counts: vec![0; (bucket_count + 1) * sub_bucket_half_count],
+            highest_trackable,
+            significant_digits,
+            sub_bucket_half_count_magnitude,
+            sub_bucket_half_count,
+            sub_bucket_mask: sub_bucket_count - 1,
+            count: 0,
+            min: u64::max_value(),
+            max: 0,
+        }
+    }
+
+    /// Records a value.
+    pub fn record(&mut self, value: u64) {
+        self.record_many(value, 1);
+    }
+
+    /// Records a value as if it had been recorded `count` times.
+    pub fn record_many(&mut self, value: u64, count: u64) {
+        if count == 0 {
+            return;
+        }
+
+        let value = cmp::min(value, self.highest_trackable);
+        let index = self.index_of(value);
+        self.counts[index] = self.counts[index].saturating_add(count);
+        self.count = self.count.saturating_add(count);
+        self.min = cmp::min(self.min, value);
+        self.max = cmp::max(self.max, value);
+    }
+
+    /// Calculates the value at the given quantile.
+    ///
+    /// The quantile is clamped between 0.0 and 1.0.  The value returned is the highest value that
+    /// is stored the same as the actual value at the quantile, so it is within the configured
+    /// precision of it, but the minimum and maximum values are returned exactly.  Returns `None`
+    /// if no values have been recorded.
+    pub fn quantile(&self, quantile: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let quantile = quantile.max(0.0);
+        let quantile = quantile.min(1.0);
+        let target = cmp::max((quantile * self.count as f64).ceil() as u64, 1);
+
+        let mut seen = 0u64;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen = seen.saturating_add(*count);
+            if seen >= target {
+                let (lowest, size) = self.range_of(index);
+                let highest = lowest.saturating_add(size - 1);
+                return Some(cmp::max(cmp::min(highest, self.max), self.min));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Iterates over the recorded values, as pairs of a value and the number of times it was
+    /// recorded, in ascending order of value.
+    ///
+    /// Values stored the same are yielded once, as the highest value stored that way, so each is
+    /// within the configured precision of the values it stands for.  As with
+    /// [`quantile`](LogHistogram::quantile), the minimum and maximum values are yielded exactly.
+    pub fn iter_recorded(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(move |(index, count)| {
+                let (lowest, size) = self.range_of(index);
+                let highest = lowest.saturating_add(size - 1);
+                (cmp::max(cmp::min(highest, self.max), self.min), *count)
+            })
+    }
+
+    /// Number of values recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Whether or not no values have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Smallest value recorded, if any.
+    pub fn min(&self) -> Option<u64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.min)
+        }
+    }
+
+    /// Largest value recorded, if any.
+    pub fn max(&self) -> Option<u64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.max)
+        }
+    }
+
+    /// Highest value that can be tracked without being clamped.
+    pub fn highest_trackable(&self) -> u64 {
+        self.highest_trackable
+    }
+
+    /// Number of significant decimal digits of precision values are kept to.
+    pub fn significant_digits(&self) -> u8 {
+        self.significant_digits
+    }
+
+    /// Clears the histogram.
+    pub fn clear(&mut self) {
+        for count in self.counts.iter_mut() {
+            *count = 0;
+        }
+        self.count = 0;
+        self.min = u64::max_value();
+        self.max = 0;
+    }
+
+    fn index_of(&self, value: u64) -> usize {
+        // The bucket is the power of two the value falls under, counting the first bucket as
+        // covering everything below the sub-bucket count.
+        let leading_zero_count_base = 64 - self.sub_bucket_half_count_magnitude - 1;
+        let bucket = leading_zero_count_base - (value | self.sub_bucket_mask).leading_zeros();
+        let sub_bucket = (value >> bucket) as usize;
+
+        // Only the top half of each bucket's sub-buckets is stored for every bucket after the
+        // first, as the bottom half overlaps with the bucket before it.
+        ((bucket as usize + 1) << self.sub_bucket_half_count_magnitude) + sub_bucket
+            - self.sub_bucket_half_count
+    }
+
+    /// Gets the lowest value stored at the given index, and how many values share the index.
+    fn range_of(&self, index: usize) -> (u64, u64) {
+        let mut bucket = (index >> self.sub_bucket_half_count_magnitude) as i64 - 1;
+        let mut sub_bucket =
+            (index & (self.sub_bucket_half_count - 1)) + self.sub_bucket_half_count;
+        if bucket < 0 {
+            sub_bucket -= self.sub_bucket_half_count;
+            bucket = 0;
+        }
+        ((sub_bucket as u64) << bucket, 1u64 << bucket)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogHistogram;
+
+    fn exact_quantile(sorted: &[u64], quantile: f64) -> u64 {
+        let target = ((quantile * sorted.len() as f64).ceil() as usize).max(1);
+        sorted[target - 1]
+    }
+
+    #[test]
+    fn test_log_histogram_accuracy() {
+        for digits in 1..=4 {
+            let mut histogram = LogHistogram::new(3_600_000_000_000, digits);
+
+            // Values spread over eleven decades, from a nanosecond up to nearly two minutes.
+            let mut values = Vec::new();
+            let mut value = 1.0f64;
+            while value < 1.0e11 {
+                values.push(value as u64);
+                value *= 1.037;
+            }
+            for value in &values {
+                histogram.record(*value);
+            }
+            values.sort_unstable();
+
+            let precision = 10f64.powi(-i32::from(digits));
+            for quantile in &[0.0, 0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99, 0.999, 1.0] {
+                let expected = exact_quantile(&values, *quantile);
+                let actual = histogram.quantile(*quantile).unwrap();
+                let error = (actual as f64 - expected as f64).abs() / expected as f64;
+                assert!(
+                    error <= precision,
+                    "q{} with {} digits: expected {}, got {}",
+                    quantile,
+                    digits,
+                    expected,
+                    actual
+                );
+            }
+
+            assert_eq!(histogram.count(), values.len() as u64);
+            assert_eq!(histogram.min(), values.first().cloned());
+            assert_eq!(histogram.max(), values.last().cloned());
+            assert_eq!(histogram.quantile(0.0), histogram.min());
+            assert_eq!(histogram.quantile(1.0), histogram.max());
+        }
+    }
+
+    #[test]
+    fn test_log_histogram_small_values_exact() {
+        // Values below the sub-bucket count are stored exactly.
+        let mut histogram = LogHistogram::new(1_000, 2);
+        for value in 0..100 {
+            histogram.record(value);
+        }
+        for value in 0..100u64 {
+            let quantile = (value as f64 + 0.5) / 100.0;
+            assert_eq!(histogram.quantile(quantile), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_log_histogram_clamping_and_clear() {
+        let mut histogram = LogHistogram::new(10_000, 3);
+        assert!(histogram.is_empty());
+        assert_eq!(histogram.quantile(0.5), None);
+        assert_eq!(histogram.min(), None);
+
+        histogram.record_many(u64::max_value(), 3);
+        histogram.record_many(5, 0);
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.max(), Some(10_000));
+        assert_eq!(histogram.quantile(0.5), Some(10_000));
+        assert_eq!(histogram.highest_trackable(), 10_000);
+        assert_eq!(histogram.significant_digits(), 3);
+
+        histogram.clear();
+        assert!(histogram.is_empty());
+        assert_eq!(histogram.quantile(1.0), None);
+        histogram.record(7);
+        assert_eq!(histogram.quantile(0.5), Some(7));
+    }
+
+    #[test]
+    fn test_log_histogram_full_range() {
+        let mut histogram = LogHistogram::new(u64::max_value(), 3);
+        histogram.record(u64::max_value());
+        histogram.record(1);
+        assert_eq!(histogram.quantile(0.0), Some(1));
+        assert_eq!(histogram.quantile(1.0), Some(u64::max_value()));
+    }
+
+    #[test]
+    #[should_panic(expected = "significant digits must be between 1 and 5")]
+    fn test_log_histogram_invalid_digits() {
+        let _ = LogHistogram::new(1_000, 6);
+    }
+}
@@ -0,0 +1,333 @@
+use crate::{Clock, SystemClock};
+use metrics_core::Key;
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+/// A map which tracks the order its entries were last used in, so that the least recently used
+/// entry can be evicted.
+///
+/// This holds the bookkeeping shared by [`KeyLru`] and the interners, which each decide for
+/// themselves when to evict.
+#[derive(Debug)]
+pub(crate) struct Lru<K, V> {
+    // Maps each key to the tick it was last used at, and its value.
+    entries: HashMap<K, (u64, V)>,
+    // Maps each tick to the key that was used at it, in least to most recently used order.
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> Lru<K, V> {
+    pub(crate) fn new() -> Self {
+        Lru {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Gets the entry for the given key, marking it as the most recently used.
+    pub(crate) fn get<Q>(&mut self, key: &Q) -> Option<(&K, &mut V)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let (used, value) = self.entries.get_mut(key)?;
+        let stored = self
+            .recency
+            .remove(used)
+            .expect("cached key missing from recency list");
+
+        self.tick += 1;
+        *used = self.tick;
+        self.recency.insert(self.tick, stored);
+        Some((&self.recency[&self.tick], value))
+    }
+
+    /// Inserts an entry for a key which isn't held yet, as the most recently used, evicting the
+    /// least recently used entry first if there are already `capacity` entries.
+    pub(crate) fn insert(&mut self, key: K, value: V, capacity: usize) {
+        if self.entries.len() >= capacity {
+            self.pop_oldest();
+        }
+
+        self.tick += 1;
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, (self.tick, value));
+    }
+
+    /// Gets the least recently used entry, without marking it as used.
+    pub(crate) fn peek_oldest(&self) -> Option<(&K, &V)> {
+        let key = self.recency.values().next()?;
+        Some((key, &self.entries[key].1))
+    }
+
+    /// Removes the least recently used entry.
+    pub(crate) fn pop_oldest(&mut self) -> Option<(K, V)> {
+        let oldest = *self.recency.keys().next()?;
+        let key = self.recency.remove(&oldest)?;
+        let (_, value) = self.entries.remove(&key)?;
+        Some((key, value))
+    }
+
+    pub(crate) fn contains<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.entries.contains_key(key)
+    }
+
+    pub(crate) fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let (used, value) = self.entries.remove(key)?;
+        self.recency.remove(&used);
+        Some(value)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// A bounded cache of values by [`Key`].
+///
+/// Recorders and layers often keep some state for every key they've seen, such as a rendered
+/// name or the last value sent upstream.  With label values that churn, such as connection IDs,
+/// a plain map of that state grows forever.  `KeyLru` holds at most `capacity` entries, and
+/// evicts the least recently used entry when a new key is inserted while full.
+///
+/// Looking up an entry counts as using it.  As the cache needs mutable access to track recency,
+/// wrap it in a lock to share it between threads.
+///
+/// A cache created with [`with_ttl`](KeyLru::with_ttl) also expires entries which haven't been
+/// used for the given time to live, so that state for keys which have gone quiet is dropped even
+/// while the cache isn't full.  Times are taken from the system clock by default, or from any other
+/// [`Clock`] given to [`with_clock`](KeyLru::with_clock).
+///
+/// # Examples
+/// ```rust
+/// # use metrics_core::{Key, Label};
+/// # use metrics_util::KeyLru;
+/// let mut names = KeyLru::with_capacity(1024);
+/// let key = Key::from_name_and_labels("requests", vec![Label::new("code", "200")]);
+///
+/// let name = names.get_or_insert_with(&key, || format!("{}", key.name()));
+/// assert_eq!(name, "requests");
+/// assert_eq!(names.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct KeyLru<V, C = SystemClock> {
+    // Maps each key to the time it was last used at, and its value.
+    entries: Lru<Key, (Instant, V)>,
+    capacity: usize,
+    ttl: Option<Duration>,
+    clock: C,
+}
+
+impl<V> KeyLru<V> {
+    /// Creates a new, empty cache that holds at most `capacity` entries.
+    ///
+    /// A capacity of zero is treated as one, as the cache must hold on to an inserted value to be
+    /// able to hand out a reference to it.
+    pub fn with_capacity(capacity: usize) -> Self {
+        KeyLru::new(capacity, None, SystemClock)
+    }
+
+    /// Creates a new, empty cache that holds at most `capacity` entries, each for at most `ttl`
+    /// since it was last used.
+    pub fn with_ttl(capacity: usize, ttl: Duration) -> Self {
+        KeyLru::new(capacity, Some(ttl), SystemClock)
+    }
+}
+
+impl<V, C: Clock> KeyLru<V, C> {
+    /// Creates a new, empty cache that holds at most `capacity` entries, each for at most `ttl`
+    /// since it was last used, as measured by the given clock.
+    pub fn with_clock(capacity: usize, ttl: Duration, clock: C) -> Self {
+        KeyLru::new(capacity, Some(ttl), clock)
+    }
+
+    fn new(capacity: usize, ttl: Option<Duration>, clock: C) -> Self {
+        KeyLru {
+            entries: Lru::new(),
+            capacity: if capacity == 0 { 1 } else { capacity },
+            ttl,
+            clock,
+        }
+    }
+
+    /// Gets the value for the given key, marking it as the most recently used.
+    pub fn get(&mut self, key: &Key) -> Option<&mut V> {
+        let now = self.clock.now();
+        self.expire_at(now);
+
+        self.entries.get(key).map(|(_, (used_at, value))| {
+            *used_at = now;
+            value
+        })
+    }
+
+    /// Removes every entry which hasn't been used for longer than the time to live, if there is
+    /// one.
+    ///
+    /// Expired entries are also removed whenever the cache is used, so this only needs to be
+    /// called to release their memory sooner.
+    pub fn expire(&mut self) {
+        let now = self.clock.now();
+        self.expire_at(now);
+    }
+
+    fn expire_at(&mut self, now: Instant) {
+        let ttl = match self.ttl {
+            Some(ttl) => ttl,
+            None => return,
+        };
+
+        // Entries are used in order, so the least recently used entry was also used the longest
+        // time ago.
+        while let Some((_, (used_at, _))) = self.entries.peek_oldest() {
+            if now.duration_since(*used_at) <= ttl {
+                return;
+            }
+            self.entries.pop_oldest();
+        }
+    }
+
+    /// Gets the value for the given key, marking it as the most recently used, or inserts the value
+    /// returned by `f` if there is none.
+    ///
+    /// If the cache is full, inserting evicts the least recently used entry first.
+    pub fn get_or_insert_with<F>(&mut self, key: &Key, f: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        self.expire();
+        if !self.entries.contains(key) {
+            let value = f();
+            let now = self.clock.now();
+            self.entries
+                .insert(key.clone(), (now, value), self.capacity);
+        }
+
+        self.get(key).expect("inserted key missing from cache")
+    }
+
+    /// Removes the entry for the given key, returning its value if there was one.
+    pub fn remove(&mut self, key: &Key) -> Option<V> {
+        self.entries.remove(key).map(|(_, value)| value)
+    }
+
+    /// Removes every entry.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Maximum number of entries held by this cache.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of entries currently held by this cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether or not this cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeyLru;
+    use crate::MockClock;
+    use metrics_core::{Key, Label};
+    use std::time::Duration;
+
+    #[test]
+    fn test_key_lru_evicts_least_recently_used() {
+        let mut lru = KeyLru::with_capacity(2);
+        let key = |code: &'static str| {
+            Key::from_name_and_labels("requests", vec![Label::new("code", code)])
+        };
+
+        assert_eq!(*lru.get_or_insert_with(&key("200"), || 1), 1);
+        assert_eq!(*lru.get_or_insert_with(&key("404"), || 2), 2);
+        // Existing entries are returned as they are.
+        *lru.get_or_insert_with(&key("200"), || 10) += 1;
+        assert_eq!(lru.get(&key("200")), Some(&mut 2));
+        assert_eq!(lru.len(), 2);
+
+        // "404" is now the least recently used, so it's evicted first.
+        assert_eq!(*lru.get_or_insert_with(&key("500"), || 3), 3);
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.get(&key("404")), None);
+
+        // Looking up "200" makes "500" the least recently used.
+        assert!(lru.get(&key("200")).is_some());
+        lru.get_or_insert_with(&key("503"), || 4);
+        assert_eq!(lru.get(&key("500")), None);
+        assert_eq!(lru.get(&key("200")), Some(&mut 2));
+
+        assert_eq!(lru.remove(&key("503")), Some(4));
+        assert_eq!(lru.remove(&key("503")), None);
+        lru.get_or_insert_with(&key("404"), || 5);
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.get(&key("200")), Some(&mut 2));
+
+        lru.clear();
+        assert!(lru.is_empty());
+    }
+
+    #[test]
+    fn test_key_lru_zero_capacity() {
+        let mut lru = KeyLru::with_capacity(0);
+        assert_eq!(lru.capacity(), 1);
+
+        lru.get_or_insert_with(&Key::from_name("a"), || 1);
+        lru.get_or_insert_with(&Key::from_name("b"), || 2);
+        assert_eq!(lru.len(), 1);
+        assert_eq!(lru.get(&Key::from_name("b")), Some(&mut 2));
+    }
+
+    #[test]
+    fn test_key_lru_ttl() {
+        let clock = MockClock::new();
+        let mut lru = KeyLru::with_clock(16, Duration::from_secs(10), clock.clone());
+
+        lru.get_or_insert_with(&Key::from_name("a"), || 1);
+        clock.advance(Duration::from_secs(6));
+        lru.get_or_insert_with(&Key::from_name("b"), || 2);
+        clock.advance(Duration::from_secs(4));
+
+        // "a" was last used exactly 10 seconds ago, so it's only expired from here on.
+        assert_eq!(lru.len(), 2);
+        clock.advance(Duration::from_secs(1));
+        lru.expire();
+        assert_eq!(lru.len(), 1);
+        assert_eq!(lru.get(&Key::from_name("a")), None);
+
+        // Using an entry keeps it alive for another time to live.
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(lru.get(&Key::from_name("b")), Some(&mut 2));
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(lru.get(&Key::from_name("b")), Some(&mut 2));
+        clock.advance(Duration::from_secs(11));
+        assert_eq!(*lru.get_or_insert_with(&Key::from_name("b"), || 3), 3);
+        assert_eq!(lru.len(), 1);
+    }
+}
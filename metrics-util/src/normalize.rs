@@ -0,0 +1,297 @@
+use metrics::Recorder;
+use metrics_core::{Key, Label, ScopedString};
+use std::{any::Any, collections::HashMap};
+
+type NormalizeFn = dyn Fn(&str) -> Option<ScopedString> + Send + Sync;
+
+enum Rule {
+    Pattern(String, ScopedString),
+    Function(Box<NormalizeFn>),
+}
+
+impl Rule {
+    fn apply(&self, label: &Label) -> Option<ScopedString> {
+        match self {
+            Rule::Pattern(pattern, replacement) => {
+                if label.value_matches_glob(pattern) {
+                    Some(replacement.clone())
+                } else {
+                    None
+                }
+            }
+            Rule::Function(f) => f(label.value()),
+        }
+    }
+}
+
+/// A recorder wrapper that rewrites label values into canonical values.
+///
+/// Label values such as status codes or user IDs can take far more distinct values than anyone
+/// wants to look at, and every distinct value makes for another series in the backend.
+/// `NormalizeLabels` rewrites the values of chosen labels according to a set of rules before
+/// passing each operation along to the wrapped recorder, so that, for example, every `5xx` status
+/// code ends up as `5xx`.
+///
+/// Rules are added per label key, either as a glob pattern and the value to replace matching
+/// values with, or as a function which returns the replacement value, if any.  As rules are plain
+/// data, they can be built from configuration loaded at runtime.  The rules for each label are
+/// tried in the order they were added, and the first one that matches wins.  Values which no rule
+/// matches are left as they are, as are the names of keys.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::{Key, Label};
+/// # use metrics_util::NormalizeLabels;
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let recorder = NormalizeLabels::new(NoopRecorder)
+///     .with_pattern("code", "5??", "5xx")
+///     .with_pattern("code", "4??", "4xx")
+///     .with_fn("path", |path| {
+///         if path.starts_with("/users/") {
+///             Some("/users/:id".into())
+///         } else {
+///             None
+///         }
+///     });
+///
+/// let key = Key::from_name_and_labels("requests", vec![Label::new("code", "503")]);
+/// assert_eq!(
+///     recorder.normalize(key),
+///     Key::from_name_and_labels("requests", vec![Label::new("code", "5xx")])
+/// );
+/// ```
+pub struct NormalizeLabels<R> {
+    inner: R,
+    rules: HashMap<String, Vec<Rule>>,
+}
+
+impl<R> NormalizeLabels<R> {
+    /// Creates a new `NormalizeLabels` wrapper around the given recorder, without any rules.
+    pub fn new(inner: R) -> Self {
+        NormalizeLabels {
+            inner,
+            rules: HashMap::new(),
+        }
+    }
+
+    /// Adds a rule replacing values of the given label which match the given glob pattern.
+    ///
+    /// Patterns use the syntax of [`Label::value_matches_glob`].
+    pub fn with_pattern<K, P, V>(mut self, label: K, pattern: P, replacement: V) -> Self
+    where
+        K: Into<String>,
+        P: Into<String>,
+        V: Into<ScopedString>,
+    {
+        self.rules
+            .entry(label.into())
+            .or_default()
+            .push(Rule::Pattern(pattern.into(), replacement.into()));
+        self
+    }
+
+    /// Adds a rule replacing values of the given label with whatever the given function returns
+    /// for them.
+    ///
+    /// The function returns `None` for values that it leaves to later rules.
+    pub fn with_fn<K, F>(mut self, label: K, f: F) -> Self
+    where
+        K: Into<String>,
+        F: Fn(&str) -> Option<ScopedString> + Send + Sync + 'static,
+    {
+        self.rules
+            .entry(label.into())
+            .or_default()
+            .push(Rule::Function(Box::new(f)));
+        self
+    }
+
+    /// Applies the rules to the labels of the given key.
+    ///
+    /// Keys without any labels that have rules are returned as they are.
+    pub fn normalize(&self, key: Key) -> Key {
+        // Functions can be expensive, so every rule is only applied once, and the key is only
+        // taken apart if some label is actually replaced.
+        let replacements = key
+            .labels()
+            .map(|label| {
+                self.rules
+                    .get(label.key())
+                    .and_then(|rules| rules.iter().filter_map(|rule| rule.apply(label)).next())
+            })
+            .collect::<Vec<_>>();
+        if replacements.iter().all(Option::is_none) {
+            return key;
+        }
+
+        let (name, labels) = key.into_parts();
+        let labels = labels
+            .into_iter()
+            .zip(replacements)
+            .map(|(label, replacement)| match replacement {
+                Some(value) => {
+                    let (key, _) = label.into_parts();
+                    Label::new(key, value)
+                }
+                None => label,
+            })
+            .collect();
+        Key::from_parts(name, labels)
+    }
+
+    /// Gets a reference to the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the wrapped recorder.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Recorder> Recorder for NormalizeLabels<R> {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.inner.increment_counter(self.normalize(key), value);
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.inner.update_gauge(self.normalize(key), value);
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.inner.record_histogram(self.normalize(key), value);
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        self.inner
+            .record_histogram_many(self.normalize(key), value, count);
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        self.inner
+            .record_histogram_buckets(self.normalize(key), buckets, sum, count);
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.inner.try_record_histogram(self.normalize(key), value)
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        self.inner
+            .record_histogram_with_exemplar(self.normalize(key), value, exemplar);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        self.inner.counter_value(&self.normalize(key.clone()))
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        self.inner.gauge_value(&self.normalize(key.clone()))
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.inner
+            .describe_counter(self.normalize(key), description);
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.inner.describe_gauge(self.normalize(key), description);
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.inner
+            .describe_histogram(self.normalize(key), description);
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.inner.describe_with_meta(self.normalize(key), meta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NormalizeLabels;
+    use crate::test_util::CapturingRecorder;
+    use metrics::Recorder;
+    use metrics_core::{Key, Label};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    fn key(code: &'static str, user: &'static str) -> Key {
+        Key::from_name_and_labels(
+            "requests",
+            vec![Label::new("code", code), Label::new("user", user)],
+        )
+    }
+
+    #[test]
+    fn test_normalize_labels() {
+        let recorder = NormalizeLabels::new(CapturingRecorder::default())
+            .with_pattern("code", "5??", "5xx")
+            .with_pattern("code", "*", "other")
+            .with_fn("user", |user| {
+                if user == "admin" {
+                    None
+                } else {
+                    Some("user".into())
+                }
+            });
+
+        for code in &["500", "502", "503"] {
+            recorder.increment_counter(key(code, "admin"), 1);
+        }
+        recorder.update_gauge(key("404", "1234"), 1);
+        recorder.record_histogram(Key::from_name("latency"), 1);
+
+        assert_eq!(
+            recorder.inner().take_keys(),
+            vec![
+                key("5xx", "admin"),
+                key("5xx", "admin"),
+                key("5xx", "admin"),
+                key("other", "user"),
+                Key::from_name("latency"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_labels_untouched() {
+        let recorder =
+            NormalizeLabels::new(CapturingRecorder::default()).with_pattern("code", "5??", "5xx");
+
+        // Values which no rule matches, and labels without rules, are left as they are.
+        let untouched = key("200", "1234");
+        assert_eq!(recorder.normalize(untouched.clone()), untouched);
+
+        let unlabeled = Key::from_name("requests");
+        assert_eq!(recorder.normalize(unlabeled.clone()), unlabeled);
+    }
+
+    #[test]
+    fn test_normalize_labels_applies_rules_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let recorder =
+            NormalizeLabels::new(CapturingRecorder::default()).with_fn("user", move |_| {
+                counted.fetch_add(1, Ordering::SeqCst);
+                Some("user".into())
+            });
+
+        assert_eq!(recorder.normalize(key("200", "1234")), key("200", "user"));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
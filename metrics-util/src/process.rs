@@ -0,0 +1,190 @@
+use metrics::Recorder;
+use metrics_core::Key;
+
+/// Number of clock ticks per second used by `/proc`.
+///
+/// This is `USER_HZ`, which is fixed at 100 on every mainstream Linux architecture.
+#[cfg(target_os = "linux")]
+const TICKS_PER_SECOND: u64 = 100;
+
+/// Collects metrics about the current process.
+///
+/// Nearly every application wants to export the same handful of process metrics, such as memory
+/// usage and open file descriptors.  `ProcessCollector` gathers them and records them as gauges,
+/// using the names conventionally used by Prometheus client libraries, except for CPU time, which
+/// is recorded in milliseconds rather than fractional seconds as gauges only hold integers:
+///
+/// - `process_cpu_milliseconds_total`: user and system CPU time spent, in milliseconds
+/// - `process_resident_memory_bytes`: resident memory size, in bytes
+/// - `process_virtual_memory_bytes`: virtual memory size, in bytes
+/// - `process_open_fds`: number of open file descriptors
+/// - `process_max_fds`: maximum number of open file descriptors, unless unlimited
+/// - `process_threads`: number of threads
+/// - `process_start_time_seconds`: start time of the process since the UNIX epoch, in seconds
+///
+/// Metrics are only collected when [`collect`](ProcessCollector::collect) is called, so callers
+/// should call it on a timer, or just before metrics are exported.  Stats are currently read from
+/// `/proc`, so only Linux is supported, and collecting does nothing on other platforms.  Any stat
+/// that can't be read is skipped.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::ProcessCollector;
+/// let collector = ProcessCollector::new();
+///
+/// // Records the process metrics through the global recorder, if one is installed.
+/// collector.collect();
+/// ```
+#[derive(Debug, Default)]
+pub struct ProcessCollector {
+    _private: (),
+}
+
+impl ProcessCollector {
+    /// Creates a new `ProcessCollector`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects process metrics, recording them through the global recorder.
+    ///
+    /// If no recorder is installed, nothing is collected.
+    pub fn collect(&self) {
+        if let Some(recorder) = metrics::try_recorder() {
+            self.collect_into(recorder);
+        }
+    }
+
+    /// Collects process metrics, recording them through the given recorder.
+    pub fn collect_into(&self, recorder: &dyn Recorder) {
+        for (name, value) in read_stats() {
+            recorder.update_gauge(Key::from_name(name), value);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_stats() -> Vec<(&'static str, i64)> {
+    use std::fs;
+
+    let mut stats = Vec::new();
+
+    if let Ok(stat) = fs::read_to_string("/proc/self/stat") {
+        // The command name is wrapped in parentheses and may itself contain spaces or
+        // parentheses, so fields are counted from after the last closing parenthesis, where the
+        // third field, the process state, begins.
+        let fields = stat
+            .rfind(')')
+            .map(|idx| stat[idx + 1..].split_whitespace().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let field = |n: usize| fields.get(n - 3).and_then(|f| f.parse::<u64>().ok());
+
+        if let (Some(utime), Some(stime)) = (field(14), field(15)) {
+            let cpu_millis = (utime + stime) * 1000 / TICKS_PER_SECOND;
+            stats.push(("process_cpu_milliseconds_total", cpu_millis as i64));
+        }
+        if let Some(threads) = field(20) {
+            stats.push(("process_threads", threads as i64));
+        }
+        if let Some(vsize) = field(23) {
+            stats.push(("process_virtual_memory_bytes", vsize as i64));
+        }
+        if let (Some(start_ticks), Some(boot_time)) = (field(22), read_boot_time()) {
+            let start_time = boot_time + start_ticks / TICKS_PER_SECOND;
+            stats.push(("process_start_time_seconds", start_time as i64));
+        }
+    }
+
+    if let Ok(status) = fs::read_to_string("/proc/self/status") {
+        let rss_kb = status
+            .lines()
+            .find(|line| line.starts_with("VmRSS:"))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|kb| kb.parse::<u64>().ok());
+        if let Some(rss_kb) = rss_kb {
+            stats.push(("process_resident_memory_bytes", (rss_kb * 1024) as i64));
+        }
+    }
+
+    if let Ok(fds) = fs::read_dir("/proc/self/fd") {
+        // Reading the directory opens a descriptor of its own, which is listed along with the rest.
+        let open_fds = fds.count().saturating_sub(1);
+        stats.push(("process_open_fds", open_fds as i64));
+    }
+
+    if let Ok(limits) = fs::read_to_string("/proc/self/limits") {
+        let max_fds = limits
+            .lines()
+            .find(|line| line.starts_with("Max open files"))
+            .and_then(|line| line["Max open files".len()..].split_whitespace().next())
+            .and_then(|soft| soft.parse::<u64>().ok());
+        if let Some(max_fds) = max_fds {
+            stats.push(("process_max_fds", max_fds as i64));
+        }
+    }
+
+    stats
+}
+
+#[cfg(target_os = "linux")]
+fn read_boot_time() -> Option<u64> {
+    std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find(|line| line.starts_with("btime "))
+        .and_then(|line| line["btime ".len()..].trim().parse().ok())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_stats() -> Vec<(&'static str, i64)> {
+    Vec::new()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::ProcessCollector;
+    use crate::test_util::CapturingRecorder;
+    use std::{
+        collections::HashMap,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    #[test]
+    fn test_process_collector() {
+        let recorder = CapturingRecorder::default();
+        ProcessCollector::new().collect_into(&recorder);
+
+        let gauges = recorder
+            .take_gauges()
+            .into_iter()
+            .map(|(key, value)| (key.name().into_owned(), value))
+            .collect::<HashMap<_, _>>();
+        for name in &[
+            "process_cpu_milliseconds_total",
+            "process_resident_memory_bytes",
+            "process_virtual_memory_bytes",
+            "process_open_fds",
+            "process_threads",
+            "process_start_time_seconds",
+        ] {
+            assert!(gauges.contains_key(*name), "missing {}", name);
+        }
+
+        assert!(gauges["process_cpu_milliseconds_total"] >= 0);
+        assert!(gauges["process_resident_memory_bytes"] > 0);
+        assert!(gauges["process_virtual_memory_bytes"] >= gauges["process_resident_memory_bytes"]);
+        assert!(gauges["process_threads"] >= 1);
+        assert!(gauges["process_open_fds"] >= 1);
+        // There's no maximum to record when the limit on open files is unlimited.
+        if let Some(max_fds) = gauges.get("process_max_fds") {
+            assert!(*max_fds >= gauges["process_open_fds"]);
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let start_time = gauges["process_start_time_seconds"];
+        assert!(start_time > 0 && start_time <= now + 1);
+    }
+}
@@ -0,0 +1,557 @@
+use crate::{
+    parse_quantiles,
+    value::{push_summary, sanitize_prometheus_name},
+    AtomicCounter, ExportError, LogHistogram, MetricValue, Quantile,
+};
+use atomic_shim::AtomicI64;
+use metrics::Recorder;
+use metrics_core::{Key, Observe, Observer};
+use std::{
+    cmp,
+    collections::HashMap,
+    io::Write,
+    sync::{atomic::Ordering, Mutex, RwLock},
+};
+
+type GaugeFn = dyn Fn() -> i64 + Send + Sync;
+
+// Histograms track the whole range of `u64` to two significant digits, which bounds each of them
+// to a few tens of kilobytes however many values are recorded.
+const HISTOGRAM_SIGNIFICANT_DIGITS: u8 = 2;
+
+enum Backend {
+    Counter(AtomicCounter),
+    Gauge(AtomicI64),
+    GaugeCallback(Box<GaugeFn>),
+    Histogram(Mutex<Summary>),
+}
+
+struct Summary {
+    values: LogHistogram,
+    sum: u64,
+}
+
+impl Summary {
+    fn new() -> Self {
+        Summary {
+            values: LogHistogram::new(u64::max_value(), HISTOGRAM_SIGNIFICANT_DIGITS),
+            sum: 0,
+        }
+    }
+}
+
+/// A recorder which renders everything recorded to it in the Prometheus exposition format.
+///
+/// `PrometheusRecorder` is the smallest possible exporter: it keeps every metric in an atomic of
+/// its own, and renders all of them on demand with [`render`](PrometheusRecorder::render) or
+/// [`render_to`](PrometheusRecorder::render_to).  Serving the output is left to whatever web
+/// framework the application already uses.
+///
+/// Metrics are sorted by name and then by key, and each is rendered as with [`MetricValue::render_prometheus`],
+/// so histograms are rendered as summaries with the configured quantiles.  Each histogram is kept
+/// in a [`LogHistogram`] of its own, so it takes bounded memory however many values are recorded
+/// to it, and its quantiles are accurate to two significant digits.  Sums and counts are exact.
+///
+/// Pre-aggregated histogram buckets are recorded as their upper bounds, weighted by their
+/// counts, so quantiles are accurate to the resolution of the given buckets, while the sum and
+/// count are kept as given.  Values above the highest bound are recorded as their mean.
+///
+/// Each key holds a single kind of metric, which is whichever kind was recorded to it first.
+/// Later operations of another kind on the same key are ignored.  Descriptions are rendered as a
+/// `# HELP` line ahead of the first line of each metric name, whichever kind they were given
+/// for.
+///
+/// [`MetricValue::render_prometheus`]: crate::MetricValue::render_prometheus
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::Key;
+/// # use metrics_util::PrometheusRecorder;
+/// let recorder = PrometheusRecorder::with_quantiles(&[0.5]);
+/// recorder.increment_counter(Key::from_name("requests"), 42);
+/// recorder.update_gauge(Key::from_name("connections"), 7);
+///
+/// assert_eq!(recorder.render(), "connections 7\nrequests 42\n");
+/// ```
+pub struct PrometheusRecorder {
+    metrics: RwLock<HashMap<Key, Backend>>,
+    descriptions: RwLock<HashMap<String, &'static str>>,
+    quantiles: Vec<Quantile>,
+}
+
+impl PrometheusRecorder {
+    /// Creates a new, empty recorder, rendering histograms with the min, max, p50, p90, p95, p99
+    /// and p999 quantiles.
+    pub fn new() -> Self {
+        PrometheusRecorder::with_quantiles(&[0.0, 0.5, 0.9, 0.95, 0.99, 0.999, 1.0])
+    }
+
+    /// Creates a new, empty recorder, rendering histograms with the given quantiles.
+    pub fn with_quantiles(quantiles: &[f64]) -> Self {
+        PrometheusRecorder {
+            metrics: RwLock::new(HashMap::new()),
+            descriptions: RwLock::new(HashMap::new()),
+            quantiles: parse_quantiles(quantiles),
+        }
+    }
+
+    /// Renders every metric in the Prometheus exposition format.
+    pub fn render(&self) -> String {
+        let mut output = Vec::new();
+        // Writing to a `Vec` never fails, and rendered metrics are always valid UTF-8.
+        let _ = self.render_to(&mut output);
+        String::from_utf8(output).unwrap_or_default()
+    }
+
+    /// Renders every metric in the Prometheus exposition format, writing the output to `writer`
+    /// as it goes.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if writing to `writer` fails.
+    pub fn render_to<W: Write>(&self, writer: &mut W) -> Result<(), ExportError> {
+        // Histograms are rendered straight from their buckets, rather than through `Observe`,
+        // so that their values never need to be expanded.
+        let metrics = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+        let descriptions = self.descriptions.read().unwrap_or_else(|e| e.into_inner());
+        let mut previous_name = None;
+        for (key, backend) in sorted(&metrics) {
+            let name = key.name();
+            if previous_name.as_ref() != Some(&name) {
+                if let Some(description) = descriptions.get(name.as_ref()) {
+                    let help = format!(
+                        "# HELP {} {}\n",
+                        sanitize_prometheus_name(&name),
+                        description.replace('\\', "\\\\").replace('\n', "\\n")
+                    );
+                    writer.write_all(help.as_bytes())?;
+                }
+                previous_name = Some(name);
+            }
+
+            let rendered = match backend {
+                Backend::Counter(counter) => {
+                    MetricValue::Counter(counter.get()).render_prometheus(key, &self.quantiles)
+                }
+                Backend::Gauge(gauge) => MetricValue::Gauge(gauge.load(Ordering::Acquire))
+                    .render_prometheus(key, &self.quantiles),
+                Backend::GaugeCallback(callback) => {
+                    MetricValue::Gauge(callback()).render_prometheus(key, &self.quantiles)
+                }
+                Backend::Histogram(summary) => {
+                    let summary = summary.lock().unwrap_or_else(|e| e.into_inner());
+                    let key = key.clone().map_name(|name| sanitize_prometheus_name(&name));
+                    let mut output = String::new();
+                    push_summary(
+                        &mut output,
+                        &key,
+                        &self.quantiles,
+                        |q| summary.values.quantile(q),
+                        summary.sum,
+                        summary.values.count(),
+                    );
+                    output
+                }
+            };
+            writer.write_all(rendered.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Registers a gauge whose value is computed by the given callback.
+    ///
+    /// Rather than being updated as it changes, the gauge is read by calling `callback` whenever
+    /// metrics are rendered or observed, and at no other time.  This suits gauges which are
+    /// costly to compute, such as the length of a queue behind a lock.
+    ///
+    /// Any metric already recorded under the same key is replaced.  The callback is called while
+    /// the recorder's metrics are locked, so it must not record to this recorder itself.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::Key;
+    /// # use metrics_util::PrometheusRecorder;
+    /// # use std::sync::{Arc, Mutex};
+    /// let queue = Arc::new(Mutex::new(vec!["a", "b"]));
+    /// let recorder = PrometheusRecorder::new();
+    ///
+    /// let handle = queue.clone();
+    /// recorder.register_gauge_callback(
+    ///     Key::from_name("queue.length"),
+    ///     Box::new(move || handle.lock().unwrap().len() as i64),
+    /// );
+    ///
+    /// queue.lock().unwrap().push("c");
+    /// assert_eq!(recorder.render(), "queue_length 3\n");
+    /// ```
+    pub fn register_gauge_callback(&self, key: Key, callback: Box<GaugeFn>) {
+        let mut metrics = self.metrics.write().unwrap_or_else(|e| e.into_inner());
+        metrics.insert(key, Backend::GaugeCallback(callback));
+    }
+
+    fn describe(&self, key: Key, description: &'static str) {
+        let mut descriptions = self.descriptions.write().unwrap_or_else(|e| e.into_inner());
+        descriptions.insert(key.name().into_owned(), description);
+    }
+
+    fn with_backend<F, B>(&self, key: Key, new_backend: B, f: F)
+    where
+        F: Fn(&Backend),
+        B: FnOnce() -> Backend,
+    {
+        {
+            let metrics = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(backend) = metrics.get(&key) {
+                f(backend);
+                return;
+            }
+        }
+
+        let mut metrics = self.metrics.write().unwrap_or_else(|e| e.into_inner());
+        f(metrics.entry(key).or_insert_with(new_backend));
+    }
+}
+
+impl Default for PrometheusRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recorder for PrometheusRecorder {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.with_backend(
+            key,
+            || Backend::Counter(AtomicCounter::new()),
+            |backend| {
+                if let Backend::Counter(counter) = backend {
+                    counter.increment(value);
+                }
+            },
+        );
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.with_backend(
+            key,
+            || Backend::Gauge(AtomicI64::new(0)),
+            |backend| {
+                if let Backend::Gauge(gauge) = backend {
+                    gauge.store(value, Ordering::Release);
+                }
+            },
+        );
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.record_histogram_many(key, value, 1);
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        self.with_backend(
+            key,
+            || Backend::Histogram(Mutex::new(Summary::new())),
+            |backend| {
+                if let Backend::Histogram(summary) = backend {
+                    let mut summary = summary.lock().unwrap_or_else(|e| e.into_inner());
+                    summary.values.record_many(value, count);
+                    summary.sum = summary.sum.wrapping_add(value.wrapping_mul(count));
+                }
+            },
+        );
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        self.with_backend(
+            key,
+            || Backend::Histogram(Mutex::new(Summary::new())),
+            |backend| {
+                if let Backend::Histogram(summary) = backend {
+                    let mut summary = summary.lock().unwrap_or_else(|e| e.into_inner());
+                    let mut bucketed = 0u64;
+                    let mut bucketed_sum = 0u64;
+                    let mut highest_bound = 0;
+                    for (upper_bound, bucket_count) in buckets {
+                        summary.values.record_many(*upper_bound, *bucket_count);
+                        bucketed = bucketed.saturating_add(*bucket_count);
+                        bucketed_sum =
+                            bucketed_sum.saturating_add(upper_bound.saturating_mul(*bucket_count));
+                        highest_bound = cmp::max(highest_bound, *upper_bound);
+                    }
+
+                    let overflow = count.saturating_sub(bucketed);
+                    if let Some(mean) = sum.saturating_sub(bucketed_sum).checked_div(overflow) {
+                        summary
+                            .values
+                            .record_many(cmp::max(mean, highest_bound), overflow);
+                    }
+                    summary.sum = summary.sum.wrapping_add(sum);
+                }
+            },
+        );
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        let metrics = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+        match metrics.get(key) {
+            Some(Backend::Counter(counter)) => Some(counter.get()),
+            _ => None,
+        }
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        let metrics = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+        match metrics.get(key) {
+            Some(Backend::Gauge(gauge)) => Some(gauge.load(Ordering::Acquire)),
+            Some(Backend::GaugeCallback(callback)) => Some(callback()),
+            _ => None,
+        }
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.describe(key, description);
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.describe(key, description);
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.describe(key, description);
+    }
+}
+
+impl Observe for PrometheusRecorder {
+    fn observe<O: Observer>(&self, observer: &mut O) {
+        let metrics = self.metrics.read().unwrap_or_else(|e| e.into_inner());
+        for (key, backend) in sorted(&metrics) {
+            match backend {
+                Backend::Counter(counter) => observer.observe_counter(key.clone(), counter.get()),
+                Backend::Gauge(gauge) => {
+                    observer.observe_gauge(key.clone(), gauge.load(Ordering::Acquire))
+                }
+                Backend::GaugeCallback(callback) => observer.observe_gauge(key.clone(), callback()),
+                Backend::Histogram(summary) => {
+                    let summary = summary.lock().unwrap_or_else(|e| e.into_inner());
+                    let values = summary.values.iter_recorded().collect::<Vec<_>>();
+                    observer.observe_histogram_weighted(key.clone(), &values);
+                }
+            }
+        }
+    }
+}
+
+/// Sorts metrics by name and then by key, in the order they're rendered in, so that every metric
+/// with the same name is rendered together.
+fn sorted(metrics: &HashMap<Key, Backend>) -> Vec<(&Key, &Backend)> {
+    let mut sorted = metrics
+        .iter()
+        .map(|(key, backend)| ((key.name(), key.to_canonical_string()), key, backend))
+        .collect::<Vec<_>>();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+    sorted
+        .into_iter()
+        .map(|(_, key, backend)| (key, backend))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PrometheusRecorder;
+    use crate::ExportError;
+    use metrics::Recorder;
+    use metrics_core::{Key, Label};
+    use std::{
+        io::{self, Write},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    #[test]
+    fn test_prometheus_recorder_render() {
+        let recorder = PrometheusRecorder::with_quantiles(&[0.0, 0.5, 1.0]);
+        assert_eq!(recorder.render(), "");
+
+        let requests = |code: &'static str| {
+            Key::from_name_and_labels("http.requests", vec![Label::new("code", code)])
+        };
+        recorder.increment_counter(requests("200"), 3);
+        recorder.increment_counter(requests("500"), 1);
+        recorder.increment_counter(requests("200"), 2);
+        recorder.update_gauge(Key::from_name("connections"), 4);
+        recorder.update_gauge(Key::from_name("connections"), -2);
+        for value in &[30, 10, 20] {
+            recorder.record_histogram(Key::from_name("latency"), *value);
+        }
+        recorder.record_histogram_many(Key::from_name("latency"), 40, 2);
+
+        // Operations of another kind on an existing key are ignored.
+        recorder.update_gauge(requests("200"), 100);
+
+        assert_eq!(
+            recorder.render(),
+            concat!(
+                "connections -2\n",
+                "http_requests{code=\"200\"} 5\n",
+                "http_requests{code=\"500\"} 1\n",
+                "latency{quantile=\"0\"} 10\n",
+                "latency{quantile=\"0.5\"} 30\n",
+                "latency{quantile=\"1\"} 40\n",
+                "latency_sum 140\n",
+                "latency_count 5\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_prometheus_recorder_weighted_histogram() {
+        let recorder = PrometheusRecorder::with_quantiles(&[0.0, 0.5, 1.0]);
+        let key = Key::from_name("batch.size");
+
+        // Recording a value many times at once costs the same as recording it once.
+        recorder.record_histogram_many(key.clone(), 7, u64::max_value() / 8);
+        recorder.record_histogram(key.clone(), 1_000_000);
+
+        let count = u64::max_value() / 8 + 1;
+        let sum = (u64::max_value() / 8)
+            .wrapping_mul(7)
+            .wrapping_add(1_000_000);
+        assert_eq!(
+            recorder.render(),
+            format!(
+                concat!(
+                    "batch_size{{quantile=\"0\"}} 7\n",
+                    "batch_size{{quantile=\"0.5\"}} 7\n",
+                    "batch_size{{quantile=\"1\"}} 1000000\n",
+                    "batch_size_sum {}\n",
+                    "batch_size_count {}\n",
+                ),
+                sum, count
+            )
+        );
+    }
+
+    #[test]
+    fn test_prometheus_recorder_buckets() {
+        let recorder = PrometheusRecorder::with_quantiles(&[0.5, 1.0]);
+        let key = Key::from_name("federated.latency");
+
+        // A million values in the lowest bucket, and one above the highest bound.
+        recorder.record_histogram_buckets(
+            key.clone(),
+            &[(10, 1_000_000), (50, 4)],
+            10_001_000,
+            1_000_005,
+        );
+        recorder.record_histogram_many(key, 50, 2);
+
+        assert_eq!(
+            recorder.render(),
+            concat!(
+                "federated_latency{quantile=\"0.5\"} 10\n",
+                "federated_latency{quantile=\"1\"} 800\n",
+                "federated_latency_sum 10001100\n",
+                "federated_latency_count 1000007\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_prometheus_recorder_descriptions() {
+        let recorder = PrometheusRecorder::with_quantiles(&[]);
+        let requests = |code: &'static str| {
+            Key::from_name_and_labels("http.requests", vec![Label::new("code", code)])
+        };
+        recorder.describe_counter(requests("200"), "Requests served,\nby status code.");
+        recorder.describe_histogram(Key::from_name("latency"), "Request latency.");
+        recorder.increment_counter(requests("200"), 1);
+        recorder.increment_counter(requests("500"), 1);
+        recorder.increment_counter(Key::from_name("http.requests_total"), 2);
+        recorder.update_gauge(Key::from_name("connections"), 3);
+
+        // Descriptions are rendered once per name, and only for metrics that have been recorded.
+        assert_eq!(
+            recorder.render(),
+            concat!(
+                "connections 3\n",
+                "# HELP http_requests Requests served,\\nby status code.\n",
+                "http_requests{code=\"200\"} 1\n",
+                "http_requests{code=\"500\"} 1\n",
+                "http_requests_total 2\n",
+            )
+        );
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_prometheus_recorder_read_back() {
+        let recorder = PrometheusRecorder::new();
+        let requests = Key::from_name("requests");
+        let connections = Key::from_name("connections");
+        assert_eq!(recorder.counter_value(&requests), None);
+
+        recorder.increment_counter(requests.clone(), 2);
+        recorder.increment_counter(requests.clone(), 3);
+        recorder.update_gauge(connections.clone(), 7);
+        recorder.update_gauge(connections.clone(), -1);
+        recorder.record_histogram(Key::from_name("latency"), 1);
+
+        assert_eq!(recorder.counter_value(&requests), Some(5));
+        assert_eq!(recorder.gauge_value(&connections), Some(-1));
+        // Values are only read back as the kind of metric they were recorded as.
+        assert_eq!(recorder.gauge_value(&requests), None);
+        assert_eq!(recorder.counter_value(&Key::from_name("latency")), None);
+    }
+
+    #[test]
+    fn test_prometheus_recorder_gauge_callback() {
+        let recorder = PrometheusRecorder::new();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        recorder.update_gauge(Key::from_name("queue.length"), 100);
+        recorder.register_gauge_callback(
+            Key::from_name("queue.length"),
+            Box::new(move || counted.fetch_add(1, Ordering::SeqCst) as i64 + 10),
+        );
+
+        // The callback is only called when metrics are read, and replaces the pushed gauge.
+        recorder.update_gauge(Key::from_name("queue.length"), 200);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        assert_eq!(recorder.render(), "queue_length 10\n");
+        assert_eq!(recorder.render(), "queue_length 11\n");
+        assert_eq!(
+            recorder.gauge_value(&Key::from_name("queue.length")),
+            Some(12)
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_prometheus_recorder_render_to() {
+        let recorder = PrometheusRecorder::new();
+        recorder.increment_counter(Key::from_name("requests"), 1);
+
+        let mut output = Vec::new();
+        recorder.render_to(&mut output).unwrap();
+        assert_eq!(output, b"requests 1\n");
+
+        match recorder.render_to(&mut FailingWriter) {
+            Err(ExportError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use crate::{ExportError, MetricValue, Quantile};
+use metrics_core::{Key, Observe, Observer};
+use std::{
+    io::{self, Write},
+    vec,
+};
+
+/// Renders every metric from the given source in the Prometheus exposition format, writing the
+/// output to `writer` as it goes.
+///
+/// Each metric is rendered as with [`MetricValue::render_prometheus`], and written out as soon as
+/// it has been observed, so neither a full snapshot of the source nor the full output is ever
+/// held in memory.  This keeps scrapes of very large registries cheap for memory-constrained
+/// exporters.  The output is identical to rendering each metric one by one and concatenating the
+/// results.
+///
+/// Histograms may be observed in several chunks, which are expected to arrive back to back, so
+/// only the values of the histogram currently being observed are buffered.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_core::{Key, Observe, Observer};
+/// # use metrics_util::render_prometheus_to;
+/// struct Source;
+///
+/// impl Observe for Source {
+///     fn observe<O: Observer>(&self, observer: &mut O) {
+///         observer.observe_counter(Key::from_name("requests"), 42);
+///         observer.observe_gauge(Key::from_name("connections"), 7);
+///     }
+/// }
+///
+/// let mut output = Vec::new();
+/// render_prometheus_to(&Source, &mut output, &[]).expect("failed to render");
+/// assert_eq!(output, b"requests 42\nconnections 7\n");
+/// ```
+pub fn render_prometheus_to<S, W>(
+    source: &S,
+    writer: &mut W,
+    quantiles: &[Quantile],
+) -> Result<(), ExportError>
+where
+    S: Observe,
+    W: Write,
+{
+    let mut observer = StreamingObserver {
+        writer,
+        quantiles,
+        histogram: None,
+        error: None,
+    };
+    source.observe(&mut observer);
+    observer.flush_histogram();
+
+    match observer.error {
+        Some(e) => Err(ExportError::Io(e)),
+        None => Ok(()),
+    }
+}
+
+/// Renders every metric from the given source in the Prometheus exposition format, one metric at
+/// a time.
+///
+/// The source is observed up front, but each metric is only rendered when the iterator reaches
+/// it, yielding the block of lines for that metric, as with [`MetricValue::render_prometheus`].
+/// This suits handlers which stream the response body, as the full output is never built.
+/// Concatenating every item gives the same output as [`render_prometheus_to`].
+///
+/// # Examples
+/// ```rust
+/// # use metrics_core::{Key, Observe, Observer};
+/// # use metrics_util::prometheus_lines;
+/// struct Source;
+///
+/// impl Observe for Source {
+///     fn observe<O: Observer>(&self, observer: &mut O) {
+///         observer.observe_counter(Key::from_name("requests"), 42);
+///         observer.observe_gauge(Key::from_name("connections"), 7);
+///     }
+/// }
+///
+/// let lines = prometheus_lines(&Source, &[]).collect::<Vec<_>>();
+/// assert_eq!(lines, vec!["requests 42\n", "connections 7\n"]);
+/// ```
+pub fn prometheus_lines<S>(source: &S, quantiles: &[Quantile]) -> PrometheusLines
+where
+    S: Observe,
+{
+    let mut observer = CollectingObserver {
+        metrics: Vec::new(),
+    };
+    source.observe(&mut observer);
+
+    PrometheusLines {
+        metrics: observer.metrics.into_iter(),
+        quantiles: quantiles.to_vec(),
+    }
+}
+
+/// Iterator over the rendered metrics of a source.
+///
+/// Created by [`prometheus_lines`].
+#[derive(Debug)]
+pub struct PrometheusLines {
+    metrics: vec::IntoIter<(Key, MetricValue)>,
+    quantiles: Vec<Quantile>,
+}
+
+impl Iterator for PrometheusLines {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.metrics
+            .next()
+            .map(|(key, value)| value.render_prometheus(&key, &self.quantiles))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.metrics.size_hint()
+    }
+}
+
+struct CollectingObserver {
+    metrics: Vec<(Key, MetricValue)>,
+}
+
+impl Observer for CollectingObserver {
+    fn observe_counter(&mut self, key: Key, value: u64) {
+        self.metrics.push((key, MetricValue::Counter(value)));
+    }
+
+    fn observe_gauge(&mut self, key: Key, value: i64) {
+        self.metrics.push((key, MetricValue::Gauge(value)));
+    }
+
+    fn observe_histogram(&mut self, key: Key, values: &[u64]) {
+        // Chunks of the same histogram arrive back to back.
+        if let Some((current, MetricValue::Histogram(buffered))) = self.metrics.last_mut() {
+            if *current == key {
+                buffered.extend_from_slice(values);
+                return;
+            }
+        }
+        self.metrics
+            .push((key, MetricValue::Histogram(values.to_vec())));
+    }
+
+    fn observe_histogram_weighted(&mut self, key: Key, values: &[(u64, u64)]) {
+        self.metrics
+            .push((key, MetricValue::WeightedHistogram(values.to_vec())));
+    }
+}
+
+struct StreamingObserver<'a, W> {
+    writer: &'a mut W,
+    quantiles: &'a [Quantile],
+    // The histogram currently being observed, which may still receive more values.
+    histogram: Option<(Key, Vec<u64>)>,
+    // The first error hit while writing, after which nothing more is written.
+    error: Option<io::Error>,
+}
+
+impl<'a, W: Write> StreamingObserver<'a, W> {
+    fn write(&mut self, key: &Key, value: &MetricValue) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let rendered = value.render_prometheus(key, self.quantiles);
+        if let Err(e) = self.writer.write_all(rendered.as_bytes()) {
+            self.error = Some(e);
+        }
+    }
+
+    fn flush_histogram(&mut self) {
+        if let Some((key, values)) = self.histogram.take() {
+            self.write(&key, &MetricValue::Histogram(values));
+        }
+    }
+}
+
+impl<'a, W: Write> Observer for StreamingObserver<'a, W> {
+    fn observe_counter(&mut self, key: Key, value: u64) {
+        self.flush_histogram();
+        self.write(&key, &MetricValue::Counter(value));
+    }
+
+    fn observe_gauge(&mut self, key: Key, value: i64) {
+        self.flush_histogram();
+        self.write(&key, &MetricValue::Gauge(value));
+    }
+
+    fn observe_histogram(&mut self, key: Key, values: &[u64]) {
+        match &mut self.histogram {
+            Some((current, buffered)) if *current == key => buffered.extend_from_slice(values),
+            _ => {
+                self.flush_histogram();
+                self.histogram = Some((key, values.to_vec()));
+            }
+        }
+    }
+
+    fn observe_histogram_weighted(&mut self, key: Key, values: &[(u64, u64)]) {
+        self.flush_histogram();
+        self.write(&key, &MetricValue::WeightedHistogram(values.to_vec()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prometheus_lines, render_prometheus_to};
+    use crate::{parse_quantiles, ExportError, MetricValue};
+    use metrics_core::{Key, Label, Observe, Observer};
+    use std::io::{self, Write};
+
+    struct Source(Vec<(Key, MetricValue)>);
+
+    impl Observe for Source {
+        fn observe<O: Observer>(&self, observer: &mut O) {
+            for (key, value) in &self.0 {
+                match value {
+                    MetricValue::Counter(value) => observer.observe_counter(key.clone(), *value),
+                    MetricValue::Gauge(value) => observer.observe_gauge(key.clone(), *value),
+                    // Hand over histograms in small chunks, as the runtime does.
+                    MetricValue::Histogram(values) => {
+                        for chunk in values.chunks(2) {
+                            observer.observe_histogram(key.clone(), chunk);
+                        }
+                    }
+                    MetricValue::WeightedHistogram(values) => {
+                        observer.observe_histogram_weighted(key.clone(), values)
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_prometheus_to() {
+        let labels = vec![Label::new("table", "users")];
+        let source = Source(vec![
+            (Key::from_name("requests"), MetricValue::Counter(42)),
+            (
+                Key::from_name_and_labels("latency", labels.clone()),
+                MetricValue::Histogram(vec![5, 1, 4, 2, 3]),
+            ),
+            (
+                Key::from_name_and_labels("latency", vec![Label::new("table", "orders")]),
+                MetricValue::Histogram(vec![10, 20, 30]),
+            ),
+            (Key::from_name("connections"), MetricValue::Gauge(-3)),
+            (
+                Key::from_name("size"),
+                MetricValue::Histogram(vec![100, 200]),
+            ),
+            (
+                Key::from_name("batch_size"),
+                MetricValue::WeightedHistogram(vec![(8, 1_000_000), (64, 3)]),
+            ),
+        ]);
+        let quantiles = parse_quantiles(&[0.0, 0.5, 1.0]);
+
+        let mut output = Vec::new();
+        render_prometheus_to(&source, &mut output, &quantiles).unwrap();
+
+        let batch = source
+            .0
+            .iter()
+            .map(|(key, value)| value.render_prometheus(key, &quantiles))
+            .collect::<String>();
+        assert_eq!(String::from_utf8(output).unwrap(), batch);
+    }
+
+    #[test]
+    fn test_prometheus_lines() {
+        let source = Source(vec![
+            (Key::from_name("requests"), MetricValue::Counter(42)),
+            (
+                Key::from_name("latency"),
+                MetricValue::Histogram(vec![5, 1, 4, 2, 3]),
+            ),
+            (
+                Key::from_name_and_labels("latency", vec![Label::new("table", "orders")]),
+                MetricValue::Histogram(vec![10, 20, 30]),
+            ),
+            (Key::from_name("connections"), MetricValue::Gauge(-3)),
+        ]);
+        let quantiles = parse_quantiles(&[0.5, 0.99]);
+
+        let lines = prometheus_lines(&source, &quantiles);
+        assert_eq!(lines.size_hint(), (4, Some(4)));
+
+        // Histograms observed in chunks still come out as a single block each.
+        let lines = lines.collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[1],
+            MetricValue::Histogram(vec![5, 1, 4, 2, 3])
+                .render_prometheus(&Key::from_name("latency"), &quantiles)
+        );
+
+        let mut output = Vec::new();
+        render_prometheus_to(&source, &mut output, &quantiles).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), lines.concat());
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_render_prometheus_to_error() {
+        let source = Source(vec![(Key::from_name("requests"), MetricValue::Counter(42))]);
+        match render_prometheus_to(&source, &mut FailingWriter, &[]) {
+            Err(ExportError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
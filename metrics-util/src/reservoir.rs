@@ -0,0 +1,185 @@
+/// A fixed-size, uniformly sampled reservoir of values.
+///
+/// Tracking every value recorded into a histogram requires memory proportional to the number of
+/// values recorded, which is unbounded for a long-lived, high-traffic metric.  `Reservoir` instead
+/// holds at most `capacity` values: once full, each newly recorded value replaces a randomly
+/// selected existing value with a probability of `capacity / count`.  This is "Algorithm R"
+/// reservoir sampling, and it guarantees that the held values are always a uniform random sample
+/// of every value recorded so far, so quantiles calculated from the reservoir remain unbiased
+/// estimates of the true quantiles.
+///
+/// # Accuracy versus memory
+/// Memory usage is fixed at `capacity` 64-bit integers, regardless of how many values are
+/// recorded.  The accuracy of estimated quantiles improves with the square root of the capacity:
+/// a capacity of 1,000 typically estimates the median to within a few percent of the rank, while
+/// the extreme tails (p99.9 and beyond) need a larger capacity to have enough samples to draw
+/// from.  Low-traffic metrics can use a small capacity, as they may never fill their reservoir at
+/// all, in which case quantiles are exact.
+///
+/// Sampling is driven by a deterministic, seeded pseudo-random number generator, so the same
+/// sequence of values always produces the same reservoir.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::Reservoir;
+/// let mut reservoir = Reservoir::with_capacity(128);
+/// for value in 0..10_000 {
+///     reservoir.record(value);
+/// }
+///
+/// assert_eq!(reservoir.len(), 128);
+/// assert_eq!(reservoir.count(), 10_000);
+/// assert!(reservoir.quantile(0.5).is_some());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Reservoir {
+    values: Vec<u64>,
+    capacity: usize,
+    count: u64,
+    rng: XorShift,
+}
+
+impl Reservoir {
+    /// Creates a new, empty reservoir that holds at most `capacity` values.
+    ///
+    /// # Panics
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "reservoir capacity must be greater than zero");
+
+        Reservoir {
+            values: Vec::with_capacity(capacity),
+            capacity,
+            count: 0,
+            rng: XorShift::new(),
+        }
+    }
+
+    /// Records a value.
+    pub fn record(&mut self, value: u64) {
+        self.count += 1;
+        if self.values.len() < self.capacity {
+            self.values.push(value);
+        } else {
+            let idx = self.rng.next() % self.count;
+            if idx < self.capacity as u64 {
+                self.values[idx as usize] = value;
+            }
+        }
+    }
+
+    /// Maximum number of values held by this reservoir.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of values currently held by this reservoir.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether or not this reservoir is empty.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Total number of values recorded, including those not held by this reservoir.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Values currently held by this reservoir, in no particular order.
+    pub fn values(&self) -> &[u64] {
+        &self.values
+    }
+
+    /// Estimates the value at the given quantile.
+    ///
+    /// The quantile is clamped between 0.0 and 1.0.  Returns `None` if no values have been
+    /// recorded.
+    pub fn quantile(&self, quantile: f64) -> Option<u64> {
+        if self.values.is_empty() {
+            return None;
+        }
+
+        let quantile = quantile.max(0.0);
+        let quantile = quantile.min(1.0);
+        let mut sorted = self.values.clone();
+        sorted.sort_unstable();
+
+        let idx = (quantile * (sorted.len() - 1) as f64).round() as usize;
+        Some(sorted[idx])
+    }
+
+    /// Clears this reservoir, removing all values and resetting the count.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.count = 0;
+    }
+}
+
+/// A small, seeded xorshift64* generator.
+///
+/// We only need cheap, well-distributed indices here, not cryptographic randomness.
+#[derive(Debug, Clone)]
+struct XorShift(u64);
+
+impl XorShift {
+    fn new() -> Self {
+        XorShift(0x853c_49e6_748f_ea9b)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Reservoir;
+
+    #[test]
+    fn test_reservoir_under_capacity() {
+        let mut reservoir = Reservoir::with_capacity(16);
+        assert_eq!(reservoir.quantile(0.5), None);
+
+        for value in 1..=10 {
+            reservoir.record(value);
+        }
+
+        assert_eq!(reservoir.len(), 10);
+        assert_eq!(reservoir.count(), 10);
+        assert_eq!(reservoir.quantile(0.0), Some(1));
+        assert_eq!(reservoir.quantile(1.0), Some(10));
+
+        reservoir.clear();
+        assert!(reservoir.is_empty());
+        assert_eq!(reservoir.count(), 0);
+    }
+
+    #[test]
+    fn test_reservoir_skewed_distribution() {
+        // Squares of uniformly distributed values, so most values are small relative to the max.
+        // The true p50 is 250,000, and the true p90 is 810,000.
+        let mut reservoir = Reservoir::with_capacity(1000);
+        for i in 0..200_000u64 {
+            let u = (i * 7919) % 1000;
+            reservoir.record(u * u);
+        }
+
+        assert_eq!(reservoir.len(), 1000);
+        assert_eq!(reservoir.values().len(), 1000);
+        assert_eq!(reservoir.count(), 200_000);
+
+        let p50 = reservoir.quantile(0.5).unwrap();
+        assert!(p50 > 200_000 && p50 < 300_000, "p50 was {}", p50);
+
+        let p90 = reservoir.quantile(0.9).unwrap();
+        assert!(p90 > 740_000 && p90 < 880_000, "p90 was {}", p90);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{Clock, SystemClock};
+use metrics::{MetricOp, Recorder};
+use metrics_core::{Key, Label, MetricKind};
+use std::{any::Any, collections::VecDeque, sync::Mutex, time::Instant};
+
+/// A recorder wrapper that keeps the most recent operations in memory.
+///
+/// `RingBufferRecorder` forwards every operation to the wrapped recorder, and additionally keeps
+/// the last `capacity` counter, gauge, and histogram operations in a ring buffer, along with the
+/// time they were recorded.  Once the buffer is full, the oldest operation is dropped to make room
+/// for each new one.
+///
+/// This is meant for live debugging, such as exposing recent activity on an admin endpoint,
+/// without needing to run a full exporter.  Values are stored as `f64` so that all kinds of
+/// metrics can be held side by side.  Weighted histogram values are tracked as a single
+/// operation, while pre-aggregated histogram buckets are forwarded but not tracked.
+///
+/// Times are taken from the system clock by default, or from any other [`Clock`] given to
+/// [`with_clock`](RingBufferRecorder::with_clock).
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::{Key, MetricKind};
+/// # use metrics_util::RingBufferRecorder;
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let recorder = RingBufferRecorder::new(NoopRecorder, 2);
+/// recorder.increment_counter(Key::from_name("requests"), 1);
+/// recorder.update_gauge(Key::from_name("connections"), 5);
+/// recorder.record_histogram(Key::from_name("latency"), 42);
+///
+/// let recent = recorder.recent();
+/// assert_eq!(recent.len(), 2);
+/// assert_eq!(recent[0].1, MetricKind::Gauge);
+/// assert_eq!(recent[1].1, MetricKind::Histogram);
+/// ```
+pub struct RingBufferRecorder<R, C = SystemClock> {
+    inner: R,
+    capacity: usize,
+    clock: C,
+    events: Mutex<VecDeque<(Key, MetricKind, f64, Instant)>>,
+}
+
+impl<R> RingBufferRecorder<R> {
+    /// Creates a new `RingBufferRecorder` around the given recorder, holding at most `capacity`
+    /// operations.
+    pub fn new(inner: R, capacity: usize) -> Self {
+        RingBufferRecorder::with_clock(inner, capacity, SystemClock)
+    }
+}
+
+impl<R, C: Clock> RingBufferRecorder<R, C> {
+    /// Creates a new `RingBufferRecorder` around the given recorder, holding at most `capacity`
+    /// operations, and timing them with the given clock.
+    pub fn with_clock(inner: R, capacity: usize, clock: C) -> Self {
+        RingBufferRecorder {
+            inner,
+            capacity,
+            clock,
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Gets the most recent operations, from oldest to newest.
+    ///
+    /// Each operation is given as its key, the kind of metric, the value, and the time it was
+    /// recorded.
+    pub fn recent(&self) -> Vec<(Key, MetricKind, f64, Instant)> {
+        let events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        events.iter().cloned().collect()
+    }
+
+    /// Gets a reference to the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the wrapped recorder.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn push(&self, key: Key, kind: MetricKind, value: f64) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let now = self.clock.now();
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back((key, kind, value, now));
+    }
+}
+
+impl<R: Recorder, C: Clock> Recorder for RingBufferRecorder<R, C> {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.push(key.clone(), MetricKind::Counter, value as f64);
+        self.inner.increment_counter(key, value);
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.push(key.clone(), MetricKind::Gauge, value as f64);
+        self.inner.update_gauge(key, value);
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.push(key.clone(), MetricKind::Histogram, value as f64);
+        self.inner.record_histogram(key, value);
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        self.push(key.clone(), MetricKind::Histogram, value as f64);
+        self.inner.record_histogram_many(key, value, count);
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        self.inner
+            .record_histogram_buckets(key, buckets, sum, count);
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.push(key.clone(), MetricKind::Histogram, value as f64);
+        self.inner.try_record_histogram(key, value)
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        self.push(key.clone(), MetricKind::Histogram, value as f64);
+        self.inner
+            .record_histogram_with_exemplar(key, value, exemplar);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        self.inner.counter_value(key)
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        self.inner.gauge_value(key)
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.inner.describe_counter(key, description);
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.inner.describe_gauge(key, description);
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.inner.describe_histogram(key, description);
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.inner.describe_with_meta(key, meta);
+    }
+
+    fn record_batch(&self, ops: &[MetricOp]) {
+        for op in ops {
+            match op {
+                MetricOp::IncrementCounter(key, value) => {
+                    self.push(key.clone(), MetricKind::Counter, *value as f64)
+                }
+                MetricOp::UpdateGauge(key, value) => {
+                    self.push(key.clone(), MetricKind::Gauge, *value as f64)
+                }
+                MetricOp::RecordHistogram(key, value) => {
+                    self.push(key.clone(), MetricKind::Histogram, *value as f64)
+                }
+            }
+        }
+        self.inner.record_batch(ops);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RingBufferRecorder;
+    use crate::{test_util::CapturingRecorder, Clock, MockClock};
+    use metrics::Recorder;
+    use metrics_core::{Key, MetricKind};
+    use std::time::Duration;
+
+    #[test]
+    fn test_ring_buffer_retains_most_recent() {
+        let recorder = RingBufferRecorder::new(CapturingRecorder::default(), 3);
+        assert!(recorder.recent().is_empty());
+
+        for i in 0..10 {
+            recorder.increment_counter(Key::from_name("requests"), i);
+        }
+        recorder.update_gauge(Key::from_name("connections"), -4);
+
+        let recent = recorder.recent();
+        assert_eq!(recent.len(), 3);
+
+        let summary = recent
+            .iter()
+            .map(|(key, kind, value, _)| (key.name().to_string(), *kind, *value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("requests".to_string(), MetricKind::Counter, 8.0),
+                ("requests".to_string(), MetricKind::Counter, 9.0),
+                ("connections".to_string(), MetricKind::Gauge, -4.0),
+            ]
+        );
+        assert!(recent[0].3 <= recent[2].3);
+
+        // Everything still reaches the wrapped recorder.
+        assert_eq!(recorder.inner().take_ops().len(), 11);
+    }
+
+    #[test]
+    fn test_ring_buffer_zero_capacity() {
+        let recorder = RingBufferRecorder::new(CapturingRecorder::default(), 0);
+        recorder.record_histogram(Key::from_name("latency"), 42);
+        assert!(recorder.recent().is_empty());
+        assert_eq!(recorder.inner().take_ops().len(), 1);
+    }
+
+    #[test]
+    fn test_ring_buffer_with_clock() {
+        let clock = MockClock::new();
+        let start = clock.now();
+        let recorder =
+            RingBufferRecorder::with_clock(CapturingRecorder::default(), 3, clock.clone());
+
+        recorder.increment_counter(Key::from_name("requests"), 1);
+        clock.advance(Duration::from_secs(2));
+        recorder.increment_counter(Key::from_name("requests"), 1);
+        clock.advance(Duration::from_millis(250));
+        recorder.increment_counter(Key::from_name("requests"), 1);
+
+        let times = recorder
+            .recent()
+            .iter()
+            .map(|(_, _, _, at)| *at - start)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            vec![
+                Duration::from_secs(0),
+                Duration::from_secs(2),
+                Duration::from_millis(2250)
+            ]
+        );
+    }
+}
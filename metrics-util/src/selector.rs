@@ -0,0 +1,308 @@
+use metrics_core::Key;
+use std::{error::Error, fmt, str::Chars};
+
+/// Errors during selector parsing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectorError {
+    /// The selector was missing its opening or closing brace.
+    MissingBrace,
+
+    /// A label matcher was missing its label name.
+    MissingLabel,
+
+    /// A label matcher had an operator that is not supported.
+    UnsupportedOperator(String),
+
+    /// A label value was not a properly terminated, double-quoted string.
+    InvalidValue,
+
+    /// There was unexpected input after the end of the selector.
+    TrailingInput,
+}
+
+impl Error for SelectorError {}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectorError::MissingBrace => write!(f, "selector is missing a brace"),
+            SelectorError::MissingLabel => write!(f, "matcher is missing a label name"),
+            SelectorError::UnsupportedOperator(op) => {
+                write!(f, "matcher operator '{}' is not supported", op)
+            }
+            SelectorError::InvalidValue => write!(f, "matcher value must be a quoted string"),
+            SelectorError::TrailingInput => write!(f, "unexpected input after selector"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Matcher {
+    Equal(String, String),
+    NotEqual(String, String),
+}
+
+impl Matcher {
+    fn matches(&self, key: &Key) -> bool {
+        match self {
+            Matcher::Equal(label, value) => label_value(key, label) == value,
+            Matcher::NotEqual(label, value) => label_value(key, label) != value,
+        }
+    }
+}
+
+/// A Prometheus-style label selector.
+///
+/// Selectors are parsed from the familiar `name{label="value", other!="value"}` syntax, where
+/// either the metric name or the label matchers are optional, and can then be matched against a [`Key`].  Exact (`=`) and negated
+/// (`!=`) matchers are supported.  As in Prometheus, a label that is not present on a key is
+/// treated as having an empty value, so `{env!="test"}` matches keys without an `env` label.
+///
+/// Regular expression matchers (`=~` and `!~`) are not supported, and are rejected when parsing.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_core::{Key, Label};
+/// # use metrics_util::Selector;
+/// let selector = Selector::parse(r#"{job="api", env!="test"}"#).expect("invalid selector");
+///
+/// let key = Key::from_name_and_labels("requests", vec![Label::new("job", "api")]);
+/// assert!(selector.matches(&key));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector {
+    name: Option<String>,
+    matchers: Vec<Matcher>,
+}
+
+impl Selector {
+    /// Parses a selector from its string form.
+    ///
+    /// Either the name or the braces can be left out, so `requests`, `{job="api"}` and
+    /// `requests{job="api"}` are all valid selectors.
+    pub fn parse(input: &str) -> Result<Selector, SelectorError> {
+        let input = input.trim();
+        let name_len = input
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == ':'))
+            .unwrap_or(input.len());
+        let name = &input[..name_len];
+
+        let mut matchers = Vec::new();
+        let mut chars = input[name_len..].chars();
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None if !name.is_empty() => {}
+            Some('{') => loop {
+                skip_whitespace(&mut chars);
+                if chars.as_str().starts_with('}') {
+                    chars.next();
+                    break;
+                }
+
+                matchers.push(parse_matcher(&mut chars)?);
+
+                skip_whitespace(&mut chars);
+                match chars.next() {
+                    None => return Err(SelectorError::MissingBrace),
+                    Some(',') => continue,
+                    Some('}') => break,
+                    Some(_) => return Err(SelectorError::TrailingInput),
+                }
+            },
+            _ => return Err(SelectorError::MissingBrace),
+        }
+        if !chars.as_str().trim().is_empty() {
+            return Err(SelectorError::TrailingInput);
+        }
+
+        let name = if name.is_empty() {
+            None
+        } else {
+            Some(name.to_string())
+        };
+
+        Ok(Selector { name, matchers })
+    }
+
+    /// Whether or not the given key is matched by this selector.
+    ///
+    /// A key matches when its name is equal to the selector name, if one was given, and every
+    /// label matcher holds.
+    pub fn matches(&self, key: &Key) -> bool {
+        if let Some(name) = &self.name {
+            if key.name() != name.as_str() {
+                return false;
+            }
+        }
+
+        self.matchers.iter().all(|m| m.matches(key))
+    }
+}
+
+fn label_value<'a>(key: &'a Key, label: &str) -> &'a str {
+    key.labels()
+        .find(|l| l.key() == label)
+        .map(|l| l.value())
+        .unwrap_or("")
+}
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars.as_str().starts_with(char::is_whitespace) {
+        chars.next();
+    }
+}
+
+fn parse_matcher(chars: &mut Chars) -> Result<Matcher, SelectorError> {
+    let label = chars
+        .as_str()
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect::<String>();
+    if label.is_empty() {
+        return Err(SelectorError::MissingLabel);
+    }
+    for _ in 0..label.chars().count() {
+        chars.next();
+    }
+
+    skip_whitespace(chars);
+    let op = chars
+        .as_str()
+        .chars()
+        .take_while(|c| *c == '=' || *c == '!' || *c == '~')
+        .collect::<String>();
+    for _ in 0..op.chars().count() {
+        chars.next();
+    }
+
+    skip_whitespace(chars);
+    let value = parse_quoted(chars)?;
+
+    match op.as_str() {
+        "=" => Ok(Matcher::Equal(label, value)),
+        "!=" => Ok(Matcher::NotEqual(label, value)),
+        _ => Err(SelectorError::UnsupportedOperator(op)),
+    }
+}
+
+fn parse_quoted(chars: &mut Chars) -> Result<String, SelectorError> {
+    if chars.next() != Some('"') {
+        return Err(SelectorError::InvalidValue);
+    }
+
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            None => return Err(SelectorError::InvalidValue),
+            Some('"') => return Ok(value),
+            Some('\\') => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some(c) => value.push(c),
+                None => return Err(SelectorError::InvalidValue),
+            },
+            Some(c) => value.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Selector, SelectorError};
+    use metrics_core::{Key, Label};
+
+    #[test]
+    fn test_selector_parse() {
+        let selector = Selector::parse(r#"{job="api", env!="test"}"#);
+        assert!(selector.is_ok());
+
+        let selector = Selector::parse(r#"requests { code = "200" , }"#);
+        assert!(selector.is_ok());
+
+        let selector = Selector::parse("{}");
+        assert!(selector.is_ok());
+
+        assert_eq!(
+            Selector::parse(r#"job="api""#),
+            Err(SelectorError::MissingBrace)
+        );
+        assert_eq!(
+            Selector::parse(r#"{job="api""#),
+            Err(SelectorError::MissingBrace)
+        );
+        assert_eq!(
+            Selector::parse(r#"{="api"}"#),
+            Err(SelectorError::MissingLabel)
+        );
+        assert_eq!(
+            Selector::parse(r#"{job=api}"#),
+            Err(SelectorError::InvalidValue)
+        );
+        assert_eq!(
+            Selector::parse(r#"{job=~"a.*"}"#),
+            Err(SelectorError::UnsupportedOperator("=~".to_string()))
+        );
+        assert_eq!(
+            Selector::parse(r#"{job="api" env="test"}"#),
+            Err(SelectorError::TrailingInput)
+        );
+        assert_eq!(
+            Selector::parse(r#"{job="api"} extra"#),
+            Err(SelectorError::TrailingInput)
+        );
+        assert_eq!(
+            Selector::parse(r#"{path="}"} extra"#),
+            Err(SelectorError::TrailingInput)
+        );
+        assert_eq!(Selector::parse(""), Err(SelectorError::MissingBrace));
+        assert_eq!(
+            Selector::parse("requests extra"),
+            Err(SelectorError::MissingBrace)
+        );
+    }
+
+    #[test]
+    fn test_selector_matches() {
+        let selector = Selector::parse(r#"{job="api", env!="test"}"#).unwrap();
+
+        let prod = Key::from_name_and_labels(
+            "requests",
+            vec![Label::new("job", "api"), Label::new("env", "prod")],
+        );
+        let test = Key::from_name_and_labels(
+            "requests",
+            vec![Label::new("job", "api"), Label::new("env", "test")],
+        );
+        let no_env = Key::from_name_and_labels("requests", vec![Label::new("job", "api")]);
+        let other_job = Key::from_name_and_labels("requests", vec![Label::new("job", "db")]);
+
+        assert!(selector.matches(&prod));
+        assert!(!selector.matches(&test));
+        assert!(selector.matches(&no_env));
+        assert!(!selector.matches(&other_job));
+
+        let named = Selector::parse(r#"requests{job="api"}"#).unwrap();
+        assert!(named.matches(&prod));
+        assert!(!named.matches(&Key::from_name_and_labels(
+            "errors",
+            vec![Label::new("job", "api")]
+        )));
+
+        // A bare name matches every key with that name, whatever its labels.
+        let bare = Selector::parse("requests").unwrap();
+        assert!(bare.matches(&prod));
+        assert!(bare.matches(&Key::from_name("requests")));
+        assert!(!bare.matches(&Key::from_name("errors")));
+
+        let braced = Selector::parse(r#"{path="a}b"}"#).unwrap();
+        assert!(braced.matches(&Key::from_name_and_labels(
+            "requests",
+            vec![Label::new("path", "a}b")]
+        )));
+
+        let escaped = Selector::parse(r#"{path="a\"b"}"#).unwrap();
+        assert!(escaped.matches(&Key::from_name_and_labels(
+            "requests",
+            vec![Label::new("path", "a\"b")]
+        )));
+    }
+}
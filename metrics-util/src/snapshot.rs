@@ -0,0 +1,714 @@
+use crate::value::sanitize_prometheus_name;
+use metrics_core::{Key, Label, RenderOptions};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Values with a magnitude smaller than this are treated as zero by sketches.
+const SKETCH_MIN_VALUE: f64 = 1e-9;
+
+/// Errors during the merging of histogram snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeError {
+    /// One snapshot holds buckets, and the other holds a sketch.
+    ModeMismatch,
+
+    /// The snapshots hold buckets with different bounds.
+    BoundsMismatch,
+
+    /// The snapshots hold sketches with different relative accuracies.
+    AccuracyMismatch,
+}
+
+impl Error for MergeError {}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeError::ModeMismatch => write!(f, "cannot merge buckets with a sketch"),
+            MergeError::BoundsMismatch => write!(f, "bucket bounds do not match"),
+            MergeError::AccuracyMismatch => write!(f, "sketch accuracies do not match"),
+        }
+    }
+}
+
+/// An exemplar: a single observation, identified by a set of labels such as a trace ID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exemplar {
+    labels: Vec<Label>,
+    value: f64,
+    timestamp: SystemTime,
+}
+
+impl Exemplar {
+    /// The labels identifying the observation.
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    /// The observed value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// When the value was observed.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Data {
+    Buckets {
+        bounds: Vec<f64>,
+        // One count per bound, plus a final count for values above the highest bound.
+        counts: Vec<u64>,
+        // The most recent exemplar recorded into each bucket, if any.
+        exemplars: Vec<Option<Exemplar>>,
+    },
+    Sketch {
+        relative_accuracy: f64,
+        gamma_ln: f64,
+        negative: BTreeMap<i32, u64>,
+        zero: u64,
+        positive: BTreeMap<i32, u64>,
+    },
+}
+
+/// A mergeable summary of the values recorded into a histogram.
+///
+/// Aggregating histograms from several shards or instances requires a representation that can be
+/// combined without access to the original values.  `HistogramSnapshot` tracks the count and sum
+/// of recorded values, along with their distribution in one of two modes:
+///
+/// - **buckets**, where values are counted against a fixed set of upper bounds, as with
+///   Prometheus histograms.  Quantiles are estimated by interpolating within a bucket.
+/// - **sketch**, where values are counted in logarithmically sized bins, such that any quantile
+///   can be estimated to within a given relative accuracy, no matter how the values are
+///   distributed.
+///
+/// Snapshots can be [merged](HistogramSnapshot::merge) when they use the same mode: the same
+/// bucket bounds, or the same sketch accuracy.  The merged snapshot is identical to one which had
+/// every value from both snapshots recorded into it.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::HistogramSnapshot;
+/// let mut first = HistogramSnapshot::with_sketch(0.01);
+/// let mut second = HistogramSnapshot::with_sketch(0.01);
+/// for value in 1..=100 {
+///     first.record(value as f64);
+///     second.record((value + 100) as f64);
+/// }
+///
+/// first.merge(&second).expect("snapshots should be compatible");
+/// assert_eq!(first.count(), 200);
+///
+/// let median = first.quantile(0.5).unwrap();
+/// assert!((median - 100.0).abs() <= 100.0 * 0.01);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    count: u64,
+    sum: f64,
+    data: Data,
+}
+
+impl HistogramSnapshot {
+    /// Creates a new, empty snapshot that counts values against the given bucket upper bounds.
+    ///
+    /// The bounds are sorted, and duplicates are removed.  Values above the highest bound are
+    /// counted in an implicit `+Inf` bucket.
+    ///
+    /// # Panics
+    /// Panics if any of the bounds is NaN.
+    pub fn with_buckets(bounds: &[f64]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_by(|a, b| a.partial_cmp(b).expect("bucket bounds must not be NaN"));
+        bounds.dedup();
+        let counts = vec![0; bounds.len() + 1];
+        let exemplars = vec![None; bounds.len() + 1];
+
+        HistogramSnapshot {
+            count: 0,
+            sum: 0.0,
+            data: Data::Buckets {
+                bounds,
+                counts,
+                exemplars,
+            },
+        }
+    }
+
+    /// Creates a new, empty snapshot that holds a sketch with the given relative accuracy.
+    ///
+    /// A relative accuracy of `0.01` means that estimated quantiles are within 1% of the value of
+    /// the true quantile.
+    ///
+    /// # Panics
+    /// Panics if `relative_accuracy` is not between zero and one, exclusive.
+    pub fn with_sketch(relative_accuracy: f64) -> Self {
+        assert!(
+            relative_accuracy > 0.0 && relative_accuracy < 1.0,
+            "relative accuracy must be between zero and one"
+        );
+
+        let gamma = (1.0 + relative_accuracy) / (1.0 - relative_accuracy);
+        HistogramSnapshot {
+            count: 0,
+            sum: 0.0,
+            data: Data::Sketch {
+                relative_accuracy,
+                gamma_ln: gamma.ln(),
+                negative: BTreeMap::new(),
+                zero: 0,
+                positive: BTreeMap::new(),
+            },
+        }
+    }
+
+    /// Records a value.
+    ///
+    /// NaN values can't be placed in any bucket or bin, so they're ignored.
+    pub fn record(&mut self, value: f64) {
+        self.record_inner(value, None);
+    }
+
+    /// Records a value, along with an exemplar identifying it, such as by its trace ID.
+    ///
+    /// With buckets, the exemplar replaces any earlier exemplar of the bucket the value falls
+    /// into, so each bucket holds its most recent exemplar.  Sketches don't hold exemplars, so
+    /// with a sketch, the exemplar is ignored and the value is recorded normally.
+    pub fn record_with_exemplar(&mut self, value: f64, labels: &[Label]) {
+        let exemplar = Exemplar {
+            labels: labels.to_vec(),
+            value,
+            timestamp: SystemTime::now(),
+        };
+        self.record_inner(value, Some(exemplar));
+    }
+
+    fn record_inner(&mut self, value: f64, exemplar: Option<Exemplar>) {
+        if value.is_nan() {
+            return;
+        }
+        self.count += 1;
+        self.sum += value;
+
+        match &mut self.data {
+            Data::Buckets {
+                bounds,
+                counts,
+                exemplars,
+            } => {
+                let idx = bounds
+                    .iter()
+                    .position(|bound| value <= *bound)
+                    .unwrap_or(bounds.len());
+                counts[idx] += 1;
+                if exemplar.is_some() {
+                    exemplars[idx] = exemplar;
+                }
+            }
+            Data::Sketch {
+                gamma_ln,
+                negative,
+                zero,
+                positive,
+                ..
+            } => {
+                if value.abs() < SKETCH_MIN_VALUE {
+                    *zero += 1;
+                } else {
+                    let bins = if value > 0.0 { positive } else { negative };
+                    // Casting a float that's out of range to an integer is undefined behavior on
+                    // older compilers, and infinite values, or tiny accuracies, give indexes which
+                    // are out of range, so the index is clamped first.
+                    let idx = (value.abs().ln() / *gamma_ln)
+                        .ceil()
+                        .max(f64::from(std::i32::MIN))
+                        .min(f64::from(std::i32::MAX)) as i32;
+                    *bins.entry(idx).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    /// Merges another snapshot into this one.
+    ///
+    /// # Errors
+    /// An error is returned, and this snapshot is left unchanged, if the snapshots use different
+    /// modes, different bucket bounds, or different sketch accuracies.
+    pub fn merge(&mut self, other: &HistogramSnapshot) -> Result<(), MergeError> {
+        match (&mut self.data, &other.data) {
+            (
+                Data::Buckets {
+                    bounds,
+                    counts,
+                    exemplars,
+                },
+                Data::Buckets {
+                    bounds: other_bounds,
+                    counts: other_counts,
+                    exemplars: other_exemplars,
+                },
+            ) => {
+                if bounds != other_bounds {
+                    return Err(MergeError::BoundsMismatch);
+                }
+                for (count, other) in counts.iter_mut().zip(other_counts) {
+                    *count += other;
+                }
+                for (exemplar, other) in exemplars.iter_mut().zip(other_exemplars) {
+                    let newer = match (&exemplar, other) {
+                        (_, None) => false,
+                        (None, Some(_)) => true,
+                        (Some(ours), Some(theirs)) => theirs.timestamp > ours.timestamp,
+                    };
+                    if newer {
+                        *exemplar = other.clone();
+                    }
+                }
+            }
+            (
+                Data::Sketch {
+                    relative_accuracy,
+                    negative,
+                    zero,
+                    positive,
+                    ..
+                },
+                Data::Sketch {
+                    relative_accuracy: other_accuracy,
+                    negative: other_negative,
+                    zero: other_zero,
+                    positive: other_positive,
+                    ..
+                },
+            ) => {
+                if relative_accuracy != other_accuracy {
+                    return Err(MergeError::AccuracyMismatch);
+                }
+                for (idx, count) in other_negative {
+                    *negative.entry(*idx).or_insert(0) += count;
+                }
+                *zero += other_zero;
+                for (idx, count) in other_positive {
+                    *positive.entry(*idx).or_insert(0) += count;
+                }
+            }
+            _ => return Err(MergeError::ModeMismatch),
+        }
+
+        self.count += other.count;
+        self.sum += other.sum;
+        Ok(())
+    }
+
+    /// Gets the number of values recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Gets the sum of all values recorded.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Whether or not any values have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Gets the bucket upper bounds and their cumulative counts, if this snapshot holds buckets.
+    ///
+    /// The implicit `+Inf` bucket is not included, as its cumulative count is always equal to
+    /// [`count`](HistogramSnapshot::count).
+    pub fn buckets(&self) -> Option<Vec<(f64, u64)>> {
+        match &self.data {
+            Data::Buckets { bounds, counts, .. } => {
+                let mut cumulative = 0;
+                let buckets = bounds
+                    .iter()
+                    .zip(counts)
+                    .map(|(bound, count)| {
+                        cumulative += count;
+                        (*bound, cumulative)
+                    })
+                    .collect();
+                Some(buckets)
+            }
+            Data::Sketch { .. } => None,
+        }
+    }
+
+    /// Gets the most recent exemplar of each bucket, if this snapshot holds buckets.
+    ///
+    /// Each exemplar is paired with the upper bound of its bucket, which is infinite for the
+    /// implicit `+Inf` bucket.  Buckets without an exemplar are skipped.
+    pub fn exemplars(&self) -> Option<Vec<(f64, &Exemplar)>> {
+        match &self.data {
+            Data::Buckets {
+                bounds, exemplars, ..
+            } => {
+                let exemplars = bounds
+                    .iter()
+                    .cloned()
+                    .chain(Some(std::f64::INFINITY))
+                    .zip(exemplars)
+                    .filter_map(|(bound, exemplar)| exemplar.as_ref().map(|e| (bound, e)))
+                    .collect();
+                Some(exemplars)
+            }
+            Data::Sketch { .. } => None,
+        }
+    }
+
+    /// Renders this snapshot as a histogram in the OpenMetrics text format.
+    ///
+    /// With buckets, a cumulative `_bucket` sample is rendered for each bucket, including the
+    /// `+Inf` bucket, followed by the `_count` and `_sum` samples.  A bucket holding an exemplar
+    /// has it appended to its sample, in the `# {trace_id="..."} value timestamp` form.  With a
+    /// sketch, only the `_count` and `_sum` samples are rendered.
+    ///
+    /// Characters in the metric name which aren't valid in Prometheus are replaced with
+    /// underscores, and label values are escaped.  Every line, including the last, ends with a
+    /// newline.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// # use metrics_util::HistogramSnapshot;
+    /// let mut snapshot = HistogramSnapshot::with_buckets(&[0.1, 1.0]);
+    /// snapshot.record(0.05);
+    /// snapshot.record_with_exemplar(0.5, &[Label::new("trace_id", "4bf92f35")]);
+    ///
+    /// let rendered = snapshot.render_openmetrics(&Key::from_name("latency"));
+    /// assert!(rendered.starts_with("latency_bucket{le=\"0.1\"} 1\n"));
+    /// assert!(rendered.contains("latency_bucket{le=\"1\"} 2 # {trace_id=\"4bf92f35\"} 0.5 "));
+    /// assert!(rendered.ends_with("latency_count 2\nlatency_sum 0.55\n"));
+    /// ```
+    pub fn render_openmetrics(&self, key: &Key) -> String {
+        let key = key.clone().map_name(|name| sanitize_prometheus_name(&name));
+        let opts = RenderOptions::new();
+
+        let mut output = String::new();
+        if let Data::Buckets {
+            bounds,
+            counts,
+            exemplars,
+        } = &self.data
+        {
+            let les = bounds
+                .iter()
+                .map(|bound| bound.to_string())
+                .chain(Some("+Inf".to_string()));
+
+            let mut cumulative = 0;
+            for ((le, count), exemplar) in les.zip(counts).zip(exemplars) {
+                cumulative += count;
+
+                let mut bucket_key = key.clone().map_name(|name| format!("{}_bucket", name));
+                bucket_key.add_labels(vec![Label::new("le", le)]);
+                output.push_str(&bucket_key.render_with(&opts));
+                output.push(' ');
+                output.push_str(&cumulative.to_string());
+                if let Some(exemplar) = exemplar {
+                    push_exemplar(&mut output, exemplar);
+                }
+                output.push('\n');
+            }
+        }
+
+        let count_key = key.clone().map_name(|name| format!("{}_count", name));
+        output.push_str(&format!(
+            "{} {}\n",
+            count_key.render_with(&opts),
+            self.count
+        ));
+        let sum_key = key.map_name(|name| format!("{}_sum", name));
+        output.push_str(&format!("{} {}\n", sum_key.render_with(&opts), self.sum));
+        output
+    }
+
+    /// Estimates the value at the given quantile.
+    ///
+    /// With buckets, the estimate is interpolated linearly within the bucket that the quantile
+    /// falls into, and quantiles falling into the `+Inf` bucket are estimated as the highest
+    /// bound.  With a sketch, the estimate is within the configured relative accuracy.
+    ///
+    /// Returns `None` if no values have been recorded.
+    pub fn quantile(&self, quantile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let quantile = if quantile < 0.0 {
+            0.0
+        } else if quantile > 1.0 {
+            1.0
+        } else {
+            quantile
+        };
+
+        match &self.data {
+            Data::Buckets { bounds, counts, .. } => {
+                let rank = quantile * self.count as f64;
+                let mut cumulative = 0;
+                for (idx, count) in counts.iter().enumerate() {
+                    let previous = cumulative;
+                    cumulative += count;
+                    if (cumulative as f64) < rank || *count == 0 {
+                        continue;
+                    }
+
+                    if idx == bounds.len() {
+                        return bounds.last().cloned().or(Some(std::f64::INFINITY));
+                    }
+
+                    let upper = bounds[idx];
+                    let lower = if idx == 0 {
+                        if upper > 0.0 {
+                            0.0
+                        } else {
+                            return Some(upper);
+                        }
+                    } else {
+                        bounds[idx - 1]
+                    };
+                    let fraction = (rank - previous as f64) / *count as f64;
+                    return Some(lower + (upper - lower) * fraction);
+                }
+
+                bounds.last().cloned()
+            }
+            Data::Sketch {
+                gamma_ln,
+                negative,
+                zero,
+                positive,
+                ..
+            } => {
+                let rank = (quantile * (self.count - 1) as f64) as u64;
+                let value_at =
+                    |idx: i32| 2.0 * (*gamma_ln * idx as f64).exp() / (gamma_ln.exp() + 1.0);
+
+                let mut cumulative = 0;
+                for (idx, count) in negative.iter().rev() {
+                    cumulative += count;
+                    if cumulative > rank {
+                        return Some(-value_at(*idx));
+                    }
+                }
+
+                cumulative += zero;
+                if cumulative > rank {
+                    return Some(0.0);
+                }
+
+                for (idx, count) in positive {
+                    cumulative += count;
+                    if cumulative > rank {
+                        return Some(value_at(*idx));
+                    }
+                }
+
+                None
+            }
+        }
+    }
+}
+
+fn push_exemplar(output: &mut String, exemplar: &Exemplar) {
+    let labels = exemplar
+        .labels
+        .iter()
+        .map(|label| format!("{}=\"{}\"", label.key(), label.value_escaped_prometheus()))
+        .collect::<Vec<_>>()
+        .join(",");
+    let timestamp = exemplar
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0);
+    output.push_str(&format!(
+        " # {{{}}} {} {}",
+        labels, exemplar.value, timestamp
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Exemplar, HistogramSnapshot, MergeError};
+    use metrics_core::{Key, Label};
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_merge_buckets() {
+        let mut first = HistogramSnapshot::with_buckets(&[1.0, 5.0, 10.0]);
+        for value in &[0.5, 2.0, 7.0] {
+            first.record(*value);
+        }
+
+        let mut second = HistogramSnapshot::with_buckets(&[10.0, 1.0, 5.0]);
+        for value in &[3.0, 4.0, 20.0] {
+            second.record(*value);
+        }
+
+        first.merge(&second).unwrap();
+        assert_eq!(first.count(), 6);
+        assert_eq!(first.sum(), 36.5);
+        assert_eq!(first.buckets(), Some(vec![(1.0, 1), (5.0, 4), (10.0, 5)]));
+
+        // The median is the second of the three values in the (1, 5] bucket.
+        let median = first.quantile(0.5).unwrap();
+        assert!((median - 11.0 / 3.0).abs() < 1e-9);
+        assert_eq!(first.quantile(1.0), Some(10.0));
+
+        let mismatched = HistogramSnapshot::with_buckets(&[1.0, 2.0]);
+        assert_eq!(first.merge(&mismatched), Err(MergeError::BoundsMismatch));
+        assert_eq!(first.count(), 6);
+    }
+
+    #[test]
+    fn test_merge_sketches() {
+        let mut first = HistogramSnapshot::with_sketch(0.01);
+        let mut second = HistogramSnapshot::with_sketch(0.01);
+        let mut all = Vec::new();
+        for value in 1..=1000 {
+            let value = value as f64;
+            if value as u64 % 3 == 0 {
+                first.record(value);
+            } else {
+                second.record(-value);
+            }
+            all.push(if value as u64 % 3 == 0 { value } else { -value });
+        }
+        first.record(0.0);
+        all.push(0.0);
+        all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        first.merge(&second).unwrap();
+        assert_eq!(first.count(), 1001);
+        assert_eq!(first.sum(), all.iter().sum::<f64>());
+        assert_eq!(first.buckets(), None);
+
+        for quantile in &[0.0, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99, 1.0] {
+            let expected = all[(quantile * (all.len() - 1) as f64) as usize];
+            let estimate = first.quantile(*quantile).unwrap();
+            assert!(
+                (estimate - expected).abs() <= expected.abs() * 0.01,
+                "q{}: estimated {}, expected {}",
+                quantile,
+                estimate,
+                expected
+            );
+        }
+
+        let mismatched = HistogramSnapshot::with_sketch(0.05);
+        assert_eq!(first.merge(&mismatched), Err(MergeError::AccuracyMismatch));
+
+        let buckets = HistogramSnapshot::with_buckets(&[1.0]);
+        assert_eq!(first.merge(&buckets), Err(MergeError::ModeMismatch));
+        assert_eq!(first.count(), 1001);
+    }
+
+    #[test]
+    fn test_exemplars() {
+        let mut snapshot = HistogramSnapshot::with_buckets(&[1.0, 5.0]);
+        snapshot.record_with_exemplar(0.5, &[Label::new("trace_id", "first")]);
+        snapshot.record_with_exemplar(0.7, &[Label::new("trace_id", "second")]);
+        snapshot.record(3.0);
+        snapshot.record_with_exemplar(9.0, &[Label::new("trace_id", "a\"b")]);
+
+        let exemplars = snapshot.exemplars().unwrap();
+        assert_eq!(exemplars.len(), 2);
+        assert_eq!(exemplars[0].0, 1.0);
+        assert_eq!(exemplars[0].1.value(), 0.7);
+        assert_eq!(
+            exemplars[0].1.labels(),
+            &[Label::new("trace_id", "second")][..]
+        );
+        assert_eq!(exemplars[1].0, std::f64::INFINITY);
+        assert_eq!(exemplars[1].1.value(), 9.0);
+
+        let seconds = |exemplar: &Exemplar| {
+            exemplar
+                .timestamp()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs_f64()
+        };
+        let key = Key::from_name_and_labels("rpc.latency", vec![Label::new("method", "get")]);
+        assert_eq!(
+            snapshot.render_openmetrics(&key),
+            format!(
+                "rpc_latency_bucket{{method=\"get\",le=\"1\"}} 2 # {{trace_id=\"second\"}} 0.7 {}\n\
+                 rpc_latency_bucket{{method=\"get\",le=\"5\"}} 3\n\
+                 rpc_latency_bucket{{method=\"get\",le=\"+Inf\"}} 4 # {{trace_id=\"a\\\"b\"}} 9 {}\n\
+                 rpc_latency_count{{method=\"get\"}} 4\n\
+                 rpc_latency_sum{{method=\"get\"}} 13.2\n",
+                seconds(exemplars[0].1),
+                seconds(exemplars[1].1)
+            )
+        );
+
+        // Merging keeps the most recent exemplar of each bucket.
+        let mut other = HistogramSnapshot::with_buckets(&[1.0, 5.0]);
+        other.record_with_exemplar(2.0, &[Label::new("trace_id", "other")]);
+        other.record_with_exemplar(0.1, &[Label::new("trace_id", "latest")]);
+        snapshot.merge(&other).unwrap();
+
+        let merged = snapshot.exemplars().unwrap();
+        let traces = merged
+            .iter()
+            .map(|(bound, e)| (*bound, e.labels()[0].value().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            traces,
+            vec![
+                (1.0, "latest".to_string()),
+                (5.0, "other".to_string()),
+                (std::f64::INFINITY, "a\"b".to_string()),
+            ]
+        );
+
+        let mut sketch = HistogramSnapshot::with_sketch(0.01);
+        sketch.record_with_exemplar(1.0, &[Label::new("trace_id", "ignored")]);
+        assert_eq!(sketch.count(), 1);
+        assert!(sketch.exemplars().is_none());
+        assert_eq!(
+            sketch.render_openmetrics(&Key::from_name("latency")),
+            "latency_count 1\nlatency_sum 1\n"
+        );
+    }
+
+    #[test]
+    fn test_non_finite_values() {
+        let mut sketch = HistogramSnapshot::with_sketch(0.01);
+        sketch.record(std::f64::NAN);
+        assert_eq!(sketch.count(), 0);
+
+        // Infinite values land in the outermost bins, rather than overflowing the bin index.
+        sketch.record(std::f64::INFINITY);
+        sketch.record(std::f64::NEG_INFINITY);
+        sketch.record(1.0);
+        assert_eq!(sketch.count(), 3);
+        assert!(sketch.quantile(1.0).unwrap() > 1e300);
+        assert!(sketch.quantile(0.0).unwrap() < -1e300);
+
+        let mut buckets = HistogramSnapshot::with_buckets(&[1.0]);
+        buckets.record(std::f64::NAN);
+        assert_eq!(buckets.count(), 0);
+        assert_eq!(buckets.sum(), 0.0);
+    }
+
+    #[test]
+    fn test_empty_quantile() {
+        assert_eq!(HistogramSnapshot::with_sketch(0.01).quantile(0.5), None);
+        assert_eq!(HistogramSnapshot::with_buckets(&[1.0]).quantile(0.5), None);
+    }
+}
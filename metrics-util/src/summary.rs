@@ -0,0 +1,226 @@
+use crate::{Clock, SystemClock};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone)]
+struct AgeBucket {
+    start: Instant,
+    values: Vec<u64>,
+}
+
+/// A summary which calculates quantiles over a sliding window of recent values.
+///
+/// Quantiles calculated over every value ever recorded barely move once a process has been up for
+/// a while, and so hide changes in recent behavior.  `SlidingSummary` only keeps values recorded
+/// within the last `max_age`, in the same way as the summaries of the Prometheus client libraries.
+///
+/// Rather than tracking the age of each value, values are grouped into `age_buckets` buckets which
+/// each cover `max_age / age_buckets` of time, and a bucket is dropped as a whole once its oldest
+/// possible value is older than `max_age`.  Values are therefore kept for at most `max_age`, and
+/// at least `max_age` minus the width of one bucket.  More buckets make expiry smoother, at the
+/// cost of some bookkeeping.
+///
+/// Quantiles are exact, calculated from every value within the window, so memory usage is
+/// proportional to the number of values recorded within `max_age`.
+///
+/// Times are taken from the system clock by default, or from any other [`Clock`] given to
+/// [`with_clock`](SlidingSummary::with_clock).
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::SlidingSummary;
+/// # use std::time::Duration;
+/// let mut summary = SlidingSummary::new(Duration::from_secs(60), 6);
+/// for value in 1..=100 {
+///     summary.record(value);
+/// }
+///
+/// assert_eq!(summary.len(), 100);
+/// assert_eq!(summary.quantile(0.5), Some(51));
+/// assert_eq!(summary.quantile(1.0), Some(100));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlidingSummary<C = SystemClock> {
+    buckets: VecDeque<AgeBucket>,
+    max_age: Duration,
+    age_buckets: u32,
+    bucket_width: Duration,
+    clock: C,
+}
+
+impl SlidingSummary {
+    /// Creates a new, empty summary which keeps values for up to `max_age`, expiring them in
+    /// `age_buckets` steps.
+    ///
+    /// # Panics
+    /// Panics if `max_age` or `age_buckets` is zero.
+    pub fn new(max_age: Duration, age_buckets: u32) -> Self {
+        SlidingSummary::with_clock(max_age, age_buckets, SystemClock)
+    }
+}
+
+impl<C: Clock> SlidingSummary<C> {
+    /// Creates a new, empty summary which keeps values for up to `max_age`, expiring them in
+    /// `age_buckets` steps, and taking the time from the given clock.
+    ///
+    /// # Panics
+    /// Panics if `max_age` or `age_buckets` is zero.
+    pub fn with_clock(max_age: Duration, age_buckets: u32, clock: C) -> Self {
+        assert!(
+            max_age > Duration::from_secs(0),
+            "summary max age must be greater than zero"
+        );
+        assert!(
+            age_buckets > 0,
+            "summary age buckets must be greater than zero"
+        );
+
+        SlidingSummary {
+            buckets: VecDeque::with_capacity(age_buckets as usize + 1),
+            max_age,
+            age_buckets,
+            bucket_width: max_age / age_buckets,
+            clock,
+        }
+    }
+
+    /// Records a value.
+    pub fn record(&mut self, value: u64) {
+        let now = self.clock.now();
+        self.expire(now);
+
+        let width = self.bucket_width;
+        match self.buckets.back_mut() {
+            Some(bucket) if now < bucket.start + width => bucket.values.push(value),
+            _ => self.buckets.push_back(AgeBucket {
+                start: now,
+                values: vec![value],
+            }),
+        }
+    }
+
+    /// Calculates the value at the given quantile, out of the values within the window.
+    ///
+    /// The quantile is clamped between 0.0 and 1.0.  Returns `None` if there are no values within
+    /// the window.
+    pub fn quantile(&self, quantile: f64) -> Option<u64> {
+        let mut sorted = self.live_values().collect::<Vec<_>>();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_unstable();
+
+        let quantile = quantile.max(0.0);
+        let quantile = quantile.min(1.0);
+        let idx = (quantile * (sorted.len() - 1) as f64).round() as usize;
+        Some(sorted[idx])
+    }
+
+    /// Number of values within the window.
+    pub fn len(&self) -> usize {
+        self.live_values().count()
+    }
+
+    /// Whether or not there are no values within the window.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum age of the values kept by this summary.
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Number of steps old values are expired in.
+    pub fn age_buckets(&self) -> u32 {
+        self.age_buckets
+    }
+
+    fn live_values(&self) -> impl Iterator<Item = u64> + '_ {
+        let now = self.clock.now();
+        let max_age = self.max_age;
+        self.buckets
+            .iter()
+            .filter(move |bucket| now < bucket.start + max_age)
+            .flat_map(|bucket| bucket.values.iter().cloned())
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(bucket) = self.buckets.front() {
+            if now < bucket.start + self.max_age {
+                break;
+            }
+            self.buckets.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SlidingSummary;
+    use crate::MockClock;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sliding_summary_expires_old_values() {
+        let clock = MockClock::new();
+        let mut summary = SlidingSummary::with_clock(Duration::from_secs(60), 3, clock.clone());
+        assert_eq!(summary.quantile(0.5), None);
+
+        // A burst of slow requests...
+        for _ in 0..100 {
+            summary.record(1_000);
+        }
+        assert_eq!(summary.quantile(0.5), Some(1_000));
+
+        // ...followed by fast ones, which only slowly take over the quantiles.
+        clock.advance(Duration::from_secs(30));
+        for _ in 0..50 {
+            summary.record(10);
+        }
+        assert_eq!(summary.len(), 150);
+        assert_eq!(summary.quantile(0.5), Some(1_000));
+        assert_eq!(summary.quantile(0.0), Some(10));
+
+        // Once the slow requests are older than the max age, they no longer count.
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(summary.len(), 50);
+        assert_eq!(summary.quantile(0.5), Some(10));
+        assert_eq!(summary.quantile(1.0), Some(10));
+
+        clock.advance(Duration::from_secs(30));
+        assert!(summary.is_empty());
+        assert_eq!(summary.quantile(0.99), None);
+
+        summary.record(5);
+        assert_eq!(summary.quantile(0.5), Some(5));
+    }
+
+    #[test]
+    fn test_sliding_summary_expires_by_bucket() {
+        let clock = MockClock::new();
+        let mut summary = SlidingSummary::with_clock(Duration::from_secs(60), 3, clock.clone());
+
+        // Both values land in the same 20 second bucket, so they expire together, once the
+        // first of them is 60 seconds old.
+        summary.record(1);
+        clock.advance(Duration::from_secs(15));
+        summary.record(2);
+        clock.advance(Duration::from_secs(10));
+        summary.record(3);
+
+        clock.advance(Duration::from_secs(34));
+        assert_eq!(summary.len(), 3);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary.quantile(0.5), Some(3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sliding_summary_zero_age_buckets() {
+        let _ = SlidingSummary::new(Duration::from_secs(60), 0);
+    }
+}
@@ -0,0 +1,106 @@
+use metrics::{MetricOp, Recorder};
+use metrics_core::Key;
+use std::sync::Mutex;
+
+/// A recorder that captures every operation passed to it, for checking what recorder wrappers
+/// pass along.
+#[derive(Default)]
+pub(crate) struct CapturingRecorder {
+    ops: Mutex<Vec<MetricOp>>,
+    descriptions: Mutex<Vec<(Key, &'static str)>>,
+}
+
+impl CapturingRecorder {
+    /// Takes every operation captured so far, in the order they were recorded.
+    pub(crate) fn take_ops(&self) -> Vec<MetricOp> {
+        let mut ops = self.ops.lock().unwrap();
+        ops.drain(..).collect()
+    }
+
+    /// Takes the key of every operation captured so far, in the order they were recorded.
+    pub(crate) fn take_keys(&self) -> Vec<Key> {
+        self.take_ops()
+            .into_iter()
+            .map(|op| match op {
+                MetricOp::IncrementCounter(key, _)
+                | MetricOp::UpdateGauge(key, _)
+                | MetricOp::RecordHistogram(key, _) => key,
+            })
+            .collect()
+    }
+
+    /// Takes the name and value of every operation captured so far, in the order they were
+    /// recorded.
+    pub(crate) fn take_values(&self) -> Vec<(String, i64)> {
+        self.take_ops()
+            .into_iter()
+            .map(|op| match op {
+                MetricOp::IncrementCounter(key, value) | MetricOp::RecordHistogram(key, value) => {
+                    (key.name().into_owned(), value as i64)
+                }
+                MetricOp::UpdateGauge(key, value) => (key.name().into_owned(), value),
+            })
+            .collect()
+    }
+
+    /// Takes the name and value of every operation captured so far, sorted by name and value.
+    pub(crate) fn take_sorted_values(&self) -> Vec<(String, i64)> {
+        let mut values = self.take_values();
+        values.sort();
+        values
+    }
+
+    /// Takes every gauge update captured so far, in the order they were recorded, dropping
+    /// everything else.
+    pub(crate) fn take_gauges(&self) -> Vec<(Key, i64)> {
+        self.take_ops()
+            .into_iter()
+            .filter_map(|op| match op {
+                MetricOp::UpdateGauge(key, value) => Some((key, value)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Takes the key and text of every description captured so far, in the order they were
+    /// given.
+    pub(crate) fn take_descriptions(&self) -> Vec<(Key, &'static str)> {
+        let mut descriptions = self.descriptions.lock().unwrap();
+        descriptions.drain(..).collect()
+    }
+}
+
+impl Recorder for CapturingRecorder {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.ops
+            .lock()
+            .unwrap()
+            .push(MetricOp::IncrementCounter(key, value));
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.ops
+            .lock()
+            .unwrap()
+            .push(MetricOp::UpdateGauge(key, value));
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.ops
+            .lock()
+            .unwrap()
+            .push(MetricOp::RecordHistogram(key, value));
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.descriptions.lock().unwrap().push((key, description));
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.descriptions.lock().unwrap().push((key, description));
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.descriptions.lock().unwrap().push((key, description));
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{Clock, SystemClock};
+use metrics_core::Key;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+/// How a [`Timed`] future measures its duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    WallClock,
+    ActiveTime,
+}
+
+/// Wraps a future so that the time it takes to complete is recorded into a histogram.
+///
+/// Timing async code with a drop guard doesn't work well: the guard lives across every `.await`,
+/// so it can't tell apart the time the task spent running from the time it spent suspended.  The
+/// returned [`Timed`] future instead tracks time from within `poll`, and records into the
+/// histogram with the given key, in nanoseconds, when the wrapped future completes.
+///
+/// By default, the recorded duration is the wall-clock time from the first poll of the future to
+/// its completion.  This is the latency as seen by whoever awaits the future, and includes the
+/// time spent suspended, waiting on I/O or timers, or waiting for the executor to get around to
+/// the task again.  Calling [`active_time`](Timed::active_time) switches to recording the sum of
+/// the time spent inside the wrapped future's `poll` only, which is the CPU time the future cost
+/// the executor.
+///
+/// The duration is recorded through the global recorder, if one is installed.  Nothing is
+/// recorded if the future is dropped before it completes.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::timed_histogram;
+/// # async fn fetch_user(id: u64) -> u64 { id }
+/// async fn handle_request(id: u64) -> u64 {
+///     timed_histogram("db.fetch_user", fetch_user(id)).await
+/// }
+/// ```
+pub fn timed_histogram<K, F>(key: K, future: F) -> Timed<F>
+where
+    K: Into<Key>,
+    F: Future,
+{
+    Timed {
+        future,
+        key: Some(key.into()),
+        clock: SystemClock,
+        mode: Mode::WallClock,
+        started: None,
+        active: Duration::from_secs(0),
+    }
+}
+
+/// A future that records how long the wrapped future took to complete.
+///
+/// Created by [`timed_histogram`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Timed<F, C = SystemClock> {
+    future: F,
+    key: Option<Key>,
+    clock: C,
+    mode: Mode,
+    started: Option<Instant>,
+    active: Duration,
+}
+
+impl<F, C> Timed<F, C> {
+    /// Records only the time spent polling the wrapped future, rather than the wall-clock time
+    /// until it completes.
+    pub fn active_time(mut self) -> Self {
+        self.mode = Mode::ActiveTime;
+        self
+    }
+
+    /// Takes the time from the given clock, rather than from the system clock.
+    pub fn with_clock<C2: Clock>(self, clock: C2) -> Timed<F, C2> {
+        Timed {
+            future: self.future,
+            key: self.key,
+            clock,
+            mode: self.mode,
+            started: self.started,
+            active: self.active,
+        }
+    }
+}
+
+impl<F: Future, C: Clock> Future for Timed<F, C> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: the wrapped future is never moved out of `self`, and is only ever accessed
+        // through a pinned reference.  None of the other fields are structurally pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        let start = this.clock.now();
+        let started = *this.started.get_or_insert(start);
+        let result = future.poll(cx);
+        let end = this.clock.now();
+        this.active += end.duration_since(start);
+
+        if result.is_ready() {
+            if let Some(key) = this.key.take() {
+                let elapsed = match this.mode {
+                    Mode::WallClock => end.duration_since(started),
+                    Mode::ActiveTime => this.active,
+                };
+                if let Some(recorder) = metrics::try_recorder() {
+                    recorder.record_histogram(key, elapsed.as_nanos() as u64);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::timed_histogram;
+    use crate::MockClock;
+    use metrics::Recorder;
+    use metrics_core::Key;
+    use std::{
+        future::Future,
+        pin::Pin,
+        ptr,
+        sync::Mutex,
+        task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+        time::Duration,
+    };
+
+    #[derive(Default)]
+    struct HistogramRecorder(Mutex<Vec<(Key, u64)>>);
+
+    impl Recorder for HistogramRecorder {
+        fn increment_counter(&self, _key: Key, _value: u64) {}
+        fn update_gauge(&self, _key: Key, _value: i64) {}
+        fn record_histogram(&self, key: Key, value: u64) {
+            self.0.lock().unwrap().push((key, value));
+        }
+    }
+
+    /// A future that spends 5ms in each poll, and needs two polls to complete.
+    struct TwoStep {
+        clock: MockClock,
+        polls: usize,
+    }
+
+    impl Future for TwoStep {
+        type Output = usize;
+
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<usize> {
+            self.clock.advance(Duration::from_millis(5));
+            self.polls += 1;
+            if self.polls == 2 {
+                Poll::Ready(self.polls)
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+        unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+    }
+
+    /// Polls the future to completion, advancing the clock by 20ms between polls to simulate
+    /// time spent suspended.
+    fn run<F: Future>(future: F, clock: &MockClock) -> F::Output {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            clock.advance(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_timed_histogram() {
+        let recorder: &'static HistogramRecorder = Box::leak(Box::default());
+        metrics::set_recorder(recorder).unwrap();
+
+        let clock = MockClock::new();
+        let future = TwoStep {
+            clock: clock.clone(),
+            polls: 0,
+        };
+        let timed = timed_histogram("wall", future).with_clock(clock.clone());
+        assert!(recorder.0.lock().unwrap().is_empty());
+        assert_eq!(run(timed, &clock), 2);
+
+        let future = TwoStep {
+            clock: clock.clone(),
+            polls: 0,
+        };
+        let timed = timed_histogram("active", future)
+            .active_time()
+            .with_clock(clock.clone());
+        assert_eq!(run(timed, &clock), 2);
+
+        // A future dropped before completing records nothing.
+        let mut dropped = Box::pin(
+            timed_histogram(
+                "dropped",
+                TwoStep {
+                    clock: clock.clone(),
+                    polls: 0,
+                },
+            )
+            .with_clock(clock.clone()),
+        );
+        let waker = noop_waker();
+        assert!(dropped
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending());
+        drop(dropped);
+
+        let recorded = recorder.0.lock().unwrap();
+        assert_eq!(
+            *recorded,
+            vec![
+                (Key::from_name("wall"), 30_000_000),
+                (Key::from_name("active"), 10_000_000),
+            ]
+        );
+    }
+}
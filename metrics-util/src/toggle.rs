@@ -0,0 +1,293 @@
+use arc_swap::ArcSwap;
+use metrics::Recorder;
+use metrics_core::{Key, Label};
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// A set of flags turning metrics on and off by name, at runtime.
+///
+/// Every metric is enabled until it is explicitly disabled, so only the names an operator has
+/// touched take up any space.  The registry is cheap to clone, and every clone shares the same
+/// flags, so one clone can be handed to a [`ToggleRecorder`] while another is kept around for
+/// whatever admin endpoint flips the flags.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::ToggleRegistry;
+/// let registry = ToggleRegistry::new();
+/// assert!(registry.is_enabled("cache.entries_scanned"));
+///
+/// registry.disable("cache.entries_scanned");
+/// assert!(!registry.is_enabled("cache.entries_scanned"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ToggleRegistry {
+    // Flags are looked up on every operation, but only added when a name is first turned on or
+    // off, so the map is copied and swapped in whole whenever a flag is added, and looking one up
+    // never takes a lock.
+    flags: Arc<ArcSwap<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl ToggleRegistry {
+    /// Creates a new registry, with every metric enabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turns the metric with the given name on or off.
+    pub fn set_enabled(&self, name: &str, enabled: bool) {
+        if let Some(flag) = self.flags.load().get(name) {
+            flag.store(enabled, Ordering::Release);
+            return;
+        }
+
+        let flags = self.flags.rcu(|flags| {
+            let mut flags = HashMap::clone(flags);
+            flags
+                .entry(name.to_string())
+                .or_insert_with(|| Arc::new(AtomicBool::new(enabled)));
+            flags
+        });
+        // Somebody else may have added the flag first, in which case it's set here instead.
+        if let Some(flag) = flags.get(name) {
+            flag.store(enabled, Ordering::Release);
+        }
+    }
+
+    /// Turns the metric with the given name on.
+    pub fn enable(&self, name: &str) {
+        self.set_enabled(name, true);
+    }
+
+    /// Turns the metric with the given name off.
+    pub fn disable(&self, name: &str) {
+        self.set_enabled(name, false);
+    }
+
+    /// Whether or not the metric with the given name is enabled.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        let flags = self.flags.load();
+        // Until a metric is turned on or off, there's nothing to look up.
+        if flags.is_empty() {
+            return true;
+        }
+        flags
+            .get(name)
+            .map_or(true, |flag| flag.load(Ordering::Acquire))
+    }
+
+    /// Gets the state of every metric which has been explicitly turned on or off, sorted by name.
+    pub fn flags(&self) -> Vec<(String, bool)> {
+        let flags = self.flags.load();
+        let mut states = flags
+            .iter()
+            .map(|(name, flag)| (name.clone(), flag.load(Ordering::Acquire)))
+            .collect::<Vec<_>>();
+        states.sort();
+        states
+    }
+}
+
+/// A recorder wrapper that drops operations for metrics turned off in a [`ToggleRegistry`].
+///
+/// Some metrics are too costly to record all the time, but are worth turning on while chasing
+/// down a problem.  `ToggleRecorder` checks the flag for the name of each metric before passing
+/// an operation along to the wrapped recorder, and silently drops the operation if the metric is
+/// turned off.  Flags are looked up on every operation, so flipping one takes effect
+/// immediately, without reloading any configuration.  The lookup never takes a lock, and is
+/// skipped entirely until some metric has been turned on or off, so it costs little next to the
+/// recording itself.
+///
+/// Descriptions are always passed along, so that metrics which are turned back on still have
+/// their metadata.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::Key;
+/// # use metrics_util::{ToggleRecorder, ToggleRegistry};
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let registry = ToggleRegistry::new();
+/// let recorder = ToggleRecorder::new(NoopRecorder, registry.clone());
+///
+/// // Later on, from an admin endpoint:
+/// registry.disable("cache.entries_scanned");
+///
+/// // This is dropped before it reaches the wrapped recorder.
+/// recorder.increment_counter(Key::from_name("cache.entries_scanned"), 1);
+/// ```
+pub struct ToggleRecorder<R> {
+    inner: R,
+    registry: ToggleRegistry,
+}
+
+impl<R> ToggleRecorder<R> {
+    /// Creates a new `ToggleRecorder` around the given recorder, checking flags in the given
+    /// registry.
+    pub fn new(inner: R, registry: ToggleRegistry) -> Self {
+        ToggleRecorder { inner, registry }
+    }
+
+    /// Gets a reference to the registry holding the flags.
+    pub fn registry(&self) -> &ToggleRegistry {
+        &self.registry
+    }
+
+    /// Gets a reference to the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the wrapped recorder.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn is_enabled(&self, key: &Key) -> bool {
+        self.registry.is_enabled(&key.name())
+    }
+}
+
+impl<R: Recorder> Recorder for ToggleRecorder<R> {
+    fn increment_counter(&self, key: Key, value: u64) {
+        if self.is_enabled(&key) {
+            self.inner.increment_counter(key, value);
+        }
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        if self.is_enabled(&key) {
+            self.inner.update_gauge(key, value);
+        }
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        if self.is_enabled(&key) {
+            self.inner.record_histogram(key, value);
+        }
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        if self.is_enabled(&key) {
+            self.inner.record_histogram_many(key, value, count);
+        }
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        if self.is_enabled(&key) {
+            self.inner
+                .record_histogram_buckets(key, buckets, sum, count);
+        }
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        // A disabled metric drops values on purpose, which is not a rejection of the value.
+        if self.is_enabled(&key) {
+            self.inner.try_record_histogram(key, value)
+        } else {
+            true
+        }
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        if self.is_enabled(&key) {
+            self.inner
+                .record_histogram_with_exemplar(key, value, exemplar);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        self.inner.counter_value(key)
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        self.inner.gauge_value(key)
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.inner.describe_counter(key, description);
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.inner.describe_gauge(key, description);
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.inner.describe_histogram(key, description);
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.inner.describe_with_meta(key, meta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ToggleRecorder, ToggleRegistry};
+    use crate::test_util::CapturingRecorder;
+    use metrics::Recorder;
+    use metrics_core::{Key, Label};
+
+    #[test]
+    fn test_toggle_recorder() {
+        let registry = ToggleRegistry::new();
+        let recorder = ToggleRecorder::new(CapturingRecorder::default(), registry.clone());
+
+        let scanned = || Key::from_name_and_labels("scanned", vec![Label::new("cache", "users")]);
+        recorder.increment_counter(scanned(), 1);
+        recorder.record_histogram(Key::from_name("latency"), 40);
+        assert_eq!(
+            recorder.inner().take_values(),
+            vec![("scanned".to_string(), 1), ("latency".to_string(), 40)]
+        );
+
+        // Turning a metric off mid-run drops every later update to it, whatever its labels.
+        registry.disable("scanned");
+        recorder.increment_counter(scanned(), 2);
+        recorder.increment_counter(Key::from_name("scanned"), 3);
+        recorder.update_gauge(Key::from_name("connections"), 4);
+        assert!(recorder.try_record_histogram(Key::from_name("scanned"), 5));
+        assert_eq!(
+            recorder.inner().take_values(),
+            vec![("connections".to_string(), 4)]
+        );
+
+        registry.enable("scanned");
+        recorder.increment_counter(scanned(), 6);
+        assert_eq!(
+            recorder.inner().take_values(),
+            vec![("scanned".to_string(), 6)]
+        );
+    }
+
+    #[test]
+    fn test_toggle_registry_flags() {
+        let registry = ToggleRegistry::new();
+        assert!(registry.flags().is_empty());
+
+        registry.disable("b");
+        registry.set_enabled("a", true);
+        registry.clone().disable("a");
+        assert!(!registry.is_enabled("a"));
+        assert!(registry.is_enabled("c"));
+        assert_eq!(
+            registry.flags(),
+            vec![("a".to_string(), false), ("b".to_string(), false)]
+        );
+    }
+}
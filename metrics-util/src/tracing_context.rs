@@ -0,0 +1,325 @@
+use metrics::{MetricOp, Recorder};
+use metrics_core::{Key, Label};
+use std::{any::Any, fmt};
+use tracing::{
+    field::{Field, Visit},
+    span, Span, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer, Registry};
+
+/// Labels collected from the fields of a span.
+struct SpanLabels(Vec<Label>);
+
+impl SpanLabels {
+    fn push(&mut self, field: &Field, value: String) {
+        let name = field.name();
+        match self.0.iter_mut().find(|l| l.key() == name) {
+            Some(existing) => *existing = Label::new(name, value),
+            None => self.0.push(Label::new(name, value)),
+        }
+    }
+}
+
+impl Visit for SpanLabels {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, value.to_string());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, value.to_string());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, value.to_string());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format!("{:?}", value));
+    }
+}
+
+/// A [`tracing`] layer which captures the fields of spans so they can be used as metric labels.
+///
+/// This must be installed on a subscriber built on the [`Registry`] from `tracing-subscriber`,
+/// and is used along with [`TracingContext`], which attaches the captured fields to metrics.
+///
+/// Every field of a span becomes a label, keyed by the field name.  Strings, integers, and
+/// booleans are used as-is, and any other value is formatted with its `Debug` representation.  As
+/// values recorded with `%` are wrapped in a `Debug` implementation that defers to `Display`, they
+/// use their `Display` representation.  Fields without a value are skipped until one is recorded.
+///
+/// [`Registry`]: tracing_subscriber::Registry
+#[derive(Debug, Default)]
+pub struct TracingContextLayer {
+    _private: (),
+}
+
+impl TracingContextLayer {
+    /// Creates a new `TracingContextLayer`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S> Layer<S> for TracingContextLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut labels = SpanLabels(Vec::new());
+            attrs.record(&mut labels);
+            span.extensions_mut().insert(labels);
+        }
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(labels) = span.extensions_mut().get_mut::<SpanLabels>() {
+                values.record(labels);
+            }
+        }
+    }
+}
+
+/// A recorder wrapper which labels metrics with the fields of the current [`tracing`] span.
+///
+/// Request-scoped context, such as a route or a tenant, is usually already attached to the
+/// current span, and is a natural source of metric labels.  `TracingContext` adds the fields of
+/// the current span, and of all of its parents, as labels on every metric it records, so that
+/// this context doesn't need to be threaded through to every call site by hand.
+///
+/// Fields are captured by [`TracingContextLayer`], which describes how field values are turned
+/// into labels.  When a span and one of its parents have a field with the same name, the field of
+/// the innermost span is used, and labels given explicitly at the call site always take
+/// precedence over fields.
+///
+/// This is only available when the `tracing-context` feature is enabled.
+///
+/// # Examples
+/// ```rust
+/// # use metrics::Recorder;
+/// # use metrics_core::Key;
+/// # use metrics_util::{TracingContext, TracingContextLayer};
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # struct NoopRecorder;
+/// # impl Recorder for NoopRecorder {
+/// #     fn increment_counter(&self, _key: Key, _value: u64) {}
+/// #     fn update_gauge(&self, _key: Key, _value: i64) {}
+/// #     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// # }
+/// let subscriber = Registry::default().with(TracingContextLayer::new());
+/// tracing::subscriber::set_global_default(subscriber).expect("failed to set subscriber");
+///
+/// let recorder = TracingContext::new(NoopRecorder);
+/// let span = tracing::info_span!("request", route = "/users");
+/// let _guard = span.enter();
+///
+/// // Recorded as `requests{route="/users"}`.
+/// recorder.increment_counter(Key::from_name("requests"), 1);
+/// ```
+pub struct TracingContext<R> {
+    inner: R,
+}
+
+impl<R> TracingContext<R> {
+    /// Creates a new `TracingContext` wrapper around the given recorder.
+    pub fn new(inner: R) -> Self {
+        TracingContext { inner }
+    }
+
+    /// Gets a reference to the wrapped recorder.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Consumes this wrapper, returning the wrapped recorder.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn enrich(&self, mut key: Key) -> Key {
+        let labels = current_labels()
+            .into_iter()
+            .filter(|label| !key.labels().any(|l| l.key() == label.key()))
+            .collect::<Vec<_>>();
+        if !labels.is_empty() {
+            key.add_labels(labels);
+        }
+        key
+    }
+}
+
+fn current_labels() -> Vec<Label> {
+    let labels = Span::current().with_subscriber(|(id, dispatch)| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+
+        // Walk from the current span up to the root, so that the fields of inner spans are seen,
+        // and take precedence, before those of their parents.
+        let mut labels: Vec<Label> = Vec::new();
+        let mut span = registry.span(id);
+        while let Some(current) = span {
+            if let Some(span_labels) = current.extensions().get::<SpanLabels>() {
+                for label in &span_labels.0 {
+                    if !labels.iter().any(|l| l.key() == label.key()) {
+                        labels.push(label.clone());
+                    }
+                }
+            }
+            span = current.parent();
+        }
+        Some(labels)
+    });
+
+    labels.and_then(|labels| labels).unwrap_or_default()
+}
+
+impl<R: Recorder> Recorder for TracingContext<R> {
+    fn increment_counter(&self, key: Key, value: u64) {
+        self.inner.increment_counter(self.enrich(key), value);
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        self.inner.update_gauge(self.enrich(key), value);
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        self.inner.record_histogram(self.enrich(key), value);
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        self.inner
+            .record_histogram_many(self.enrich(key), value, count);
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        self.inner
+            .record_histogram_buckets(self.enrich(key), buckets, sum, count);
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.inner.try_record_histogram(self.enrich(key), value)
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        self.inner
+            .record_histogram_with_exemplar(self.enrich(key), value, exemplar);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        self.inner.counter_value(&self.enrich(key.clone()))
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        self.inner.gauge_value(&self.enrich(key.clone()))
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        self.inner.describe_counter(key, description);
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        self.inner.describe_gauge(key, description);
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        self.inner.describe_histogram(key, description);
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        self.inner.describe_with_meta(key, meta);
+    }
+
+    fn record_batch(&self, ops: &[MetricOp]) {
+        let ops = ops
+            .iter()
+            .map(|op| match op {
+                MetricOp::IncrementCounter(key, value) => {
+                    MetricOp::IncrementCounter(self.enrich(key.clone()), *value)
+                }
+                MetricOp::UpdateGauge(key, value) => {
+                    MetricOp::UpdateGauge(self.enrich(key.clone()), *value)
+                }
+                MetricOp::RecordHistogram(key, value) => {
+                    MetricOp::RecordHistogram(self.enrich(key.clone()), *value)
+                }
+            })
+            .collect::<Vec<_>>();
+        self.inner.record_batch(&ops);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TracingContext, TracingContextLayer};
+    use crate::test_util::CapturingRecorder;
+    use metrics::Recorder;
+    use metrics_core::{Key, Label};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    fn labels(key: &Key) -> Vec<(String, String)> {
+        key.labels()
+            .map(|l| (l.key().to_string(), l.value().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_tracing_context() {
+        let subscriber = Registry::default().with(TracingContextLayer::new());
+        let recorder = TracingContext::new(CapturingRecorder::default());
+
+        tracing::subscriber::with_default(subscriber, || {
+            // Outside of any span, keys are left alone.
+            recorder.increment_counter(Key::from_name("requests"), 1);
+
+            let request = tracing::info_span!("request", route = "/users", id = 42, cached = false);
+            let _request = request.enter();
+            recorder.increment_counter(Key::from_name("requests"), 1);
+
+            let query = tracing::info_span!(
+                "query",
+                route = %"/users/:id",
+                table = ?"users",
+                rows = tracing::field::Empty
+            );
+            let _query = query.enter();
+            query.record("rows", 7u64);
+            recorder.record_histogram(
+                Key::from_name_and_labels("latency", vec![Label::new("table", "orders")]),
+                10,
+            );
+        });
+
+        let keys = recorder.inner().take_keys();
+        assert_eq!(keys.len(), 3);
+        assert!(labels(&keys[0]).is_empty());
+
+        let pair = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(
+            labels(&keys[1]),
+            vec![
+                pair("route", "/users"),
+                pair("id", "42"),
+                pair("cached", "false")
+            ]
+        );
+        assert_eq!(
+            labels(&keys[2]),
+            vec![
+                pair("table", "orders"),
+                pair("route", "/users/:id"),
+                pair("rows", "7"),
+                pair("id", "42"),
+                pair("cached", "false"),
+            ]
+        );
+    }
+}
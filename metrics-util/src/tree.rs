@@ -1,7 +1,11 @@
+use crate::{value::parse_prometheus_key, ExportError};
+use metrics_core::Key;
 use serde::ser::{Serialize, Serializer};
+use serde_json::Value;
 use std::collections::HashMap;
 
 /// An integer metric value.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Integer {
     /// A signed value.
     Signed(i64),
@@ -120,3 +124,134 @@ impl Serialize for MetricsTree {
         serializer.collect_map(sorted)
     }
 }
+
+/// Loads metrics from the JSON rendering of a [`MetricsTree`].
+///
+/// Each nested object is a level of the metric name, and each leaf is named as
+/// `name{label="value"}`, so `{"http": {"requests{code=\"200\"}": 42}}` is loaded as the metric
+/// `http.requests` with the label `code="200"`.  Histograms are rendered as one leaf per
+/// statistic, such as `latency count` or `latency p99`, and each statistic is loaded as a metric
+/// named after its leaf, such as `latency count`.
+///
+/// Input which isn't valid JSON, leaves which aren't integers, and leaf names which can't be
+/// parsed are rejected with [`ExportError::Decode`].
+///
+/// # Examples
+/// ```rust
+/// # use metrics_core::{Key, Label};
+/// # use metrics_util::{load_json, Integer};
+/// let metrics = load_json(r#"{"http": {"requests{code=\"200\"}": 42}}"#).expect("invalid input");
+/// let key = Key::from_name_and_labels("http.requests", vec![Label::new("code", "200")]);
+/// assert_eq!(metrics, vec![(key, Integer::Unsigned(42))]);
+/// ```
+pub fn load_json(input: &str) -> Result<Vec<(Key, Integer)>, ExportError> {
+    let root = serde_json::from_str(input).map_err(|e| ExportError::Decode(e.to_string()))?;
+    let mut metrics = Vec::new();
+    load_entries(&mut Vec::new(), &root, &mut metrics)?;
+    Ok(metrics)
+}
+
+fn load_entries(
+    levels: &mut Vec<String>,
+    value: &Value,
+    metrics: &mut Vec<(Key, Integer)>,
+) -> Result<(), ExportError> {
+    let entries = match value {
+        Value::Object(entries) => entries,
+        _ => {
+            return Err(ExportError::Decode(format!(
+                "expected an object at '{}'",
+                levels.join(".")
+            )))
+        }
+    };
+
+    // Entries are sorted when rendered, but the parsed map may not preserve their order.
+    let mut sorted = entries.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|p| p.0);
+
+    for (name, entry) in sorted {
+        if let Value::Object(_) = entry {
+            levels.push(name.clone());
+            load_entries(levels, entry, metrics)?;
+            levels.pop();
+            continue;
+        }
+
+        let value = match entry {
+            Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(value), _) => Integer::Unsigned(value),
+                (None, Some(value)) => Integer::Signed(value),
+                _ => return Err(ExportError::Decode(format!("invalid value '{}'", n))),
+            },
+            other => return Err(ExportError::Decode(format!("invalid value '{}'", other))),
+        };
+
+        let (key, statistic) = parse_prometheus_key(name)?;
+        let statistic = statistic.trim();
+        let key = key.map_name(|leaf| {
+            let mut name = levels.clone();
+            name.push(leaf.into_owned());
+            let name = name.join(".");
+            if statistic.is_empty() {
+                name
+            } else {
+                format!("{} {}", name, statistic)
+            }
+        });
+        metrics.push((key, value));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_json, Integer, MetricsTree};
+    use crate::ExportError;
+    use metrics_core::{Key, Label};
+
+    #[test]
+    fn test_load_json() {
+        let mut tree = MetricsTree::default();
+        tree.insert_value(
+            vec!["http".to_string()],
+            "requests{code=\"200\"}".to_string(),
+            42u64,
+        );
+        tree.insert_value(vec!["db".to_string()], "connections".to_string(), -3i64);
+        tree.insert_values(
+            vec!["http".to_string()],
+            vec![
+                ("latency count".to_string(), 7u64),
+                ("latency p99".to_string(), 250u64),
+            ],
+        );
+        let rendered = serde_json::to_string(&tree).unwrap();
+
+        assert_eq!(
+            load_json(&rendered).unwrap(),
+            vec![
+                (Key::from_name("db.connections"), Integer::Signed(-3)),
+                (Key::from_name("http.latency count"), Integer::Unsigned(7)),
+                (Key::from_name("http.latency p99"), Integer::Unsigned(250)),
+                (
+                    Key::from_name_and_labels("http.requests", vec![Label::new("code", "200")]),
+                    Integer::Unsigned(42)
+                ),
+            ]
+        );
+
+        for input in &[
+            "{\"requests\": 4",
+            "[1, 2]",
+            "{\"requests\": 1.5}",
+            "{\"requests\": \"4\"}",
+            "{\"requests{code=200}\": 4}",
+        ] {
+            match load_json(input) {
+                Err(ExportError::Decode(_)) => {}
+                other => panic!("unexpected result for '{}': {:?}", input, other),
+            }
+        }
+    }
+}
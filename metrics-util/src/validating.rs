@@ -0,0 +1,177 @@
+/// How a [`ValidatingHistogram`] handles negative samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NegativePolicy {
+    /// Negative samples are recorded as-is.
+    ///
+    /// Some metrics are legitimately signed, so this is what [`ValidatingHistogram::default`]
+    /// uses.
+    Allow,
+
+    /// Negative samples are recorded as zero.
+    Clamp,
+
+    /// Negative samples are dropped, and counted as such.
+    Drop,
+}
+
+/// A histogram of floating-point samples that validates each sample before recording it.
+///
+/// A single bad sample, such as a NaN from a division by zero or a negative duration from a
+/// clock going backwards, can silently skew every quantile calculated from a distribution.
+/// `ValidatingHistogram` always drops NaN samples, and handles negative samples according to its
+/// [`NegativePolicy`].  Dropped samples are counted rather than recorded, so that they can be
+/// reported and alerted on separately.
+///
+/// # Examples
+/// ```rust
+/// # use metrics_util::{NegativePolicy, ValidatingHistogram};
+/// let mut histogram = ValidatingHistogram::new(NegativePolicy::Drop);
+/// histogram.record(0.25);
+/// histogram.record(-1.0);
+/// histogram.record(std::f64::NAN);
+///
+/// assert_eq!(histogram.samples(), &[0.25]);
+/// assert_eq!(histogram.dropped(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ValidatingHistogram {
+    samples: Vec<f64>,
+    dropped: u64,
+    negatives: NegativePolicy,
+}
+
+impl Default for ValidatingHistogram {
+    fn default() -> Self {
+        Self::new(NegativePolicy::Allow)
+    }
+}
+
+impl ValidatingHistogram {
+    /// Creates a new, empty histogram with the given policy for negative samples.
+    pub fn new(negatives: NegativePolicy) -> Self {
+        ValidatingHistogram {
+            samples: Vec::new(),
+            dropped: 0,
+            negatives,
+        }
+    }
+
+    /// Records a sample.
+    ///
+    /// Returns `true` if the sample was recorded, possibly after being clamped, or `false` if it
+    /// was dropped.
+    pub fn record(&mut self, value: f64) -> bool {
+        let value = if value.is_nan() {
+            None
+        } else if value < 0.0 {
+            match self.negatives {
+                NegativePolicy::Allow => Some(value),
+                NegativePolicy::Clamp => Some(0.0),
+                NegativePolicy::Drop => None,
+            }
+        } else {
+            Some(value)
+        };
+
+        match value {
+            Some(value) => {
+                self.samples.push(value);
+                true
+            }
+            None => {
+                self.dropped += 1;
+                false
+            }
+        }
+    }
+
+    /// Samples recorded so far, in the order they were recorded.
+    pub fn samples(&self) -> &[f64] {
+        &self.samples
+    }
+
+    /// Number of samples that were dropped.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Clears all recorded samples and the dropped sample count.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.dropped = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NegativePolicy, ValidatingHistogram};
+    use crate::LatestGauge;
+    use metrics::Recorder;
+    use metrics_core::Key;
+    use std::{f64, sync::Mutex};
+
+    /// A backend for floating-point samples, which are handed over as their bits.
+    struct ValidatingRecorder(Mutex<ValidatingHistogram>);
+
+    impl Recorder for ValidatingRecorder {
+        fn increment_counter(&self, _key: Key, _value: u64) {}
+        fn update_gauge(&self, _key: Key, _value: i64) {}
+        fn record_histogram(&self, key: Key, value: u64) {
+            self.try_record_histogram(key, value);
+        }
+        fn try_record_histogram(&self, _key: Key, value: u64) -> bool {
+            self.0.lock().unwrap().record(f64::from_bits(value))
+        }
+    }
+
+    #[test]
+    fn test_validating_recorder() {
+        let recorder = LatestGauge::new(ValidatingRecorder(Mutex::new(ValidatingHistogram::new(
+            NegativePolicy::Drop,
+        ))));
+        let key = || Key::from_name("ratio");
+
+        // Whether the backend accepted a sample makes it back through wrapping recorders.
+        assert!(recorder.try_record_histogram(key(), 0.5f64.to_bits()));
+        assert!(!recorder.try_record_histogram(key(), f64::NAN.to_bits()));
+        assert!(!recorder.try_record_histogram(key(), (-1.0f64).to_bits()));
+
+        let histogram = recorder.into_inner().0.into_inner().unwrap();
+        assert_eq!(histogram.samples(), &[0.5]);
+        assert_eq!(histogram.dropped(), 2);
+    }
+
+    #[test]
+    fn test_validating_histogram_default() {
+        let mut histogram = ValidatingHistogram::default();
+        assert!(histogram.record(1.5));
+        assert!(!histogram.record(f64::NAN));
+        assert!(histogram.record(-2.0));
+        assert!(histogram.record(f64::INFINITY));
+
+        assert_eq!(histogram.samples(), &[1.5, -2.0, f64::INFINITY]);
+        assert_eq!(histogram.dropped(), 1);
+
+        histogram.clear();
+        assert!(histogram.samples().is_empty());
+        assert_eq!(histogram.dropped(), 0);
+    }
+
+    #[test]
+    fn test_validating_histogram_negative_policies() {
+        let mut clamping = ValidatingHistogram::new(NegativePolicy::Clamp);
+        assert!(clamping.record(-1.0));
+        assert!(clamping.record(3.0));
+        assert!(!clamping.record(f64::NAN));
+        assert_eq!(clamping.samples(), &[0.0, 3.0]);
+        assert_eq!(clamping.dropped(), 1);
+
+        let mut dropping = ValidatingHistogram::new(NegativePolicy::Drop);
+        assert!(!dropping.record(-1.0));
+        assert!(!dropping.record(f64::NEG_INFINITY));
+        assert!(dropping.record(0.0));
+        assert!(!dropping.record(f64::NAN));
+        assert_eq!(dropping.samples(), &[0.0]);
+        assert_eq!(dropping.dropped(), 3);
+    }
+}
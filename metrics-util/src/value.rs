@@ -0,0 +1,703 @@
+use crate::{ExportError, Quantile};
+use metrics_core::{Key, Label, RenderOptions};
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A metric value, ready to be rendered by an exporter.
+///
+/// Exporters all need to turn the same handful of metric types into their own wire format.
+/// `MetricValue` centralizes that formatting, so that it can be shared instead of reimplemented
+/// by each exporter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricValue {
+    /// A counter.
+    Counter(u64),
+
+    /// A gauge.
+    Gauge(i64),
+
+    /// A histogram, holding its raw values.
+    Histogram(Vec<u64>),
+
+    /// A histogram holding weighted values, such as pre-aggregated buckets.
+    ///
+    /// Each value is given as a pair of the value and the number of times it was recorded, so
+    /// values recorded many times at once are never expanded.
+    WeightedHistogram(Vec<(u64, u64)>),
+}
+
+impl MetricValue {
+    /// Renders this value in the Prometheus exposition format.
+    ///
+    /// Counters and gauges are rendered as a single sample.  Histograms are rendered as a
+    /// summary, with a sample for each of the given quantiles, followed by the `_sum` and `_count`
+    /// samples.  Characters in the metric name which aren't valid in Prometheus are replaced with
+    /// underscores, and label values are escaped.  Every line, including the last, ends with a
+    /// newline.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// # use metrics_util::MetricValue;
+    /// let key = Key::from_name_and_labels("http.requests", vec![Label::new("code", "200")]);
+    /// let rendered = MetricValue::Counter(42).render_prometheus(&key, &[]);
+    /// assert_eq!(rendered, "http_requests{code=\"200\"} 42\n");
+    /// ```
+    pub fn render_prometheus(&self, key: &Key, quantiles: &[Quantile]) -> String {
+        let key = key.clone().map_name(|name| sanitize_prometheus_name(&name));
+        let opts = RenderOptions::new();
+
+        let mut output = String::new();
+        match self {
+            MetricValue::Counter(value) => push_sample(&mut output, &key.render_with(&opts), value),
+            MetricValue::Gauge(value) => push_sample(&mut output, &key.render_with(&opts), value),
+            MetricValue::Histogram(values) => {
+                let mut sorted = values.clone();
+                sorted.sort_unstable();
+
+                let sum = sorted.iter().fold(0u64, |acc, v| acc.wrapping_add(*v));
+                let quantile = |q: f64| {
+                    if sorted.is_empty() {
+                        return None;
+                    }
+                    let idx = (q * (sorted.len() - 1) as f64).round() as usize;
+                    sorted.get(idx).cloned()
+                };
+                push_summary(
+                    &mut output,
+                    &key,
+                    quantiles,
+                    quantile,
+                    sum,
+                    sorted.len() as u64,
+                );
+            }
+            MetricValue::WeightedHistogram(values) => {
+                let mut sorted = values.clone();
+                sorted.sort_unstable();
+
+                let count = sorted.iter().fold(0u64, |acc, (_, c)| acc.wrapping_add(*c));
+                let sum = sorted
+                    .iter()
+                    .fold(0u64, |acc, (v, c)| acc.wrapping_add(v.wrapping_mul(*c)));
+                let quantile = |q: f64| {
+                    if count == 0 {
+                        return None;
+                    }
+                    let rank = (q * (count - 1) as f64).round() as u64;
+                    let mut seen = 0u64;
+                    sorted
+                        .iter()
+                        .find(|(_, c)| {
+                            seen = seen.saturating_add(*c);
+                            seen > rank
+                        })
+                        .map(|(v, _)| *v)
+                };
+                push_summary(&mut output, &key, quantiles, quantile, sum, count);
+            }
+        }
+        output
+    }
+
+    /// Parses metrics in the Prometheus exposition format.
+    ///
+    /// The kind of each metric is taken from its `# TYPE` comment, and metrics without one are
+    /// parsed as gauges.  Other comments, blank lines and sample timestamps are skipped.  As
+    /// histograms are rendered as summaries, their raw values can't be recovered, so metrics of
+    /// any kind other than `counter`, `gauge` or `untyped` are rejected with
+    /// [`ExportError::UnknownMetricKind`].  Malformed lines, and values which aren't integers,
+    /// are rejected with [`ExportError::Decode`].
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// # use metrics_util::MetricValue;
+    /// let input = "# TYPE http_requests counter\nhttp_requests{code=\"200\"} 42\n";
+    /// let metrics = MetricValue::parse_prometheus(input).expect("invalid input");
+    /// let key = Key::from_name_and_labels("http_requests", vec![Label::new("code", "200")]);
+    /// assert_eq!(metrics, vec![(key, MetricValue::Counter(42))]);
+    /// ```
+    pub fn parse_prometheus(input: &str) -> Result<Vec<(Key, MetricValue)>, ExportError> {
+        let mut kinds = HashMap::new();
+        let mut metrics = Vec::new();
+        for line in input.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('#') {
+                let mut parts = line[1..].split_whitespace();
+                if parts.next() == Some("TYPE") {
+                    match (parts.next(), parts.next()) {
+                        (Some(name), Some(kind)) => {
+                            kinds.insert(name.to_string(), kind.to_string());
+                        }
+                        _ => {
+                            return Err(ExportError::Decode(format!(
+                                "invalid type comment '{}'",
+                                line
+                            )))
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let (key, rest) = parse_prometheus_key(line)?;
+            let raw_value = match rest.split_whitespace().next() {
+                Some(raw_value) => raw_value,
+                None => return Err(ExportError::Decode(format!("missing value in '{}'", line))),
+            };
+            let invalid_value = || ExportError::Decode(format!("invalid value '{}'", raw_value));
+            let name = key.name();
+            // The `_sum` and `_count` samples of summaries and histograms are named after them.
+            let family = ["_sum", "_count", "_bucket"]
+                .iter()
+                .filter(|suffix| name.ends_with(*suffix))
+                .filter_map(|suffix| kinds.get(&name[..name.len() - suffix.len()]))
+                .find(|kind| *kind == "summary" || *kind == "histogram");
+            let value = match kinds.get(&*name).or(family).map(String::as_str) {
+                Some("counter") => {
+                    MetricValue::Counter(raw_value.parse().map_err(|_| invalid_value())?)
+                }
+                Some("gauge") | Some("untyped") | None => {
+                    MetricValue::Gauge(raw_value.parse().map_err(|_| invalid_value())?)
+                }
+                Some(other) => return Err(ExportError::UnknownMetricKind(other.to_string())),
+            };
+            metrics.push((key, value));
+        }
+        Ok(metrics)
+    }
+
+    /// Renders the `_created` sample of this value in the Prometheus exposition format.
+    ///
+    /// OpenMetrics counters can carry a companion `<name>_created` sample, holding the time the
+    /// counter was created in seconds since the UNIX epoch, so that rates are calculated correctly
+    /// across restarts.  Only counters have a `_created` sample, so this renders nothing for gauges
+    /// and histograms.  The name and labels are rendered as in
+    /// [`render_prometheus`](MetricValue::render_prometheus), and the line ends with a newline.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::Key;
+    /// # use metrics_util::MetricValue;
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let created = UNIX_EPOCH + Duration::from_millis(1_500_000_000_250);
+    /// let key = Key::from_name("requests");
+    /// let rendered = MetricValue::Counter(42).render_prometheus_created(&key, created);
+    /// assert_eq!(rendered, "requests_created 1500000000.25\n");
+    /// ```
+    pub fn render_prometheus_created(&self, key: &Key, created: SystemTime) -> String {
+        let mut output = String::new();
+        if let MetricValue::Counter(_) = self {
+            let key = key
+                .clone()
+                .map_name(|name| format!("{}_created", sanitize_prometheus_name(&name)));
+            let seconds = created
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or(0.0);
+            push_sample(
+                &mut output,
+                &key.render_with(&RenderOptions::new()),
+                seconds,
+            );
+        }
+        output
+    }
+
+    /// Renders this value in the StatsD line format.
+    ///
+    /// Counters use the `c` type, gauges use the `g` type, and histograms are rendered as one `h`
+    /// line per value.  Weighted histograms are rendered as one `h` line per distinct value, with
+    /// a sample rate of one over the number of times it was recorded.  Labels are rendered as DogStatsD-style tags.  Characters in the name and
+    /// labels which are part of the StatsD line syntax (`:`, `|`, `,`, `#` and newlines) are
+    /// replaced with underscores.  Every line, including the last, ends with a newline.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// # use metrics_util::MetricValue;
+    /// let key = Key::from_name_and_labels("http.requests", vec![Label::new("code", "200")]);
+    /// let rendered = MetricValue::Counter(42).render_statsd(&key);
+    /// assert_eq!(rendered, "http.requests:42|c|#code:200\n");
+    /// ```
+    pub fn render_statsd(&self, key: &Key) -> String {
+        let name = sanitize_statsd(&key.name());
+        let tags = key
+            .labels()
+            .map(|l| {
+                format!(
+                    "{}:{}",
+                    sanitize_statsd(l.key()),
+                    sanitize_statsd(l.value())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let line = |value: &dyn ToString, kind: &str| {
+            let mut line = format!("{}:{}|{}", name, value.to_string(), kind);
+            if !tags.is_empty() {
+                line.push_str("|#");
+                line.push_str(&tags);
+            }
+            line.push('\n');
+            line
+        };
+
+        match self {
+            MetricValue::Counter(value) => line(value, "c"),
+            MetricValue::Gauge(value) => line(value, "g"),
+            MetricValue::Histogram(values) => values.iter().map(|v| line(v, "h")).collect(),
+            MetricValue::WeightedHistogram(values) => values
+                .iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(v, count)| match count {
+                    1 => line(v, "h"),
+                    count => line(v, &format!("h|@{}", 1.0 / *count as f64)),
+                })
+                .collect(),
+        }
+    }
+
+    /// Writes this value to the given writer in the StatsD line format.
+    ///
+    /// See [`render_statsd`](MetricValue::render_statsd) for details on the format.  Rather than
+    /// being silently replaced, names and labels containing characters which are part of the
+    /// StatsD line syntax are rejected with [`ExportError::Encode`].
+    pub fn write_statsd<W: Write>(&self, key: &Key, writer: &mut W) -> Result<(), ExportError> {
+        if key.name().contains(is_statsd_reserved) {
+            return Err(ExportError::Encode(format!(
+                "name '{}' contains reserved characters",
+                key.name()
+            )));
+        }
+        if let Some(label) = key.labels().find(|l| {
+            l.key().contains(is_statsd_reserved) || l.value().contains(is_statsd_reserved)
+        }) {
+            return Err(ExportError::Encode(format!(
+                "label '{}' contains reserved characters",
+                label.key()
+            )));
+        }
+
+        writer.write_all(self.render_statsd(key).as_bytes())?;
+        Ok(())
+    }
+
+    /// Parses a single line in the StatsD line format.
+    ///
+    /// This is the inverse of [`render_statsd`](MetricValue::render_statsd).  A trailing newline
+    /// is allowed.  As each line holds a single value, histograms are returned with exactly one
+    /// value.  Sample rates, as rendered for weighted histograms, aren't supported.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use metrics_core::{Key, Label};
+    /// # use metrics_util::MetricValue;
+    /// let (key, value) = MetricValue::parse_statsd("http.requests:42|c|#code:200\n")
+    ///     .expect("invalid line");
+    /// assert_eq!(key, Key::from_name_and_labels("http.requests", vec![Label::new("code", "200")]));
+    /// assert_eq!(value, MetricValue::Counter(42));
+    /// ```
+    pub fn parse_statsd(line: &str) -> Result<(Key, MetricValue), ExportError> {
+        let line = line.trim_end_matches('\n');
+        let mut parts = line.split('|');
+
+        let sample = parts.next().unwrap_or("");
+        let (name, raw_value) = match sample.rfind(':') {
+            Some(idx) if idx > 0 => (&sample[..idx], &sample[idx + 1..]),
+            _ => return Err(ExportError::Decode(format!("missing name in '{}'", line))),
+        };
+
+        let kind = match parts.next() {
+            Some(kind) => kind,
+            None => return Err(ExportError::Decode(format!("missing type in '{}'", line))),
+        };
+        let invalid_value = || ExportError::Decode(format!("invalid value '{}'", raw_value));
+        let value = match kind {
+            "c" => MetricValue::Counter(raw_value.parse().map_err(|_| invalid_value())?),
+            "g" => MetricValue::Gauge(raw_value.parse().map_err(|_| invalid_value())?),
+            "h" => MetricValue::Histogram(vec![raw_value.parse().map_err(|_| invalid_value())?]),
+            other => return Err(ExportError::UnknownMetricKind(other.to_string())),
+        };
+
+        let mut labels = Vec::new();
+        if let Some(tags) = parts.next() {
+            if !tags.starts_with('#') {
+                return Err(ExportError::Decode(format!("invalid tags '{}'", tags)));
+            }
+            for tag in tags[1..].split(',') {
+                match tag.find(':') {
+                    Some(idx) => labels.push(Label::new(
+                        tag[..idx].to_string(),
+                        tag[idx + 1..].to_string(),
+                    )),
+                    None => return Err(ExportError::Decode(format!("invalid tag '{}'", tag))),
+                }
+            }
+        }
+
+        if parts.next().is_some() {
+            return Err(ExportError::Decode(format!(
+                "unexpected input after tags in '{}'",
+                line
+            )));
+        }
+
+        Ok((Key::from_name_and_labels(name.to_string(), labels), value))
+    }
+}
+
+/// Parses the name and labels at the start of a Prometheus sample, such as `name{k="v"}`,
+/// returning them along with the rest of the input.
+pub(crate) fn parse_prometheus_key(input: &str) -> Result<(Key, &str), ExportError> {
+    let invalid = || ExportError::Decode(format!("invalid metric '{}'", input));
+    let end = input
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .unwrap_or(input.len());
+    if end == 0 {
+        return Err(ExportError::Decode(format!("missing name in '{}'", input)));
+    }
+    let name = input[..end].to_string();
+    let mut rest = &input[end..];
+
+    let mut labels = Vec::new();
+    if rest.starts_with('{') {
+        rest = rest[1..].trim_start();
+        while !rest.starts_with('}') {
+            let eq = rest.find('=').ok_or_else(invalid)?;
+            let label_key = rest[..eq].trim().to_string();
+            rest = rest[eq + 1..].trim_start();
+            if label_key.is_empty() || !rest.starts_with('"') {
+                return Err(invalid());
+            }
+
+            let mut value = String::new();
+            let mut chars = rest[1..].char_indices();
+            let close = loop {
+                match chars.next() {
+                    Some((idx, '"')) => break idx + 1,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, c)) => value.push(c),
+                        None => return Err(invalid()),
+                    },
+                    Some((_, c)) => value.push(c),
+                    None => return Err(invalid()),
+                }
+            };
+            labels.push(Label::new(label_key, value));
+
+            rest = rest[close + 1..].trim_start();
+            if rest.starts_with(',') {
+                rest = rest[1..].trim_start();
+            } else if !rest.starts_with('}') {
+                return Err(invalid());
+            }
+        }
+        rest = &rest[1..];
+    }
+
+    Ok((Key::from_name_and_labels(name, labels), rest))
+}
+
+pub(crate) fn sanitize_prometheus_name(name: &str) -> String {
+    let sanitize = |c| c == '.' || c == '=' || c == '{' || c == '}' || c == '+' || c == '-';
+    name.replace(sanitize, "_")
+}
+
+fn is_statsd_reserved(c: char) -> bool {
+    c == ':' || c == '|' || c == ',' || c == '#' || c == '\n'
+}
+
+fn sanitize_statsd(s: &str) -> String {
+    s.replace(is_statsd_reserved, "_")
+}
+
+/// Pushes the samples of a summary for the given, already sanitized, key.
+///
+/// Quantiles are only pushed if `quantile` returns a value for them, so that empty histograms
+/// are rendered with just their sum and count.
+pub(crate) fn push_summary<F>(
+    output: &mut String,
+    key: &Key,
+    quantiles: &[Quantile],
+    quantile: F,
+    sum: u64,
+    count: u64,
+) where
+    F: Fn(f64) -> Option<u64>,
+{
+    let opts = RenderOptions::new();
+    for q in quantiles {
+        if let Some(value) = quantile(q.value()) {
+            let mut quantile_key = key.clone();
+            quantile_key.add_labels(vec![Label::new("quantile", q.value().to_string())]);
+            push_sample(output, &quantile_key.render_with(&opts), value);
+        }
+    }
+
+    let sum_key = key.clone().map_name(|name| format!("{}_sum", name));
+    push_sample(output, &sum_key.render_with(&opts), sum);
+    let count_key = key.clone().map_name(|name| format!("{}_count", name));
+    push_sample(output, &count_key.render_with(&opts), count);
+}
+
+fn push_sample<V: ToString>(output: &mut String, name: &str, value: V) {
+    output.push_str(name);
+    output.push(' ');
+    output.push_str(&value.to_string());
+    output.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetricValue;
+    use crate::{parse_quantiles, ExportError};
+    use metrics_core::{Key, Label};
+    use std::io::{self, Write};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_render_prometheus() {
+        let key = Key::from_name_and_labels("db.queries", vec![Label::new("table", "us\"ers")]);
+        let unlabeled = Key::from_name("db.connections");
+
+        assert_eq!(
+            MetricValue::Counter(7).render_prometheus(&key, &[]),
+            "db_queries{table=\"us\\\"ers\"} 7\n"
+        );
+        assert_eq!(
+            MetricValue::Gauge(-3).render_prometheus(&unlabeled, &[]),
+            "db_connections -3\n"
+        );
+
+        let quantiles = parse_quantiles(&[0.0, 0.5, 1.0]);
+        let histogram = MetricValue::Histogram(vec![30, 10, 20]);
+        assert_eq!(
+            histogram.render_prometheus(&unlabeled, &quantiles),
+            "db_connections{quantile=\"0\"} 10\n\
+             db_connections{quantile=\"0.5\"} 20\n\
+             db_connections{quantile=\"1\"} 30\n\
+             db_connections_sum 60\n\
+             db_connections_count 3\n"
+        );
+
+        let empty = MetricValue::Histogram(Vec::new());
+        assert_eq!(
+            empty.render_prometheus(&key, &quantiles),
+            "db_queries_sum{table=\"us\\\"ers\"} 0\n\
+             db_queries_count{table=\"us\\\"ers\"} 0\n"
+        );
+
+        // Weighted values render the same as their expanded form would.
+        let weighted = MetricValue::WeightedHistogram(vec![(30, 1), (10, 1_000_000), (20, 2)]);
+        assert_eq!(
+            weighted.render_prometheus(&unlabeled, &quantiles),
+            "db_connections{quantile=\"0\"} 10\n\
+             db_connections{quantile=\"0.5\"} 10\n\
+             db_connections{quantile=\"1\"} 30\n\
+             db_connections_sum 10000070\n\
+             db_connections_count 1000003\n"
+        );
+        assert_eq!(
+            MetricValue::WeightedHistogram(vec![(10, 0)]).render_prometheus(&unlabeled, &quantiles),
+            "db_connections_sum 0\ndb_connections_count 0\n"
+        );
+    }
+
+    #[test]
+    fn test_render_prometheus_created() {
+        let key = Key::from_name_and_labels("db.queries", vec![Label::new("table", "users")]);
+        let created = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+
+        assert_eq!(
+            MetricValue::Counter(7).render_prometheus_created(&key, created),
+            "db_queries_created{table=\"users\"} 1600000000\n"
+        );
+        assert_eq!(
+            MetricValue::Gauge(7).render_prometheus_created(&key, created),
+            ""
+        );
+        assert_eq!(
+            MetricValue::Histogram(vec![7]).render_prometheus_created(&key, created),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_parse_prometheus() {
+        let input = "# HELP db_queries Queries run.\n\
+                     # TYPE db_queries counter\n\
+                     db_queries{table=\"us\\\"ers\",op=\"a\\nb\"} 7 1600000000\n\
+                     \n\
+                     db_connections -3\n";
+        let metrics = MetricValue::parse_prometheus(input).unwrap();
+        assert_eq!(
+            metrics,
+            vec![
+                (
+                    Key::from_name_and_labels(
+                        "db_queries",
+                        vec![Label::new("table", "us\"ers"), Label::new("op", "a\nb")]
+                    ),
+                    MetricValue::Counter(7)
+                ),
+                (Key::from_name("db_connections"), MetricValue::Gauge(-3)),
+            ]
+        );
+
+        // Rendered counters and gauges parse back to the same values.
+        let key = Key::from_name_and_labels("db_queries", vec![Label::new("table", "us\"ers")]);
+        let rendered = format!(
+            "# TYPE db_queries counter\n{}",
+            MetricValue::Counter(7).render_prometheus(&key, &[])
+        );
+        assert_eq!(
+            MetricValue::parse_prometheus(&rendered).unwrap(),
+            vec![(key, MetricValue::Counter(7))]
+        );
+
+        let decode = |input| match MetricValue::parse_prometheus(input) {
+            Err(ExportError::Decode(_)) => {}
+            other => panic!("unexpected result for '{}': {:?}", input, other),
+        };
+        decode("db_queries");
+        decode("db_queries 1.5");
+        decode("{table=\"users\"} 7");
+        decode("db_queries{table=\"users} 7");
+        decode("db_queries{table=users} 7");
+        decode("db_queries{table=\"users\" op=\"read\"} 7");
+        decode("# TYPE db_queries\n");
+        decode("# TYPE db_queries counter\ndb_queries -1");
+
+        for sample in &["latency{quantile=\"1\"} 3", "latency_count 3"] {
+            let input = format!("# TYPE latency summary\n{}", sample);
+            match MetricValue::parse_prometheus(&input) {
+                Err(ExportError::UnknownMetricKind(kind)) => assert_eq!(kind, "summary"),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_statsd() {
+        let key = Key::from_name_and_labels(
+            "db.queries",
+            vec![Label::new("table", "users"), Label::new("op", "read")],
+        );
+        let unlabeled = Key::from_name("db.connections");
+
+        assert_eq!(
+            MetricValue::Counter(7).render_statsd(&key),
+            "db.queries:7|c|#table:users,op:read\n"
+        );
+        assert_eq!(
+            MetricValue::Gauge(-3).render_statsd(&unlabeled),
+            "db.connections:-3|g\n"
+        );
+        assert_eq!(
+            MetricValue::Histogram(vec![5, 15]).render_statsd(&unlabeled),
+            "db.connections:5|h\ndb.connections:15|h\n"
+        );
+        assert_eq!(MetricValue::Histogram(Vec::new()).render_statsd(&key), "");
+        assert_eq!(
+            MetricValue::WeightedHistogram(vec![(5, 1), (15, 4), (25, 0)])
+                .render_statsd(&unlabeled),
+            "db.connections:5|h\ndb.connections:15|h|@0.25\n"
+        );
+
+        let reserved =
+            Key::from_name_and_labels("db:queries|total", vec![Label::new("ta#ble", "a,b:c|d\ne")]);
+        assert_eq!(
+            MetricValue::Counter(7).render_statsd(&reserved),
+            "db_queries_total:7|c|#ta_ble:a_b_c_d_e\n"
+        );
+        let (key, value) =
+            MetricValue::parse_statsd(&MetricValue::Counter(7).render_statsd(&reserved)).unwrap();
+        assert_eq!(
+            key,
+            Key::from_name_and_labels("db_queries_total", vec![Label::new("ta_ble", "a_b_c_d_e")])
+        );
+        assert_eq!(value, MetricValue::Counter(7));
+    }
+
+    #[test]
+    fn test_parse_statsd() {
+        let key = Key::from_name_and_labels(
+            "db.queries",
+            vec![Label::new("table", "users"), Label::new("op", "read")],
+        );
+        for value in vec![
+            MetricValue::Counter(7),
+            MetricValue::Gauge(-3),
+            MetricValue::Histogram(vec![15]),
+        ] {
+            let line = value.render_statsd(&key);
+            assert_eq!(
+                MetricValue::parse_statsd(&line).unwrap(),
+                (key.clone(), value)
+            );
+        }
+        assert_eq!(
+            MetricValue::parse_statsd("db.connections:4|g").unwrap(),
+            (Key::from_name("db.connections"), MetricValue::Gauge(4))
+        );
+
+        let decode = |line| match MetricValue::parse_statsd(line) {
+            Err(ExportError::Decode(_)) => true,
+            _ => false,
+        };
+        assert!(decode(""));
+        assert!(decode(":7|c"));
+        assert!(decode("requests|c"));
+        assert!(decode("requests:7"));
+        assert!(decode("requests:-7|c"));
+        assert!(decode("requests:seven|g"));
+        assert!(decode("requests:7|c|table:users"));
+        assert!(decode("requests:7|c|#table"));
+        assert!(decode("requests:7|c|#table:users|@0.5"));
+
+        match MetricValue::parse_statsd("requests:7|ms") {
+            Err(ExportError::UnknownMetricKind(kind)) => assert_eq!(kind, "ms"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_statsd() {
+        let key = Key::from_name_and_labels("db.queries", vec![Label::new("table", "users")]);
+        let mut output = Vec::new();
+        MetricValue::Counter(7)
+            .write_statsd(&key, &mut output)
+            .unwrap();
+        assert_eq!(output, b"db.queries:7|c|#table:users\n");
+
+        let reserved = Key::from_name_and_labels("db.queries", vec![Label::new("table", "a,b")]);
+        match MetricValue::Counter(7).write_statsd(&reserved, &mut output) {
+            Err(ExportError::Encode(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match MetricValue::Counter(7).write_statsd(&key, &mut FailingWriter) {
+            Err(ExportError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Recording through C recorders.
+//!
+//! The global recorder can only be set once per process, so installing a C recorder is tested in a
+//! test binary of its own, apart from the unit tests that install recorders of their own.
+#![cfg(feature = "ffi")]
+
+use metrics::Recorder;
+use metrics_core::{Key, Label};
+use metrics_util::{metrics_install_c_recorder, CKey, CRecorder, FfiRecorder};
+use std::{
+    ffi::CStr,
+    os::raw::c_void,
+    slice,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Default)]
+struct Captured {
+    ops: Mutex<Vec<String>>,
+}
+
+unsafe fn push(context: *mut c_void, kind: &str, key: *const CKey, value: String) {
+    let captured = &*(context as *const Captured);
+    let key = &*key;
+    let mut op = format!("{} {}", kind, CStr::from_ptr(key.name).to_str().unwrap());
+    for label in slice::from_raw_parts(key.labels, key.labels_len) {
+        op.push_str(&format!(
+            " {}={}",
+            CStr::from_ptr(label.key).to_str().unwrap(),
+            CStr::from_ptr(label.value).to_str().unwrap()
+        ));
+    }
+    op.push_str(&format!(" {}", value));
+    captured.ops.lock().unwrap().push(op);
+}
+
+unsafe extern "C" fn increment_counter(context: *mut c_void, key: *const CKey, value: u64) {
+    push(context, "counter", key, value.to_string());
+}
+
+unsafe extern "C" fn update_gauge(context: *mut c_void, key: *const CKey, value: i64) {
+    push(context, "gauge", key, value.to_string());
+}
+
+unsafe extern "C" fn record_histogram(context: *mut c_void, key: *const CKey, value: u64) {
+    push(context, "histogram", key, value.to_string());
+}
+
+unsafe extern "C" fn flush(context: *mut c_void) {
+    let captured = &*(context as *const Captured);
+    captured.ops.lock().unwrap().push("flush".to_string());
+}
+
+unsafe extern "C" fn free(context: *mut c_void) {
+    drop(Arc::from_raw(context as *const Captured));
+}
+
+fn c_recorder(captured: &Arc<Captured>) -> CRecorder {
+    CRecorder {
+        context: Arc::into_raw(captured.clone()) as *mut c_void,
+        increment_counter,
+        update_gauge,
+        record_histogram,
+        flush: Some(flush),
+        free: Some(free),
+    }
+}
+
+#[test]
+fn test_ffi_recorder() {
+    let captured = Arc::new(Captured::default());
+    let recorder = unsafe { FfiRecorder::new(c_recorder(&captured)) };
+
+    recorder.increment_counter(
+        Key::from_name_and_labels(
+            "requests",
+            vec![Label::new("code", "200"), Label::new("path", "/a\0b")],
+        ),
+        3,
+    );
+    recorder.update_gauge(Key::from_name("connections"), -2);
+    recorder.record_histogram_many(Key::from_name("latency"), 40, 2);
+    recorder.flush();
+
+    assert_eq!(
+        *captured.ops.lock().unwrap(),
+        vec![
+            "counter requests code=200 path=/ab 3",
+            "gauge connections -2",
+            "histogram latency 40",
+            "histogram latency 40",
+            "flush",
+        ]
+    );
+
+    // Dropping the recorder frees its context.
+    assert_eq!(Arc::strong_count(&captured), 2);
+    drop(recorder);
+    assert_eq!(Arc::strong_count(&captured), 1);
+}
+
+#[test]
+fn test_install_c_recorder() {
+    let captured = Arc::new(Captured::default());
+    assert!(unsafe { metrics_install_c_recorder(c_recorder(&captured)) });
+
+    metrics::counter!("ffi.requests", 1, "code" => "200");
+    metrics::gauge!("ffi.connections", 5);
+    assert_eq!(
+        *captured.ops.lock().unwrap(),
+        vec!["counter ffi.requests code=200 1", "gauge ffi.connections 5"]
+    );
+
+    // A recorder that can't be installed is freed right away.
+    let rejected = Arc::new(Captured::default());
+    assert!(!unsafe { metrics_install_c_recorder(c_recorder(&rejected)) });
+    assert_eq!(Arc::strong_count(&rejected), 1);
+}
@@ -0,0 +1,150 @@
+use crate::try_recorder;
+use metrics_core::{Key, MetricKind, Unit};
+
+/// A description of a metric, as declared with [`define_metrics!`](crate::define_metrics).
+///
+/// Descriptors are `const`-constructible, so a whole catalog of them can live in a `static`,
+/// giving one place to see every metric that a binary can emit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricDescriptor {
+    name: &'static str,
+    kind: MetricKind,
+    description: &'static str,
+    unit: Option<Unit>,
+}
+
+impl MetricDescriptor {
+    /// Creates a new `MetricDescriptor`.
+    pub const fn new(
+        name: &'static str,
+        kind: MetricKind,
+        description: &'static str,
+        unit: Option<Unit>,
+    ) -> Self {
+        MetricDescriptor {
+            name,
+            kind,
+            description,
+            unit,
+        }
+    }
+
+    /// Name of the metric.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Kind of the metric.
+    pub fn kind(&self) -> MetricKind {
+        self.kind
+    }
+
+    /// Human-readable description of the metric.
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+
+    /// Unit of the metric, if one was given.
+    pub fn unit(&self) -> Option<Unit> {
+        self.unit
+    }
+
+    /// Describes the metric to the installed recorder.
+    ///
+    /// This calls the `describe_*` method of the recorder that matches the kind of the metric,
+    /// and, if the metric has a unit, passes the [`Unit`] along with
+    /// [`describe_with_meta`](crate::Recorder::describe_with_meta).  If no recorder is installed,
+    /// nothing happens.
+    pub fn describe(&self) {
+        if let Some(recorder) = try_recorder() {
+            let key = Key::from_name(self.name);
+            match self.kind {
+                MetricKind::Counter => recorder.describe_counter(key.clone(), self.description),
+                MetricKind::Gauge => recorder.describe_gauge(key.clone(), self.description),
+                MetricKind::Histogram => recorder.describe_histogram(key.clone(), self.description),
+            }
+            if let Some(unit) = self.unit {
+                recorder.describe_with_meta(key, Box::new(unit));
+            }
+        }
+    }
+}
+
+/// Describes every metric in the given table to the installed recorder.
+///
+/// Each entry is the name, kind, unit, and description of a metric, in that order, which puts the
+/// unit before the description, unlike the arguments to [`MetricDescriptor::new`].  This is a
+/// shorthand for describing a whole catalog of
+/// metrics at startup, so that their descriptions are in place before any of them are first
+/// recorded.  As with [`MetricDescriptor::describe`], the `describe_*` method of the recorder
+/// that matches the kind of each metric is called, and nothing happens if no recorder is
+/// installed.
+///
+/// Units are passed along to the recorder as metadata, as with [`MetricDescriptor::describe`].
+///
+/// # Examples
+/// ```rust
+/// use metrics::{describe_all, Key, MetricKind, Recorder, Unit};
+/// use std::{any::Any, sync::Mutex};
+///
+/// #[derive(Default)]
+/// struct CapturingRecorder {
+///     descriptions: Mutex<Vec<(MetricKind, Key, &'static str)>>,
+///     units: Mutex<Vec<(Key, Unit)>>,
+/// }
+///
+/// impl Recorder for CapturingRecorder {
+///     fn increment_counter(&self, _key: Key, _value: u64) {}
+///     fn update_gauge(&self, _key: Key, _value: i64) {}
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+///
+///     fn describe_counter(&self, key: Key, description: &'static str) {
+///         let mut descriptions = self.descriptions.lock().unwrap();
+///         descriptions.push((MetricKind::Counter, key, description));
+///     }
+///
+///     fn describe_gauge(&self, key: Key, description: &'static str) {
+///         let mut descriptions = self.descriptions.lock().unwrap();
+///         descriptions.push((MetricKind::Gauge, key, description));
+///     }
+///
+///     fn describe_histogram(&self, key: Key, description: &'static str) {
+///         let mut descriptions = self.descriptions.lock().unwrap();
+///         descriptions.push((MetricKind::Histogram, key, description));
+///     }
+///
+///     fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+///         if let Some(unit) = meta.downcast_ref::<Unit>() {
+///             self.units.lock().unwrap().push((key, *unit));
+///         }
+///     }
+/// }
+///
+/// let recorder: &'static CapturingRecorder = Box::leak(Box::default());
+/// metrics::set_recorder(recorder).unwrap();
+///
+/// describe_all(&[
+///     ("requests_total", MetricKind::Counter, None, "Total number of requests served."),
+///     ("connections", MetricKind::Gauge, None, "Number of open connections."),
+///     ("request_latency", MetricKind::Histogram, Some(Unit::Nanoseconds), "Time taken to serve a request."),
+/// ]);
+///
+/// let descriptions = recorder.descriptions.lock().unwrap();
+/// assert_eq!(
+///     *descriptions,
+///     vec![
+///         (MetricKind::Counter, Key::from_name("requests_total"), "Total number of requests served."),
+///         (MetricKind::Gauge, Key::from_name("connections"), "Number of open connections."),
+///         (MetricKind::Histogram, Key::from_name("request_latency"), "Time taken to serve a request."),
+///     ]
+/// );
+/// assert_eq!(
+///     *recorder.units.lock().unwrap(),
+///     vec![(Key::from_name("request_latency"), Unit::Nanoseconds)]
+/// );
+/// ```
+pub fn describe_all(table: &[(&'static str, MetricKind, Option<Unit>, &'static str)]) {
+    for &(name, kind, unit, description) in table {
+        MetricDescriptor::new(name, kind, description, unit).describe();
+    }
+}
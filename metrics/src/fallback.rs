@@ -0,0 +1,271 @@
+use crate::{MetricOp, Recorder};
+use metrics_core::{Key, Label};
+use std::{
+    any::Any,
+    mem, ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex,
+    },
+};
+
+/// A recorder that buffers operations until the primary recorder is installed.
+///
+/// Once [`replay_into`](FallbackRecorder::replay_into) is called, the buffered operations are
+/// handed to the primary recorder, and any operation recorded afterwards is forwarded to it
+/// directly.
+pub(crate) struct FallbackRecorder {
+    capacity: usize,
+    // Points to the primary recorder once every buffered operation has been replayed into it.
+    primary: AtomicPtr<&'static dyn Recorder>,
+    state: Mutex<State>,
+}
+
+struct State {
+    ops: Vec<Buffered>,
+    replaying: bool,
+}
+
+/// An operation buffered until the primary recorder is installed.
+enum Buffered {
+    Op(MetricOp),
+    Many(Key, u64, u64),
+    Buckets(Key, Vec<(u64, u64)>, u64, u64),
+    Exemplar(Key, u64, Vec<Label>),
+    DescribeCounter(Key, &'static str),
+    DescribeGauge(Key, &'static str),
+    DescribeHistogram(Key, &'static str),
+    Meta(Key, Box<dyn Any + Send + Sync>),
+}
+
+impl Buffered {
+    fn replay(self, primary: &dyn Recorder) {
+        match self {
+            Buffered::Op(op) => primary.record_batch(&[op]),
+            Buffered::Many(key, value, count) => primary.record_histogram_many(key, value, count),
+            Buffered::Buckets(key, buckets, sum, count) => {
+                primary.record_histogram_buckets(key, &buckets, sum, count)
+            }
+            Buffered::Exemplar(key, value, exemplar) => {
+                primary.record_histogram_with_exemplar(key, value, &exemplar)
+            }
+            Buffered::DescribeCounter(key, description) => {
+                primary.describe_counter(key, description)
+            }
+            Buffered::DescribeGauge(key, description) => primary.describe_gauge(key, description),
+            Buffered::DescribeHistogram(key, description) => {
+                primary.describe_histogram(key, description)
+            }
+            Buffered::Meta(key, meta) => primary.describe_with_meta(key, meta),
+        }
+    }
+}
+
+/// Replays the given operations in order, handing runs of plain operations over as one batch.
+fn replay(ops: Vec<Buffered>, primary: &dyn Recorder) {
+    let mut batch = Vec::new();
+    for op in ops {
+        match op {
+            Buffered::Op(op) => batch.push(op),
+            op => {
+                if !batch.is_empty() {
+                    primary.record_batch(&mem::replace(&mut batch, Vec::new()));
+                }
+                op.replay(primary);
+            }
+        }
+    }
+    if !batch.is_empty() {
+        primary.record_batch(&batch);
+    }
+}
+
+impl FallbackRecorder {
+    pub(crate) fn new(capacity: usize) -> Self {
+        FallbackRecorder {
+            capacity,
+            primary: AtomicPtr::new(ptr::null_mut()),
+            state: Mutex::new(State {
+                ops: Vec::new(),
+                replaying: false,
+            }),
+        }
+    }
+
+    /// Replays all buffered operations into the primary recorder, and forwards to it from then on.
+    pub(crate) fn replay_into(&self, primary: &'static dyn Recorder) {
+        {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state.replaying {
+                return;
+            }
+            state.replaying = true;
+        }
+
+        // Operations are replayed without holding the lock, so that the primary recorder can
+        // record metrics of its own while replaying.  Anything recorded in the meantime is still
+        // buffered, and replayed in turn, and the primary recorder is only published once there
+        // is nothing left to replay, so that every operation reaches it in order.
+        loop {
+            let ops = {
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+                if state.ops.is_empty() {
+                    let primary = Box::into_raw(Box::new(primary));
+                    self.primary.store(primary, Ordering::Release);
+                    return;
+                }
+                mem::replace(&mut state.ops, Vec::new())
+            };
+            replay(ops, primary);
+        }
+    }
+
+    fn primary(&self) -> Option<&'static dyn Recorder> {
+        let primary = self.primary.load(Ordering::Acquire);
+        if primary.is_null() {
+            None
+        } else {
+            // The pointer is only ever set once, to a leaked box, so it is valid from then on.
+            Some(unsafe { *primary })
+        }
+    }
+
+    /// Buffers the given operations, returning whether or not there was room for all of them.
+    fn buffer<I: IntoIterator<Item = Buffered>>(&self, ops: I) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        // The primary recorder may have been published while we were waiting for the lock.
+        if let Some(primary) = self.primary() {
+            drop(state);
+            replay(ops.into_iter().collect(), primary);
+            return true;
+        }
+
+        let mut ops = ops.into_iter();
+        let remaining = self.capacity.saturating_sub(state.ops.len());
+        state.ops.extend(ops.by_ref().take(remaining));
+        ops.next().is_none()
+    }
+}
+
+impl Recorder for FallbackRecorder {
+    fn increment_counter(&self, key: Key, value: u64) {
+        match self.primary() {
+            Some(primary) => primary.increment_counter(key, value),
+            None => {
+                self.buffer(Some(Buffered::Op(MetricOp::IncrementCounter(key, value))));
+            }
+        }
+    }
+
+    fn update_gauge(&self, key: Key, value: i64) {
+        match self.primary() {
+            Some(primary) => primary.update_gauge(key, value),
+            None => {
+                self.buffer(Some(Buffered::Op(MetricOp::UpdateGauge(key, value))));
+            }
+        }
+    }
+
+    fn record_histogram(&self, key: Key, value: u64) {
+        match self.primary() {
+            Some(primary) => primary.record_histogram(key, value),
+            None => {
+                self.buffer(Some(Buffered::Op(MetricOp::RecordHistogram(key, value))));
+            }
+        }
+    }
+
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        match self.primary() {
+            Some(primary) => primary.try_record_histogram(key, value),
+            None => self.buffer(Some(Buffered::Op(MetricOp::RecordHistogram(key, value)))),
+        }
+    }
+
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        match self.primary() {
+            Some(primary) => primary.record_histogram_many(key, value, count),
+            None => {
+                self.buffer(Some(Buffered::Many(key, value, count)));
+            }
+        }
+    }
+
+    fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+        match self.primary() {
+            Some(primary) => primary.record_histogram_buckets(key, buckets, sum, count),
+            None => {
+                self.buffer(Some(Buffered::Buckets(key, buckets.to_vec(), sum, count)));
+            }
+        }
+    }
+
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+        match self.primary() {
+            Some(primary) => primary.record_histogram_with_exemplar(key, value, exemplar),
+            None => {
+                self.buffer(Some(Buffered::Exemplar(key, value, exemplar.to_vec())));
+            }
+        }
+    }
+
+    fn record_batch(&self, ops: &[MetricOp]) {
+        match self.primary() {
+            Some(primary) => primary.record_batch(ops),
+            None => {
+                self.buffer(ops.iter().cloned().map(Buffered::Op));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(primary) = self.primary() {
+            primary.flush();
+        }
+    }
+
+    fn counter_value(&self, key: &Key) -> Option<u64> {
+        self.primary()
+            .and_then(|primary| primary.counter_value(key))
+    }
+
+    fn gauge_value(&self, key: &Key) -> Option<i64> {
+        self.primary().and_then(|primary| primary.gauge_value(key))
+    }
+
+    fn describe_counter(&self, key: Key, description: &'static str) {
+        match self.primary() {
+            Some(primary) => primary.describe_counter(key, description),
+            None => {
+                self.buffer(Some(Buffered::DescribeCounter(key, description)));
+            }
+        }
+    }
+
+    fn describe_gauge(&self, key: Key, description: &'static str) {
+        match self.primary() {
+            Some(primary) => primary.describe_gauge(key, description),
+            None => {
+                self.buffer(Some(Buffered::DescribeGauge(key, description)));
+            }
+        }
+    }
+
+    fn describe_histogram(&self, key: Key, description: &'static str) {
+        match self.primary() {
+            Some(primary) => primary.describe_histogram(key, description),
+            None => {
+                self.buffer(Some(Buffered::DescribeHistogram(key, description)));
+            }
+        }
+    }
+
+    fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+        match self.primary() {
+            Some(primary) => primary.describe_with_meta(key, meta),
+            None => {
+                self.buffer(Some(Buffered::Meta(key, meta)));
+            }
+        }
+    }
+}
@@ -47,7 +47,8 @@
 //!
 //! Executables should choose a metrics implementation and initialize it early in the runtime of
 //! the program.  Metrics implementations will typically include a function to do this.  Any
-//! metrics recordered before the implementation is initialized will be ignored.
+//! metrics recordered before the implementation is initialized will be ignored, unless a fallback
+//! recorder has been set with [`set_fallback_recorder`] to buffer them in the meantime.
 //!
 //! The executable itself may use the `metrics` crate to record metrics well.
 //!
@@ -137,29 +138,79 @@
 //!
 //! [metrics-runtime]: https://docs.rs/metrics-runtime
 #![deny(missing_docs)]
-use metrics_core::AsNanoseconds;
-pub use metrics_core::{labels, Key, Label};
-#[cfg(feature = "std")]
-use std::error;
+pub use metrics_core::{labels, Key, Label, MetricKind, Unit};
+use metrics_core::{AsGaugeValue, AsNanoseconds};
 use std::{
-    fmt,
-    sync::atomic::{AtomicUsize, Ordering},
+    any::Any,
+    error, fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 #[macro_use]
 mod macros;
 
+mod catalog;
+pub use self::catalog::{describe_all, MetricDescriptor};
+
+mod fallback;
+use self::fallback::FallbackRecorder;
+
+mod local;
+pub use self::local::LocalRecorder;
+
 static mut RECORDER: &'static dyn Recorder = &NoopRecorder;
 static STATE: AtomicUsize = AtomicUsize::new(0);
 
+static mut FALLBACK: Option<&'static FallbackRecorder> = None;
+static FALLBACK_STATE: AtomicUsize = AtomicUsize::new(0);
+
 const UNINITIALIZED: usize = 0;
 const INITIALIZING: usize = 1;
 const INITIALIZED: usize = 2;
 
-static SET_RECORDER_ERROR: &str =
-    "attempted to set a recorder after the metrics system was already initialized";
+static ALREADY_INITIALIZED_ERROR: &str =
+    "metrics recorder already initialized; the recorder is global and set_recorder may only be \
+     called once, so initialize metrics once, early in the program";
+static INIT_IN_PROGRESS_ERROR: &str =
+    "metrics recorder was being initialized concurrently by another thread; the recorder is \
+     global and set_recorder may only be called once, so initialize metrics from a single place";
 
 /// A value that records metrics behind the facade.
+///
+/// Recorders behind an [`Arc`] or a [`Box`] are recorders themselves, forwarding every method to
+/// the recorder they point to, so a shared backend can be handed to anything expecting a
+/// recorder without writing a wrapper for it.
+///
+/// ```rust
+/// use metrics::{Key, Recorder};
+/// use std::sync::{
+///     atomic::{AtomicU64, Ordering},
+///     Arc,
+/// };
+///
+/// #[derive(Default)]
+/// struct CountingRecorder(AtomicU64);
+///
+/// impl Recorder for CountingRecorder {
+///     fn increment_counter(&self, _key: Key, value: u64) {
+///         self.0.fetch_add(value, Ordering::SeqCst);
+///     }
+///     fn update_gauge(&self, _key: Key, _value: i64) {}
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// }
+///
+/// fn record_requests<R: Recorder>(recorder: R) {
+///     recorder.increment_counter(Key::from_name("requests"), 1);
+/// }
+///
+/// let shared = Arc::new(CountingRecorder::default());
+/// record_requests(shared.clone());
+/// record_requests(Box::new(shared.clone()) as Box<dyn Recorder>);
+/// assert_eq!(shared.0.load(Ordering::SeqCst), 2);
+/// ```
 pub trait Recorder {
     /// Records a counter.
     ///
@@ -186,6 +237,342 @@ pub trait Recorder {
     ///
     /// There is no guarantee that this method will not be called multiple times for the same key.
     fn record_histogram(&self, key: Key, value: u64);
+
+    /// Records a value into a histogram, returning whether or not it was accepted.
+    ///
+    /// Recorders which validate values, or which may shed load, can reject a value rather than
+    /// record it.  This lets callers which care find out, such as to count rejected values
+    /// themselves, while [`record_histogram`](Recorder::record_histogram) stays as cheap as it
+    /// is.  Recorders which can reject values should override this to report it.
+    ///
+    /// The default implementation calls [`record_histogram`](Recorder::record_histogram) and
+    /// returns `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use metrics::{Key, Recorder};
+    /// use std::sync::Mutex;
+    ///
+    /// /// Only accepts durations of up to an hour, as anything longer is from a clock going awry.
+    /// #[derive(Default)]
+    /// struct DurationRecorder(Mutex<Vec<u64>>);
+    ///
+    /// const HOUR_NS: u64 = 3_600_000_000_000;
+    ///
+    /// impl Recorder for DurationRecorder {
+    ///     fn increment_counter(&self, _key: Key, _value: u64) {}
+    ///     fn update_gauge(&self, _key: Key, _value: i64) {}
+    ///     fn record_histogram(&self, key: Key, value: u64) {
+    ///         self.try_record_histogram(key, value);
+    ///     }
+    ///     fn try_record_histogram(&self, _key: Key, value: u64) -> bool {
+    ///         if value > HOUR_NS {
+    ///             return false;
+    ///         }
+    ///         self.0.lock().unwrap().push(value);
+    ///         true
+    ///     }
+    /// }
+    ///
+    /// let recorder = DurationRecorder::default();
+    /// assert!(recorder.try_record_histogram(Key::from_name("latency"), 250));
+    /// assert!(!recorder.try_record_histogram(Key::from_name("latency"), std::u64::MAX));
+    /// assert_eq!(*recorder.0.lock().unwrap(), vec![250]);
+    /// ```
+    fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+        self.record_histogram(key, value);
+        true
+    }
+
+    /// Records a value into a histogram as if it had been recorded `count` times.
+    ///
+    /// This is useful for recording weighted samples, such as when importing data that was
+    /// already counted or downsampled.  Recorders can override this to add `count` to their
+    /// storage directly, which is far cheaper than recording the value in a loop.
+    ///
+    /// The default implementation calls [`record_histogram`](Recorder::record_histogram) `count`
+    /// times.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use metrics::{Key, Recorder};
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// #[derive(Default)]
+    /// struct CountingRecorder(AtomicU64);
+    ///
+    /// impl Recorder for CountingRecorder {
+    ///     fn increment_counter(&self, _key: Key, _value: u64) {}
+    ///     fn update_gauge(&self, _key: Key, _value: i64) {}
+    ///     fn record_histogram(&self, _key: Key, _value: u64) {
+    ///         self.0.fetch_add(1, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// let recorder = CountingRecorder::default();
+    /// recorder.record_histogram_many(Key::from_name("latency"), 250, 4);
+    /// assert_eq!(recorder.0.load(Ordering::SeqCst), 4);
+    /// ```
+    fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+        for _ in 0..count {
+            self.record_histogram(key.clone(), value);
+        }
+    }
+
+    /// Records pre-aggregated buckets into a histogram.
+    ///
+    /// Each bucket is given as a pair of its upper bound and the number of values that fell into
+    /// it.  Counts are per-bucket, not cumulative.  `sum` and `count` are the sum and number of
+    /// every value, including any above the highest bound, which have no bucket of their own, as
+    /// with the `+Inf` bucket, `_sum` and `_count` of a Prometheus histogram.  This allows data
+    /// that was already aggregated by an upstream system to be merged into a histogram without
+    /// access to the raw values.
+    ///
+    /// Recorders which cannot merge bucketed data can ignore this, which is the default behavior.
+    fn record_histogram_buckets(&self, _key: Key, _buckets: &[(u64, u64)], _sum: u64, _count: u64) {
+    }
+
+    /// Records a histogram, along with an exemplar for the value.
+    ///
+    /// An exemplar is a set of labels, such as a trace ID, that identifies one specific
+    /// observation, so that a histogram can be correlated with the traces that fed it, as with
+    /// OpenMetrics exemplars.  Recorders which support exemplars typically keep the most recent
+    /// exemplar for each bucket.
+    ///
+    /// The default implementation ignores the exemplar and calls
+    /// [`record_histogram`](Recorder::record_histogram).
+    fn record_histogram_with_exemplar(&self, key: Key, value: u64, _exemplar: &[Label]) {
+        self.record_histogram(key, value);
+    }
+
+    /// Flushes any buffered metrics.
+    ///
+    /// Recorders which batch metrics internally, before sending them on to their final
+    /// destination, should send along anything they are holding when this is called.
+    /// Applications call this, via [`flush`], during shutdown so that the last metrics recorded
+    /// are not lost.
+    ///
+    /// Recorders which do not buffer can ignore this, which is the default behavior.
+    fn flush(&self) {}
+
+    /// Reads back the current value of a counter.
+    ///
+    /// This lets code that has just updated a counter log its total, for example.  Not every
+    /// recorder keeps the values it records, such as recorders which send every update straight
+    /// on to a remote system, so reading is optional: `None` is returned by recorders which can't
+    /// read values back, which is the default behavior, as well as for counters which haven't
+    /// been recorded.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use metrics::{Key, Recorder};
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// #[derive(Default)]
+    /// struct TotalRecorder(AtomicU64);
+    ///
+    /// impl Recorder for TotalRecorder {
+    ///     fn increment_counter(&self, _key: Key, value: u64) {
+    ///         self.0.fetch_add(value, Ordering::SeqCst);
+    ///     }
+    ///     fn update_gauge(&self, _key: Key, _value: i64) {}
+    ///     fn record_histogram(&self, _key: Key, _value: u64) {}
+    ///     fn counter_value(&self, _key: &Key) -> Option<u64> {
+    ///         Some(self.0.load(Ordering::SeqCst))
+    ///     }
+    /// }
+    ///
+    /// let recorder = TotalRecorder::default();
+    /// recorder.increment_counter(Key::from_name("requests"), 3);
+    /// assert_eq!(recorder.counter_value(&Key::from_name("requests")), Some(3));
+    /// assert_eq!(recorder.gauge_value(&Key::from_name("connections")), None);
+    /// ```
+    fn counter_value(&self, _key: &Key) -> Option<u64> {
+        None
+    }
+
+    /// Reads back the current value of a gauge.
+    ///
+    /// See [`counter_value`](Recorder::counter_value) for more details.
+    fn gauge_value(&self, _key: &Key) -> Option<i64> {
+        None
+    }
+
+    /// Describes a counter.
+    ///
+    /// Descriptions are human-readable help text for a metric, which exporters can emit alongside
+    /// its values.  No value is recorded, so a counter can be described before it is ever
+    /// incremented.
+    ///
+    /// Recorders which do not keep metadata can ignore this, which is the default behavior.
+    fn describe_counter(&self, _key: Key, _description: &'static str) {}
+
+    /// Describes a gauge.
+    ///
+    /// See [`describe_counter`](Recorder::describe_counter) for more details.
+    fn describe_gauge(&self, _key: Key, _description: &'static str) {}
+
+    /// Describes a histogram.
+    ///
+    /// See [`describe_counter`](Recorder::describe_counter) for more details.
+    fn describe_histogram(&self, _key: Key, _description: &'static str) {}
+
+    /// Attaches arbitrary metadata to a metric.
+    ///
+    /// Metadata is type-erased, so it can carry anything a particular backend understands, such as
+    /// an override for the job a metric is exported under, or a retention policy.  Recorders which
+    /// keep metadata should store it alongside the metric, so that it can be downcast back to its
+    /// original type at export time.
+    ///
+    /// Recorders which do not keep metadata can ignore this, which is the default behavior.
+    fn describe_with_meta(&self, _key: Key, _meta: Box<dyn Any + Send + Sync>) {}
+
+    /// Records a batch of operations.
+    ///
+    /// This is useful when replaying operations that were buffered elsewhere, as recorders can
+    /// override it to look up their storage once for the entire batch, rather than once per
+    /// operation.
+    ///
+    /// The default implementation applies each operation in order through the individual
+    /// recording methods.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use metrics::{Key, MetricOp, Recorder};
+    /// use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+    ///
+    /// #[derive(Default)]
+    /// struct SummingRecorder {
+    ///     counters: AtomicU64,
+    ///     gauge: AtomicI64,
+    ///     histograms: AtomicU64,
+    /// }
+    ///
+    /// impl Recorder for SummingRecorder {
+    ///     fn increment_counter(&self, _key: Key, value: u64) {
+    ///         self.counters.fetch_add(value, Ordering::SeqCst);
+    ///     }
+    ///     fn update_gauge(&self, _key: Key, value: i64) {
+    ///         self.gauge.store(value, Ordering::SeqCst);
+    ///     }
+    ///     fn record_histogram(&self, _key: Key, value: u64) {
+    ///         self.histograms.fetch_add(value, Ordering::SeqCst);
+    ///     }
+    /// }
+    ///
+    /// let recorder = SummingRecorder::default();
+    /// recorder.record_batch(&[
+    ///     MetricOp::IncrementCounter(Key::from_name("requests"), 2),
+    ///     MetricOp::UpdateGauge(Key::from_name("connections"), 7),
+    ///     MetricOp::RecordHistogram(Key::from_name("latency"), 40),
+    ///     MetricOp::IncrementCounter(Key::from_name("requests"), 3),
+    ///     MetricOp::UpdateGauge(Key::from_name("connections"), -1),
+    ///     MetricOp::RecordHistogram(Key::from_name("latency"), 2),
+    /// ]);
+    ///
+    /// assert_eq!(recorder.counters.load(Ordering::SeqCst), 5);
+    /// assert_eq!(recorder.gauge.load(Ordering::SeqCst), -1);
+    /// assert_eq!(recorder.histograms.load(Ordering::SeqCst), 42);
+    /// ```
+    fn record_batch(&self, ops: &[MetricOp]) {
+        for op in ops {
+            match op {
+                MetricOp::IncrementCounter(key, value) => {
+                    self.increment_counter(key.clone(), *value)
+                }
+                MetricOp::UpdateGauge(key, value) => self.update_gauge(key.clone(), *value),
+                MetricOp::RecordHistogram(key, value) => self.record_histogram(key.clone(), *value),
+            }
+        }
+    }
+}
+
+macro_rules! impl_recorder_for_pointer {
+    ($($ptr:ident),*) => {
+        $(
+            impl<R: Recorder + ?Sized> Recorder for $ptr<R> {
+                fn increment_counter(&self, key: Key, value: u64) {
+                    (**self).increment_counter(key, value);
+                }
+
+                fn update_gauge(&self, key: Key, value: i64) {
+                    (**self).update_gauge(key, value);
+                }
+
+                fn record_histogram(&self, key: Key, value: u64) {
+                    (**self).record_histogram(key, value);
+                }
+
+                fn try_record_histogram(&self, key: Key, value: u64) -> bool {
+                    (**self).try_record_histogram(key, value)
+                }
+
+                fn record_histogram_many(&self, key: Key, value: u64, count: u64) {
+                    (**self).record_histogram_many(key, value, count);
+                }
+
+                fn record_histogram_buckets(&self, key: Key, buckets: &[(u64, u64)], sum: u64, count: u64) {
+                    (**self).record_histogram_buckets(key, buckets, sum, count);
+                }
+
+                fn record_histogram_with_exemplar(&self, key: Key, value: u64, exemplar: &[Label]) {
+                    (**self).record_histogram_with_exemplar(key, value, exemplar);
+                }
+
+                fn flush(&self) {
+                    (**self).flush();
+                }
+
+                fn counter_value(&self, key: &Key) -> Option<u64> {
+                    (**self).counter_value(key)
+                }
+
+                fn gauge_value(&self, key: &Key) -> Option<i64> {
+                    (**self).gauge_value(key)
+                }
+
+                fn describe_counter(&self, key: Key, description: &'static str) {
+                    (**self).describe_counter(key, description);
+                }
+
+                fn describe_gauge(&self, key: Key, description: &'static str) {
+                    (**self).describe_gauge(key, description);
+                }
+
+                fn describe_histogram(&self, key: Key, description: &'static str) {
+                    (**self).describe_histogram(key, description);
+                }
+
+                fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+                    (**self).describe_with_meta(key, meta);
+                }
+
+                fn record_batch(&self, ops: &[MetricOp]) {
+                    (**self).record_batch(ops);
+                }
+            }
+        )*
+    };
+}
+
+impl_recorder_for_pointer!(Arc, Box);
+
+/// A single metric operation.
+///
+/// Used to pass several operations to a recorder at once via [`Recorder::record_batch`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricOp {
+    /// Increments a counter by the given value.
+    IncrementCounter(Key, u64),
+
+    /// Updates a gauge to the given value.
+    UpdateGauge(Key, i64),
+
+    /// Records the given value into a histogram.
+    RecordHistogram(Key, u64),
 }
 
 struct NoopRecorder;
@@ -199,7 +586,8 @@ impl Recorder for NoopRecorder {
 /// Sets the global recorder to a `&'static Recorder`.
 ///
 /// This function may only be called once in the lifetime of a program.  Any metrics recorded
-/// before the call to `set_recorder` occurs will be completely ignored.
+/// before the call to `set_recorder` occurs will be completely ignored, unless they were buffered
+/// by a fallback recorder set with [`set_fallback_recorder`].
 ///
 /// This function does not typically need to be called manually.  Metrics implementations should
 /// provide an initialization method that installs the recorder internally.
@@ -237,18 +625,123 @@ where
         match STATE.compare_and_swap(UNINITIALIZED, INITIALIZING, Ordering::SeqCst) {
             UNINITIALIZED => {
                 RECORDER = make_recorder();
+                replay_fallback(RECORDER);
                 STATE.store(INITIALIZED, Ordering::SeqCst);
                 Ok(())
             }
             INITIALIZING => {
                 while STATE.load(Ordering::SeqCst) == INITIALIZING {}
-                Err(SetRecorderError(()))
+                Err(SetRecorderError::InitInProgress)
             }
-            _ => Err(SetRecorderError(())),
+            _ => Err(SetRecorderError::AlreadyInitialized),
+        }
+    }
+}
+
+/// Sets a fallback recorder that buffers metrics until the global recorder is set.
+///
+/// Metrics recorded before the global recorder is set are normally ignored, which loses anything
+/// recorded while the application is still starting up.  Once a fallback recorder is set, updates
+/// and descriptions made before the call to [`set_recorder`] are buffered instead, and then
+/// replayed into the global recorder as soon as it is set.
+///
+/// At most `capacity` operations are buffered, and any operations beyond that are ignored.  While
+/// the fallback recorder is in use, [`try_recorder`] returns it, but [`is_initialized`] still
+/// returns `false`.
+///
+/// # Errors
+///
+/// An error is returned if a fallback recorder or the global recorder has already been set.
+///
+/// # Examples
+///
+/// ```rust
+/// use metrics::{counter, describe_counter, gauge, Key, Recorder};
+/// use std::sync::Mutex;
+///
+/// #[derive(Default)]
+/// struct CapturingRecorder(Mutex<Vec<String>>);
+///
+/// impl Recorder for CapturingRecorder {
+///     fn increment_counter(&self, key: Key, value: u64) {
+///         self.0.lock().unwrap().push(format!("counter {} {}", key.name(), value));
+///     }
+///     fn update_gauge(&self, key: Key, value: i64) {
+///         self.0.lock().unwrap().push(format!("gauge {} {}", key.name(), value));
+///     }
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+///     fn describe_counter(&self, key: Key, description: &'static str) {
+///         self.0.lock().unwrap().push(format!("describe {} {}", key.name(), description));
+///     }
+/// }
+///
+/// # fn main() {
+/// metrics::set_fallback_recorder(1024).unwrap();
+///
+/// // Recorded during startup, before the real recorder exists.
+/// describe_counter!("boot.stages", "startup stages completed");
+/// counter!("boot.stages", 1);
+/// gauge!("boot.config_size", 12);
+///
+/// let recorder = Box::leak(Box::new(CapturingRecorder::default()));
+/// metrics::set_recorder(recorder).unwrap();
+/// counter!("requests", 1);
+///
+/// assert_eq!(
+///     *recorder.0.lock().unwrap(),
+///     vec![
+///         "describe boot.stages startup stages completed",
+///         "counter boot.stages 1",
+///         "gauge boot.config_size 12",
+///         "counter requests 1",
+///     ]
+/// );
+/// # }
+/// ```
+#[cfg(atomic_cas)]
+pub fn set_fallback_recorder(capacity: usize) -> Result<(), SetRecorderError> {
+    if STATE.load(Ordering::SeqCst) != UNINITIALIZED {
+        return Err(SetRecorderError::AlreadyInitialized);
+    }
+
+    unsafe {
+        match FALLBACK_STATE.compare_and_swap(UNINITIALIZED, INITIALIZING, Ordering::SeqCst) {
+            UNINITIALIZED => {
+                FALLBACK = Some(Box::leak(Box::new(FallbackRecorder::new(capacity))));
+                FALLBACK_STATE.store(INITIALIZED, Ordering::SeqCst);
+
+                // The global recorder may have been set while we were setting the fallback, in
+                // which case it never saw the fallback, and we have to replay into it ourselves.
+                if STATE.load(Ordering::SeqCst) == INITIALIZED {
+                    replay_fallback(RECORDER);
+                }
+                Ok(())
+            }
+            INITIALIZING => {
+                while FALLBACK_STATE.load(Ordering::SeqCst) == INITIALIZING {}
+                Err(SetRecorderError::InitInProgress)
+            }
+            _ => Err(SetRecorderError::AlreadyInitialized),
+        }
+    }
+}
+
+fn fallback_recorder() -> Option<&'static FallbackRecorder> {
+    unsafe {
+        if FALLBACK_STATE.load(Ordering::SeqCst) != INITIALIZED {
+            None
+        } else {
+            FALLBACK
         }
     }
 }
 
+fn replay_fallback(recorder: &'static dyn Recorder) {
+    if let Some(fallback) = fallback_recorder() {
+        fallback.replay_into(recorder);
+    }
+}
+
 /// A thread-unsafe version of [`set_recorder`].
 ///
 /// This function is available on all platforms, even those that do not have support for atomics
@@ -270,6 +763,7 @@ pub unsafe fn set_recorder_racy(recorder: &'static dyn Recorder) -> Result<(), S
     match STATE.load(Ordering::SeqCst) {
         UNINITIALIZED => {
             RECORDER = recorder;
+            replay_fallback(RECORDER);
             STATE.store(INITIALIZED, Ordering::SeqCst);
             Ok(())
         }
@@ -277,26 +771,88 @@ pub unsafe fn set_recorder_racy(recorder: &'static dyn Recorder) -> Result<(), S
             // This is just plain UB, since we were racing another initialization function
             unreachable!("set_recorder_racy must not be used with other initialization functions")
         }
-        _ => Err(SetRecorderError(())),
+        _ => Err(SetRecorderError::AlreadyInitialized),
     }
 }
 
-/// The type returned by [`set_recorder`] if [`set_recorder`] has already been called.
-#[derive(Debug)]
-pub struct SetRecorderError(());
+/// The type returned by [`set_recorder`] if the global recorder could not be set.
+///
+/// # Examples
+///
+/// Applications can treat initialization as idempotent by ignoring the case where a recorder was
+/// already set:
+///
+/// ```rust
+/// use metrics::{Key, Recorder, SetRecorderError};
+///
+/// struct SimpleRecorder;
+///
+/// impl Recorder for SimpleRecorder {
+///     fn increment_counter(&self, _key: Key, _value: u64) {}
+///     fn update_gauge(&self, _key: Key, _value: i64) {}
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// }
+///
+/// static RECORDER: SimpleRecorder = SimpleRecorder;
+///
+/// # fn main() {
+/// assert!(!metrics::is_initialized());
+/// assert!(metrics::set_recorder(&RECORDER).is_ok());
+/// assert!(metrics::is_initialized());
+///
+/// match metrics::set_recorder(&RECORDER) {
+///     Err(SetRecorderError::AlreadyInitialized) => {}
+///     other => panic!("unexpected result: {:?}", other),
+/// }
+/// # }
+/// ```
+///
+/// The error message explains what went wrong, and how to avoid it:
+///
+/// ```rust
+/// use metrics::SetRecorderError;
+/// use std::error::Error;
+///
+/// let err: Box<dyn Error> = Box::new(SetRecorderError::AlreadyInitialized);
+/// let message = err.to_string();
+/// assert!(message.starts_with("metrics recorder already initialized"));
+/// assert!(message.contains("set_recorder may only be called once"));
+///
+/// let message = SetRecorderError::InitInProgress.to_string();
+/// assert!(message.contains("initialized concurrently"));
+/// assert!(message.contains("set_recorder may only be called once"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetRecorderError {
+    /// A recorder has already been set.
+    AlreadyInitialized,
+
+    /// Another recorder was being set at the same time, and was set instead.
+    InitInProgress,
+}
+
+impl SetRecorderError {
+    fn message(self) -> &'static str {
+        match self {
+            SetRecorderError::AlreadyInitialized => ALREADY_INITIALIZED_ERROR,
+            SetRecorderError::InitInProgress => INIT_IN_PROGRESS_ERROR,
+        }
+    }
+}
 
 impl fmt::Display for SetRecorderError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_str(SET_RECORDER_ERROR)
+        fmt.write_str(self.message())
     }
 }
 
-// The Error trait is not available in libcore
-#[cfg(feature = "std")]
-impl error::Error for SetRecorderError {
-    fn description(&self) -> &str {
-        SET_RECORDER_ERROR
-    }
+impl error::Error for SetRecorderError {}
+
+/// Whether or not a global recorder has been set.
+///
+/// This returns `false` while a recorder is still in the process of being set.
+pub fn is_initialized() -> bool {
+    STATE.load(Ordering::SeqCst) == INITIALIZED
 }
 
 /// Returns a reference to the recorder.
@@ -309,34 +865,96 @@ pub fn recorder() -> &'static dyn Recorder {
 
 /// Returns a reference to the recorder.
 ///
-/// If a recorder has not been set, returns `None`.
+/// If a recorder has not been set, returns the fallback recorder set by [`set_fallback_recorder`],
+/// or `None` if there is no fallback recorder either.
 pub fn try_recorder() -> Option<&'static dyn Recorder> {
     unsafe {
         if STATE.load(Ordering::SeqCst) != INITIALIZED {
-            None
+            fallback_recorder().map(|fallback| fallback as &'static dyn Recorder)
         } else {
             Some(RECORDER)
         }
     }
 }
 
+/// Flushes the recorder.
+///
+/// Delegates to [`Recorder::flush`] on the installed recorder, if any.  This should be called
+/// during a graceful shutdown to make sure recorders which buffer internally have sent along
+/// everything recorded so far.
+///
+/// # Examples
+///
+/// ```rust
+/// use metrics::{counter, Recorder};
+/// use metrics_core::Key;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// struct BufferingRecorder {
+///     buffered: AtomicU64,
+///     sent: AtomicU64,
+/// }
+///
+/// impl Recorder for BufferingRecorder {
+///     fn increment_counter(&self, _key: Key, value: u64) {
+///         self.buffered.fetch_add(value, Ordering::SeqCst);
+///     }
+///     fn update_gauge(&self, _key: Key, _value: i64) {}
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+///
+///     fn flush(&self) {
+///         let buffered = self.buffered.swap(0, Ordering::SeqCst);
+///         self.sent.fetch_add(buffered, Ordering::SeqCst);
+///     }
+/// }
+///
+/// static RECORDER: BufferingRecorder = BufferingRecorder {
+///     buffered: AtomicU64::new(0),
+///     sent: AtomicU64::new(0),
+/// };
+///
+/// # fn main() {
+/// metrics::set_recorder(&RECORDER).unwrap();
+///
+/// counter!("requests", 3);
+/// assert_eq!(RECORDER.sent.load(Ordering::SeqCst), 0);
+///
+/// metrics::flush();
+/// assert_eq!(RECORDER.buffered.load(Ordering::SeqCst), 0);
+/// assert_eq!(RECORDER.sent.load(Ordering::SeqCst), 3);
+/// # }
+/// ```
+pub fn flush() {
+    if let Some(recorder) = try_recorder() {
+        recorder.flush();
+    }
+}
+
 #[doc(hidden)]
 pub fn __private_api_increment_counter(recorder: &'static dyn Recorder, key: Key, value: u64) {
     recorder.increment_counter(key, value);
 }
 
 #[doc(hidden)]
-pub fn __private_api_update_gauge<K: Into<Key>>(
-    recorder: &'static dyn Recorder,
+pub fn __private_api_update_gauge<K: Into<Key>, V: AsGaugeValue>(
+    recorder: &dyn Recorder,
     key: K,
-    value: i64,
+    value: V,
 ) {
-    recorder.update_gauge(key.into(), value);
+    recorder.update_gauge(key.into(), value.as_gauge_value());
+}
+
+#[doc(hidden)]
+pub fn __private_api_unix_time() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
 }
 
 #[doc(hidden)]
 pub fn __private_api_record_histogram<K: Into<Key>, V: AsNanoseconds>(
-    recorder: &'static dyn Recorder,
+    recorder: &dyn Recorder,
     key: K,
     value: V,
 ) {
@@ -0,0 +1,111 @@
+use crate::Recorder;
+use metrics_core::{AsGaugeValue, AsNanoseconds, Key, Label};
+
+/// A handle for recording metrics against a specific recorder.
+///
+/// The macros in this crate always record against the global recorder, which suits most
+/// applications, but not a library that may be embedded several times in the same process, or a
+/// subsystem that needs to keep its metrics isolated from everything else.  `LocalRecorder` wraps
+/// a recorder instance and provides an explicit, non-macro API that records against that instance
+/// only, without touching the global recorder.
+///
+/// # Examples
+///
+/// ```rust
+/// use metrics::{Key, LocalRecorder, Recorder};
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// #[derive(Default)]
+/// struct CountingRecorder(AtomicU64);
+///
+/// impl Recorder for CountingRecorder {
+///     fn increment_counter(&self, _key: Key, value: u64) {
+///         self.0.fetch_add(value, Ordering::SeqCst);
+///     }
+///     fn update_gauge(&self, _key: Key, _value: i64) {}
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// }
+///
+/// let first = LocalRecorder::new(CountingRecorder::default());
+/// let second = LocalRecorder::new(CountingRecorder::default());
+///
+/// first.counter("requests", 3);
+/// second.counter("requests", 1);
+/// first.counter(("requests", &[("service", "api")]), 2);
+///
+/// assert_eq!(first.recorder().0.load(Ordering::SeqCst), 5);
+/// assert_eq!(second.recorder().0.load(Ordering::SeqCst), 1);
+/// ```
+pub struct LocalRecorder<R> {
+    recorder: R,
+}
+
+impl<R: Recorder> LocalRecorder<R> {
+    /// Creates a new `LocalRecorder` that records against the given recorder.
+    pub fn new(recorder: R) -> Self {
+        LocalRecorder { recorder }
+    }
+
+    /// Increments a counter by a value.
+    pub fn counter<K: Into<Key>>(&self, key: K, value: u64) {
+        self.recorder.increment_counter(key.into(), value);
+    }
+
+    /// Updates a gauge with a value.
+    ///
+    /// The value can be any integer type; see [`AsGaugeValue`] for how it is converted.
+    pub fn gauge<K: Into<Key>, V: AsGaugeValue>(&self, key: K, value: V) {
+        self.recorder
+            .update_gauge(key.into(), value.as_gauge_value());
+    }
+
+    /// Records a value, or timing, into a histogram.
+    pub fn histogram<K: Into<Key>, V: AsNanoseconds>(&self, key: K, value: V) {
+        self.recorder.record_histogram(key.into(), value.as_nanos());
+    }
+
+    /// Records a value, or timing, into a histogram, returning whether or not it was accepted.
+    ///
+    /// See [`Recorder::try_record_histogram`] for details.
+    pub fn try_histogram<K: Into<Key>, V: AsNanoseconds>(&self, key: K, value: V) -> bool {
+        self.recorder
+            .try_record_histogram(key.into(), value.as_nanos())
+    }
+
+    /// Records a value, or timing, into a histogram, along with an exemplar for the value.
+    ///
+    /// See [`Recorder::record_histogram_with_exemplar`] for details.
+    pub fn histogram_with_exemplar<K: Into<Key>, V: AsNanoseconds>(
+        &self,
+        key: K,
+        value: V,
+        exemplar: &[Label],
+    ) {
+        self.recorder
+            .record_histogram_with_exemplar(key.into(), value.as_nanos(), exemplar);
+    }
+
+    /// Reads back the current value of a counter, if the underlying recorder supports it.
+    ///
+    /// See [`Recorder::counter_value`] for details.
+    pub fn counter_value<K: Into<Key>>(&self, key: K) -> Option<u64> {
+        self.recorder.counter_value(&key.into())
+    }
+
+    /// Reads back the current value of a gauge, if the underlying recorder supports it.
+    ///
+    /// See [`Recorder::gauge_value`] for details.
+    pub fn gauge_value<K: Into<Key>>(&self, key: K) -> Option<i64> {
+        self.recorder.gauge_value(&key.into())
+    }
+
+    /// Gets a reference to the underlying recorder.
+    pub fn recorder(&self) -> &R {
+        &self.recorder
+    }
+
+    /// Consumes this handle, returning the underlying recorder.
+    pub fn into_inner(self) -> R {
+        self.recorder
+    }
+}
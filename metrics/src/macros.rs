@@ -30,8 +30,85 @@
 /// }
 /// # fn main() {}
 /// ```
+///
+/// Labels built at runtime can be passed as a trailing expression implementing
+/// [`IntoLabels`](metrics_core::IntoLabels), after any inline labels.
+///
+/// ```rust
+/// use metrics::{counter, Label};
+///
+/// fn do_thing(extra: Vec<Label>) {
+///     counter!("do_thing", 1, "service" => "admin", extra);
+/// }
+/// # fn main() {}
+/// ```
+///
+/// ### Conditional recording
+///
+/// A condition can be given with `when = <condition>`, directly after the value, to only record
+/// when the condition is true.  This allows whole categories of metrics to be turned on and off
+/// at runtime, such as with a feature flag, at the cost of a single inline check.
+///
+/// The condition is evaluated first, and short-circuits the rest of the macro: when it is false,
+/// the name, value, and labels are never evaluated, and the recorder is never called, so there is
+/// no cost to computing an expensive value or label set that would only be thrown away.  The
+/// [`gauge!`], [`timing!`], and [`value!`] macros accept a condition in the same way.
+///
+/// ```rust
+/// use metrics::{counter, Key, Recorder};
+/// use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+///
+/// struct CountingRecorder(AtomicU64);
+///
+/// impl Recorder for CountingRecorder {
+///     fn increment_counter(&self, _key: Key, value: u64) {
+///         self.0.fetch_add(value, Ordering::SeqCst);
+///     }
+///     fn update_gauge(&self, _key: Key, _value: i64) {}
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// }
+///
+/// static RECORDER: CountingRecorder = CountingRecorder(AtomicU64::new(0));
+/// static DETAILED_METRICS: AtomicBool = AtomicBool::new(false);
+///
+/// # fn expensive_count(evaluated: &mut bool) -> u64 { *evaluated = true; 5 }
+/// # fn main() {
+/// metrics::set_recorder(&RECORDER).unwrap();
+///
+/// let mut evaluated = false;
+/// counter!(
+///     "cache.entries_scanned",
+///     expensive_count(&mut evaluated),
+///     when = DETAILED_METRICS.load(Ordering::Relaxed)
+/// );
+/// assert!(!evaluated);
+/// assert_eq!(RECORDER.0.load(Ordering::SeqCst), 0);
+///
+/// DETAILED_METRICS.store(true, Ordering::Relaxed);
+/// counter!(
+///     "cache.entries_scanned",
+///     expensive_count(&mut evaluated),
+///     when = DETAILED_METRICS.load(Ordering::Relaxed),
+///     "cache" => "users"
+/// );
+/// assert!(evaluated);
+/// assert_eq!(RECORDER.0.load(Ordering::SeqCst), 5);
+/// # }
+/// ```
 #[macro_export]
 macro_rules! counter {
+    ($name:expr, $value:expr, when = $condition:expr) => {
+        if $condition {
+            $crate::counter!($name, $value)
+        }
+    };
+
+    ($name:expr, $value:expr, when = $condition:expr, $($labels:tt)*) => {
+        if $condition {
+            $crate::counter!($name, $value, $($labels)*)
+        }
+    };
+
     ($name:expr, $value:expr) => {
         if let Some(recorder) = $crate::try_recorder() {
             recorder.increment_counter($crate::Key::from_name($name), $value);
@@ -54,6 +131,11 @@ macro_rules! counter {
 /// a set of labels, of the form `key => value`, can be passed to further
 /// describe the gauge.
 ///
+/// The value can be of any integer type, and is converted to the `i64` held by gauges in one
+/// place, rather than with an `as` cast at the call site.  Values that don't fit, which are `u64`
+/// and `usize` values above `i64::MAX`, saturate to `i64::MAX`, and trigger a debug assertion in
+/// debug builds.  See [`AsGaugeValue`](metrics_core::AsGaugeValue) for details.
+///
 /// Functionally equivalent to calling [`Recorder::update_gauge`].
 ///
 /// ### Examples
@@ -67,6 +149,37 @@ macro_rules! counter {
 /// # fn main() {}
 /// ```
 ///
+/// Unsigned values are recorded exactly, without a lossy cast:
+///
+/// ```rust
+/// use metrics::{gauge, Key, Recorder};
+/// use std::sync::atomic::{AtomicI64, Ordering};
+///
+/// struct GaugeRecorder(AtomicI64);
+///
+/// impl Recorder for GaugeRecorder {
+///     fn increment_counter(&self, _key: Key, _value: u64) {}
+///     fn update_gauge(&self, _key: Key, value: i64) {
+///         self.0.store(value, Ordering::SeqCst);
+///     }
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// }
+///
+/// static RECORDER: GaugeRecorder = GaugeRecorder(AtomicI64::new(0));
+///
+/// # fn main() {
+/// metrics::set_recorder(&RECORDER).unwrap();
+///
+/// let bytes_stored: u64 = (1 << 53) + 1;
+/// gauge!("storage.bytes", bytes_stored);
+/// assert_eq!(RECORDER.0.load(Ordering::SeqCst), (1 << 53) + 1);
+///
+/// let queue: Vec<u8> = vec![0; 3];
+/// gauge!("queue.depth", queue.len());
+/// assert_eq!(RECORDER.0.load(Ordering::SeqCst), 3);
+/// # }
+/// ```
+///
 /// Labels can also be passed along:
 ///
 /// ```rust
@@ -81,6 +194,18 @@ macro_rules! counter {
 /// ```
 #[macro_export]
 macro_rules! gauge {
+    ($name:expr, $value:expr, when = $condition:expr) => {
+        if $condition {
+            $crate::gauge!($name, $value)
+        }
+    };
+
+    ($name:expr, $value:expr, when = $condition:expr, $($labels:tt)*) => {
+        if $condition {
+            $crate::gauge!($name, $value, $($labels)*)
+        }
+    };
+
     ($name:expr, $value:expr) => {
         if let Some(recorder) = $crate::try_recorder() {
             $crate::__private_api_update_gauge(recorder, $crate::Key::from_name($name), $value);
@@ -96,6 +221,67 @@ macro_rules! gauge {
     };
 }
 
+/// Sets a gauge to the current time.
+///
+/// This will register a gauge with the given name, if it does not already exist, then update it
+/// to the current UNIX time, in seconds.  Optionally, a set of labels, of the form
+/// `key => value`, can be passed to further describe the gauge.
+///
+/// This is a convenient way to track when something last happened, such as the last successful
+/// run of a periodic job.
+///
+/// ### Examples
+///
+/// ```rust
+/// use metrics::gauge_set_to_current_time;
+///
+/// # fn run_job() {}
+/// fn run() {
+///     run_job();
+///     gauge_set_to_current_time!("job.last_success_time", "job" => "cleanup");
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The gauge is set to seconds since the UNIX epoch:
+///
+/// ```rust
+/// use metrics::{gauge_set_to_current_time, Key, Recorder};
+/// use std::sync::atomic::{AtomicI64, Ordering};
+/// use std::time::{SystemTime, UNIX_EPOCH};
+///
+/// struct GaugeRecorder(AtomicI64);
+///
+/// impl Recorder for GaugeRecorder {
+///     fn increment_counter(&self, _key: Key, _value: u64) {}
+///     fn update_gauge(&self, _key: Key, value: i64) {
+///         self.0.store(value, Ordering::SeqCst);
+///     }
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// }
+///
+/// static RECORDER: GaugeRecorder = GaugeRecorder(AtomicI64::new(0));
+///
+/// # fn main() {
+/// metrics::set_recorder(&RECORDER).unwrap();
+///
+/// gauge_set_to_current_time!("job.last_success_time");
+///
+/// let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+/// assert!((now - RECORDER.0.load(Ordering::SeqCst)).abs() <= 1);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! gauge_set_to_current_time {
+    ($name:expr) => {
+        $crate::gauge!($name, $crate::__private_api_unix_time())
+    };
+
+    ($name:expr, $($labels:tt)*) => {
+        $crate::gauge!($name, $crate::__private_api_unix_time(), $($labels)*)
+    };
+}
+
 /// Records a timing.
 ///
 /// This will register an histogram with the given name, if it does not already
@@ -167,9 +353,55 @@ macro_rules! gauge {
 /// # fn main() {}
 /// ```
 ///
+/// Recording can be made conditional, as with [`counter!`]:
+///
+/// ```rust
+/// use metrics::timing;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::time::Instant;
+///
+/// static TRACE_TIMINGS: AtomicBool = AtomicBool::new(false);
+///
+/// # fn process() {}
+/// fn handle_request() {
+///     let start = Instant::now();
+///     process();
+///     let end = Instant::now();
+///
+///     let enabled = TRACE_TIMINGS.load(Ordering::Relaxed);
+///     timing!("perf.request_processed", start, end, when = enabled);
+///     timing!("perf.request_processed", end - start, when = enabled, "service" => "http");
+/// }
+/// # fn main() {}
+/// ```
+///
 /// [`AsNanoseconds`]: https://docs.rs/metrics-core/0.5/metrics_core/trait.AsNanoseconds.html
 #[macro_export]
 macro_rules! timing {
+    ($name:expr, $value:expr, when = $condition:expr) => {
+        if $condition {
+            $crate::timing!($name, $value)
+        }
+    };
+
+    ($name:expr, $value:expr, when = $condition:expr, $($labels:tt)*) => {
+        if $condition {
+            $crate::timing!($name, $value, $($labels)*)
+        }
+    };
+
+    ($name:expr, $start:expr, $end:expr, when = $condition:expr) => {
+        if $condition {
+            $crate::timing!($name, $start, $end)
+        }
+    };
+
+    ($name:expr, $start:expr, $end:expr, when = $condition:expr, $($labels:tt)*) => {
+        if $condition {
+            $crate::timing!($name, $start, $end, $($labels)*)
+        }
+    };
+
     ($name:expr, $value:expr) => {
         if let Some(recorder) = $crate::try_recorder() {
             $crate::__private_api_record_histogram(recorder, $crate::Key::from_name($name), $value);
@@ -228,6 +460,18 @@ macro_rules! timing {
 /// ```
 #[macro_export]
 macro_rules! value {
+    ($name:expr, $value:expr, when = $condition:expr) => {
+        if $condition {
+            $crate::value!($name, $value)
+        }
+    };
+
+    ($name:expr, $value:expr, when = $condition:expr, $($labels:tt)*) => {
+        if $condition {
+            $crate::value!($name, $value, $($labels)*)
+        }
+    };
+
     ($name:expr, $value:expr) => {
         if let Some(recorder) = $crate::try_recorder() {
             $crate::__private_api_record_histogram(recorder, $crate::Key::from_name($name), $value);
@@ -242,3 +486,411 @@ macro_rules! value {
         }
     };
 }
+
+/// Increments a counter by a value, on the given recorder.
+///
+/// This behaves exactly like [`counter!`], including the handling of labels and of conditions given
+/// with `when = <condition>`, but records against the given recorder, a reference to any
+/// [`Recorder`], rather than the global recorder.  This suits libraries which let callers inject
+/// the recorder to use, as well as tests.  The global recorder is never touched.
+///
+/// [`gauge_on!`] and [`value_on!`] do the same for [`gauge!`] and [`value!`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use metrics::{counter_on, gauge_on, value_on, Key, Recorder};
+/// use std::sync::{
+///     atomic::{AtomicU64, Ordering},
+///     Mutex,
+/// };
+///
+/// struct CountingRecorder(AtomicU64);
+///
+/// impl Recorder for CountingRecorder {
+///     fn increment_counter(&self, _key: Key, value: u64) {
+///         self.0.fetch_add(value, Ordering::SeqCst);
+///     }
+///     fn update_gauge(&self, _key: Key, _value: i64) {}
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+/// }
+///
+/// #[derive(Default)]
+/// struct CapturingRecorder(Mutex<Vec<(Key, i64)>>);
+///
+/// impl Recorder for CapturingRecorder {
+///     fn increment_counter(&self, key: Key, value: u64) {
+///         self.0.lock().unwrap().push((key, value as i64));
+///     }
+///     fn update_gauge(&self, key: Key, value: i64) {
+///         self.0.lock().unwrap().push((key, value));
+///     }
+///     fn record_histogram(&self, key: Key, value: u64) {
+///         self.0.lock().unwrap().push((key, value as i64));
+///     }
+/// }
+///
+/// static GLOBAL: CountingRecorder = CountingRecorder(AtomicU64::new(0));
+///
+/// # fn main() {
+/// metrics::set_recorder(&GLOBAL).unwrap();
+///
+/// let local = CapturingRecorder::default();
+/// counter_on!(&local, "requests", 1);
+/// counter_on!(&local, "requests", 2, "service" => "api");
+/// counter_on!(&local, "requests", 3, when = false);
+/// gauge_on!(&local, "connections", 5usize);
+/// gauge_on!(&local, "connections", 6usize, when = false, "service" => "api");
+///
+/// let injected: &dyn Recorder = &local;
+/// value_on!(injected, "latency", 40);
+///
+/// assert_eq!(
+///     *local.0.lock().unwrap(),
+///     vec![
+///         (Key::from_name("requests"), 1),
+///         (Key::from_name_and_labels("requests", metrics::labels!("service" => "api")), 2),
+///         (Key::from_name("connections"), 5),
+///         (Key::from_name("latency"), 40),
+///     ]
+/// );
+/// assert_eq!(GLOBAL.0.load(Ordering::SeqCst), 0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! counter_on {
+    ($recorder:expr, $name:expr, $value:expr, when = $condition:expr) => {
+        if $condition {
+            $crate::counter_on!($recorder, $name, $value)
+        }
+    };
+
+    ($recorder:expr, $name:expr, $value:expr, when = $condition:expr, $($labels:tt)*) => {
+        if $condition {
+            $crate::counter_on!($recorder, $name, $value, $($labels)*)
+        }
+    };
+
+    ($recorder:expr, $name:expr, $value:expr) => {
+        $crate::Recorder::increment_counter($recorder, $crate::Key::from_name($name), $value)
+    };
+
+    ($recorder:expr, $name:expr, $value:expr, $($labels:tt)*) => {{
+        let labels = $crate::labels!( $($labels)* );
+        let key = $crate::Key::from_name_and_labels($name, labels);
+        $crate::Recorder::increment_counter($recorder, key, $value)
+    }};
+}
+
+/// Updates a gauge with a value, on the given recorder.
+///
+/// This behaves exactly like [`gauge!`], but records against the given recorder rather than the
+/// global recorder.  See [`counter_on!`] for details.
+#[macro_export]
+macro_rules! gauge_on {
+    ($recorder:expr, $name:expr, $value:expr, when = $condition:expr) => {
+        if $condition {
+            $crate::gauge_on!($recorder, $name, $value)
+        }
+    };
+
+    ($recorder:expr, $name:expr, $value:expr, when = $condition:expr, $($labels:tt)*) => {
+        if $condition {
+            $crate::gauge_on!($recorder, $name, $value, $($labels)*)
+        }
+    };
+
+    ($recorder:expr, $name:expr, $value:expr) => {
+        $crate::__private_api_update_gauge($recorder, $crate::Key::from_name($name), $value)
+    };
+
+    ($recorder:expr, $name:expr, $value:expr, $($labels:tt)*) => {{
+        let labels = $crate::labels!( $($labels)* );
+        let key = $crate::Key::from_name_and_labels($name, labels);
+        $crate::__private_api_update_gauge($recorder, key, $value)
+    }};
+}
+
+/// Records a value, on the given recorder.
+///
+/// This behaves exactly like [`value!`], but records against the given recorder rather than the
+/// global recorder.  See [`counter_on!`] for details.
+#[macro_export]
+macro_rules! value_on {
+    ($recorder:expr, $name:expr, $value:expr, when = $condition:expr) => {
+        if $condition {
+            $crate::value_on!($recorder, $name, $value)
+        }
+    };
+
+    ($recorder:expr, $name:expr, $value:expr, when = $condition:expr, $($labels:tt)*) => {
+        if $condition {
+            $crate::value_on!($recorder, $name, $value, $($labels)*)
+        }
+    };
+
+    ($recorder:expr, $name:expr, $value:expr) => {
+        $crate::__private_api_record_histogram($recorder, $crate::Key::from_name($name), $value)
+    };
+
+    ($recorder:expr, $name:expr, $value:expr, $($labels:tt)*) => {{
+        let labels = $crate::labels!( $($labels)* );
+        let key = $crate::Key::from_name_and_labels($name, labels);
+        $crate::__private_api_record_histogram($recorder, key, $value)
+    }};
+}
+
+/// Describes a counter.
+///
+/// This attaches human-readable help text to the counter with the given name, without recording a
+/// value, so that exporters have metadata for it even before it is first used.  Libraries should
+/// generally call this once, during initialization.
+///
+/// Functionally equivalent to calling [`Recorder::describe_counter`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use metrics::describe_counter;
+///
+/// fn init() {
+///     describe_counter!("msg_sent_total", "Total number of messages sent.");
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The description reaches the installed recorder:
+///
+/// ```rust
+/// use metrics::{describe_counter, Key, Recorder};
+/// use std::{collections::HashMap, sync::Mutex};
+///
+/// #[derive(Default)]
+/// struct CapturingRecorder {
+///     descriptions: Mutex<HashMap<Key, &'static str>>,
+/// }
+///
+/// impl Recorder for CapturingRecorder {
+///     fn increment_counter(&self, _key: Key, _value: u64) {}
+///     fn update_gauge(&self, _key: Key, _value: i64) {}
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+///
+///     fn describe_counter(&self, key: Key, description: &'static str) {
+///         self.descriptions.lock().unwrap().insert(key, description);
+///     }
+/// }
+///
+/// # fn main() {
+/// let recorder: &'static CapturingRecorder = Box::leak(Box::new(CapturingRecorder::default()));
+/// metrics::set_recorder(recorder).unwrap();
+///
+/// describe_counter!("requests", "Total number of requests served.");
+///
+/// let descriptions = recorder.descriptions.lock().unwrap();
+/// assert_eq!(
+///     descriptions.get(&Key::from_name("requests")),
+///     Some(&"Total number of requests served.")
+/// );
+/// # }
+/// ```
+#[macro_export]
+macro_rules! describe_counter {
+    ($name:expr, $description:expr) => {
+        if let Some(recorder) = $crate::try_recorder() {
+            recorder.describe_counter($crate::Key::from_name($name), $description);
+        }
+    };
+}
+
+/// Describes a gauge.
+///
+/// This attaches human-readable help text to the gauge with the given name, without recording a
+/// value, so that exporters have metadata for it even before it is first used.  Libraries should
+/// generally call this once, during initialization.
+///
+/// Functionally equivalent to calling [`Recorder::describe_gauge`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use metrics::describe_gauge;
+///
+/// fn init() {
+///     describe_gauge!("current_value", "The current value.");
+/// }
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! describe_gauge {
+    ($name:expr, $description:expr) => {
+        if let Some(recorder) = $crate::try_recorder() {
+            recorder.describe_gauge($crate::Key::from_name($name), $description);
+        }
+    };
+}
+
+/// Describes a histogram.
+///
+/// This attaches human-readable help text to the histogram with the given name, without recording a
+/// value, so that exporters have metadata for it even before it is first used.  Libraries should
+/// generally call this once, during initialization.
+///
+/// Functionally equivalent to calling [`Recorder::describe_histogram`].
+///
+/// ### Examples
+///
+/// ```rust
+/// use metrics::describe_histogram;
+///
+/// fn init() {
+///     describe_histogram!("perf.request_processed", "Time taken to process a request, in nanoseconds.");
+/// }
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! describe_histogram {
+    ($name:expr, $description:expr) => {
+        if let Some(recorder) = $crate::try_recorder() {
+            recorder.describe_histogram($crate::Key::from_name($name), $description);
+        }
+    };
+}
+
+/// Declares a catalog of metrics.
+///
+/// Each metric is declared once, with its name, kind (`counter`, `gauge`, or `histogram`),
+/// description, and, optionally, its [`Unit`](crate::Unit).  For each metric, a `const` holding its name is
+/// generated, and call sites can use these constants instead of string literals, so that a typo
+/// in a metric name becomes a compile error rather than a second, silently diverging metric.
+///
+/// A `static` slice of [`MetricDescriptor`]s, listing every declared metric, is generated as
+/// well.  This gives a single place to see every metric the binary can emit, which is handy for
+/// documentation and for detecting drift, and describing every metric at startup is a matter of
+/// iterating over it.
+///
+/// ### Examples
+///
+/// ```rust
+/// use metrics::{counter, define_metrics, Key, MetricKind, Recorder, Unit};
+/// use std::{any::Any, collections::HashMap, sync::Mutex};
+///
+/// define_metrics! {
+///     /// Every metric emitted by the server.
+///     pub static SERVER_METRICS = {
+///         /// Total number of requests served.
+///         pub REQUESTS_TOTAL: counter("requests_total", "Total number of requests served.");
+///         pub CONNECTIONS: gauge("connections", "Number of open connections.");
+///         pub REQUEST_LATENCY: histogram(
+///             "request_latency",
+///             "Time taken to serve a request.",
+///             Unit::Nanoseconds
+///         );
+///     }
+/// }
+///
+/// #[derive(Default)]
+/// struct CapturingRecorder {
+///     descriptions: Mutex<HashMap<Key, &'static str>>,
+///     units: Mutex<HashMap<Key, Unit>>,
+/// }
+///
+/// impl Recorder for CapturingRecorder {
+///     fn increment_counter(&self, _key: Key, _value: u64) {}
+///     fn update_gauge(&self, _key: Key, _value: i64) {}
+///     fn record_histogram(&self, _key: Key, _value: u64) {}
+///
+///     fn describe_counter(&self, key: Key, description: &'static str) {
+///         self.descriptions.lock().unwrap().insert(key, description);
+///     }
+///
+///     fn describe_histogram(&self, key: Key, description: &'static str) {
+///         self.descriptions.lock().unwrap().insert(key, description);
+///     }
+///
+///     fn describe_with_meta(&self, key: Key, meta: Box<dyn Any + Send + Sync>) {
+///         if let Ok(unit) = meta.downcast::<Unit>() {
+///             self.units.lock().unwrap().insert(key, *unit);
+///         }
+///     }
+/// }
+///
+/// # fn main() {
+/// let recorder: &'static CapturingRecorder = Box::leak(Box::new(CapturingRecorder::default()));
+/// metrics::set_recorder(recorder).unwrap();
+///
+/// assert_eq!(REQUESTS_TOTAL, "requests_total");
+/// assert_eq!(SERVER_METRICS.len(), 3);
+/// assert_eq!(SERVER_METRICS[1].kind(), MetricKind::Gauge);
+/// assert_eq!(SERVER_METRICS[2].unit(), Some(Unit::Nanoseconds));
+///
+/// for metric in SERVER_METRICS {
+///     metric.describe();
+/// }
+/// counter!(REQUESTS_TOTAL, 1);
+///
+/// let descriptions = recorder.descriptions.lock().unwrap();
+/// assert_eq!(descriptions.len(), 2);
+/// assert_eq!(
+///     descriptions.get(&Key::from_name("request_latency")),
+///     Some(&"Time taken to serve a request.")
+/// );
+///
+/// // Units are passed along as metadata.
+/// let units = recorder.units.lock().unwrap();
+/// assert_eq!(*units, vec![(Key::from_name("request_latency"), Unit::Nanoseconds)].into_iter().collect());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! define_metrics {
+    (
+        $(#[$catalog_attr:meta])*
+        $catalog_vis:vis static $catalog:ident = {
+            $(
+                $(#[$attr:meta])*
+                $vis:vis $const:ident: $kind:ident($name:expr, $description:expr $(, $unit:expr)? $(,)?);
+            )*
+        }
+    ) => {
+        $(
+            $(#[$attr])*
+            $vis const $const: &str = $name;
+        )*
+
+        $(#[$catalog_attr])*
+        $catalog_vis static $catalog: &[$crate::MetricDescriptor] = &[
+            $(
+                $crate::MetricDescriptor::new(
+                    $const,
+                    $crate::__metric_kind!($kind),
+                    $description,
+                    $crate::__metric_unit!($($unit)?),
+                ),
+            )*
+        ];
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __metric_kind {
+    (counter) => {
+        $crate::MetricKind::Counter
+    };
+    (gauge) => {
+        $crate::MetricKind::Gauge
+    };
+    (histogram) => {
+        $crate::MetricKind::Histogram
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __metric_unit {
+    () => {
+        None
+    };
+    ($unit:expr) => {
+        Some($unit)
+    };
+}